@@ -0,0 +1,67 @@
+use std::fmt::Write;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use ogkr::Ogkr;
+
+/// Marathon-sized chart where most timing points hold one note and every fourth holds two, the
+/// usual shape of note buckets.
+fn marathon_chart(measures: u32) -> String {
+    let mut source = String::from("TRESOLUTION 1920\nXRESOLUTION 4096\n");
+    writeln!(source, "LCS 0 0 0 0\nLCE 0 {measures} 0 0").unwrap();
+    writeln!(source, "BPL A CEN 0 PLR 1.0 L CIR 0").unwrap();
+    for measure in 0..measures {
+        for beat in 0..16 {
+            let offset = beat * 120;
+            writeln!(source, "TAP 0 {measure} {offset} 0 0").unwrap();
+            if beat % 4 == 0 {
+                writeln!(source, "FLK {measure} {offset} 8 L").unwrap();
+                writeln!(source, "CTP 0 {measure} {offset} 4 0").unwrap();
+            }
+        }
+        writeln!(source, "HLD 0 {measure} 60 0 0 {measure} 900 0 0").unwrap();
+        writeln!(source, "BEL {measure} 1000 4").unwrap();
+        writeln!(source, "BLT A {measure} 1500 0 NML").unwrap();
+    }
+    source
+}
+
+const BUNDLED_CHARTS: [&str; 3] = [
+    include_str!("../charts/1.ogkr"),
+    include_str!("../charts/2.ogkr"),
+    include_str!("../charts/3.ogkr"),
+];
+
+fn buckets(c: &mut Criterion) {
+    c.bench_function("parse bundled charts", |b| {
+        b.iter(|| {
+            BUNDLED_CHARTS
+                .iter()
+                .map(|source| black_box(source).parse::<Ogkr>().unwrap())
+                .collect::<Vec<_>>()
+        })
+    });
+
+    let source = marathon_chart(1000);
+    let ogkr: Ogkr = source.parse().unwrap();
+
+    c.bench_function("parse marathon chart", |b| {
+        b.iter(|| black_box(&source).parse::<Ogkr>().unwrap())
+    });
+    c.bench_function("iterate marathon notes", |b| {
+        b.iter(|| black_box(&ogkr).notes.all().count())
+    });
+    c.bench_function("clone marathon notes", |b| {
+        b.iter(|| black_box(&ogkr).notes.clone())
+    });
+    c.bench_function("view marathon measures", |b| {
+        b.iter(|| {
+            (0..1000)
+                .step_by(10)
+                .map(|measure| black_box(&ogkr).measure(measure).taps.len())
+                .sum::<usize>()
+        })
+    });
+}
+
+criterion_group!(benches, buckets);
+criterion_main!(benches);
@@ -0,0 +1,64 @@
+//! Applies the input as edits to a real chart, reaching deeper into the parser than arbitrary
+//! bytes do.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ogkr::{
+    lex::tokenize,
+    parse::{analysis::parse_raw_ogkr, raw::parse_tokens},
+};
+
+const CHARTS: [&str; 3] = [
+    include_str!("../../charts/1.ogkr"),
+    include_str!("../../charts/2.ogkr"),
+    include_str!("../../charts/3.ogkr"),
+];
+
+/// Text spliced into charts by edits, values around the limits of the argument types.
+const FRAGMENTS: [&str; 8] = [
+    " ",
+    "\n",
+    " 0",
+    " -1",
+    " 4294967295",
+    " -2147483648",
+    " 1e38",
+    " NaN",
+];
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&chart, edits)) = data.split_first() else {
+        return;
+    };
+    let mut source = CHARTS[chart as usize % CHARTS.len()].as_bytes().to_vec();
+
+    // Every edit is 3 bytes: a little endian position and an operation.
+    for edit in edits.chunks_exact(3) {
+        let position = u16::from_le_bytes([edit[0], edit[1]]) as usize % (source.len() + 1);
+        match edit[2] % 4 {
+            0 if position < source.len() => {
+                source.remove(position);
+            }
+            1 => {
+                let fragment = FRAGMENTS[edit[2] as usize / 4 % FRAGMENTS.len()];
+                source.splice(position..position, fragment.bytes());
+            }
+            2 => {
+                let end = source[position..]
+                    .iter()
+                    .position(|&byte| byte == b'\n')
+                    .map_or(source.len(), |end| position + end);
+                source.drain(position..end);
+            }
+            _ => source.insert(position, edit[2] / 4 + b' '),
+        }
+    }
+
+    let source = String::from_utf8_lossy(&source);
+    if let Ok(tokens) = tokenize(&source) {
+        if let Ok(raw_ogkr) = parse_tokens(tokens) {
+            let _ = parse_raw_ogkr(raw_ogkr);
+        }
+    }
+});
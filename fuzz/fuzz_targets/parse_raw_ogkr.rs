@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ogkr::{
+    lex::tokenize,
+    parse::{analysis::parse_raw_ogkr, raw::parse_tokens},
+};
+
+fuzz_target!(|data: &[u8]| {
+    let source = String::from_utf8_lossy(data);
+    if let Ok(tokens) = tokenize(&source) {
+        if let Ok(raw_ogkr) = parse_tokens(tokens) {
+            let _ = parse_raw_ogkr(raw_ogkr);
+        }
+    }
+});
@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ogkr::{lex::tokenize, parse::raw::parse_tokens};
+
+fuzz_target!(|data: &[u8]| {
+    let source = String::from_utf8_lossy(data);
+    if let Ok(tokens) = tokenize(&source) {
+        let _ = parse_tokens(tokens);
+    }
+});
@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ogkr::lex::{tokenize, tokenize_with_options, LexOptions};
+
+fuzz_target!(|data: &[u8]| {
+    let source = String::from_utf8_lossy(data);
+    let _ = tokenize(&source);
+    let _ = tokenize_with_options(
+        &source,
+        &LexOptions::default().case_insensitive().lenient(),
+    );
+});
@@ -0,0 +1,582 @@
+//! Programmatic chart construction.
+
+use thiserror::Error;
+
+use crate::{
+    lex::command::*,
+    parse::{
+        analysis::{Ogkr, Resolution},
+        raw::{BeamSection, LaneSection, ObliqueBeamSection, RawOgkr, WallSection},
+        ParseError,
+    },
+    write::write_raw_ogkr,
+};
+
+impl Ogkr {
+    /// Minimal playable chart, see [`ChartBuilder::empty_template`].
+    pub fn empty_template(bpm: f32, meter: MeterDefinition, measures: u32) -> Self {
+        ChartBuilder::empty_template(bpm, meter, measures)
+            .build_ogkr()
+            .expect("template chart is valid")
+    }
+}
+
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Error)]
+pub enum BuildError {
+    #[error("bullet palette {0} already exists")]
+    DuplicatePaletteId(String),
+    #[error("bullet palette {0} does not exist")]
+    UnknownPaletteId(String),
+    #[error("bullet palette {id} has invalid speed {speed}")]
+    InvalidPaletteSpeed { id: String, speed: f32 },
+    #[error("bullet palette {0} mixes the legacy damage type syntax with size, type or offset")]
+    MixedPaletteSyntax(String),
+    #[error("bullet palette {0} does not use the same syntax as the other palettes of the chart")]
+    InconsistentPaletteSyntax(String),
+    #[error("bullet using palette {0} requires a damage type")]
+    MissingDamageType(String),
+    #[error("bullet palette {0} does not target the player")]
+    UnaimedPalette(String),
+    #[error("bullet palette {id} does not use the syntax of version {version}")]
+    PaletteSyntaxForVersion { id: String, version: Version },
+    #[error("section {0} has fewer than two points")]
+    IncompleteSection(u32),
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+}
+
+pub type Result<T> = std::result::Result<T, BuildError>;
+
+/// Builds a [`BulletPalette`] command.
+///
+/// Palettes either use the legacy syntax, only specifying a damage type, or the current syntax
+/// specifying size, bullet type and random offset, leaving the damage type to each bullet.
+#[derive(Clone, Debug)]
+pub struct BulletPaletteBuilder {
+    id: String,
+    shooter: BulletShooter,
+    target: BulletTarget,
+    target_x_offset: i32,
+    speed: f32,
+    size: Option<BulletSize>,
+    ty: Option<BulletType>,
+    random_position_offset: Option<i32>,
+    damage_type: Option<BulletDamageType>,
+}
+
+impl BulletPaletteBuilder {
+    /// Palette shooting from the center at the player with a speed of 1.
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            shooter: BulletShooter::Center,
+            target: BulletTarget::Player,
+            target_x_offset: 0,
+            speed: 1.0,
+            size: None,
+            ty: None,
+            random_position_offset: None,
+            damage_type: None,
+        }
+    }
+
+    pub fn shooter(mut self, shooter: BulletShooter) -> Self {
+        self.shooter = shooter;
+        self
+    }
+
+    pub fn target(mut self, target: BulletTarget) -> Self {
+        self.target = target;
+        self
+    }
+
+    pub fn target_x_offset(mut self, offset: i32) -> Self {
+        self.target_x_offset = offset;
+        self
+    }
+
+    pub fn speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn size(mut self, size: BulletSize) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    pub fn bullet_type(mut self, ty: BulletType) -> Self {
+        self.ty = Some(ty);
+        self
+    }
+
+    pub fn random_position_offset(mut self, offset: i32) -> Self {
+        self.random_position_offset = Some(offset);
+        self
+    }
+
+    /// Uses the legacy syntax, bullets of this palette do not need their own damage type.
+    pub fn damage_type(mut self, damage_type: BulletDamageType) -> Self {
+        self.damage_type = Some(damage_type);
+        self
+    }
+
+    pub fn build(self) -> Result<BulletPalette> {
+        if !self.speed.is_finite() || self.speed <= 0.0 {
+            return Err(BuildError::InvalidPaletteSpeed {
+                id: self.id,
+                speed: self.speed,
+            });
+        }
+
+        let is_current_syntax =
+            self.size.is_some() || self.ty.is_some() || self.random_position_offset.is_some();
+        if self.damage_type.is_some() && is_current_syntax {
+            return Err(BuildError::MixedPaletteSyntax(self.id));
+        }
+
+        let (size, ty, random_position_offset) = if self.damage_type.is_some() {
+            (None, None, None)
+        } else {
+            (
+                Some(self.size.unwrap_or(BulletSize::Normal)),
+                Some(self.ty.unwrap_or(BulletType::Circle)),
+                Some(self.random_position_offset.unwrap_or(0)),
+            )
+        };
+
+        Ok(BulletPalette {
+            id: self.id,
+            shooter: self.shooter,
+            target_x_offset: self.target_x_offset,
+            target: self.target,
+            speed: self.speed.to_bits(),
+            size,
+            ty,
+            random_position_offset,
+            damage_type: self.damage_type,
+        })
+    }
+}
+
+/// Builds a chart command by command.
+#[derive(Clone, Debug, Default)]
+pub struct ChartBuilder {
+    raw: RawOgkr,
+    /// Lowest IDs not handed out by the `alloc_*_id` methods yet.
+    next_lane_id: u64,
+    next_beam_id: u64,
+    next_oblique_beam_id: u64,
+}
+
+impl ChartBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts from a minimal playable chart of `measures` measures, at least one, at a constant
+    /// `bpm` and `meter`. It has straight walls at both sides of the track, a center lane going
+    /// along them, and enemy waves splitting the chart in thirds.
+    pub fn empty_template(bpm: f32, meter: MeterDefinition, measures: u32) -> Self {
+        let measures = measures.max(1);
+        let start = CommandTime::default();
+        let end = CommandTime {
+            measure: measures,
+            offset: 0,
+        };
+
+        let mut builder = Self::new();
+        builder.raw.header.bpm_definition = Some(BpmDefinition {
+            first: bpm.to_bits(),
+            common: bpm.to_bits(),
+            minimum: bpm.to_bits(),
+            maximum: bpm.to_bits(),
+        });
+        builder.raw.header.meter_definition = Some(meter);
+        builder.raw.composition.bpm_first = bpm.to_bits();
+        builder.raw.composition.meter_first = meter;
+
+        let mut alloc_lane_id = || builder.alloc_lane_id().expect("template has no lanes yet");
+        let (left_wall, right_wall, center_lane) =
+            (alloc_lane_id(), alloc_lane_id(), alloc_lane_id());
+        builder
+            .version(1, 8, 0)
+            .resolution(Resolution::DEFAULT_TICKS, Resolution::DEFAULT_X)
+            .bpm(start, bpm)
+            .meter(start, meter.num_beats, meter.note_value)
+            .wall(WallSide::Left, left_wall, [(start, -24), (end, -24)])
+            .wall(WallSide::Right, right_wall, [(start, 24), (end, 24)])
+            .lane(LaneKind::Center, center_lane, [(start, 0), (end, 0)])
+            .enemy_wave(start, EnemyWave::Wave1)
+            .enemy_wave(
+                CommandTime {
+                    measure: measures / 3,
+                    offset: 0,
+                },
+                EnemyWave::Wave2,
+            )
+            .enemy_wave(
+                CommandTime {
+                    measure: measures * 2 / 3,
+                    offset: 0,
+                },
+                EnemyWave::Boss,
+            );
+        builder
+    }
+
+    /// Continues building from an existing chart.
+    pub fn from_raw(raw: RawOgkr) -> Self {
+        Self {
+            raw,
+            ..Default::default()
+        }
+    }
+
+    /// Returns an ID for a new wall, lane or enemy lane, unused by every section added so far
+    /// and different from the IDs returned before, or `None` once every ID up to `u32::MAX` is
+    /// taken.
+    pub fn alloc_lane_id(&mut self) -> Option<u32> {
+        alloc(&mut self.next_lane_id, self.raw.track.next_lane_id())
+    }
+
+    /// Returns an ID for a new beam, see [`ChartBuilder::alloc_lane_id`].
+    pub fn alloc_beam_id(&mut self) -> Option<u32> {
+        alloc(&mut self.next_beam_id, self.raw.track.next_beam_id())
+    }
+
+    /// Returns an ID for a new oblique beam, see [`ChartBuilder::alloc_lane_id`].
+    pub fn alloc_oblique_beam_id(&mut self) -> Option<u32> {
+        alloc(
+            &mut self.next_oblique_beam_id,
+            self.raw.track.next_oblique_beam_id(),
+        )
+    }
+
+    pub fn version(&mut self, major: u32, minor: u32, release: u32) -> &mut Self {
+        self.raw.header.version = Some(Version {
+            major,
+            minor,
+            release,
+        });
+        self
+    }
+
+    pub fn creator(&mut self, name: impl Into<String>) -> &mut Self {
+        self.raw.header.set_creator(name);
+        self
+    }
+
+    pub fn resolution(&mut self, ticks: u32, x: u32) -> &mut Self {
+        self.raw.header.tick_resolution = Some(TickResolution { resolution: ticks });
+        self.raw.header.x_resolution = Some(XResolution { resolution: x });
+        self
+    }
+
+    pub fn bpm(&mut self, time: CommandTime, bpm: f32) -> &mut Self {
+        self.raw.composition.bpm_changes.push(BpmChange {
+            time,
+            bpm: bpm.to_bits(),
+        });
+        self
+    }
+
+    pub fn meter(&mut self, time: CommandTime, num_beats: u32, note_value: u32) -> &mut Self {
+        self.raw.composition.meter_changes.push(MeterChange {
+            time,
+            num_beats,
+            note_value,
+        });
+        self
+    }
+
+    pub fn soflan(&mut self, time: CommandTime, duration: u32, speed_multiplier: f32) -> &mut Self {
+        self.raw.composition.soflans.push(Soflan {
+            time,
+            duration,
+            current_speed_multiplier: speed_multiplier.to_bits(),
+        });
+        self
+    }
+
+    pub fn enemy_wave(&mut self, time: CommandTime, wave: EnemyWave) -> &mut Self {
+        let waves = &mut self.raw.enemy_wave_assignment;
+        match wave {
+            EnemyWave::Wave1 => waves.wave_1 = Some(time),
+            EnemyWave::Wave2 => waves.wave_2 = Some(time),
+            EnemyWave::Boss => waves.boss = Some(time),
+        }
+        self
+    }
+
+    /// Adds a bullet palette, all palettes of a chart have to use the same syntax.
+    pub fn bullet_palette(&mut self, palette: BulletPaletteBuilder) -> Result<&mut Self> {
+        let palette = palette.build()?;
+        let palettes = &self.raw.bullet_pallete_list;
+
+        if palettes.iter().any(|p| p.id == palette.id) {
+            return Err(BuildError::DuplicatePaletteId(palette.id));
+        }
+        if palettes
+            .first()
+            .is_some_and(|p| p.damage_type.is_some() != palette.damage_type.is_some())
+        {
+            return Err(BuildError::InconsistentPaletteSyntax(palette.id));
+        }
+
+        self.raw.bullet_pallete_list.push(palette);
+        Ok(self)
+    }
+
+    /// Adds a bullet, `damage_type` may only be omitted for palettes using the legacy syntax.
+    pub fn bullet(
+        &mut self,
+        palette_id: &str,
+        time: CommandTime,
+        x_position: i32,
+        damage_type: Option<BulletDamageType>,
+    ) -> Result<&mut Self> {
+        let palette = self
+            .raw
+            .bullet_pallete_list
+            .iter()
+            .find(|p| p.id == palette_id)
+            .ok_or_else(|| BuildError::UnknownPaletteId(palette_id.to_string()))?;
+        if damage_type.is_none() && palette.damage_type.is_none() {
+            return Err(BuildError::MissingDamageType(palette_id.to_string()));
+        }
+
+        self.raw.bullets.push(Bullet {
+            pallete_id: palette_id.to_string(),
+            time,
+            x_position,
+            damage_type,
+        });
+        Ok(self)
+    }
+
+    /// Adds a left or right wall going through `points`.
+    pub fn wall(
+        &mut self,
+        side: WallSide,
+        group_id: u32,
+        points: impl IntoIterator<Item = (CommandTime, i32)>,
+    ) -> &mut Self {
+        let section = WallSection {
+            group_id,
+            points: points
+                .into_iter()
+                .map(|(time, x_position)| WallPoint {
+                    group_id,
+                    time,
+                    x_position,
+                })
+                .collect(),
+        };
+        match side {
+            WallSide::Left => self.raw.track.walls_left.push(section),
+            WallSide::Right => self.raw.track.walls_right.push(section),
+        }
+        self
+    }
+
+    /// Adds a lane going through `points`.
+    pub fn lane(
+        &mut self,
+        kind: LaneKind,
+        group_id: u32,
+        points: impl IntoIterator<Item = (CommandTime, i32)>,
+    ) -> &mut Self {
+        let section = LaneSection {
+            group_id,
+            points: points
+                .into_iter()
+                .map(|(time, x_position)| LanePoint {
+                    group_id,
+                    time,
+                    x_position,
+                })
+                .collect(),
+        };
+        let track = &mut self.raw.track;
+        match kind {
+            LaneKind::Left => track.lanes_left.push(section),
+            LaneKind::Center => track.lanes_center.push(section),
+            LaneKind::Right => track.lanes_right.push(section),
+            LaneKind::Enemy => track.enemy_lanes.push(section),
+        }
+        self
+    }
+
+    /// Adds a beam going through `points` of position and width.
+    pub fn beam(
+        &mut self,
+        record_id: u32,
+        points: impl IntoIterator<Item = (CommandTime, i32, u32)>,
+    ) -> &mut Self {
+        self.raw.track.beams.push(BeamSection {
+            record_id,
+            points: points
+                .into_iter()
+                .map(|(time, x_position, width)| BeamPoint {
+                    record_id,
+                    time,
+                    x_position,
+                    width,
+                })
+                .collect(),
+        });
+        self
+    }
+
+    /// Adds an oblique beam going through `points` of position, width and shooter offset.
+    pub fn oblique_beam(
+        &mut self,
+        record_id: u32,
+        points: impl IntoIterator<Item = (CommandTime, i32, u32, i32)>,
+    ) -> &mut Self {
+        self.raw.track.oblique_beams.push(ObliqueBeamSection {
+            record_id,
+            points: points
+                .into_iter()
+                .map(
+                    |(time, x_position, width, shoot_position_x_offset)| ObliqueBeamPoint {
+                        record_id,
+                        time,
+                        x_position,
+                        width,
+                        shoot_position_x_offset,
+                    },
+                )
+                .collect(),
+        });
+        self
+    }
+
+    pub fn tap(&mut self, tap: Tap, is_critical: bool) -> &mut Self {
+        let notes = &mut self.raw.notes;
+        if is_critical {
+            notes.critical_taps.push(tap);
+        } else {
+            notes.taps.push(tap);
+        }
+        self
+    }
+
+    pub fn hold(&mut self, hold: Hold, is_critical: bool) -> &mut Self {
+        let notes = &mut self.raw.notes;
+        if is_critical {
+            notes.critical_holds.push(hold);
+        } else {
+            notes.holds.push(hold);
+        }
+        self
+    }
+
+    pub fn flick(&mut self, flick: Flick, is_critical: bool) -> &mut Self {
+        let notes = &mut self.raw.notes;
+        if is_critical {
+            notes.critical_flicks.push(flick);
+        } else {
+            notes.flicks.push(flick);
+        }
+        self
+    }
+
+    pub fn bell(&mut self, bell: Bell) -> &mut Self {
+        self.raw.notes.bells.push(bell);
+        self
+    }
+
+    pub fn raw(&self) -> &RawOgkr {
+        &self.raw
+    }
+
+    /// Returns the built chart, checking that it can be written and read back: palettes have to
+    /// use the syntax of the `VERSION` if there is one, and sections need a start and an end.
+    pub fn build(&self) -> Result<RawOgkr> {
+        if let Some(version) = self.raw.header.version {
+            let current_syntax = version.supports_bullet_damage_in_blt();
+            if let Some(palette) = self
+                .raw
+                .bullet_pallete_list
+                .iter()
+                .find(|palette| palette.damage_type.is_some() == current_syntax)
+            {
+                return Err(BuildError::PaletteSyntaxForVersion {
+                    id: palette.id.clone(),
+                    version,
+                });
+            }
+        }
+
+        let track = &self.raw.track;
+        let walls = track.walls_left.iter().chain(&track.walls_right);
+        let lanes = [
+            &track.lanes_left,
+            &track.lanes_center,
+            &track.lanes_right,
+            &track.enemy_lanes,
+        ]
+        .into_iter()
+        .flatten();
+        let incomplete = walls
+            .map(|wall| (wall.group_id, wall.points.len()))
+            .chain(lanes.map(|lane| (lane.group_id, lane.points.len())))
+            .chain(
+                track
+                    .colorful_lanes
+                    .iter()
+                    .map(|lane| (lane.group_id, lane.points.len())),
+            )
+            .chain(
+                track
+                    .beams
+                    .iter()
+                    .map(|beam| (beam.record_id, beam.points.len())),
+            )
+            .chain(
+                track
+                    .oblique_beams
+                    .iter()
+                    .map(|beam| (beam.record_id, beam.points.len())),
+            )
+            .find(|(_, points)| *points < 2);
+        if let Some((id, _)) = incomplete {
+            return Err(BuildError::IncompleteSection(id));
+        }
+
+        Ok(self.raw.clone())
+    }
+
+    /// Analyzes the built chart.
+    pub fn build_ogkr(&self) -> Result<Ogkr> {
+        Ok(Ogkr::from_raw(self.build()?)?)
+    }
+
+    /// Serializes the built chart into the text format.
+    pub fn write(&self) -> Result<String> {
+        Ok(write_raw_ogkr(&self.build()?))
+    }
+}
+
+fn alloc(next: &mut u64, unused: Option<u32>) -> Option<u32> {
+    let id = u32::try_from((*next).max(u64::from(unused?))).ok()?;
+    *next = u64::from(id) + 1;
+    Some(id)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WallSide {
+    Left,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LaneKind {
+    Left,
+    Center,
+    Right,
+    Enemy,
+}
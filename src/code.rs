@@ -0,0 +1,142 @@
+//! Stable codes of every error, warning and validation issue of the crate.
+//!
+//! Messages are meant for humans and may change between releases, codes do not. A code keeps its
+//! number once released, and numbers of removed kinds are never reused. Codes are grouped by
+//! hundreds:
+//! * `OGKR00xx`: lexing errors, [`LexError`](crate::lex::LexError).
+//! * `OGKR01xx`: parsing errors, [`ParseError`](crate::parse::ParseError).
+//! * `OGKR02xx`: parsing warnings, [`Warning`](crate::parse::Warning).
+//! * `OGKR03xx`: validation issues, [`Issue`](crate::validate::Issue).
+//! * `OGKR04xx`: chart file errors, [`LoadError`](crate::LoadError).
+
+use std::{fmt, str::FromStr};
+
+use thiserror::Error;
+
+macro_rules! error_codes {
+    ($($(#[doc = $doc:literal])+ $name:ident = $number:literal,)+) => {
+        /// Stable identifier of a kind of error, warning or issue, see the
+        /// [module documentation](self).
+        #[non_exhaustive]
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub enum ErrorCode {
+            $($(#[doc = $doc])+ $name,)+
+        }
+
+        impl ErrorCode {
+            /// Every code, sorted by number.
+            pub const ALL: &'static [ErrorCode] = &[$(Self::$name,)+];
+
+            pub fn number(self) -> u16 {
+                match self {
+                    $(Self::$name => $number,)+
+                }
+            }
+
+            /// Short description of the kind, the catalog entry of the code.
+            pub fn description(self) -> &'static str {
+                match self {
+                    $(Self::$name => concat!($($doc),+).trim_ascii(),)+
+                }
+            }
+        }
+    };
+}
+
+error_codes! {
+    /// A line starts with an unknown command.
+    UnknownCommand = 1,
+    /// A command is missing an argument or has an argument of the wrong type.
+    ExpectedToken = 2,
+    /// Content follows the last argument of a command, with strict whitespace.
+    TrailingContent = 3,
+    /// Separators other than single ones between arguments, with strict whitespace.
+    IrregularWhitespace = 4,
+
+    /// Commands are not in the order the format requires.
+    Syntax = 101,
+    /// Commands are well formed but contradict each other or reference missing objects.
+    Semantic = 102,
+    /// A section or object is missing its closing commands.
+    ExpectedCommand = 103,
+    /// A time range ends before it starts.
+    InvalidTimeRange = 104,
+    /// The chart exceeds a limit of the parse options.
+    LimitExceeded = 105,
+
+    /// A lane or wall reuses the ID of another one.
+    DuplicateLaneId = 201,
+    /// A colorful lane reuses the ID of another one.
+    DuplicateColorfulLaneId = 202,
+    /// A beam reuses the ID of another one.
+    DuplicateBeamId = 203,
+    /// An oblique beam reuses the ID of another one.
+    DuplicateObliqueBeamId = 204,
+    /// A note referencing a missing lane was dropped.
+    NoteDroppedMissingLane = 205,
+    /// A synthetic lane was created for notes referencing a missing lane.
+    SyntheticLaneCreated = 206,
+    /// A bullet referencing a missing palette was dropped.
+    BulletDroppedMissingPalette = 207,
+    /// A placeholder palette was created for bullets referencing a missing palette.
+    PlaceholderPaletteCreated = 208,
+    /// A line failed to tokenize in lenient mode and was skipped.
+    SkippedInvalidLine = 210,
+    /// A header command is repeated with a different value.
+    ConflictingHeaderCommand = 211,
+    /// Points of a section were not in time order and were sorted.
+    SectionPointsSorted = 212,
+    /// An offset past the end of its measure was clamped.
+    OffsetClamped = 213,
+    /// A wall missing its end point was ended by the parser.
+    WallEndGenerated = 214,
+
+    /// A point of a section is not strictly after the previous one.
+    UnorderedPoints = 301,
+    /// A hold starts before the previous hold on the same lane ends.
+    OverlappingHolds = 302,
+    /// A soflan starts before the previous one ends.
+    OverlappingSoflans = 303,
+    /// A soflan lasts past the end of the chart.
+    SoflanPastEnd = 304,
+    /// Flicks at the same time and position have opposite directions.
+    ContradictoryFlicks = 305,
+
+    /// The chart file could not be read.
+    FileRead = 401,
+    /// The chart file is not valid UTF-8.
+    InvalidEncoding = 402,
+}
+
+impl ErrorCode {
+    /// Returns the code with the given number.
+    pub fn from_number(number: u16) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|code| code.number() == number)
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "OGKR{:04}", self.number())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Error)]
+#[error("unknown error code {0:?}")]
+pub struct UnknownErrorCode(pub String);
+
+impl FromStr for ErrorCode {
+    type Err = UnknownErrorCode;
+
+    /// Parses codes as displayed, e.g. `OGKR0012`, ignoring ASCII case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.get(..4)
+            .filter(|prefix| prefix.eq_ignore_ascii_case("OGKR"))
+            .and_then(|_| s[4..].parse().ok())
+            .and_then(Self::from_number)
+            .ok_or_else(|| UnknownErrorCode(s.to_string()))
+    }
+}
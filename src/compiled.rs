@@ -0,0 +1,220 @@
+//! Read-only chart snapshot baked for gameplay.
+//!
+//! [`Ogkr`] keeps charts in the shape they are written in, keyed by timing points. Playing a chart
+//! needs millisecond times and screen positions instead, which [`CompiledChart`] computes once
+//! into flat arrays sorted by time.
+
+use std::collections::HashMap;
+
+use crate::{
+    geometry::Geometry,
+    lex::command::{BulletDamageType, FlickDirection},
+    parse::{
+        analysis::{BulletPalette, LaneId, LaneType, Ogkr, PaletteIdRef, TrackPosition},
+        query::NoteRef,
+        timing::{ScrollSegment, ScrollTimeline, TimingConverter},
+    },
+};
+
+/// Chart position baked into times and a normalized horizontal position.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CompiledPoint {
+    /// Milliseconds from the start of the chart.
+    pub ms: f64,
+    /// Milliseconds scrolled at base speed, see [`ScrollTimeline::position_at`].
+    pub scroll: f64,
+    /// 0 at the left end of the x range of the [`Geometry`], 1 at the right end.
+    pub x: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CompiledNoteKind {
+    Tap,
+    Hold,
+    Bell,
+    Flick(FlickDirection),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CompiledNote {
+    pub kind: CompiledNoteKind,
+    pub start: CompiledPoint,
+    /// Same as `start` for notes other than holds.
+    pub end: CompiledPoint,
+    /// Index in [`CompiledChart::lanes`], `None` for bells and flicks.
+    pub lane: Option<u32>,
+    pub is_critical: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CompiledBullet {
+    /// Milliseconds from the start of the chart, negative if shot before the chart starts.
+    pub spawn_ms: f64,
+    /// Normalized horizontal position of the shooter at spawn time.
+    pub spawn_x: f32,
+    pub arrival: CompiledPoint,
+    /// Index in [`CompiledChart::palettes`].
+    pub palette: PaletteIdRef,
+    pub damage_type: BulletDamageType,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompiledLane {
+    pub id: LaneId,
+    pub lane_type: LaneType,
+    /// Sorted by time.
+    pub points: Vec<CompiledPoint>,
+}
+
+/// Chart with every time and position computed ahead, see the [module documentation](self).
+/// Beams are not part of it.
+#[derive(Clone, Debug)]
+pub struct CompiledChart {
+    /// Sorted by start time, notes at the same time in the order of
+    /// [`Notes::all`](crate::parse::analysis::Notes::all).
+    pub notes: Vec<CompiledNote>,
+    /// Sorted by spawn time.
+    pub bullets: Vec<CompiledBullet>,
+    /// Sorted by lane ID.
+    pub lanes: Vec<CompiledLane>,
+    /// Indexed by [`PaletteIdRef`].
+    pub palettes: Vec<BulletPalette>,
+    /// Milliseconds of every click sound, sorted.
+    pub clicks_ms: Vec<f64>,
+    pub scroll: Vec<ScrollSegment>,
+}
+
+impl CompiledChart {
+    pub fn new(ogkr: &Ogkr) -> Self {
+        Self::with_geometry(ogkr, &Geometry::new(ogkr))
+    }
+
+    /// Same as [`CompiledChart::new`], normalizing horizontal positions with `geometry`.
+    pub fn with_geometry(ogkr: &Ogkr, geometry: &Geometry) -> Self {
+        let timing = ogkr.timing();
+        let scroll = ogkr.scroll_timeline();
+        let point = |position: TrackPosition| bake(position, &timing, &scroll, geometry);
+
+        let mut lanes = ogkr.track.lanes_data.values().collect::<Vec<_>>();
+        lanes.sort_by_key(|lane| lane.id.0);
+        let lane_indices = lanes
+            .iter()
+            .enumerate()
+            .map(|(i, lane)| (lane.id, i as u32))
+            .collect::<HashMap<_, _>>();
+        let lanes = lanes
+            .into_iter()
+            .map(|lane| CompiledLane {
+                id: lane.id,
+                lane_type: lane.lane_type,
+                points: lane
+                    .points
+                    .iter()
+                    .map(|&position| point(position))
+                    .collect(),
+            })
+            .collect();
+
+        let notes = ogkr
+            .notes
+            .all()
+            .map(|note| {
+                let (kind, end, lane, is_critical) = match note {
+                    NoteRef::Tap(tap) => (
+                        CompiledNoteKind::Tap,
+                        tap.position,
+                        Some(tap.lane_id),
+                        tap.is_critical,
+                    ),
+                    NoteRef::Hold(hold) => (
+                        CompiledNoteKind::Hold,
+                        hold.end,
+                        Some(hold.lane_id),
+                        hold.is_critical,
+                    ),
+                    NoteRef::Bell(bell) => (CompiledNoteKind::Bell, bell.position, None, false),
+                    NoteRef::Flick(flick) => (
+                        CompiledNoteKind::Flick(flick.direction),
+                        flick.position,
+                        None,
+                        flick.is_critical,
+                    ),
+                };
+                CompiledNote {
+                    kind,
+                    start: point(note.position()),
+                    end: point(end),
+                    lane: lane.and_then(|lane| lane_indices.get(&lane).copied()),
+                    is_critical,
+                }
+            })
+            .collect();
+
+        let mut bullets = ogkr
+            .bullet_spawns()
+            .into_values()
+            .flatten()
+            .map(|spawn| CompiledBullet {
+                spawn_ms: spawn.spawn_ms,
+                spawn_x: geometry.normalize_track_x(spawn.spawn_x),
+                arrival: point(spawn.bullet.position),
+                palette: spawn.bullet.palette,
+                damage_type: spawn.bullet.damage_type,
+            })
+            .collect::<Vec<_>>();
+        // Spawns before the chart starts share the first timing point.
+        bullets.sort_by(|a, b| a.spawn_ms.total_cmp(&b.spawn_ms));
+
+        Self {
+            notes,
+            bullets,
+            lanes,
+            palettes: ogkr.bullets.palettes().to_vec(),
+            clicks_ms: ogkr.click_times_ms(&timing),
+            scroll: scroll.segments().to_vec(),
+        }
+    }
+
+    /// Notes starting from `start_ms` included to `end_ms` excluded.
+    pub fn notes_between(&self, start_ms: f64, end_ms: f64) -> &[CompiledNote] {
+        let start = self.notes.partition_point(|note| note.start.ms < start_ms);
+        let end = self.notes.partition_point(|note| note.start.ms < end_ms);
+        &self.notes[start..end.max(start)]
+    }
+
+    /// Bullets spawning from `start_ms` included to `end_ms` excluded.
+    pub fn bullets_between(&self, start_ms: f64, end_ms: f64) -> &[CompiledBullet] {
+        let start = self
+            .bullets
+            .partition_point(|bullet| bullet.spawn_ms < start_ms);
+        let end = self
+            .bullets
+            .partition_point(|bullet| bullet.spawn_ms < end_ms);
+        &self.bullets[start..end.max(start)]
+    }
+
+    pub fn palette(&self, palette: PaletteIdRef) -> Option<&BulletPalette> {
+        self.palettes.get(palette.index())
+    }
+}
+
+impl Ogkr {
+    /// Bakes this chart for gameplay, see [`CompiledChart`].
+    pub fn compile(&self) -> CompiledChart {
+        CompiledChart::new(self)
+    }
+}
+
+fn bake(
+    position: TrackPosition,
+    timing: &TimingConverter,
+    scroll: &ScrollTimeline,
+    geometry: &Geometry,
+) -> CompiledPoint {
+    let ms = timing.to_ms(position.time);
+    CompiledPoint {
+        ms,
+        scroll: scroll.position_at(ms),
+        x: geometry.normalize_x(position.x),
+    }
+}
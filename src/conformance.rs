@@ -0,0 +1,112 @@
+//! Parsing whole chart libraries, to check how much of them the crate supports.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    parse::{ParseOptions, Warnings},
+    LoadError, Ogkr,
+};
+
+/// Outcome of parsing a single chart file.
+#[derive(Debug)]
+pub struct FileResult {
+    pub path: PathBuf,
+    /// Warnings of the parsed chart, or why it failed to load.
+    pub result: Result<Warnings, LoadError>,
+    /// Time spent reading and parsing the file.
+    pub duration: Duration,
+}
+
+impl FileResult {
+    pub fn is_ok(&self) -> bool {
+        self.result.is_ok()
+    }
+
+    /// Whether the chart parsed without any warning.
+    pub fn is_clean(&self) -> bool {
+        self.result
+            .as_ref()
+            .is_ok_and(|warnings| warnings.is_empty())
+    }
+}
+
+/// Results of [`run_dir`], sorted by path.
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub files: Vec<FileResult>,
+}
+
+impl Summary {
+    /// Number of charts that parsed, with or without warnings.
+    pub fn passed(&self) -> usize {
+        self.files.iter().filter(|file| file.is_ok()).count()
+    }
+
+    /// Number of charts that parsed with warnings.
+    pub fn warned(&self) -> usize {
+        self.files
+            .iter()
+            .filter(|file| file.is_ok() && !file.is_clean())
+            .count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.files.len() - self.passed()
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &FileResult> {
+        self.files.iter().filter(|file| !file.is_ok())
+    }
+
+    pub fn total_duration(&self) -> Duration {
+        self.files.iter().map(|file| file.duration).sum()
+    }
+}
+
+/// Parses every `.ogkr` file within `path` and its subdirectories. Fails only when a directory
+/// cannot be listed, chart errors are part of the summary.
+pub fn run_dir(path: impl AsRef<Path>) -> io::Result<Summary> {
+    run_dir_with_options(path, &ParseOptions::default())
+}
+
+/// Same as [`run_dir`], using custom parsing options.
+pub fn run_dir_with_options(path: impl AsRef<Path>, options: &ParseOptions) -> io::Result<Summary> {
+    let mut paths = vec![];
+    collect_charts(path.as_ref(), &mut paths)?;
+    paths.sort();
+
+    let files = paths
+        .into_iter()
+        .map(|path| {
+            let start = Instant::now();
+            let result = Ogkr::from_path_with_options(&path, options).map(|ogkr| ogkr.warnings);
+            FileResult {
+                path,
+                result,
+                duration: start.elapsed(),
+            }
+        })
+        .collect();
+
+    Ok(Summary { files })
+}
+
+fn collect_charts(dir: &Path, paths: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            collect_charts(&path, paths)?;
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "ogkr")
+        {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,81 @@
+//! Destination of the messages logged while lexing and parsing.
+//!
+//! Messages go to the `log` crate by default. Embedders without a `log` backend, e.g. on wasm or
+//! behind an FFI, can capture them with [`set_sink`]. Warnings are also returned in
+//! [`Warnings`](crate::parse::Warnings), the sink only sees them as text.
+
+use std::{
+    fmt,
+    sync::{Arc, RwLock},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warn,
+    Debug,
+}
+
+impl From<Level> for log::Level {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::Error => log::Level::Error,
+            Level::Warn => log::Level::Warn,
+            Level::Debug => log::Level::Debug,
+        }
+    }
+}
+
+/// Receives the diagnostic messages of the crate, see the [module documentation](self).
+pub trait DiagnosticsSink: Send + Sync {
+    fn emit(&self, level: Level, message: fmt::Arguments<'_>);
+}
+
+/// Default sink, forwarding every message to the `log` crate.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LogSink;
+
+impl DiagnosticsSink for LogSink {
+    fn emit(&self, level: Level, message: fmt::Arguments<'_>) {
+        log::log!(level.into(), "{}", message);
+    }
+}
+
+static SINK: RwLock<Option<Arc<dyn DiagnosticsSink>>> = RwLock::new(None);
+
+/// Sends the messages of every following parse to `sink`, for the whole process.
+pub fn set_sink(sink: Arc<dyn DiagnosticsSink>) {
+    *SINK.write().unwrap_or_else(|e| e.into_inner()) = Some(sink);
+}
+
+/// Goes back to forwarding messages to [`LogSink`].
+pub fn reset_sink() {
+    *SINK.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+pub(crate) fn emit(level: Level, message: fmt::Arguments<'_>) {
+    match &*SINK.read().unwrap_or_else(|e| e.into_inner()) {
+        Some(sink) => sink.emit(level, message),
+        None => LogSink.emit(level, message),
+    }
+}
+
+macro_rules! error {
+    ($($arg:tt)+) => {
+        $crate::diagnostics::emit($crate::diagnostics::Level::Error, format_args!($($arg)+))
+    };
+}
+
+macro_rules! warning {
+    ($($arg:tt)+) => {
+        $crate::diagnostics::emit($crate::diagnostics::Level::Warn, format_args!($($arg)+))
+    };
+}
+
+macro_rules! debug {
+    ($($arg:tt)+) => {
+        $crate::diagnostics::emit($crate::diagnostics::Level::Debug, format_args!($($arg)+))
+    };
+}
+
+pub(crate) use {debug, error, warning};
@@ -0,0 +1,79 @@
+use std::fmt::{self, Write};
+
+use crate::parse::analysis::{LaneId, Ogkr, TrackPosition};
+
+const HEADER: &str = "type,time_ms,measure,offset,x,lane,critical";
+
+/// Writes one row per judgeable object of `ogkr`, sorted by time. Holds have a row for their start
+/// and one for their end.
+pub fn csv(ogkr: &Ogkr) -> String {
+    let mut out = String::new();
+    write_csv(&mut out, ogkr).expect("writing to a String cannot fail");
+    out
+}
+
+/// Same as [`csv`], writing into any [`fmt::Write`].
+pub fn write_csv(out: &mut impl Write, ogkr: &Ogkr) -> fmt::Result {
+    struct Row {
+        ty: &'static str,
+        position: TrackPosition,
+        lane: Option<LaneId>,
+        is_critical: bool,
+    }
+
+    let notes = &ogkr.notes;
+    let taps = notes.all_taps().map(|tap| Row {
+        ty: "tap",
+        position: tap.position,
+        lane: Some(tap.lane_id),
+        is_critical: tap.is_critical,
+    });
+    let holds = notes.all_holds().flat_map(|hold| {
+        [("hold", hold.start), ("hold_end", hold.end)].map(|(ty, position)| Row {
+            ty,
+            position,
+            lane: Some(hold.lane_id),
+            is_critical: hold.is_critical,
+        })
+    });
+    let flicks = notes.all_flicks().map(|flick| Row {
+        ty: "flick",
+        position: flick.position,
+        lane: None,
+        is_critical: flick.is_critical,
+    });
+    let bells = notes.all_bells().map(|bell| Row {
+        ty: "bell",
+        position: bell.position,
+        lane: None,
+        is_critical: false,
+    });
+
+    let mut rows = taps
+        .chain(holds)
+        .chain(flicks)
+        .chain(bells)
+        .collect::<Vec<_>>();
+    rows.sort_by_key(|row| row.position.time);
+
+    let timing = ogkr.timing();
+    let resolution = timing.resolution();
+    writeln!(out, "{HEADER}")?;
+    for row in rows {
+        write!(
+            out,
+            "{},{:.3},{},{},{},",
+            row.ty,
+            timing.to_ms(row.position.time),
+            row.position.time.measure,
+            row.position.time.beat_offset,
+            resolution.x_of(row.position.x),
+        )?;
+        if let Some(lane) = row.lane {
+            write!(out, "{}", lane.0)?;
+        }
+        writeln!(out, ",{}", row.is_critical)?;
+    }
+
+    Ok(())
+}
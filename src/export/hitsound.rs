@@ -0,0 +1,110 @@
+use crate::parse::analysis::{Ogkr, TimingPoint};
+
+/// Sample played for a [`Hitsound`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HitsoundSample {
+    Tap,
+    CriticalTap,
+    Hold,
+    CriticalHold,
+    /// Played repeatedly while a hold is held.
+    HoldTick,
+    Flick,
+    CriticalFlick,
+    Bell,
+    /// Click sound placed by the chart.
+    Click,
+}
+
+impl HitsoundSample {
+    /// Name of the sample, suitable for looking up audio files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Tap => "tap",
+            Self::CriticalTap => "critical_tap",
+            Self::Hold => "hold",
+            Self::CriticalHold => "critical_hold",
+            Self::HoldTick => "hold_tick",
+            Self::Flick => "flick",
+            Self::CriticalFlick => "critical_flick",
+            Self::Bell => "bell",
+            Self::Click => "click",
+        }
+    }
+}
+
+/// Sample to play at a given time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hitsound {
+    /// Milliseconds from the start of the chart.
+    pub ms: f64,
+    pub sample: HitsoundSample,
+}
+
+/// Number of hold ticks per measure used by [`hitsounds`] by default, one every eighth note in 4/4.
+pub const DEFAULT_HOLD_TICKS_PER_MEASURE: u32 = 8;
+
+/// Returns the samples to play for every note, bell and click sound of `ogkr`, sorted by time.
+///
+/// Holds play a tick sound `hold_ticks_per_measure` times per measure after their start, up to but
+/// excluding their end. No ticks are generated when it is 0.
+pub fn hitsounds(ogkr: &Ogkr, hold_ticks_per_measure: u32) -> Vec<Hitsound> {
+    let timing = ogkr.timing();
+    let resolution = timing.resolution();
+    let hitsound = |time, sample| Hitsound {
+        ms: timing.to_ms(time),
+        sample,
+    };
+    let notes = &ogkr.notes;
+
+    let mut hitsounds = Vec::new();
+    for tap in notes.all_taps() {
+        let sample = if tap.is_critical {
+            HitsoundSample::CriticalTap
+        } else {
+            HitsoundSample::Tap
+        };
+        hitsounds.push(hitsound(tap.position.time, sample));
+    }
+    for hold in notes.all_holds() {
+        let sample = if hold.is_critical {
+            HitsoundSample::CriticalHold
+        } else {
+            HitsoundSample::Hold
+        };
+        hitsounds.push(hitsound(hold.start.time, sample));
+
+        let interval = match hold_ticks_per_measure {
+            0 => continue,
+            n => (resolution.ticks as u64 / n as u64).max(1),
+        };
+        let ticks_per_measure = resolution.ticks.max(1) as u64;
+        let end = resolution.ticks_at(hold.end.time);
+        let mut tick = resolution.ticks_at(hold.start.time) + interval;
+        while tick < end {
+            let time = TimingPoint::new(
+                (tick / ticks_per_measure) as u32,
+                (tick % ticks_per_measure) as u32,
+            );
+            hitsounds.push(hitsound(time, HitsoundSample::HoldTick));
+            tick += interval;
+        }
+    }
+    for flick in notes.all_flicks() {
+        let sample = if flick.is_critical {
+            HitsoundSample::CriticalFlick
+        } else {
+            HitsoundSample::Flick
+        };
+        hitsounds.push(hitsound(flick.position.time, sample));
+    }
+    for bell in notes.all_bells() {
+        hitsounds.push(hitsound(bell.position.time, HitsoundSample::Bell));
+    }
+    for click in &ogkr.click_sounds {
+        hitsounds.push(hitsound(click.time, HitsoundSample::Click));
+    }
+
+    hitsounds.sort_by(|a, b| a.ms.total_cmp(&b.ms));
+    hitsounds
+}
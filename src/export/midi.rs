@@ -0,0 +1,127 @@
+use std::io::{self, Write};
+
+use crate::parse::analysis::Ogkr;
+
+/// MIDI ticks per quarter note, with a constant tempo of one quarter note per second a tick lasts
+/// exactly one millisecond.
+const TICKS_PER_QUARTER: u16 = 1000;
+const MICROSECONDS_PER_QUARTER: u32 = 1_000_000;
+
+/// Length of notes without a duration, in milliseconds.
+const NOTE_LENGTH_MS: f64 = 50.0;
+
+/// Pitches objects are mapped to.
+pub mod pitch {
+    pub const TAP: u8 = 60;
+    pub const CRITICAL_TAP: u8 = 61;
+    pub const HOLD: u8 = 62;
+    pub const CRITICAL_HOLD: u8 = 63;
+    pub const FLICK: u8 = 64;
+    pub const CRITICAL_FLICK: u8 = 65;
+    pub const BELL: u8 = 67;
+}
+
+/// Converts the judgment timeline of `ogkr` into a single track MIDI file. Time is kept in
+/// milliseconds, so events line up with the song regardless of BPM changes.
+pub fn midi(ogkr: &Ogkr) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_midi(&mut out, ogkr).expect("writing to a Vec cannot fail");
+    out
+}
+
+/// Same as [`midi`], writing into any [`io::Write`].
+pub fn write_midi(out: &mut impl Write, ogkr: &Ogkr) -> io::Result<()> {
+    let timing = ogkr.timing();
+    let ms = |time| timing.to_ms(time);
+    let notes = &ogkr.notes;
+
+    let taps = notes.all_taps().map(|tap| {
+        let pitch = if tap.is_critical {
+            pitch::CRITICAL_TAP
+        } else {
+            pitch::TAP
+        };
+        let start = ms(tap.position.time);
+        (pitch, start, start + NOTE_LENGTH_MS)
+    });
+    let holds = notes.all_holds().map(|hold| {
+        let pitch = if hold.is_critical {
+            pitch::CRITICAL_HOLD
+        } else {
+            pitch::HOLD
+        };
+        let start = ms(hold.start.time);
+        (pitch, start, ms(hold.end.time).max(start + NOTE_LENGTH_MS))
+    });
+    let flicks = notes.all_flicks().map(|flick| {
+        let pitch = if flick.is_critical {
+            pitch::CRITICAL_FLICK
+        } else {
+            pitch::FLICK
+        };
+        let start = ms(flick.position.time);
+        (pitch, start, start + NOTE_LENGTH_MS)
+    });
+    let bells = notes.all_bells().map(|bell| {
+        let start = ms(bell.position.time);
+        (pitch::BELL, start, start + NOTE_LENGTH_MS)
+    });
+
+    // (tick, is note on, pitch), note offs sort before note ons at the same tick.
+    let mut events = taps
+        .chain(holds)
+        .chain(flicks)
+        .chain(bells)
+        .flat_map(|(pitch, start, end)| {
+            [
+                (start.round() as u32, true, pitch),
+                (end.round() as u32, false, pitch),
+            ]
+        })
+        .collect::<Vec<_>>();
+    events.sort();
+
+    let mut track = Vec::new();
+    // Tempo.
+    write_variable_length(&mut track, 0);
+    track.extend_from_slice(&[0xFF, 0x51, 0x03]);
+    track.extend_from_slice(&MICROSECONDS_PER_QUARTER.to_be_bytes()[1..]);
+
+    let mut previous_tick = 0;
+    for (tick, is_note_on, pitch) in events {
+        write_variable_length(&mut track, tick - previous_tick);
+        previous_tick = tick;
+        let (status, velocity) = if is_note_on { (0x90, 100) } else { (0x80, 0) };
+        track.extend_from_slice(&[status, pitch, velocity]);
+    }
+    // End of track.
+    write_variable_length(&mut track, 0);
+    track.extend_from_slice(&[0xFF, 0x2F, 0x00]);
+
+    out.write_all(b"MThd")?;
+    out.write_all(&6u32.to_be_bytes())?;
+    // Format 0, one track.
+    out.write_all(&0u16.to_be_bytes())?;
+    out.write_all(&1u16.to_be_bytes())?;
+    out.write_all(&TICKS_PER_QUARTER.to_be_bytes())?;
+
+    out.write_all(b"MTrk")?;
+    out.write_all(&(track.len() as u32).to_be_bytes())?;
+    out.write_all(&track)
+}
+
+fn write_variable_length(out: &mut Vec<u8>, mut value: u32) {
+    let mut bytes = [0u8; 5];
+    let mut len = 0;
+    loop {
+        bytes[len] = (value & 0x7F) as u8;
+        len += 1;
+        value >>= 7;
+        if value == 0 {
+            break;
+        }
+    }
+    for (i, byte) in bytes[..len].iter().enumerate().rev() {
+        out.push(if i > 0 { byte | 0x80 } else { *byte });
+    }
+}
@@ -0,0 +1,13 @@
+//! Conversion of parsed charts into other formats.
+
+mod csv;
+mod hitsound;
+#[cfg(feature = "midi")]
+mod midi;
+mod vsrg;
+
+pub use csv::{csv, write_csv};
+pub use hitsound::{hitsounds, Hitsound, HitsoundSample, DEFAULT_HOLD_TICKS_PER_MEASURE};
+#[cfg(feature = "midi")]
+pub use midi::{midi, pitch, write_midi};
+pub use vsrg::{VsrgChart, VsrgDropped, VsrgError, VsrgNote};
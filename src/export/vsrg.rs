@@ -0,0 +1,174 @@
+use std::fmt::{self, Write};
+
+use thiserror::Error;
+
+use crate::parse::analysis::{Ogkr, TrackPosition};
+
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Error)]
+pub enum VsrgError {
+    #[error("{0} columns is not between 1 and {max}", max = VsrgChart::MAX_COLUMNS)]
+    InvalidColumns(u32),
+}
+
+/// Note of a column based chart.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VsrgNote {
+    pub column: u32,
+    /// Milliseconds from the start of the chart.
+    pub start_ms: f64,
+    /// End of a hold, in milliseconds from the start of the chart.
+    pub end_ms: Option<f64>,
+}
+
+/// Objects that have no equivalent in a column based chart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct VsrgDropped {
+    pub flicks: usize,
+    pub bells: usize,
+    pub bullets: usize,
+    pub beams: usize,
+    /// Taps and holds landing on a column that is still occupied by another note.
+    pub overlapping_notes: usize,
+}
+
+/// Rhythm layer of a chart quantized into columns, as played by vertical scrolling rhythm games.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VsrgChart {
+    pub columns: u32,
+    /// Sorted by start time, then column.
+    pub notes: Vec<VsrgNote>,
+    /// BPM changes as milliseconds from the start of the chart and BPM.
+    pub bpm_changes: Vec<(f64, f32)>,
+    pub dropped: VsrgDropped,
+}
+
+impl VsrgChart {
+    /// Most columns a chart can be quantized into, the most keys osu!mania supports.
+    pub const MAX_COLUMNS: u32 = 18;
+
+    /// Quantizes taps and holds of `ogkr` into `columns` columns, which evenly divide the
+    /// horizontal range covered by the notes of the chart. Everything else is dropped and counted
+    /// in [`VsrgChart::dropped`].
+    ///
+    /// Fails if `columns` is 0 or above [`VsrgChart::MAX_COLUMNS`].
+    pub fn new(ogkr: &Ogkr, columns: u32) -> Result<Self, VsrgError> {
+        if !(1..=Self::MAX_COLUMNS).contains(&columns) {
+            return Err(VsrgError::InvalidColumns(columns));
+        }
+        let timing = ogkr.timing();
+        let resolution = timing.resolution();
+        let notes = &ogkr.notes;
+
+        let objects = notes
+            .all_taps()
+            .map(|tap| (tap.position, None))
+            .chain(notes.all_holds().map(|hold| (hold.start, Some(hold.end))))
+            .collect::<Vec<(TrackPosition, Option<TrackPosition>)>>();
+
+        let xs = objects
+            .iter()
+            .map(|(start, _)| resolution.x_of(start.x))
+            .collect::<Vec<_>>();
+        let x_min = xs.iter().copied().reduce(f32::min).unwrap_or(0.0);
+        let x_max = xs.iter().copied().reduce(f32::max).unwrap_or(0.0);
+        let column_of = |x: f32| {
+            if x_max > x_min {
+                (((x - x_min) / (x_max - x_min) * columns as f32) as u32).min(columns - 1)
+            } else {
+                columns / 2
+            }
+        };
+
+        let mut candidates = objects
+            .iter()
+            .zip(xs)
+            .map(|((start, end), x)| VsrgNote {
+                column: column_of(x),
+                start_ms: timing.to_ms(start.time),
+                end_ms: end.map(|end| timing.to_ms(end.time)),
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by(|a, b| {
+            a.start_ms
+                .total_cmp(&b.start_ms)
+                .then(a.column.cmp(&b.column))
+        });
+
+        let mut dropped = VsrgDropped {
+            flicks: notes.all_flicks().count(),
+            bells: notes.all_bells().count(),
+            bullets: ogkr.bullets.all_bullets().count(),
+            beams: ogkr.track.beams_data.len() + ogkr.track.oblique_beams_data.len(),
+            overlapping_notes: 0,
+        };
+
+        // Time until which each column is occupied.
+        let mut occupied_until = vec![f64::NEG_INFINITY; columns as usize];
+        let mut vsrg_notes = Vec::with_capacity(candidates.len());
+        for note in candidates {
+            let until = &mut occupied_until[note.column as usize];
+            if note.start_ms <= *until {
+                dropped.overlapping_notes += 1;
+                continue;
+            }
+            *until = note.end_ms.unwrap_or(note.start_ms);
+            vsrg_notes.push(note);
+        }
+
+        let bpm_changes = ogkr
+            .composition
+            .all_bpm_changes()
+            .map(|change| (timing.to_ms(change.time), f32::from_bits(change.bpm)))
+            .collect();
+
+        Ok(Self {
+            columns,
+            notes: vsrg_notes,
+            bpm_changes,
+            dropped,
+        })
+    }
+
+    /// Returns the chart as an osu!mania beatmap.
+    pub fn osu_mania(&self) -> String {
+        let mut out = String::new();
+        self.write_osu_mania(&mut out)
+            .expect("writing to a String cannot fail");
+        out
+    }
+
+    /// Same as [`VsrgChart::osu_mania`], writing into any [`fmt::Write`].
+    pub fn write_osu_mania(&self, out: &mut impl Write) -> fmt::Result {
+        writeln!(out, "osu file format v14")?;
+        writeln!(out)?;
+        writeln!(out, "[General]")?;
+        writeln!(out, "Mode: 3")?;
+        writeln!(out)?;
+        writeln!(out, "[Difficulty]")?;
+        writeln!(out, "CircleSize:{}", self.columns)?;
+        writeln!(out)?;
+
+        writeln!(out, "[TimingPoints]")?;
+        for (ms, bpm) in &self.bpm_changes {
+            if bpm.is_finite() && *bpm > 0.0 {
+                writeln!(out, "{},{},4,0,0,100,1,0", ms.round(), 60_000.0 / bpm)?;
+            }
+        }
+        writeln!(out)?;
+
+        writeln!(out, "[HitObjects]")?;
+        for note in &self.notes {
+            // osu!mania derives the column from the x position within the 512 units wide field.
+            // The fields are public, so stay in range whatever they hold.
+            let column = note.column.min(self.columns.saturating_sub(1)) as u64;
+            let x = (512 * column + 256) / self.columns.max(1) as u64;
+            let start = note.start_ms.round();
+            match note.end_ms {
+                Some(end) => writeln!(out, "{x},192,{start},128,0,{}:0:0:0:0:", end.round())?,
+                None => writeln!(out, "{x},192,{start},1,0,0:0:0:0:")?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,223 @@
+//! Gameplay rules shared by simulators, such as when a bullet hits the player.
+//!
+//! Sizes are in track units, where lanes are [`Track::LANE_HALF_WIDTH`] wide on each side of
+//! their center.
+//!
+//! [`Track::LANE_HALF_WIDTH`]: crate::parse::analysis::Track::LANE_HALF_WIDTH
+
+use crate::{
+    lex::command::{BulletSize, BulletTarget},
+    parse::{
+        analysis::{BulletPalette, LaneType, Ogkr, Resolution, TimingPoint},
+        query::ActiveBeam,
+        spawn::BulletSpawn,
+        DamageValues,
+    },
+};
+
+/// Distance from the center of the player to its sides.
+pub const PLAYER_HALF_WIDTH: f32 = 2.0;
+
+/// Time around its arrival during which a bullet crosses the player, two frames at 60 FPS.
+pub const HIT_WINDOW_MS: f64 = 33.0;
+
+/// Interval at which beams damage a player standing in them.
+pub const BEAM_TICK_MS: f64 = 100.0;
+
+/// Distance from the center of a bullet to its sides.
+pub fn hitbox_radius(size: BulletSize) -> f32 {
+    match size {
+        BulletSize::Normal => 2.0,
+        BulletSize::Large => 4.0,
+    }
+}
+
+/// Bullet with its whole trajectory known, moving in a straight line from its shooter to its
+/// arrival position.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ResolvedBullet {
+    /// Milliseconds from the start of the chart.
+    pub spawn_ms: f64,
+    pub arrival_ms: f64,
+    /// Horizontal position of the shooter at spawn time.
+    pub spawn_x: f32,
+    /// Horizontal position at which the bullet reaches the player.
+    pub arrival_x: f32,
+}
+
+impl ResolvedBullet {
+    /// Resolves the trajectory of `spawn`. Bullets targeting the player fly towards `player_x`,
+    /// the position of the player when they are shot; others fly towards the position of their
+    /// command. The palette offset is added to either.
+    pub fn new(
+        spawn: &BulletSpawn,
+        palette: Option<&BulletPalette>,
+        resolution: Resolution,
+        player_x: f32,
+    ) -> Self {
+        let target = match palette.map(|palette| palette.target) {
+            Some(BulletTarget::Player) | None => player_x,
+            Some(BulletTarget::FixedPosition) => resolution.x_of(spawn.bullet.position.x),
+        };
+        let offset = palette.map_or(0, |palette| palette.x_offset) as f32;
+
+        Self {
+            spawn_ms: spawn.spawn_ms,
+            arrival_ms: spawn.arrival_ms,
+            spawn_x: spawn.spawn_x,
+            arrival_x: target + offset,
+        }
+    }
+
+    /// Horizontal position of the bullet at `ms`, `None` before it is shot. It keeps going in the
+    /// same direction after its arrival.
+    pub fn x_at(&self, ms: f64) -> Option<f32> {
+        if ms < self.spawn_ms {
+            return None;
+        }
+        let travel = self.arrival_ms - self.spawn_ms;
+        if travel <= 0.0 {
+            return Some(self.arrival_x);
+        }
+
+        let t = ((ms - self.spawn_ms) / travel) as f32;
+        Some(self.spawn_x + (self.arrival_x - self.spawn_x) * t)
+    }
+}
+
+/// Returns `true` if a bullet of `size` hits a player standing at `player_x` at `ms`, which is
+/// only possible within [`HIT_WINDOW_MS`] of its arrival.
+pub fn collides(player_x: f32, ms: f64, bullet: &ResolvedBullet, size: BulletSize) -> bool {
+    if (ms - bullet.arrival_ms).abs() > HIT_WINDOW_MS {
+        return false;
+    }
+    bullet
+        .x_at(ms)
+        .is_some_and(|x| (x - player_x).abs() <= PLAYER_HALF_WIDTH + hitbox_radius(size))
+}
+
+/// Returns `true` if a player standing at `player_x` is inside `beam`, which covers half its
+/// width on each side of its center.
+pub fn beam_overlaps(player_x: f32, beam: &ActiveBeam) -> bool {
+    (beam.x - player_x).abs() < beam.width / 2.0 + PLAYER_HALF_WIDTH
+}
+
+/// Beam damage of a whole chart, see [`beam_damage`].
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct BeamDamage {
+    /// Damage that can be dodged by standing outside of every beam.
+    pub avoidable: f32,
+    /// Damage taken wherever the player stands between the walls, when beams overlap the whole
+    /// playing field.
+    pub unavoidable: f32,
+}
+
+impl BeamDamage {
+    pub fn total(&self) -> f32 {
+        self.avoidable + self.unavoidable
+    }
+}
+
+/// Splits the damage beams deal every [`BEAM_TICK_MS`] into damage the player can dodge and
+/// damage they cannot, given how far they can move between the walls. Each beam overlapping the
+/// player deals `damage.beam` per tick.
+pub fn beam_damage(ogkr: &Ogkr, damage: &DamageValues) -> BeamDamage {
+    let mut total = BeamDamage::default();
+    for time in beam_ticks(ogkr) {
+        let beams = ogkr.track.beams_active_at(time);
+        let (left, right) = walls_at(ogkr, time);
+        let least = least_overlapped(&beams, left, right);
+
+        total.unavoidable += least as f32 * damage.beam;
+        total.avoidable += (beams.len() - least) as f32 * damage.beam;
+    }
+    total
+}
+
+/// Damage taken from beams by a player at `player_x(ms)`, e.g. the recorded path of a simulated
+/// play.
+pub fn beam_damage_taken(
+    ogkr: &Ogkr,
+    damage: &DamageValues,
+    mut player_x: impl FnMut(f64) -> f32,
+) -> f32 {
+    let timing = ogkr.timing();
+    beam_ticks(ogkr)
+        .map(|time| {
+            let x = player_x(timing.to_ms(time));
+            let hits = ogkr
+                .track
+                .beams_active_at(time)
+                .iter()
+                .filter(|beam| beam_overlaps(x, beam))
+                .count();
+            hits as f32 * damage.beam
+        })
+        .sum()
+}
+
+/// Times of the damage ticks during which at least one beam is active.
+fn beam_ticks(ogkr: &Ogkr) -> impl Iterator<Item = TimingPoint> + '_ {
+    let timing = ogkr.timing();
+    let track = &ogkr.track;
+    let mut ranges = track
+        .beams_data
+        .values()
+        .map(|beam| (beam.start.position.time, beam.end.position.time))
+        .chain(
+            track
+                .oblique_beams_data
+                .values()
+                .map(|beam| (beam.start.position.time, beam.end.position.time)),
+        )
+        .map(|(start, end)| (timing.to_ms(start), timing.to_ms(end)))
+        .filter(|(start, end)| start <= end)
+        .collect::<Vec<_>>();
+    ranges.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    // Ticks are only generated while a beam is active, gaps between beams cost nothing.
+    let mut merged: Vec<(f64, f64)> = vec![];
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+        .into_iter()
+        .flat_map(|(start, end)| {
+            (start / BEAM_TICK_MS).ceil() as i64..=(end / BEAM_TICK_MS).floor() as i64
+        })
+        .map(move |tick| timing.from_ms(tick as f64 * BEAM_TICK_MS))
+}
+
+/// Horizontal range the player can stand in at `time`, unbounded on sides without a wall.
+fn walls_at(ogkr: &Ogkr, time: TimingPoint) -> (f32, f32) {
+    ogkr.track.lanes_active_at(time).iter().fold(
+        (f32::NEG_INFINITY, f32::INFINITY),
+        |(left, right), active| match active.lane.lane_type {
+            LaneType::WallLeft => (left.max(active.x), right),
+            LaneType::WallRight => (left, right.min(active.x)),
+            _ => (left, right),
+        },
+    )
+}
+
+/// Fewest beams a player standing between `left` and `right` can overlap.
+fn least_overlapped(beams: &[ActiveBeam], left: f32, right: f32) -> usize {
+    if beams.is_empty() || !left.is_finite() || !right.is_finite() {
+        return 0;
+    }
+
+    // Overlap only changes at the edges of beams, which do not hurt themselves.
+    let edges = beams.iter().flat_map(|beam| {
+        let reach = beam.width / 2.0 + PLAYER_HALF_WIDTH;
+        [beam.x - reach, beam.x + reach]
+    });
+    [left, right]
+        .into_iter()
+        .chain(edges.filter(|x| (left..=right).contains(x)))
+        .map(|x| beams.iter().filter(|beam| beam_overlaps(x, beam)).count())
+        .min()
+        .unwrap_or(0)
+}
@@ -0,0 +1,185 @@
+//! Procedural chart generation, for stress-testing clients and producing synthetic benchmark
+//! charts.
+//!
+//! Helpers produce plain commands and points for [`ChartBuilder`], times are in the tick
+//! resolution of the chart being built.
+
+use std::ops::Range;
+
+use crate::{
+    build::{self, BulletPaletteBuilder, ChartBuilder, LaneKind},
+    lex::command::{BulletDamageType, CommandTime, MeterDefinition, Tap},
+    parse::analysis::Resolution,
+};
+
+/// Times of a rhythmic grid, `division` evenly spaced times in each of `measures`, e.g. 16 for
+/// sixteenth notes in 4/4.
+pub fn grid(
+    resolution: Resolution,
+    division: u32,
+    measures: Range<u32>,
+) -> impl Iterator<Item = CommandTime> {
+    let division = division.max(1);
+    measures.flat_map(move |measure| {
+        (0..division).map(move |i| CommandTime {
+            measure,
+            offset: (i as u64 * resolution.ticks as u64 / division as u64) as u32,
+        })
+    })
+}
+
+/// Lane going back and forth between two horizontal positions at a constant speed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Zigzag {
+    pub left: i32,
+    pub right: i32,
+    /// Ticks between two turns, at least 1.
+    pub period: u32,
+}
+
+impl Zigzag {
+    /// Points of the lane from `start` to `end`, starting at `left`. Pass them to
+    /// [`ChartBuilder::lane`] or [`ChartBuilder::wall`].
+    pub fn points(
+        &self,
+        resolution: Resolution,
+        start: CommandTime,
+        end: CommandTime,
+    ) -> Vec<(CommandTime, i32)> {
+        let ticks = resolution.tick_resolution();
+        let (start, end) = (ticks.ticks_at(start), ticks.ticks_at(end));
+        let period = self.period.max(1) as u64;
+        let mut points = (start..end)
+            .step_by(period as usize)
+            .map(|tick| (ticks.time_at(tick), self.x_at_ticks(tick - start)))
+            .collect::<Vec<_>>();
+        points.push((ticks.time_at(end), self.x_at_ticks(end - start)));
+        points
+    }
+
+    /// Horizontal position of the lane at `time`, for a lane starting at `start`.
+    pub fn x_at(&self, resolution: Resolution, start: CommandTime, time: CommandTime) -> i32 {
+        let ticks = resolution.tick_resolution();
+        let elapsed = ticks.ticks_at(time).saturating_sub(ticks.ticks_at(start));
+        self.x_at_ticks(elapsed)
+    }
+
+    fn x_at_ticks(&self, elapsed: u64) -> i32 {
+        let period = self.period.max(1) as u64;
+        let progress = (elapsed % period) as f64 / period as f64;
+        let progress = if (elapsed / period).is_multiple_of(2) {
+            progress
+        } else {
+            1.0 - progress
+        };
+        self.left + ((self.right - self.left) as f64 * progress).round() as i32
+    }
+}
+
+/// Taps on `lane_group_id` at each of `times`, at the position given by `x`.
+pub fn taps(
+    lane_group_id: u32,
+    times: impl IntoIterator<Item = CommandTime>,
+    mut x: impl FnMut(CommandTime) -> i32,
+) -> impl Iterator<Item = Tap> {
+    times.into_iter().map(move |time| Tap {
+        lane_group_id,
+        time,
+        x_position: x(time),
+        x_offset: 0,
+    })
+}
+
+/// Volleys of bullets spread evenly over a range of horizontal positions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Spray {
+    pub from_x: i32,
+    pub to_x: i32,
+    /// Bullets in each volley.
+    pub count: u32,
+}
+
+impl Spray {
+    /// Horizontal positions of the bullets of a volley, from `from_x` to `to_x` included.
+    pub fn positions(&self) -> impl Iterator<Item = i32> {
+        let Self {
+            from_x,
+            to_x,
+            count,
+        } = *self;
+        (0..count).map(move |i| match count {
+            1 => (from_x + to_x) / 2,
+            _ => from_x + ((to_x - from_x) as i64 * i as i64 / (count - 1) as i64) as i32,
+        })
+    }
+
+    /// Adds a volley at each of `times`, shot with `palette_id`.
+    pub fn add_to<'a>(
+        &self,
+        builder: &'a mut ChartBuilder,
+        palette_id: &str,
+        times: impl IntoIterator<Item = CommandTime>,
+        damage_type: Option<BulletDamageType>,
+    ) -> build::Result<&'a mut ChartBuilder> {
+        for time in times {
+            for x_position in self.positions() {
+                builder.bullet(palette_id, time, x_position, damage_type)?;
+            }
+        }
+        Ok(builder)
+    }
+}
+
+/// Chart of `measures` measures with `division` taps per measure on a zig-zagging center lane and
+/// a spray of bullets every measure, see [`ChartBuilder::empty_template`] for the rest.
+pub fn stress_chart(measures: u32, division: u32) -> ChartBuilder {
+    let measures = measures.max(1);
+    let resolution = Resolution::default();
+    let meter = MeterDefinition {
+        num_beats: 4,
+        note_value: 4,
+    };
+    let mut builder = ChartBuilder::empty_template(150.0, meter, measures);
+
+    let start = CommandTime::default();
+    let end = CommandTime {
+        measure: measures,
+        offset: 0,
+    };
+    let zigzag = Zigzag {
+        left: -12,
+        right: 12,
+        period: resolution.ticks,
+    };
+    let lane = builder
+        .alloc_lane_id()
+        .expect("template lanes leave free IDs");
+    builder.lane(
+        LaneKind::Center,
+        lane,
+        zigzag.points(resolution, start, end),
+    );
+    for tap in taps(lane, grid(resolution, division, 0..measures), |time| {
+        zigzag.x_at(resolution, start, time)
+    }) {
+        builder.tap(tap, false);
+    }
+
+    let spray = Spray {
+        from_x: -16,
+        to_x: 16,
+        count: 5,
+    };
+    builder
+        .bullet_palette(BulletPaletteBuilder::new("GEN"))
+        .and_then(|builder| {
+            spray.add_to(
+                builder,
+                "GEN",
+                grid(resolution, 1, 0..measures),
+                Some(BulletDamageType::Normal),
+            )
+        })
+        .expect("generated palette is valid");
+    builder
+}
@@ -0,0 +1,89 @@
+//! Conversion of chart positions into coordinates for renderers.
+
+use crate::parse::{
+    analysis::{Ogkr, Resolution, TimingPoint, TrackPosition, XPosition},
+    timing::{ScrollTimeline, TimingConverter},
+};
+
+/// What the time axis of projected coordinates measures.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum TimeAxis {
+    /// Seconds from the start of the chart.
+    #[default]
+    Seconds,
+    /// Seconds scrolled at base speed, following soflans, see [`ScrollTimeline::position_at`].
+    Scroll,
+}
+
+/// Position projected by [`Geometry::project`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Point {
+    /// Along the [`TimeAxis`].
+    pub t: f64,
+    /// 0 at the left end of the x range of the [`Geometry`], 1 at the right end.
+    pub x: f32,
+}
+
+/// Projects chart positions of a chart.
+#[derive(Clone, Debug)]
+pub struct Geometry {
+    timing: TimingConverter,
+    scroll: ScrollTimeline,
+    resolution: Resolution,
+    x_range: (f32, f32),
+}
+
+impl Geometry {
+    /// Horizontal range of the track in track units, where walls usually are.
+    pub const DEFAULT_X_RANGE: (f32, f32) = (-24.0, 24.0);
+
+    pub fn new(ogkr: &Ogkr) -> Self {
+        Self {
+            timing: ogkr.timing(),
+            scroll: ogkr.scroll_timeline(),
+            resolution: ogkr.track.resolution,
+            x_range: Self::DEFAULT_X_RANGE,
+        }
+    }
+
+    /// Maps `min` and `max` track units to normalized 0 and 1.
+    pub fn x_range(mut self, min: f32, max: f32) -> Self {
+        self.x_range = (min, max);
+        self
+    }
+
+    pub fn seconds(&self, time: TimingPoint) -> f64 {
+        self.timing.to_ms(time) / 1000.0
+    }
+
+    /// Scroll units at `time`, equal to seconds outside of soflans.
+    pub fn scroll(&self, time: TimingPoint) -> f64 {
+        self.scroll.position_at(self.timing.to_ms(time)) / 1000.0
+    }
+
+    /// Horizontal position normalized to the x range, positions outside of it are not clamped.
+    pub fn normalize_x(&self, x: XPosition) -> f32 {
+        self.normalize_track_x(self.resolution.x_of(x))
+    }
+
+    /// Same as [`Geometry::normalize_x`] for a position already in track units.
+    pub fn normalize_track_x(&self, x: f32) -> f32 {
+        let (min, max) = self.x_range;
+        let width = max - min;
+        if width == 0.0 {
+            return 0.0;
+        }
+        (x - min) / width
+    }
+
+    pub fn project(&self, position: TrackPosition, axis: TimeAxis) -> Point {
+        let t = match axis {
+            TimeAxis::Seconds => self.seconds(position.time),
+            TimeAxis::Scroll => self.scroll(position.time),
+        };
+        Point {
+            t,
+            x: self.normalize_x(position.x),
+        }
+    }
+}
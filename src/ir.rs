@@ -0,0 +1,359 @@
+//! Format-neutral chart representation shared by importers and exporters.
+//!
+//! Times are in seconds from the start of the chart and horizontal positions in track units. Only
+//! the rhythm layer of a chart is represented: tempo, lanes and notes. Bullets, beams and soflans
+//! are not part of the IR.
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::{
+    build::{BuildError, ChartBuilder, LaneKind as BuilderLaneKind, WallSide},
+    lex::command::{self, CommandTime, FlickDirection},
+    parse::{
+        analysis::{Composition, LaneId, LaneType, Ogkr, Resolution, TrackPosition},
+        timing::TimingConverter,
+        ParseError,
+    },
+};
+
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Error)]
+pub enum IrError {
+    #[error("note at {seconds}s does not reference a lane")]
+    MissingLane { seconds: f64 },
+    #[error("note at {seconds}s references lane {lane} which does not exist")]
+    UnknownLane { seconds: f64, lane: usize },
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    #[error(transparent)]
+    Build(#[from] BuildError),
+}
+
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Chart {
+    pub creator: Option<String>,
+    /// Sorted by time.
+    pub bpm_changes: Vec<BpmChange>,
+    /// Sorted by time.
+    pub meter_changes: Vec<MeterChange>,
+    pub lanes: Vec<Lane>,
+    /// Sorted by time.
+    pub notes: Vec<Note>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BpmChange {
+    pub seconds: f64,
+    pub bpm: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MeterChange {
+    pub seconds: f64,
+    pub num_beats: u32,
+    pub note_value: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LaneKind {
+    WallLeft,
+    WallRight,
+    Left,
+    Center,
+    Right,
+    Enemy,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Point {
+    pub seconds: f64,
+    pub x: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lane {
+    pub kind: LaneKind,
+    /// Sorted by time.
+    pub points: Vec<Point>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Note {
+    pub seconds: f64,
+    pub x: f32,
+    pub kind: NoteKind,
+    pub is_critical: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NoteKind {
+    /// `lane` indexes [`Chart::lanes`].
+    Tap {
+        lane: Option<usize>,
+    },
+    /// `lane` indexes [`Chart::lanes`].
+    Hold {
+        lane: Option<usize>,
+        end: Point,
+    },
+    Flick {
+        direction: FlickDirection,
+    },
+    Bell,
+}
+
+impl From<&Ogkr> for Chart {
+    /// Synthetic lanes are not part of the IR, notes on them do not reference a lane.
+    fn from(ogkr: &Ogkr) -> Self {
+        let timing = ogkr.timing();
+        let resolution = timing.resolution();
+        let point = |position: TrackPosition| Point {
+            seconds: timing.to_ms(position.time) / 1000.0,
+            x: resolution.x_of(position.x),
+        };
+
+        let mut lane_ids = ogkr.track.lanes_data.keys().copied().collect::<Vec<_>>();
+        lane_ids.sort_by_key(|id| id.0);
+        let mut lane_indices = HashMap::new();
+        let mut lanes = Vec::new();
+        for id in lane_ids {
+            let lane = &ogkr.track.lanes_data[&id];
+            let kind = match lane.lane_type {
+                LaneType::WallLeft => LaneKind::WallLeft,
+                LaneType::WallRight => LaneKind::WallRight,
+                LaneType::Left => LaneKind::Left,
+                LaneType::Center => LaneKind::Center,
+                LaneType::Right => LaneKind::Right,
+                LaneType::Enemy => LaneKind::Enemy,
+                LaneType::Synthetic => continue,
+            };
+            lane_indices.insert(id, lanes.len());
+            lanes.push(Lane {
+                kind,
+                points: lane.points.iter().copied().map(point).collect(),
+            });
+        }
+        let lane_index = |id: LaneId| lane_indices.get(&id).copied();
+
+        let notes = &ogkr.notes;
+        let mut ir_notes = notes
+            .all_taps()
+            .map(|tap| {
+                let start = point(tap.position);
+                Note {
+                    seconds: start.seconds,
+                    x: start.x,
+                    kind: NoteKind::Tap {
+                        lane: lane_index(tap.lane_id),
+                    },
+                    is_critical: tap.is_critical,
+                }
+            })
+            .chain(notes.all_holds().map(|hold| {
+                let start = point(hold.start);
+                Note {
+                    seconds: start.seconds,
+                    x: start.x,
+                    kind: NoteKind::Hold {
+                        lane: lane_index(hold.lane_id),
+                        end: point(hold.end),
+                    },
+                    is_critical: hold.is_critical,
+                }
+            }))
+            .chain(notes.all_flicks().map(|flick| {
+                let start = point(flick.position);
+                Note {
+                    seconds: start.seconds,
+                    x: start.x,
+                    kind: NoteKind::Flick {
+                        direction: flick.direction,
+                    },
+                    is_critical: flick.is_critical,
+                }
+            }))
+            .chain(notes.all_bells().map(|bell| {
+                let start = point(bell.position);
+                Note {
+                    seconds: start.seconds,
+                    x: start.x,
+                    kind: NoteKind::Bell,
+                    is_critical: false,
+                }
+            }))
+            .collect::<Vec<_>>();
+        ir_notes.sort_by(|a, b| a.seconds.total_cmp(&b.seconds));
+
+        let composition = &ogkr.composition;
+        Self {
+            creator: ogkr
+                .header
+                .creator
+                .as_ref()
+                .map(|creator| creator.name.clone()),
+            bpm_changes: composition
+                .all_bpm_changes()
+                .map(|change| BpmChange {
+                    seconds: timing.to_ms(change.time) / 1000.0,
+                    bpm: f32::from_bits(change.bpm) as f64,
+                })
+                .collect(),
+            meter_changes: composition
+                .meter_changes
+                .values()
+                .map(|change| MeterChange {
+                    seconds: timing.to_ms(change.time) / 1000.0,
+                    num_beats: change.num_beats,
+                    note_value: change.note_value,
+                })
+                .collect(),
+            lanes,
+            notes: ir_notes,
+        }
+    }
+}
+
+impl From<Ogkr> for Chart {
+    fn from(ogkr: Ogkr) -> Self {
+        Self::from(&ogkr)
+    }
+}
+
+impl TryFrom<&Chart> for Ogkr {
+    type Error = IrError;
+
+    /// Times are snapped to the closest tick and positions to the closest x offset, using the
+    /// default [`Resolution`].
+    fn try_from(chart: &Chart) -> Result<Self, Self::Error> {
+        let resolution = Resolution::default();
+        let mut builder = ChartBuilder::new();
+        builder.resolution(resolution.ticks, resolution.x);
+        if let Some(creator) = &chart.creator {
+            builder.creator(creator.clone());
+        }
+
+        // Tempo changes move every later time, so they are placed one by one using the tempo
+        // placed before them.
+        let mut tempo_changes = chart
+            .bpm_changes
+            .iter()
+            .map(|change| (change.seconds, Some(change), None))
+            .chain(
+                chart
+                    .meter_changes
+                    .iter()
+                    .map(|change| (change.seconds, None, Some(change))),
+            )
+            .collect::<Vec<_>>();
+        tempo_changes.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for (seconds, bpm_change, meter_change) in tempo_changes {
+            let time = converter(&builder).from_ms(seconds * 1000.0).into();
+            if let Some(change) = bpm_change {
+                builder.bpm(time, change.bpm as f32);
+            }
+            if let Some(change) = meter_change {
+                builder.meter(time, change.num_beats, change.note_value);
+            }
+        }
+
+        let timing = converter(&builder);
+        let time = |seconds: f64| -> CommandTime { timing.from_ms(seconds * 1000.0).into() };
+        let x = |x: f32| {
+            let position = x.floor();
+            (
+                position as i32,
+                ((x - position) * resolution.x as f32).round() as i32,
+            )
+        };
+
+        for (id, lane) in chart.lanes.iter().enumerate() {
+            let id = id as u32;
+            let points = lane
+                .points
+                .iter()
+                .map(|point| (time(point.seconds), point.x.round() as i32));
+            match lane.kind {
+                LaneKind::WallLeft => builder.wall(WallSide::Left, id, points),
+                LaneKind::WallRight => builder.wall(WallSide::Right, id, points),
+                LaneKind::Left => builder.lane(BuilderLaneKind::Left, id, points),
+                LaneKind::Center => builder.lane(BuilderLaneKind::Center, id, points),
+                LaneKind::Right => builder.lane(BuilderLaneKind::Right, id, points),
+                LaneKind::Enemy => builder.lane(BuilderLaneKind::Enemy, id, points),
+            };
+        }
+
+        let lane_id = |note: &Note, lane: Option<usize>| match lane {
+            None => Err(IrError::MissingLane {
+                seconds: note.seconds,
+            }),
+            Some(lane) if lane >= chart.lanes.len() => Err(IrError::UnknownLane {
+                seconds: note.seconds,
+                lane,
+            }),
+            Some(lane) => Ok(lane as u32),
+        };
+        for note in &chart.notes {
+            let (x_position, x_offset) = x(note.x);
+            match note.kind {
+                NoteKind::Tap { lane } => {
+                    let tap = command::Tap {
+                        lane_group_id: lane_id(note, lane)?,
+                        time: time(note.seconds),
+                        x_position,
+                        x_offset,
+                    };
+                    builder.tap(tap, note.is_critical);
+                }
+                NoteKind::Hold { lane, end } => {
+                    let (end_x_position, end_x_offset) = x(end.x);
+                    let hold = command::Hold {
+                        lane_group_id: lane_id(note, lane)?,
+                        start_time: time(note.seconds),
+                        start_x_position: x_position,
+                        start_x_offset: x_offset,
+                        end_time: time(end.seconds),
+                        end_x_position,
+                        end_x_offset,
+                    };
+                    builder.hold(hold, note.is_critical);
+                }
+                NoteKind::Flick { direction } => {
+                    let flick = command::Flick {
+                        time: time(note.seconds),
+                        x_position,
+                        direction,
+                    };
+                    builder.flick(flick, note.is_critical);
+                }
+                NoteKind::Bell => {
+                    let bell = command::Bell {
+                        time: time(note.seconds),
+                        x_position,
+                        bullet_palette_id: None,
+                    };
+                    builder.bell(bell);
+                }
+            }
+        }
+
+        builder.build_ogkr().map_err(|error| match error {
+            BuildError::Parse(error) => IrError::Parse(error),
+            error => IrError::Build(error),
+        })
+    }
+}
+
+impl TryFrom<Chart> for Ogkr {
+    type Error = IrError;
+
+    fn try_from(chart: Chart) -> Result<Self, Self::Error> {
+        Self::try_from(&chart)
+    }
+}
+
+fn converter(builder: &ChartBuilder) -> TimingConverter {
+    let raw = builder.raw();
+    TimingConverter::new(&raw.header, &Composition::from_raw(raw.composition.clone()))
+}
@@ -1,17 +1,114 @@
-use super::{cursor::Cursor, LexError, Result};
+use std::{fmt, str::FromStr};
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+use thiserror::Error;
+
+use super::{cursor::Cursor, Result};
+
+/// Version of the chart format, ordered by major, minor and release.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Version {
     pub major: u32,
     pub minor: u32,
     pub release: u32,
 }
 
+impl Version {
+    /// Earliest version known to specify the damage type of bullets in `BLT` commands, with
+    /// palettes specifying size, bullet type and random offset instead.
+    pub const BULLET_DAMAGE_IN_BLT: Self = Self::new(1, 7, 0);
+
+    pub const fn new(major: u32, minor: u32, release: u32) -> Self {
+        Self {
+            major,
+            minor,
+            release,
+        }
+    }
+
+    /// Whether bullets of charts of this version specify their own damage type, older charts
+    /// specify it in `BPL` commands.
+    ///
+    /// With the `legacy-format` feature the lexer picks the syntax from the `VERSION` command, see
+    /// [`ChartFormat`], this is for tools writing charts for a given version.
+    pub fn supports_bullet_damage_in_blt(&self) -> bool {
+        *self >= Self::BULLET_DAMAGE_IN_BLT
+    }
+}
+
+/// Revision of the chart format, deciding the arguments of `BPL` and `BLT` commands.
+///
+/// Revisions can only be selected with the `legacy-format` feature, without it the arguments are
+/// always recognized from the commands.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum ChartFormat {
+    /// Follow the `VERSION` command with the `legacy-format` feature. Commands before it, or in
+    /// charts without one, are recognized from their arguments.
+    #[default]
+    Auto,
+    /// Revisions before [`Version::BULLET_DAMAGE_IN_BLT`], where palettes have a damage type
+    /// instead of size, bullet type and random position offset. Bullets have a damage type as well,
+    /// overriding the one of their palette.
+    #[cfg(feature = "legacy-format")]
+    Legacy,
+    /// Revisions since [`Version::BULLET_DAMAGE_IN_BLT`], where every bullet has a damage type.
+    #[cfg(feature = "legacy-format")]
+    Current,
+}
+
+#[cfg(feature = "legacy-format")]
+impl ChartFormat {
+    /// Format of charts of `version`.
+    pub fn of_version(version: &Version) -> Self {
+        if version.supports_bullet_damage_in_blt() {
+            Self::Current
+        } else {
+            Self::Legacy
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.release)
+    }
+}
+
+/// Error parsing a [`Version`] that is not written as `major.minor.release`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Error)]
+#[error("invalid version {0:?}, expected major.minor.release")]
+pub struct ParseVersionError(pub String);
+
+impl FromStr for Version {
+    type Err = ParseVersionError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let error = || ParseVersionError(s.to_owned());
+        let mut parts = s
+            .split('.')
+            .map(|part| part.parse::<u32>().map_err(|_| error()));
+        let mut next = || parts.next().unwrap_or_else(|| Err(error()));
+        let version = Self::new(next()?, next()?, next()?);
+        if parts.next().is_some() {
+            return Err(error());
+        }
+        Ok(version)
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Creator {
     pub name: String,
 }
 
+/// Custom `X_`-prefixed command carrying tool specific data.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Extension {
+    /// Command name without the `X_` prefix.
+    pub key: String,
+    pub value: String,
+}
+
 /// Values are u32 bits that represent floats.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct BpmDefinition {
@@ -29,16 +126,52 @@ pub struct MeterDefinition {
     pub note_value: u32,
 }
 
+/// Number of ticks in a measure.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct TickResolution {
     pub resolution: u32,
 }
 
+impl TickResolution {
+    /// Absolute number of ticks from the start of the chart at `time`.
+    pub fn ticks_at(&self, time: CommandTime) -> u64 {
+        time.measure as u64 * self.resolution as u64 + time.offset as u64
+    }
+
+    /// Time at an absolute number of ticks from the start of the chart, inverse of
+    /// [`TickResolution::ticks_at`].
+    pub fn time_at(&self, ticks: u64) -> CommandTime {
+        let resolution = self.resolution.max(1) as u64;
+        CommandTime {
+            measure: (ticks / resolution) as u32,
+            offset: (ticks % resolution) as u32,
+        }
+    }
+
+    /// Number of ticks in a beat of the meter, a measure always lasts the same number of ticks.
+    pub fn ticks_per_beat(&self, meter: MeterDefinition) -> f64 {
+        self.resolution as f64 / meter.num_beats.max(1) as f64
+    }
+}
+
+/// Number of offset units in a track unit of horizontal position.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct XResolution {
     pub resolution: u32,
 }
 
+impl XResolution {
+    /// Converts a horizontal offset into track units.
+    pub fn normalize_x(&self, offset: i32) -> f32 {
+        offset as f32 / self.resolution.max(1) as f32
+    }
+
+    /// Horizontal position in track units of a position and offset.
+    pub fn x_of(&self, position: i32, offset: i32) -> f32 {
+        position as f32 + self.normalize_x(offset)
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct ClickDefinition {
     pub value: u32,
@@ -217,6 +350,12 @@ pub struct CommandTime {
     pub offset: u32,
 }
 
+impl CommandTime {
+    pub fn new(measure: u32, offset: u32) -> Self {
+        Self { measure, offset }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct BpmChange {
     pub time: CommandTime,
@@ -331,21 +470,6 @@ pub enum BulletDamageType {
 }
 
 impl BulletDamageType {
-    pub(crate) fn from_str(s: &str) -> Result<Self> {
-        Ok(match s {
-            "NML" => BulletDamageType::Normal,
-            "STR" => BulletDamageType::Hard,
-            "DNG" => BulletDamageType::Danger,
-            _ => {
-                return Err(LexError::ExpectedToken {
-                    line: 0,
-                    col: 0,
-                    message: "internal dummy error",
-                })
-            }
-        })
-    }
-
     pub(crate) fn from_cursor(cursor: &mut Cursor) -> Result<Self> {
         Ok(match cursor.next_token() {
             Some("NML") => BulletDamageType::Normal,
@@ -1,8 +1,10 @@
 use super::LexError;
 
+#[derive(Clone)]
 pub(crate) struct Cursor<'a> {
     line: usize,
-    col: usize,
+    /// Byte offset at which the current line starts, columns are counted in chars from there.
+    line_start: usize,
 
     /// Position of current cursor, or the current token's end position.
     current_index: usize,
@@ -10,34 +12,126 @@ pub(crate) struct Cursor<'a> {
     current_token_start: usize,
 
     source: &'a str,
+
+    /// Reject anything but single separators between arguments, see
+    /// [`LexOptions::strict_whitespace`](super::LexOptions::strict_whitespace).
+    strict_whitespace: bool,
+    /// First irregular separator run met by [`Cursor::next_token`] in strict mode.
+    irregular_whitespace: Option<LexError>,
+    /// Inside [`Cursor::speculate`], where failing is expected and not worth logging.
+    speculating: bool,
+}
+
+/// Position of a [`Cursor`] to rewind to, see [`Cursor::checkpoint`].
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Checkpoint {
+    line: usize,
+    line_start: usize,
+    current_index: usize,
+    current_token_start: usize,
+    irregular_whitespace: bool,
+}
+
+/// Byte order mark written at the start of files by some Windows tools.
+pub(super) const BOM: char = '\u{feff}';
+
+/// Tabs and full-width spaces count as whitespace, stray byte order marks from concatenated files
+/// are skipped as well.
+pub(super) fn is_separator(c: char) -> bool {
+    c.is_whitespace() || c == BOM
+}
+
+/// Lines end with `\n`, `\r\n` or a lone `\r` as written by some old Mac tools.
+pub(super) fn is_line_break(c: char) -> bool {
+    c == '\n' || c == '\r'
+}
+
+/// Number of line breaks in `text`, counting `\r\n` once.
+pub(super) fn count_line_breaks(text: &str) -> usize {
+    text.chars().filter(|&c| c == '\n').count() + text.matches('\r').count()
+        - text.matches("\r\n").count()
 }
 
 impl<'a> Cursor<'a> {
     pub(crate) fn new(source: &'a str) -> Self {
+        // Offsets stay relative to the full source, a leading BOM just gets skipped.
+        let start = if source.starts_with(BOM) {
+            BOM.len_utf8()
+        } else {
+            0
+        };
+
         Self {
             line: 1,
-            col: 1,
-            current_index: 0,
-            current_token_start: 0,
+            line_start: start,
+            current_index: start,
+            current_token_start: start,
             source,
+            strict_whitespace: false,
+            irregular_whitespace: None,
+            speculating: false,
         }
     }
 
+    pub(crate) fn strict_whitespace(mut self, strict_whitespace: bool) -> Self {
+        self.strict_whitespace = strict_whitespace;
+        self
+    }
+
+    /// Saves the current position, cheaper than cloning the cursor.
+    pub(crate) fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            line: self.line,
+            line_start: self.line_start,
+            current_index: self.current_index,
+            current_token_start: self.current_token_start,
+            irregular_whitespace: self.irregular_whitespace.is_some(),
+        }
+    }
+
+    /// Goes back to a position saved with [`Cursor::checkpoint`], forgetting irregular whitespace
+    /// met since then.
+    pub(crate) fn rewind(&mut self, checkpoint: Checkpoint) {
+        self.line = checkpoint.line;
+        self.line_start = checkpoint.line_start;
+        self.current_index = checkpoint.current_index;
+        self.current_token_start = checkpoint.current_token_start;
+        if !checkpoint.irregular_whitespace {
+            self.irregular_whitespace = None;
+        }
+    }
+
+    /// Runs `parse`, rewinding to the current position if it fails so that another argument
+    /// layout can be tried.
+    pub(crate) fn speculate<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, LexError>,
+    ) -> Result<T, LexError> {
+        let checkpoint = self.checkpoint();
+        let speculating = self.set_speculating(true);
+        let result = parse(self);
+        self.set_speculating(speculating);
+        if result.is_err() {
+            self.rewind(checkpoint);
+        }
+        result
+    }
+
+    /// Enables or disables logging of expected token errors, giving the previous state.
+    pub(crate) fn set_speculating(&mut self, speculating: bool) -> bool {
+        std::mem::replace(&mut self.speculating, speculating)
+    }
+
     pub(crate) fn is_end(&self) -> bool {
         self.peek_token().is_none()
     }
 
     /// Returns a range for access to `source` that gives the raw token string.
     fn get_next_token_range(&self) -> std::ops::Range<usize> {
-        fn is_separator(c: char) -> bool {
-            c.is_whitespace() || c == '\n'
-        }
-
         let next_token_start = self.source[self.current_index..]
             .find(|c| !is_separator(c))
             .map_or(self.source.len(), |i| i + self.current_index);
         let next_token_end = self.source[next_token_start..]
-            .trim_start()
             .find(is_separator)
             .map_or(self.source.len(), |i| i + next_token_start);
 
@@ -57,71 +151,191 @@ impl<'a> Cursor<'a> {
     pub(crate) fn next_token(&mut self) -> Option<&'a str> {
         let token_range = self.get_next_token_range();
         if token_range.is_empty() {
+            // Nothing was consumed, so errors do not point at the previous token.
+            self.current_token_start = self.current_index;
             return None;
         }
 
-        let advanced_lines = self.source[self.current_index..token_range.end]
-            .chars()
-            .filter(|&c| c == '\n')
-            .count();
-        self.line += advanced_lines;
+        if self.strict_whitespace && self.irregular_whitespace.is_none() {
+            self.check_separators(token_range.start);
+        }
 
-        if advanced_lines != 0 {
-            self.col = 1;
+        // Tokens never contain line breaks, only the separators in front of them do.
+        let separators = &self.source[self.current_index..token_range.start];
+        if let Some(last_break) = separators.rfind(is_line_break) {
+            self.line += count_line_breaks(separators);
+            self.line_start = self.current_index + last_break + 1;
         }
-        self.col += self.source[self.current_index..token_range.end]
-            .lines()
-            .last()
-            .unwrap()
-            .chars()
-            .count();
 
         self.current_index = token_range.end;
         self.current_token_start = token_range.start;
         Some(&self.source[token_range])
     }
 
-    /// Gets the remaining characters in the current line.
-    pub(crate) fn current_remaining_line(&mut self) -> &'a str {
-        let remaining_end = self.source[self.current_index..]
-            .find('\n')
-            .unwrap_or(self.source.len());
-        let ret = if self
-            .source
-            .get(self.current_index + remaining_end - 1..=self.current_index + remaining_end)
-            == Some("\r\n")
-        {
-            &self.source[self.current_index..self.current_index + remaining_end - 1]
+    /// Records the separators in front of the token starting at `token_start` if they are
+    /// anything but a single separator between arguments or bare line breaks between lines.
+    fn check_separators(&mut self, token_start: usize) {
+        let at_line_start = self.source[..self.current_index]
+            .trim_end_matches(BOM)
+            .chars()
+            .next_back()
+            .is_none_or(is_line_break);
+        let gap = &self.source[self.current_index..token_start];
+        let mut runs = gap.split(is_line_break);
+        let first = runs.next().unwrap_or("");
+
+        let regular = if first.len() == gap.len() {
+            at_line_start && first.is_empty() || !at_line_start && first.chars().count() == 1
         } else {
-            &self.source[self.current_index..self.current_index + remaining_end]
+            first.is_empty() && runs.all(str::is_empty)
         };
-        self.col += ret.chars().count();
+        if !regular {
+            self.irregular_whitespace = Some(LexError::IrregularWhitespace {
+                line: self.line,
+                col: self.col_at(self.current_index),
+                offset: self.current_index,
+            });
+        }
+    }
+
+    /// Takes the first irregular separator run met since the last call, in strict mode.
+    pub(crate) fn take_irregular_whitespace(&mut self) -> Option<LexError> {
+        self.irregular_whitespace.take()
+    }
+
+    /// Remaining characters in the current line, without consuming them.
+    fn remaining_line_raw(&self) -> &'a str {
+        let remaining = &self.source[self.current_index..];
+        &remaining[..remaining.find(is_line_break).unwrap_or(remaining.len())]
+    }
+
+    /// Gets the remaining characters in the current line.
+    pub(crate) fn current_remaining_line(&mut self) -> &'a str {
+        let line = self.remaining_line_raw();
+
         self.current_token_start = self.current_index;
-        self.current_index += remaining_end;
-        ret.trim()
+        self.current_index += line.len();
+        line.trim_matches(is_separator)
+    }
+
+    /// Consumes the rest of the line of the next token, giving its line number, byte offset and
+    /// text without surrounding separators.
+    pub(crate) fn next_line(&mut self) -> Option<(usize, usize, &'a str)> {
+        self.next_token()?;
+        let start = self.current_token_start;
+        self.current_remaining_line();
+        self.current_token_start = start;
+        Some((self.line, start, self.span_text(start)))
+    }
+
+    /// Source text from `start` up to the current position, without trailing separators.
+    fn span_text(&self, start: usize) -> &'a str {
+        &self.source[self.span_from(start)]
+    }
+
+    /// Ends a complete command, checking nothing but separators are left on its line.
+    ///
+    /// Leftover tokens are skipped unless whitespace is strict, in which case they are an error
+    /// like any trailing separators are.
+    pub(crate) fn end_command(&mut self) -> Result<(), LexError> {
+        if let Some(error) = self.take_irregular_whitespace() {
+            return Err(error);
+        }
+
+        let line = self.remaining_line_raw();
+        let content = line.trim_start_matches(is_separator);
+        if content.is_empty() {
+            if self.strict_whitespace && !line.is_empty() {
+                return Err(LexError::IrregularWhitespace {
+                    line: self.line,
+                    col: self.col_at(self.current_index),
+                    offset: self.current_index,
+                });
+            }
+            return Ok(());
+        }
+
+        let offset = self.current_index + line.len() - content.len();
+        let error = LexError::TrailingContent {
+            line: self.line,
+            col: self.col_at(offset),
+            offset,
+            token: content.trim_end_matches(is_separator).to_string(),
+        };
+        if self.strict_whitespace {
+            return Err(error);
+        }
+
+        crate::diagnostics::warning!("Ignoring {}", error);
+        self.current_remaining_line();
+        Ok(())
+    }
+
+    /// Byte offset at which the next token starts.
+    pub(crate) fn next_token_start(&self) -> usize {
+        self.get_next_token_range().start
+    }
+
+    /// Byte range from `start` up to the last consumed token, without trailing whitespace.
+    pub(crate) fn span_from(&self, start: usize) -> std::ops::Range<usize> {
+        start
+            ..start
+                + self.source[start..self.current_index]
+                    .trim_end_matches(is_separator)
+                    .len()
     }
 
     pub(crate) fn line(&self) -> usize {
         self.line
     }
 
+    /// Byte offset at which the last consumed token starts.
+    pub(crate) fn offset(&self) -> usize {
+        self.current_token_start
+    }
+
+    /// Column of the byte `offset` on the current line, starting at 1.
+    fn col_at(&self, offset: usize) -> usize {
+        self.source[self.line_start..offset].chars().count() + 1
+    }
+
+    /// Column at which the last consumed token starts.
     pub(crate) fn col(&self) -> usize {
-        self.col
+        self.col_at(self.current_token_start)
+    }
+
+    /// Text of the last consumed token.
+    fn current_token(&self) -> &'a str {
+        &self.source[self.current_token_start..self.current_index]
     }
 
     pub(crate) fn err_expected_token(&self, message: &'static str) -> LexError {
-        log::error!(
-            "Expected token {} at line {}, col {} - but found {}",
-            message,
-            self.line(),
-            self.col(),
-            &self.source[self.current_token_start..self.current_index]
-        );
+        if !self.speculating {
+            crate::diagnostics::error!(
+                "Expected token {} at line {}, col {} - but found {}",
+                message,
+                self.line(),
+                self.col(),
+                self.current_token()
+            );
+        }
 
         LexError::ExpectedToken {
             line: self.line(),
             col: self.col(),
             message,
+            offset: self.current_token_start,
+            token: self.current_token().to_string(),
+        }
+    }
+
+    /// Error for the last consumed token not being a known command.
+    pub(crate) fn err_unknown_command(&self) -> LexError {
+        LexError::UnknownCommand {
+            line: self.line(),
+            col: self.col(),
+            offset: self.current_token_start,
+            token: self.current_token().to_string(),
         }
     }
 }
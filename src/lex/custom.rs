@@ -0,0 +1,116 @@
+//! Commands of private chart format extensions, read by handlers registered with
+//! [`LexOptions::custom_command`](super::LexOptions::custom_command).
+
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    fmt,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use super::{RawLineReader, Result};
+
+/// Command with a mnemonic unknown to the crate, parsed into a user type.
+///
+/// Unlike `X_` extensions, custom commands may have any mnemonic and typed arguments, and are kept
+/// in [`RawOgkr::custom_commands`](crate::parse::raw::RawOgkr::custom_commands) in chart order.
+pub trait CustomCommand: Any + fmt::Debug + Send + Sync {
+    /// Reads the arguments following the mnemonic, the rest of the line is handled like for any
+    /// other command.
+    fn parse(reader: &mut RawLineReader<'_>) -> Result<Self>
+    where
+        Self: Sized;
+
+    /// Arguments written after the mnemonic when writing the chart.
+    fn args(&self) -> Vec<String>;
+}
+
+/// Parsed custom command along with the mnemonic it was written with.
+///
+/// Two custom commands are equal when their mnemonics and [`CustomCommand::args`] are.
+#[derive(Clone, Debug)]
+pub struct CustomToken {
+    pub mnemonic: String,
+    pub command: Arc<dyn CustomCommand>,
+}
+
+impl CustomToken {
+    /// The command as the type it was registered with, if it is `T`.
+    pub fn downcast_ref<T: CustomCommand>(&self) -> Option<&T> {
+        (self.command.as_ref() as &dyn Any).downcast_ref()
+    }
+}
+
+impl PartialEq for CustomToken {
+    fn eq(&self, other: &Self) -> bool {
+        self.mnemonic == other.mnemonic && self.command.args() == other.command.args()
+    }
+}
+
+impl Eq for CustomToken {}
+
+impl Hash for CustomToken {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.mnemonic.hash(state);
+        self.command.args().hash(state);
+    }
+}
+
+type Handler = fn(&mut RawLineReader<'_>) -> Result<Arc<dyn CustomCommand>>;
+
+fn handle<T: CustomCommand>(reader: &mut RawLineReader<'_>) -> Result<Arc<dyn CustomCommand>> {
+    Ok(Arc::new(T::parse(reader)?))
+}
+
+/// Handlers of custom commands, by exact mnemonic.
+#[derive(Clone, Default)]
+pub struct CustomCommands {
+    handlers: BTreeMap<String, Handler>,
+}
+
+impl CustomCommands {
+    pub(crate) fn register<T: CustomCommand>(&mut self, mnemonic: String) {
+        self.handlers.insert(mnemonic, handle::<T>);
+    }
+
+    pub(crate) fn get(&self, mnemonic: &str) -> Option<Handler> {
+        self.handlers.get(mnemonic).copied()
+    }
+
+    /// Registered mnemonics.
+    pub fn mnemonics(&self) -> impl Iterator<Item = &str> {
+        self.handlers.keys().map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+}
+
+impl PartialEq for CustomCommands {
+    fn eq(&self, other: &Self) -> bool {
+        self.handlers.len() == other.handlers.len()
+            && self
+                .handlers
+                .iter()
+                .zip(&other.handlers)
+                .all(|((a, f), (b, g))| a == b && std::ptr::fn_addr_eq(*f, *g))
+    }
+}
+
+impl Eq for CustomCommands {}
+
+impl Hash for CustomCommands {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.handlers
+            .keys()
+            .for_each(|mnemonic| mnemonic.hash(state));
+    }
+}
+
+impl fmt::Debug for CustomCommands {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.handlers.keys()).finish()
+    }
+}
@@ -0,0 +1,317 @@
+//! Description of the arguments each command takes, keyed by the same [`TokenKind`] the lexer
+//! dispatches on, for editor completion and documentation.
+
+use super::token::TokenKind;
+
+/// Chart section a command is written under.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Section {
+    Header,
+    BulletPalette,
+    Composition,
+    Lane,
+    Beam,
+    Bullet,
+    Bell,
+    Notes,
+    Total,
+}
+
+impl Section {
+    /// Name written between brackets on the section line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Header => "HEADER",
+            Self::BulletPalette => "B_PALETTE",
+            Self::Composition => "COMPOSITION",
+            Self::Lane => "LANE",
+            Self::Beam => "BEAM",
+            Self::Bullet => "BULLET",
+            Self::Bell => "BELL",
+            Self::Notes => "NOTES",
+            Self::Total => "TOTAL",
+        }
+    }
+}
+
+/// Type of value expected for an argument.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArgKind {
+    UnsignedInteger,
+    Integer,
+    Float,
+    /// Single word, such as a bullet palette ID.
+    Identifier,
+    /// Rest of the line, may contain whitespace.
+    Text,
+    /// One of the given mnemonics.
+    Keyword(&'static [&'static str]),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Arg {
+    pub name: &'static str,
+    pub kind: ArgKind,
+    /// Optional arguments can only be left out at the end of the line.
+    pub optional: bool,
+}
+
+const fn arg(name: &'static str, kind: ArgKind) -> Arg {
+    Arg {
+        name,
+        kind,
+        optional: false,
+    }
+}
+
+const fn optional(name: &'static str, kind: ArgKind) -> Arg {
+    Arg {
+        name,
+        kind,
+        optional: true,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CommandGrammar {
+    pub kind: TokenKind,
+    /// Section the command belongs to, `None` for commands allowed anywhere.
+    pub section: Option<Section>,
+    pub args: &'static [Arg],
+}
+
+impl CommandGrammar {
+    /// Minimum number of arguments.
+    pub fn min_args(&self) -> usize {
+        self.args.iter().filter(|arg| !arg.optional).count()
+    }
+
+    /// Maximum number of arguments, a [`ArgKind::Text`] argument counts as one.
+    pub fn max_args(&self) -> usize {
+        self.args.len()
+    }
+}
+
+pub const BULLET_SHOOTERS: &[&str] = &["UPS", "ENE", "CEN"];
+pub const BULLET_TARGETS: &[&str] = &["PLR", "FIX"];
+pub const BULLET_SIZES: &[&str] = &["N", "L"];
+pub const BULLET_TYPES: &[&str] = &["CIR", "SQR", "NDL"];
+pub const BULLET_DAMAGE_TYPES: &[&str] = &["NML", "STR", "DNG"];
+pub const ENEMY_WAVES: &[&str] = &["WAVE1", "WAVE2", "BOSS"];
+pub const FLICK_DIRECTIONS: &[&str] = &["L", "R"];
+
+use ArgKind::*;
+
+const MEASURE: Arg = arg("measure", UnsignedInteger);
+const OFFSET: Arg = arg("offset", UnsignedInteger);
+
+const NONE: &[Arg] = &[];
+const VALUE: &[Arg] = &[arg("value", UnsignedInteger)];
+const DAMAGE: &[Arg] = &[arg("damage", Float)];
+const WALL_POINT: &[Arg] = &[
+    arg("group_id", UnsignedInteger),
+    MEASURE,
+    OFFSET,
+    arg("x_position", Integer),
+];
+const COLORFUL_LANE_POINT: &[Arg] = &[
+    arg("group_id", UnsignedInteger),
+    MEASURE,
+    OFFSET,
+    arg("x_position", Integer),
+    arg("color", UnsignedInteger),
+    arg("brightness", UnsignedInteger),
+];
+const LANE_EVENT: &[Arg] = &[
+    arg("group_id", UnsignedInteger),
+    arg("start_measure", UnsignedInteger),
+    arg("start_offset", UnsignedInteger),
+    arg("start_x_position", Integer),
+    arg("start_x_offset", Integer),
+    arg("end_measure", UnsignedInteger),
+    arg("end_offset", UnsignedInteger),
+    arg("end_x_position", Integer),
+    arg("end_x_offset", Integer),
+];
+const BEAM_POINT: &[Arg] = &[
+    arg("record_id", UnsignedInteger),
+    MEASURE,
+    OFFSET,
+    arg("x_position", Integer),
+    arg("width", UnsignedInteger),
+];
+const OBLIQUE_BEAM_POINT: &[Arg] = &[
+    arg("record_id", UnsignedInteger),
+    MEASURE,
+    OFFSET,
+    arg("x_position", Integer),
+    arg("width", UnsignedInteger),
+    arg("shoot_position_x_offset", Integer),
+];
+const FLICK: &[Arg] = &[
+    MEASURE,
+    OFFSET,
+    arg("x_position", Integer),
+    arg("direction", Keyword(FLICK_DIRECTIONS)),
+];
+const TAP: &[Arg] = &[
+    arg("lane_group_id", UnsignedInteger),
+    MEASURE,
+    OFFSET,
+    arg("x_position", Integer),
+    arg("x_offset", Integer),
+];
+const HOLD: &[Arg] = &[
+    arg("lane_group_id", UnsignedInteger),
+    arg("start_measure", UnsignedInteger),
+    arg("start_offset", UnsignedInteger),
+    arg("start_x_position", Integer),
+    arg("start_x_offset", Integer),
+    arg("end_measure", UnsignedInteger),
+    arg("end_offset", UnsignedInteger),
+    arg("end_x_position", Integer),
+    arg("end_x_offset", Integer),
+];
+
+const VERSION: &[Arg] = &[
+    arg("major", UnsignedInteger),
+    arg("minor", UnsignedInteger),
+    arg("release", UnsignedInteger),
+];
+const CREATOR: &[Arg] = &[arg("name", Text)];
+const BPM_DEFINITION: &[Arg] = &[
+    arg("first", Float),
+    arg("common", Float),
+    arg("minimum", Float),
+    arg("maximum", Float),
+];
+const METER_DEFINITION: &[Arg] = &[
+    arg("num_beats", UnsignedInteger),
+    arg("note_value", UnsignedInteger),
+];
+const RESOLUTION: &[Arg] = &[arg("resolution", UnsignedInteger)];
+const PROG_JUDGE_BPM: &[Arg] = &[arg("value", Float)];
+const BULLET_PALETTE: &[Arg] = &[
+    arg("id", Identifier),
+    arg("shooter", Keyword(BULLET_SHOOTERS)),
+    arg("target_x_offset", Integer),
+    arg("target", Keyword(BULLET_TARGETS)),
+    arg("speed", Float),
+    arg("size", Keyword(BULLET_SIZES)),
+    arg("type", Keyword(BULLET_TYPES)),
+    arg("random_position_offset", Integer),
+];
+const BPM_CHANGE: &[Arg] = &[MEASURE, OFFSET, arg("bpm", Float)];
+const METER_CHANGE: &[Arg] = &[
+    MEASURE,
+    OFFSET,
+    arg("num_beats", UnsignedInteger),
+    arg("note_value", UnsignedInteger),
+];
+const SOFLAN: &[Arg] = &[
+    MEASURE,
+    OFFSET,
+    arg("duration", UnsignedInteger),
+    arg("speed_multiplier", Float),
+];
+const CLICK_SOUND: &[Arg] = &[MEASURE, OFFSET];
+const ENEMY_SET: &[Arg] = &[MEASURE, OFFSET, arg("wave", Keyword(ENEMY_WAVES))];
+const BULLET: &[Arg] = &[
+    arg("palette_id", Identifier),
+    MEASURE,
+    OFFSET,
+    arg("x_position", Integer),
+    optional("damage_type", Keyword(BULLET_DAMAGE_TYPES)),
+];
+const BELL: &[Arg] = &[
+    MEASURE,
+    OFFSET,
+    arg("x_position", Integer),
+    optional("bullet_palette_id", Identifier),
+];
+const EXTENSION: &[Arg] = &[arg("value", Text)];
+
+/// Returns the grammar of a command.
+///
+/// Bullet palettes are described in their current form, older charts give a damage type
+/// instead of the size, type and random position offset.
+pub fn command(kind: TokenKind) -> CommandGrammar {
+    let (section, args) = match kind {
+        TokenKind::Version => (Section::Header, VERSION),
+        TokenKind::Creator => (Section::Header, CREATOR),
+        TokenKind::BpmDefinition => (Section::Header, BPM_DEFINITION),
+        TokenKind::MeterDefinition => (Section::Header, METER_DEFINITION),
+        TokenKind::TickResolution | TokenKind::XResolution => (Section::Header, RESOLUTION),
+        TokenKind::ClickDefinition | TokenKind::Tutorial => (Section::Header, VALUE),
+        TokenKind::BulletDamage
+        | TokenKind::HardBulletDamage
+        | TokenKind::DangerBulletDamage
+        | TokenKind::BeamDamage => (Section::Header, DAMAGE),
+        TokenKind::ProgJudgeBpm => (Section::Header, PROG_JUDGE_BPM),
+        TokenKind::TotalNotes
+        | TokenKind::TotalTapNotes
+        | TokenKind::TotalHoldNotes
+        | TokenKind::TotalSideNotes
+        | TokenKind::TotalSideHoldNotes
+        | TokenKind::TotalFlickNotes
+        | TokenKind::TotalBellNotes => (Section::Total, VALUE),
+        TokenKind::BulletPalette => (Section::BulletPalette, BULLET_PALETTE),
+        TokenKind::Btp => (Section::BulletPalette, NONE),
+        TokenKind::BpmChange => (Section::Composition, BPM_CHANGE),
+        TokenKind::MeterChange => (Section::Composition, METER_CHANGE),
+        TokenKind::Soflan => (Section::Composition, SOFLAN),
+        TokenKind::ClickSound => (Section::Composition, CLICK_SOUND),
+        TokenKind::EnemySet => (Section::Composition, ENEMY_SET),
+        TokenKind::WallLeftStart
+        | TokenKind::WallLeftNext
+        | TokenKind::WallLeftEnd
+        | TokenKind::WallRightStart
+        | TokenKind::WallRightNext
+        | TokenKind::WallRightEnd
+        | TokenKind::LaneLeftStart
+        | TokenKind::LaneLeftNext
+        | TokenKind::LaneLeftEnd
+        | TokenKind::LaneCenterStart
+        | TokenKind::LaneCenterNext
+        | TokenKind::LaneCenterEnd
+        | TokenKind::LaneRightStart
+        | TokenKind::LaneRightNext
+        | TokenKind::LaneRightEnd
+        | TokenKind::EnemyLaneStart
+        | TokenKind::EnemyLaneNext
+        | TokenKind::EnemyLaneEnd => (Section::Lane, WALL_POINT),
+        TokenKind::ColorfulLaneStart | TokenKind::ColorfulLaneNext | TokenKind::ColorfulLaneEnd => {
+            (Section::Lane, COLORFUL_LANE_POINT)
+        }
+        TokenKind::LaneDisappearance | TokenKind::LaneBlock => (Section::Lane, LANE_EVENT),
+        TokenKind::Bullet => (Section::Bullet, BULLET),
+        TokenKind::BeamStart | TokenKind::BeamNext | TokenKind::BeamEnd => {
+            (Section::Beam, BEAM_POINT)
+        }
+        TokenKind::ObliqueBeamStart | TokenKind::ObliqueBeamNext | TokenKind::ObliqueBeamEnd => {
+            (Section::Beam, OBLIQUE_BEAM_POINT)
+        }
+        TokenKind::Bell => (Section::Bell, BELL),
+        TokenKind::Flick | TokenKind::CriticalFlick => (Section::Notes, FLICK),
+        TokenKind::Tap | TokenKind::CriticalTap => (Section::Notes, TAP),
+        TokenKind::Hold | TokenKind::CriticalHold => (Section::Notes, HOLD),
+        TokenKind::Extension => {
+            return CommandGrammar {
+                kind,
+                section: None,
+                args: EXTENSION,
+            }
+        }
+    };
+
+    CommandGrammar {
+        kind,
+        section: Some(section),
+        args,
+    }
+}
+
+/// Returns the grammar of every command.
+pub fn commands() -> impl Iterator<Item = CommandGrammar> {
+    TokenKind::ALL.iter().map(|&kind| command(kind))
+}
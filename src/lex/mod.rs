@@ -1,23 +1,40 @@
 pub mod command;
 mod cursor;
+mod custom;
+pub mod grammar;
+mod reader;
 pub mod token;
 
+use std::{
+    collections::BTreeMap,
+    ops::{ControlFlow, Range},
+};
+
 use cursor::Cursor;
+pub use custom::{CustomCommand, CustomCommands, CustomToken};
+pub use reader::{RawLine, RawLineReader, ReaderCheckpoint};
 
 use thiserror::Error;
-use token::{Token, TokenStream};
+
+use crate::{code::ErrorCode, trace::Phase};
+use command::ChartFormat;
+use token::{Token, TokenKind, TokenStream};
 
 #[non_exhaustive]
 #[derive(Clone, PartialEq, Eq, Debug, Hash, Error)]
 pub enum LexError {
-    #[error("unknown chart content at line {line}, col {col}")]
+    #[error("unknown chart content {token:?} at line {line}, col {col}")]
     UnknownCommand {
         /// Line number inside the chart file.
         line: usize,
         /// Column number inside the chart file.
         col: usize,
+        /// Byte offset of the unknown command inside the chart file.
+        offset: usize,
+        /// The unknown command.
+        token: String,
     },
-    #[error("expected token {message} not found at line {line}, col {col}")]
+    #[error("expected token {message} not found at line {line}, col {col}, found {token:?}")]
     ExpectedToken {
         /// Line number inside the chart file.
         line: usize,
@@ -25,20 +42,262 @@ pub enum LexError {
         col: usize,
         /// Message containing expected token details.
         message: &'static str,
+        /// Byte offset of the offending token inside the chart file.
+        offset: usize,
+        /// The offending token, empty if the chart ended before it.
+        token: String,
+    },
+    #[error("unexpected content {token:?} after a complete command at line {line}, col {col}")]
+    TrailingContent {
+        /// Line number inside the chart file.
+        line: usize,
+        /// Column number inside the chart file.
+        col: usize,
+        /// Byte offset of the content inside the chart file.
+        offset: usize,
+        /// The rest of the line, from the first unexpected token.
+        token: String,
     },
+    #[error("irregular whitespace at line {line}, col {col}")]
+    IrregularWhitespace {
+        /// Line number inside the chart file.
+        line: usize,
+        /// Column number inside the chart file.
+        col: usize,
+        /// Byte offset of the first separator inside the chart file.
+        offset: usize,
+    },
+}
+
+impl LexError {
+    /// Stable identifier of the kind of error.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::UnknownCommand { .. } => ErrorCode::UnknownCommand,
+            Self::ExpectedToken { .. } => ErrorCode::ExpectedToken,
+            Self::TrailingContent { .. } => ErrorCode::TrailingContent,
+            Self::IrregularWhitespace { .. } => ErrorCode::IrregularWhitespace,
+        }
+    }
 }
 
 /// Lexical analysis result type, giving [`LexError`] when lexing fails.
 pub type Result<T> = std::result::Result<T, LexError>;
 
+/// Options for tokenizing, by default only the exact mnemonics of [`TokenKind`] are accepted.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub struct LexOptions {
+    /// Accept mnemonics and aliases regardless of their ASCII case.
+    pub case_insensitive: bool,
+    /// Additional mnemonics, for charts made by tools using nonstandard command names.
+    pub aliases: BTreeMap<String, TokenKind>,
+    /// Skip lines that fail to tokenize instead of failing, the errors are kept in
+    /// [`TokenStream::errors`].
+    pub lenient: bool,
+    /// Only accept single separators between arguments, no indentation or trailing whitespace,
+    /// and nothing after the last argument of a command. By default separator runs of any
+    /// length are accepted and content after a complete command is skipped.
+    pub strict_whitespace: bool,
+    /// Handlers of commands the crate does not know, instead of failing with
+    /// [`LexError::UnknownCommand`].
+    pub custom_commands: CustomCommands,
+}
+
+impl LexOptions {
+    /// Accepts lowercase and mixed case mnemonics.
+    pub fn case_insensitive(mut self) -> Self {
+        self.case_insensitive = true;
+        self
+    }
+
+    /// Skips invalid lines instead of failing.
+    pub fn lenient(mut self) -> Self {
+        self.lenient = true;
+        self
+    }
+
+    /// Rejects irregular whitespace and trailing content.
+    pub fn strict_whitespace(mut self) -> Self {
+        self.strict_whitespace = true;
+        self
+    }
+
+    /// Reads commands written with `mnemonic` as `T`, giving [`Token::Custom`]. Built-in
+    /// mnemonics and aliases take precedence.
+    pub fn custom_command<T: CustomCommand>(mut self, mnemonic: impl Into<String>) -> Self {
+        self.custom_commands.register::<T>(mnemonic.into());
+        self
+    }
+
+    /// Accepts `mnemonic` as a command of the given kind.
+    pub fn alias(mut self, mnemonic: impl Into<String>, kind: TokenKind) -> Self {
+        self.aliases.insert(mnemonic.into(), kind);
+        self
+    }
+
+    /// Kind of command written with `mnemonic`, taking aliases and case insensitivity into
+    /// account.
+    pub fn resolve(&self, mnemonic: &str) -> Option<TokenKind> {
+        if let Some(kind) =
+            TokenKind::from_mnemonic(mnemonic).or_else(|| self.aliases.get(mnemonic).copied())
+        {
+            return Some(kind);
+        }
+        if !self.case_insensitive {
+            return None;
+        }
+
+        TokenKind::from_mnemonic(&mnemonic.to_ascii_uppercase()).or_else(|| {
+            self.aliases
+                .iter()
+                .find(|(alias, _)| alias.eq_ignore_ascii_case(mnemonic))
+                .map(|(_, kind)| *kind)
+        })
+    }
+}
+
+/// Line and column of the byte `offset` in `source`, both starting at 1 as in [`LexError`].
+///
+/// Columns count chars rather than bytes, so they match what editors show for multibyte text.
+/// `offset` is clamped to the end of `source`.
+pub fn line_col(source: &str, offset: usize) -> (usize, usize) {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &source[..offset];
+
+    let line = 1 + cursor::count_line_breaks(before);
+    let line_text = match before.rfind(cursor::is_line_break) {
+        Some(last_break) => &before[last_break + 1..],
+        None => before.strip_prefix(cursor::BOM).unwrap_or(before),
+    };
+    (line, line_text.chars().count() + 1)
+}
+
 /// Tokenizes chart content.
 pub fn tokenize(source: &str) -> Result<TokenStream> {
-    let mut cursor = Cursor::new(source);
+    tokenize_with_options(source, &LexOptions::default())
+}
+
+/// Same as [`tokenize`], using custom lexing options.
+pub fn tokenize_with_options(source: &str, options: &LexOptions) -> Result<TokenStream> {
+    tokenize_with_format(source, options, ChartFormat::Auto)
+}
 
+/// Same as [`tokenize_with_options`], reading `BPL` and `BLT` commands in the given format.
+pub fn tokenize_with_format(
+    source: &str,
+    options: &LexOptions,
+    format: ChartFormat,
+) -> Result<TokenStream> {
     let mut tokens = vec![];
+    let mut spans = vec![];
+    let (errors, _) = stream_tokens(source, options, format, |token, span| {
+        tokens.push(token);
+        spans.push(span);
+        ControlFlow::Continue(())
+    })?;
+
+    Ok(TokenStream::from_spanned_tokens(tokens, spans, errors))
+}
+
+/// Tokenizes chart content, giving up with `None` once more than `max_tokens` tokens are found.
+pub(crate) fn tokenize_limited(
+    source: &str,
+    max_tokens: usize,
+    options: &LexOptions,
+    format: ChartFormat,
+) -> Result<Option<TokenStream>> {
+    let mut tokens = vec![];
+    let mut spans = vec![];
+    let (errors, flow) = stream_tokens(source, options, format, |token, span| {
+        if tokens.len() >= max_tokens {
+            return ControlFlow::Break(());
+        }
+        tokens.push(token);
+        spans.push(span);
+        ControlFlow::Continue(())
+    })?;
+    if flow.is_break() {
+        return Ok(None);
+    }
+
+    Ok(Some(TokenStream::from_spanned_tokens(
+        tokens, spans, errors,
+    )))
+}
+
+/// Passes every token of `source` to `f` as soon as it is read, along with the source range of
+/// its line, until `f` breaks.
+///
+/// Gives the errors of lines skipped in lenient mode and whether `f` broke.
+pub(crate) fn stream_tokens(
+    source: &str,
+    options: &LexOptions,
+    mut format: ChartFormat,
+    mut f: impl FnMut(Token, Range<usize>) -> ControlFlow<()>,
+) -> Result<(Vec<LexError>, ControlFlow<()>)> {
+    let phase = Phase::enter("lex", source.len());
+    let mut cursor = Cursor::new(source).strict_whitespace(options.strict_whitespace);
+
+    let mut count = 0;
+    let mut errors = vec![];
+    let mut flow = ControlFlow::Continue(());
     while !cursor.is_end() {
-        tokens.push(Token::from_cursor(&mut cursor)?);
+        let Some((token, span)) =
+            next_spanned_token(&mut cursor, options, &mut format, &mut errors)?
+        else {
+            continue;
+        };
+        flow = f(token, span);
+        if flow.is_break() {
+            break;
+        }
+        count += 1;
     }
 
-    Ok(TokenStream::from_tokens(tokens))
+    phase.record_output(count);
+    Ok((errors, flow))
+}
+
+/// Reads the next token along with the source byte range of its whole command line, without
+/// trailing whitespace.
+///
+/// In lenient mode errors are pushed to `errors` and the line of the failed command is skipped,
+/// giving `None`. Only the rest of that line is skipped, so a line missing arguments does not take
+/// the following command down with it.
+///
+/// With the `legacy-format` feature an automatic `format` is replaced with the one of the first
+/// `VERSION` command.
+fn next_spanned_token(
+    cursor: &mut Cursor,
+    options: &LexOptions,
+    format: &mut ChartFormat,
+    errors: &mut Vec<LexError>,
+) -> Result<Option<(Token, Range<usize>)>> {
+    let checkpoint = cursor.checkpoint();
+    let start = cursor.next_token_start();
+    let result = Token::from_cursor(cursor, options, *format).and_then(|token| {
+        #[cfg(feature = "legacy-format")]
+        if let (Token::Version(version), ChartFormat::Auto) = (&token, *format) {
+            *format = ChartFormat::of_version(version);
+        }
+        let span = cursor.span_from(start);
+        cursor.end_command()?;
+        Ok((token, span))
+    });
+    match result {
+        Ok(spanned) => Ok(Some(spanned)),
+        Err(error) if options.lenient => {
+            crate::diagnostics::warning!("Skipping line after lexing error: {}", error);
+            errors.push(error);
+            cursor.rewind(checkpoint);
+            cursor.next_token();
+            cursor.current_remaining_line();
+            Ok(None)
+        }
+        Err(error) => Err(error),
+    }
 }
@@ -0,0 +1,170 @@
+//! Low-level access to the tokenizer behind [`tokenize`](super::tokenize), for reading commands
+//! the crate does not know about with the same separator, line ending and error position rules.
+
+use std::str::FromStr;
+
+use super::{
+    cursor::{is_separator, Checkpoint, Cursor},
+    LexError, Result,
+};
+
+/// Reads chart content token by token or line by line.
+///
+/// Iterating gives the remaining lines holding any token, while the token methods read the
+/// arguments of a single command line.
+#[derive(Clone)]
+pub struct RawLineReader<'a> {
+    cursor: Cursor<'a>,
+}
+
+/// Line of chart content, without surrounding separators.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RawLine<'a> {
+    /// Line number inside the chart file.
+    pub line: usize,
+    /// Byte offset of the first token inside the chart file.
+    pub offset: usize,
+    pub text: &'a str,
+}
+
+impl<'a> RawLine<'a> {
+    /// Tokens of the line, split on runs of separators.
+    pub fn tokens(&self) -> impl Iterator<Item = &'a str> {
+        self.text
+            .split(is_separator)
+            .filter(|token| !token.is_empty())
+    }
+
+    /// First token of the line, the command mnemonic.
+    pub fn mnemonic(&self) -> &'a str {
+        self.tokens().next().unwrap_or("")
+    }
+}
+
+/// Position of a [`RawLineReader`] to rewind to.
+#[derive(Clone, Copy, Debug)]
+pub struct ReaderCheckpoint(Checkpoint);
+
+impl<'a> RawLineReader<'a> {
+    pub(crate) fn from_cursor(cursor: Cursor<'a>) -> Self {
+        Self { cursor }
+    }
+
+    pub(crate) fn into_cursor(self) -> Cursor<'a> {
+        self.cursor
+    }
+
+    /// Reader starting at the beginning of `source`, skipping a leading byte order mark.
+    pub fn new(source: &'a str) -> Self {
+        Self {
+            cursor: Cursor::new(source),
+        }
+    }
+
+    /// Rejects irregular whitespace as
+    /// [`LexOptions::strict_whitespace`](super::LexOptions::strict_whitespace) does, reported by
+    /// [`RawLineReader::end_command`].
+    pub fn strict_whitespace(mut self) -> Self {
+        self.cursor = self.cursor.strict_whitespace(true);
+        self
+    }
+
+    /// Whether no tokens are left.
+    pub fn is_end(&self) -> bool {
+        self.cursor.is_end()
+    }
+
+    /// Next token, without consuming it.
+    pub fn peek_token(&self) -> Option<&'a str> {
+        self.cursor.peek_token()
+    }
+
+    /// Consumes the next token, which may be on a following line.
+    pub fn next_token(&mut self) -> Option<&'a str> {
+        self.cursor.next_token()
+    }
+
+    /// Consumes the next token, failing with `message` describing the expected argument if the
+    /// content ended.
+    pub fn expect_token(&mut self, message: &'static str) -> Result<&'a str> {
+        self.cursor
+            .next_token()
+            .ok_or_else(|| self.cursor.err_expected_token(message))
+    }
+
+    /// Consumes the next token and parses it, failing with `message` describing the expected
+    /// argument.
+    pub fn parse_token<T: FromStr>(&mut self, message: &'static str) -> Result<T> {
+        self.expect_token(message)?
+            .parse()
+            .map_err(|_| self.cursor.err_expected_token(message))
+    }
+
+    /// Consumes the rest of the current line, for free text arguments.
+    pub fn rest_of_line(&mut self) -> &'a str {
+        self.cursor.current_remaining_line()
+    }
+
+    /// Ends a complete command, skipping or rejecting anything left on its line the same way
+    /// [`tokenize`](super::tokenize) does.
+    pub fn end_command(&mut self) -> Result<()> {
+        self.cursor.end_command()
+    }
+
+    /// Consumes the rest of the line of the next token.
+    pub fn next_line(&mut self) -> Option<RawLine<'a>> {
+        self.cursor
+            .next_line()
+            .map(|(line, offset, text)| RawLine { line, offset, text })
+    }
+
+    /// Line number of the last consumed token.
+    pub fn line(&self) -> usize {
+        self.cursor.line()
+    }
+
+    /// Column of the last consumed token, in chars.
+    pub fn col(&self) -> usize {
+        self.cursor.col()
+    }
+
+    /// Byte offset of the last consumed token.
+    pub fn offset(&self) -> usize {
+        self.cursor.offset()
+    }
+
+    /// Error for the last consumed token not being the argument described by `message`.
+    pub fn expected(&self, message: &'static str) -> LexError {
+        self.cursor.err_expected_token(message)
+    }
+
+    /// Saves the current position.
+    pub fn checkpoint(&self) -> ReaderCheckpoint {
+        ReaderCheckpoint(self.cursor.checkpoint())
+    }
+
+    /// Goes back to a position saved with [`RawLineReader::checkpoint`].
+    pub fn rewind(&mut self, checkpoint: ReaderCheckpoint) {
+        self.cursor.rewind(checkpoint.0);
+    }
+
+    /// Runs `parse`, rewinding if it fails so that another argument layout can be tried.
+    pub fn speculate<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let checkpoint = self.checkpoint();
+        let speculating = self.cursor.set_speculating(true);
+        let result = parse(self);
+        self.cursor.set_speculating(speculating);
+        if result.is_err() {
+            self.rewind(checkpoint);
+        }
+        result
+    }
+}
+
+impl<'a> Iterator for RawLineReader<'a> {
+    type Item = RawLine<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_line()
+    }
+}
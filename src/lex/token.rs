@@ -1,6 +1,6 @@
-use crate::lex::LexError;
+use std::ops::Range;
 
-use super::{command::*, cursor::Cursor, Result};
+use super::{command::*, cursor::Cursor, CustomToken, LexError, LexOptions, RawLineReader, Result};
 
 /// These tokens are not strictly lexical and and conforms to the syntax of a command line.
 /// The "lexer" here handles syntax within a single line while the "parser" will handle the overall
@@ -94,108 +94,478 @@ pub enum Token {
     CriticalTap(Tap),
     Hold(Hold),
     CriticalHold(Hold),
+
+    Extension(Extension),
+
+    /// Command read by a handler registered with [`LexOptions::custom_command`].
+    Custom(CustomToken),
 }
 
 impl Token {
-    pub(crate) fn from_cursor(cursor: &mut Cursor) -> Result<Self> {
-        loop {
-            let command = cursor
-                .next_token()
-                .ok_or_else(|| cursor.err_expected_token("valid command"))?;
+    pub(crate) fn from_cursor(
+        cursor: &mut Cursor,
+        options: &LexOptions,
+        format: ChartFormat,
+    ) -> Result<Self> {
+        let command = cursor
+            .next_token()
+            .ok_or_else(|| cursor.err_expected_token("valid command"))?;
+
+        if command.starts_with('[') {
+            crate::diagnostics::debug!("Ignoring section name {} line", command);
+            cursor.current_remaining_line();
+            return Ok(Self::SectionName);
+        }
 
-            if command.starts_with('[') {
-                log::debug!("Ignoring section name {} line", command);
-                cursor.current_remaining_line();
-                return Ok(Self::SectionName);
+        let Some(kind) = options.resolve(command) else {
+            let handler = options
+                .custom_commands
+                .get(command)
+                .ok_or_else(|| cursor.err_unknown_command())?;
+            let mut reader = RawLineReader::from_cursor(std::mem::replace(cursor, Cursor::new("")));
+            let result = handler(&mut reader);
+            *cursor = reader.into_cursor();
+            return Ok(Self::Custom(CustomToken {
+                mnemonic: command.to_string(),
+                command: result?,
+            }));
+        };
+
+        Ok(match kind {
+            TokenKind::Version => Self::Version(Version::from_cursor(cursor)?),
+            TokenKind::Creator => Self::Creator(Creator::from_cursor(cursor)?),
+            TokenKind::BpmDefinition => Self::BpmDefinition(BpmDefinition::from_cursor(cursor)?),
+            TokenKind::MeterDefinition => {
+                Self::MeterDefinition(MeterDefinition::from_cursor(cursor)?)
+            }
+            TokenKind::TickResolution => Self::TickResolution(TickResolution::from_cursor(cursor)?),
+            TokenKind::XResolution => Self::XResolution(XResolution::from_cursor(cursor)?),
+            TokenKind::ClickDefinition => {
+                Self::ClickDefinition(ClickDefinition::from_cursor(cursor)?)
+            }
+            TokenKind::Tutorial => Self::Tutorial(Tutorial::from_cursor(cursor)?),
+            TokenKind::BulletDamage => Self::BulletDamage(BulletDamage::from_cursor(cursor)?),
+            TokenKind::HardBulletDamage => {
+                Self::HardBulletDamage(HardBulletDamage::from_cursor(cursor)?)
+            }
+            TokenKind::DangerBulletDamage => {
+                Self::DangerBulletDamage(DangerBulletDamage::from_cursor(cursor)?)
+            }
+            TokenKind::BeamDamage => Self::BeamDamage(BeamDamage::from_cursor(cursor)?),
+            TokenKind::TotalNotes => Self::TotalNotes(TotalNotes::from_cursor(cursor)?),
+            TokenKind::TotalTapNotes => Self::TotalTapNotes(TotalTapNotes::from_cursor(cursor)?),
+            TokenKind::TotalHoldNotes => Self::TotalHoldNotes(TotalHoldNotes::from_cursor(cursor)?),
+            TokenKind::TotalSideNotes => Self::TotalSideNotes(TotalSideNotes::from_cursor(cursor)?),
+            TokenKind::TotalSideHoldNotes => {
+                Self::TotalSideHoldNotes(TotalSideHoldNotes::from_cursor(cursor)?)
+            }
+            TokenKind::TotalFlickNotes => {
+                Self::TotalFlickNotes(TotalFlickNotes::from_cursor(cursor)?)
+            }
+            TokenKind::TotalBellNotes => Self::TotalBellNotes(TotalBellNotes::from_cursor(cursor)?),
+            TokenKind::ProgJudgeBpm => Self::ProgJudgeBpm(ProgJudgeBpm::from_cursor(cursor)?),
+            TokenKind::BulletPalette => {
+                Self::BulletPalette(BulletPalette::from_cursor(cursor, format)?)
+            }
+            TokenKind::Btp => Self::Btp(Btp),
+            TokenKind::BpmChange => Self::BpmChange(BpmChange::from_cursor(cursor)?),
+            TokenKind::MeterChange => Self::MeterChange(MeterChange::from_cursor(cursor)?),
+            TokenKind::ClickSound => Self::ClickSound(ClickSound::from_cursor(cursor)?),
+            TokenKind::Soflan => Self::Soflan(Soflan::from_cursor(cursor)?),
+            TokenKind::EnemySet => Self::EnemySet(EnemySet::from_cursor(cursor)?),
+            TokenKind::WallLeftStart => Self::WallLeftStart(WallPoint::from_cursor(cursor)?),
+            TokenKind::WallLeftNext => Self::WallLeftNext(WallPoint::from_cursor(cursor)?),
+            TokenKind::WallLeftEnd => Self::WallLeftEnd(WallPoint::from_cursor(cursor)?),
+            TokenKind::WallRightStart => Self::WallRightStart(WallPoint::from_cursor(cursor)?),
+            TokenKind::WallRightNext => Self::WallRightNext(WallPoint::from_cursor(cursor)?),
+            TokenKind::WallRightEnd => Self::WallRightEnd(WallPoint::from_cursor(cursor)?),
+            TokenKind::LaneLeftStart => Self::LaneLeftStart(LanePoint::from_cursor(cursor)?),
+            TokenKind::LaneLeftNext => Self::LaneLeftNext(LanePoint::from_cursor(cursor)?),
+            TokenKind::LaneLeftEnd => Self::LaneLeftEnd(LanePoint::from_cursor(cursor)?),
+            TokenKind::LaneCenterStart => Self::LaneCenterStart(LanePoint::from_cursor(cursor)?),
+            TokenKind::LaneCenterNext => Self::LaneCenterNext(LanePoint::from_cursor(cursor)?),
+            TokenKind::LaneCenterEnd => Self::LaneCenterEnd(LanePoint::from_cursor(cursor)?),
+            TokenKind::LaneRightStart => Self::LaneRightStart(LanePoint::from_cursor(cursor)?),
+            TokenKind::LaneRightNext => Self::LaneRightNext(LanePoint::from_cursor(cursor)?),
+            TokenKind::LaneRightEnd => Self::LaneRightEnd(LanePoint::from_cursor(cursor)?),
+            TokenKind::ColorfulLaneStart => {
+                Self::ColorfulLaneStart(ColorfulLanePoint::from_cursor(cursor)?)
+            }
+            TokenKind::ColorfulLaneNext => {
+                Self::ColorfulLaneNext(ColorfulLanePoint::from_cursor(cursor)?)
+            }
+            TokenKind::ColorfulLaneEnd => {
+                Self::ColorfulLaneEnd(ColorfulLanePoint::from_cursor(cursor)?)
+            }
+            TokenKind::EnemyLaneStart => Self::EnemyLaneStart(EnemyLanePoint::from_cursor(cursor)?),
+            TokenKind::EnemyLaneNext => Self::EnemyLaneNext(EnemyLanePoint::from_cursor(cursor)?),
+            TokenKind::EnemyLaneEnd => Self::EnemyLaneEnd(EnemyLanePoint::from_cursor(cursor)?),
+            TokenKind::LaneDisappearance => {
+                Self::LaneDisappearance(LaneEvent::from_cursor(cursor)?)
             }
+            TokenKind::LaneBlock => Self::LaneBlock(LaneEvent::from_cursor(cursor)?),
+            TokenKind::Bullet => Self::Bullet(Bullet::from_cursor(cursor, format)?),
+            TokenKind::BeamStart => Self::BeamStart(BeamPoint::from_cursor(cursor)?),
+            TokenKind::BeamNext => Self::BeamNext(BeamPoint::from_cursor(cursor)?),
+            TokenKind::BeamEnd => Self::BeamEnd(BeamPoint::from_cursor(cursor)?),
+            TokenKind::ObliqueBeamStart => {
+                Self::ObliqueBeamStart(ObliqueBeamPoint::from_cursor(cursor)?)
+            }
+            TokenKind::ObliqueBeamNext => {
+                Self::ObliqueBeamNext(ObliqueBeamPoint::from_cursor(cursor)?)
+            }
+            TokenKind::ObliqueBeamEnd => {
+                Self::ObliqueBeamEnd(ObliqueBeamPoint::from_cursor(cursor)?)
+            }
+            TokenKind::Bell => Self::Bell(Bell::from_cursor(cursor)?),
+            TokenKind::Flick => Self::Flick(Flick::from_cursor(cursor)?),
+            TokenKind::CriticalFlick => Self::CriticalFlick(Flick::from_cursor(cursor)?),
+            TokenKind::Tap => Self::Tap(Tap::from_cursor(cursor)?),
+            TokenKind::CriticalTap => Self::CriticalTap(Tap::from_cursor(cursor)?),
+            TokenKind::Hold => Self::Hold(Hold::from_cursor(cursor)?),
+            TokenKind::CriticalHold => Self::CriticalHold(Hold::from_cursor(cursor)?),
+            TokenKind::Extension => {
+                let key = command[Extension::PREFIX.len()..].to_string();
+                Self::Extension(Extension::from_cursor(key, cursor))
+            }
+        })
+    }
 
-            break Ok(match command {
-                "VERSION" => Self::Version(Version::from_cursor(cursor)?),
-                "CREATOR" => Self::Creator(Creator::from_cursor(cursor)?),
-                "BPM_DEF" => Self::BpmDefinition(BpmDefinition::from_cursor(cursor)?),
-                "MET_DEF" => Self::MeterDefinition(MeterDefinition::from_cursor(cursor)?),
-                "TRESOLUTION" => Self::TickResolution(TickResolution::from_cursor(cursor)?),
-                "XRESOLUTION" => Self::XResolution(XResolution::from_cursor(cursor)?),
-                "CLK_DEF" => Self::ClickDefinition(ClickDefinition::from_cursor(cursor)?),
-                "TUTORIAL" => Self::Tutorial(Tutorial::from_cursor(cursor)?),
-                "BULLET_DAMAGE" => Self::BulletDamage(BulletDamage::from_cursor(cursor)?),
-                "HARDBULLET_DAMAGE" => {
-                    Self::HardBulletDamage(HardBulletDamage::from_cursor(cursor)?)
-                }
-                "DANGERBULLET_DAMAGE" => {
-                    Self::DangerBulletDamage(DangerBulletDamage::from_cursor(cursor)?)
-                }
-                "BEAM_DAMAGE" => Self::BeamDamage(BeamDamage::from_cursor(cursor)?),
-                "T_TOTAL" => Self::TotalNotes(TotalNotes::from_cursor(cursor)?),
-                "T_TAP" => Self::TotalTapNotes(TotalTapNotes::from_cursor(cursor)?),
-                "T_HOLD" => Self::TotalHoldNotes(TotalHoldNotes::from_cursor(cursor)?),
-                "T_SIDE" => Self::TotalSideNotes(TotalSideNotes::from_cursor(cursor)?),
-                "T_SHOLD" => Self::TotalSideHoldNotes(TotalSideHoldNotes::from_cursor(cursor)?),
-                "T_FLICK" => Self::TotalFlickNotes(TotalFlickNotes::from_cursor(cursor)?),
-                "T_BELL" => Self::TotalBellNotes(TotalBellNotes::from_cursor(cursor)?),
-                "PROGJUDGE_BPM" => Self::ProgJudgeBpm(ProgJudgeBpm::from_cursor(cursor)?),
-                "BPL" => Self::BulletPalette(BulletPalette::from_cursor(cursor)?),
-                "BTP" => Self::Btp(Btp),
-                "BPM" => Self::BpmChange(BpmChange::from_cursor(cursor)?),
-                "MET" => Self::MeterChange(MeterChange::from_cursor(cursor)?),
-                "CLK" => Self::ClickSound(ClickSound::from_cursor(cursor)?),
-                "SFL" => Self::Soflan(Soflan::from_cursor(cursor)?),
-                "EST" => Self::EnemySet(EnemySet::from_cursor(cursor)?),
-                "WLS" => Self::WallLeftStart(WallPoint::from_cursor(cursor)?),
-                "WLN" => Self::WallLeftNext(WallPoint::from_cursor(cursor)?),
-                "WLE" => Self::WallLeftEnd(WallPoint::from_cursor(cursor)?),
-                "WRS" => Self::WallRightStart(WallPoint::from_cursor(cursor)?),
-                "WRN" => Self::WallRightNext(WallPoint::from_cursor(cursor)?),
-                "WRE" => Self::WallRightEnd(WallPoint::from_cursor(cursor)?),
-                "LLS" => Self::LaneLeftStart(LanePoint::from_cursor(cursor)?),
-                "LLN" => Self::LaneLeftNext(LanePoint::from_cursor(cursor)?),
-                "LLE" => Self::LaneLeftEnd(LanePoint::from_cursor(cursor)?),
-                "LCS" => Self::LaneCenterStart(LanePoint::from_cursor(cursor)?),
-                "LCN" => Self::LaneCenterNext(LanePoint::from_cursor(cursor)?),
-                "LCE" => Self::LaneCenterEnd(LanePoint::from_cursor(cursor)?),
-                "LRS" => Self::LaneRightStart(LanePoint::from_cursor(cursor)?),
-                "LRN" => Self::LaneRightNext(LanePoint::from_cursor(cursor)?),
-                "LRE" => Self::LaneRightEnd(LanePoint::from_cursor(cursor)?),
-                "CLS" => Self::ColorfulLaneStart(ColorfulLanePoint::from_cursor(cursor)?),
-                "CLN" => Self::ColorfulLaneNext(ColorfulLanePoint::from_cursor(cursor)?),
-                "CLE" => Self::ColorfulLaneEnd(ColorfulLanePoint::from_cursor(cursor)?),
-                "ENS" => Self::EnemyLaneStart(EnemyLanePoint::from_cursor(cursor)?),
-                "ENN" => Self::EnemyLaneNext(EnemyLanePoint::from_cursor(cursor)?),
-                "ENE" => Self::EnemyLaneEnd(EnemyLanePoint::from_cursor(cursor)?),
-                "LDP" => Self::LaneDisappearance(LaneEvent::from_cursor(cursor)?),
-                "LBK" => Self::LaneBlock(LaneEvent::from_cursor(cursor)?),
-                "BLT" => Self::Bullet(Bullet::from_cursor(cursor)?),
-                "BMS" => Self::BeamStart(BeamPoint::from_cursor(cursor)?),
-                "BMN" => Self::BeamNext(BeamPoint::from_cursor(cursor)?),
-                "BME" => Self::BeamEnd(BeamPoint::from_cursor(cursor)?),
-                "OBS" => Self::ObliqueBeamStart(ObliqueBeamPoint::from_cursor(cursor)?),
-                "OBN" => Self::ObliqueBeamNext(ObliqueBeamPoint::from_cursor(cursor)?),
-                "OBE" => Self::ObliqueBeamEnd(ObliqueBeamPoint::from_cursor(cursor)?),
-                "BEL" => Self::Bell(Bell::from_cursor(cursor)?),
-                "FLK" => Self::Flick(Flick::from_cursor(cursor)?),
-                "CFK" => Self::CriticalFlick(Flick::from_cursor(cursor)?),
-                "TAP" => Self::Tap(Tap::from_cursor(cursor)?),
-                "CTP" | "XTP" => Self::CriticalTap(Tap::from_cursor(cursor)?),
-                "HLD" => Self::Hold(Hold::from_cursor(cursor)?),
-                "CHD" | "XHD" => Self::CriticalHold(Hold::from_cursor(cursor)?),
-                _ => {
-                    return Err(LexError::UnknownCommand {
-                        line: cursor.line(),
-                        col: cursor.col(),
-                    })
-                }
-            });
+    /// Kind of the command, `None` for section names and custom commands.
+    pub fn kind(&self) -> Option<TokenKind> {
+        Some(match self {
+            Self::SectionName => return None,
+            Self::Version(_) => TokenKind::Version,
+            Self::Creator(_) => TokenKind::Creator,
+            Self::BpmDefinition(_) => TokenKind::BpmDefinition,
+            Self::MeterDefinition(_) => TokenKind::MeterDefinition,
+            Self::TickResolution(_) => TokenKind::TickResolution,
+            Self::XResolution(_) => TokenKind::XResolution,
+            Self::ClickDefinition(_) => TokenKind::ClickDefinition,
+            Self::Tutorial(_) => TokenKind::Tutorial,
+            Self::BulletDamage(_) => TokenKind::BulletDamage,
+            Self::HardBulletDamage(_) => TokenKind::HardBulletDamage,
+            Self::DangerBulletDamage(_) => TokenKind::DangerBulletDamage,
+            Self::BeamDamage(_) => TokenKind::BeamDamage,
+            Self::TotalNotes(_) => TokenKind::TotalNotes,
+            Self::TotalTapNotes(_) => TokenKind::TotalTapNotes,
+            Self::TotalHoldNotes(_) => TokenKind::TotalHoldNotes,
+            Self::TotalSideNotes(_) => TokenKind::TotalSideNotes,
+            Self::TotalSideHoldNotes(_) => TokenKind::TotalSideHoldNotes,
+            Self::TotalFlickNotes(_) => TokenKind::TotalFlickNotes,
+            Self::TotalBellNotes(_) => TokenKind::TotalBellNotes,
+            Self::ProgJudgeBpm(_) => TokenKind::ProgJudgeBpm,
+            Self::BulletPalette(_) => TokenKind::BulletPalette,
+            Self::Btp(_) => TokenKind::Btp,
+            Self::BpmChange(_) => TokenKind::BpmChange,
+            Self::MeterChange(_) => TokenKind::MeterChange,
+            Self::ClickSound(_) => TokenKind::ClickSound,
+            Self::Soflan(_) => TokenKind::Soflan,
+            Self::EnemySet(_) => TokenKind::EnemySet,
+            Self::WallLeftStart(_) => TokenKind::WallLeftStart,
+            Self::WallLeftNext(_) => TokenKind::WallLeftNext,
+            Self::WallLeftEnd(_) => TokenKind::WallLeftEnd,
+            Self::WallRightStart(_) => TokenKind::WallRightStart,
+            Self::WallRightNext(_) => TokenKind::WallRightNext,
+            Self::WallRightEnd(_) => TokenKind::WallRightEnd,
+            Self::LaneLeftStart(_) => TokenKind::LaneLeftStart,
+            Self::LaneLeftNext(_) => TokenKind::LaneLeftNext,
+            Self::LaneLeftEnd(_) => TokenKind::LaneLeftEnd,
+            Self::LaneCenterStart(_) => TokenKind::LaneCenterStart,
+            Self::LaneCenterNext(_) => TokenKind::LaneCenterNext,
+            Self::LaneCenterEnd(_) => TokenKind::LaneCenterEnd,
+            Self::LaneRightStart(_) => TokenKind::LaneRightStart,
+            Self::LaneRightNext(_) => TokenKind::LaneRightNext,
+            Self::LaneRightEnd(_) => TokenKind::LaneRightEnd,
+            Self::ColorfulLaneStart(_) => TokenKind::ColorfulLaneStart,
+            Self::ColorfulLaneNext(_) => TokenKind::ColorfulLaneNext,
+            Self::ColorfulLaneEnd(_) => TokenKind::ColorfulLaneEnd,
+            Self::EnemyLaneStart(_) => TokenKind::EnemyLaneStart,
+            Self::EnemyLaneNext(_) => TokenKind::EnemyLaneNext,
+            Self::EnemyLaneEnd(_) => TokenKind::EnemyLaneEnd,
+            Self::LaneDisappearance(_) => TokenKind::LaneDisappearance,
+            Self::LaneBlock(_) => TokenKind::LaneBlock,
+            Self::Bullet(_) => TokenKind::Bullet,
+            Self::BeamStart(_) => TokenKind::BeamStart,
+            Self::BeamNext(_) => TokenKind::BeamNext,
+            Self::BeamEnd(_) => TokenKind::BeamEnd,
+            Self::ObliqueBeamStart(_) => TokenKind::ObliqueBeamStart,
+            Self::ObliqueBeamNext(_) => TokenKind::ObliqueBeamNext,
+            Self::ObliqueBeamEnd(_) => TokenKind::ObliqueBeamEnd,
+            Self::Bell(_) => TokenKind::Bell,
+            Self::Flick(_) => TokenKind::Flick,
+            Self::CriticalFlick(_) => TokenKind::CriticalFlick,
+            Self::Tap(_) => TokenKind::Tap,
+            Self::CriticalTap(_) => TokenKind::CriticalTap,
+            Self::Hold(_) => TokenKind::Hold,
+            Self::CriticalHold(_) => TokenKind::CriticalHold,
+            Self::Extension(_) => TokenKind::Extension,
+            Self::Custom(_) => return None,
+        })
+    }
+}
+
+/// Kind of a command, identified by its mnemonic at the start of a line.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TokenKind {
+    Version,
+    Creator,
+    BpmDefinition,
+    MeterDefinition,
+    TickResolution,
+    XResolution,
+    ClickDefinition,
+    Tutorial,
+    BulletDamage,
+    HardBulletDamage,
+    DangerBulletDamage,
+    BeamDamage,
+    TotalNotes,
+    TotalTapNotes,
+    TotalHoldNotes,
+    TotalSideNotes,
+    TotalSideHoldNotes,
+    TotalFlickNotes,
+    TotalBellNotes,
+    ProgJudgeBpm,
+    BulletPalette,
+    Btp,
+    BpmChange,
+    MeterChange,
+    ClickSound,
+    Soflan,
+    EnemySet,
+    WallLeftStart,
+    WallLeftNext,
+    WallLeftEnd,
+    WallRightStart,
+    WallRightNext,
+    WallRightEnd,
+    LaneLeftStart,
+    LaneLeftNext,
+    LaneLeftEnd,
+    LaneCenterStart,
+    LaneCenterNext,
+    LaneCenterEnd,
+    LaneRightStart,
+    LaneRightNext,
+    LaneRightEnd,
+    ColorfulLaneStart,
+    ColorfulLaneNext,
+    ColorfulLaneEnd,
+    EnemyLaneStart,
+    EnemyLaneNext,
+    EnemyLaneEnd,
+    LaneDisappearance,
+    LaneBlock,
+    Bullet,
+    BeamStart,
+    BeamNext,
+    BeamEnd,
+    ObliqueBeamStart,
+    ObliqueBeamNext,
+    ObliqueBeamEnd,
+    Bell,
+    Flick,
+    CriticalFlick,
+    Tap,
+    CriticalTap,
+    Hold,
+    CriticalHold,
+    Extension,
+}
+
+impl TokenKind {
+    /// Every command kind.
+    pub const ALL: &'static [TokenKind] = &[
+        Self::Version,
+        Self::Creator,
+        Self::BpmDefinition,
+        Self::MeterDefinition,
+        Self::TickResolution,
+        Self::XResolution,
+        Self::ClickDefinition,
+        Self::Tutorial,
+        Self::BulletDamage,
+        Self::HardBulletDamage,
+        Self::DangerBulletDamage,
+        Self::BeamDamage,
+        Self::TotalNotes,
+        Self::TotalTapNotes,
+        Self::TotalHoldNotes,
+        Self::TotalSideNotes,
+        Self::TotalSideHoldNotes,
+        Self::TotalFlickNotes,
+        Self::TotalBellNotes,
+        Self::ProgJudgeBpm,
+        Self::BulletPalette,
+        Self::Btp,
+        Self::BpmChange,
+        Self::MeterChange,
+        Self::ClickSound,
+        Self::Soflan,
+        Self::EnemySet,
+        Self::WallLeftStart,
+        Self::WallLeftNext,
+        Self::WallLeftEnd,
+        Self::WallRightStart,
+        Self::WallRightNext,
+        Self::WallRightEnd,
+        Self::LaneLeftStart,
+        Self::LaneLeftNext,
+        Self::LaneLeftEnd,
+        Self::LaneCenterStart,
+        Self::LaneCenterNext,
+        Self::LaneCenterEnd,
+        Self::LaneRightStart,
+        Self::LaneRightNext,
+        Self::LaneRightEnd,
+        Self::ColorfulLaneStart,
+        Self::ColorfulLaneNext,
+        Self::ColorfulLaneEnd,
+        Self::EnemyLaneStart,
+        Self::EnemyLaneNext,
+        Self::EnemyLaneEnd,
+        Self::LaneDisappearance,
+        Self::LaneBlock,
+        Self::Bullet,
+        Self::BeamStart,
+        Self::BeamNext,
+        Self::BeamEnd,
+        Self::ObliqueBeamStart,
+        Self::ObliqueBeamNext,
+        Self::ObliqueBeamEnd,
+        Self::Bell,
+        Self::Flick,
+        Self::CriticalFlick,
+        Self::Tap,
+        Self::CriticalTap,
+        Self::Hold,
+        Self::CriticalHold,
+        Self::Extension,
+    ];
+
+    /// Mnemonics accepted in addition to [`TokenKind::mnemonic`], with the kind they map to.
+    pub const ALIASES: &'static [(&'static str, TokenKind)] =
+        &[("XTP", Self::CriticalTap), ("XHD", Self::CriticalHold)];
+
+    /// Mnemonic written at the start of the command line. For [`TokenKind::Extension`] this is
+    /// the prefix shared by all extension commands.
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Self::Version => "VERSION",
+            Self::Creator => "CREATOR",
+            Self::BpmDefinition => "BPM_DEF",
+            Self::MeterDefinition => "MET_DEF",
+            Self::TickResolution => "TRESOLUTION",
+            Self::XResolution => "XRESOLUTION",
+            Self::ClickDefinition => "CLK_DEF",
+            Self::Tutorial => "TUTORIAL",
+            Self::BulletDamage => "BULLET_DAMAGE",
+            Self::HardBulletDamage => "HARDBULLET_DAMAGE",
+            Self::DangerBulletDamage => "DANGERBULLET_DAMAGE",
+            Self::BeamDamage => "BEAM_DAMAGE",
+            Self::TotalNotes => "T_TOTAL",
+            Self::TotalTapNotes => "T_TAP",
+            Self::TotalHoldNotes => "T_HOLD",
+            Self::TotalSideNotes => "T_SIDE",
+            Self::TotalSideHoldNotes => "T_SHOLD",
+            Self::TotalFlickNotes => "T_FLICK",
+            Self::TotalBellNotes => "T_BELL",
+            Self::ProgJudgeBpm => "PROGJUDGE_BPM",
+            Self::BulletPalette => "BPL",
+            Self::Btp => "BTP",
+            Self::BpmChange => "BPM",
+            Self::MeterChange => "MET",
+            Self::ClickSound => "CLK",
+            Self::Soflan => "SFL",
+            Self::EnemySet => "EST",
+            Self::WallLeftStart => "WLS",
+            Self::WallLeftNext => "WLN",
+            Self::WallLeftEnd => "WLE",
+            Self::WallRightStart => "WRS",
+            Self::WallRightNext => "WRN",
+            Self::WallRightEnd => "WRE",
+            Self::LaneLeftStart => "LLS",
+            Self::LaneLeftNext => "LLN",
+            Self::LaneLeftEnd => "LLE",
+            Self::LaneCenterStart => "LCS",
+            Self::LaneCenterNext => "LCN",
+            Self::LaneCenterEnd => "LCE",
+            Self::LaneRightStart => "LRS",
+            Self::LaneRightNext => "LRN",
+            Self::LaneRightEnd => "LRE",
+            Self::ColorfulLaneStart => "CLS",
+            Self::ColorfulLaneNext => "CLN",
+            Self::ColorfulLaneEnd => "CLE",
+            Self::EnemyLaneStart => "ENS",
+            Self::EnemyLaneNext => "ENN",
+            Self::EnemyLaneEnd => "ENE",
+            Self::LaneDisappearance => "LDP",
+            Self::LaneBlock => "LBK",
+            Self::Bullet => "BLT",
+            Self::BeamStart => "BMS",
+            Self::BeamNext => "BMN",
+            Self::BeamEnd => "BME",
+            Self::ObliqueBeamStart => "OBS",
+            Self::ObliqueBeamNext => "OBN",
+            Self::ObliqueBeamEnd => "OBE",
+            Self::Bell => "BEL",
+            Self::Flick => "FLK",
+            Self::CriticalFlick => "CFK",
+            Self::Tap => "TAP",
+            Self::CriticalTap => "CTP",
+            Self::Hold => "HLD",
+            Self::CriticalHold => "CHD",
+            Self::Extension => Extension::PREFIX,
         }
     }
+
+    /// Kind of command written with `mnemonic`, `None` if it is not a known command.
+    pub fn from_mnemonic(mnemonic: &str) -> Option<Self> {
+        if mnemonic.starts_with(Extension::PREFIX) {
+            return Some(Self::Extension);
+        }
+
+        Self::ALL
+            .iter()
+            .map(|&kind| (kind.mnemonic(), kind))
+            .chain(Self::ALIASES.iter().copied())
+            .find(|(candidate, _)| *candidate == mnemonic)
+            .map(|(_, kind)| kind)
+    }
 }
 
+#[derive(Debug)]
 pub struct TokenStream {
     tokens: Vec<Token>,
+    /// Source byte range of each token, same length as `tokens`.
+    spans: Vec<Range<usize>>,
+    /// Errors of lines skipped in lenient mode.
+    errors: Vec<LexError>,
 }
 
 impl TokenStream {
-    pub(crate) fn from_tokens(tokens: Vec<Token>) -> Self {
-        Self { tokens }
+    pub(crate) fn from_spanned_tokens(
+        tokens: Vec<Token>,
+        spans: Vec<Range<usize>>,
+        errors: Vec<LexError>,
+    ) -> Self {
+        debug_assert_eq!(tokens.len(), spans.len());
+        Self {
+            tokens,
+            spans,
+            errors,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
     }
 
     pub fn iter(&self) -> TokenStreamIter<'_> {
@@ -203,6 +573,22 @@ impl TokenStream {
             iter: self.tokens.iter(),
         }
     }
+
+    /// Source byte range of the command line the token at `index` was read from, starting at
+    /// the mnemonic and ending after the last argument.
+    pub fn span(&self, index: usize) -> Option<Range<usize>> {
+        self.spans.get(index).cloned()
+    }
+
+    /// Errors of the lines skipped when tokenizing in lenient mode, in source order.
+    pub fn errors(&self) -> &[LexError] {
+        &self.errors
+    }
+
+    /// Iterates over tokens along with their source byte ranges.
+    pub fn iter_spanned(&self) -> impl Iterator<Item = (&Token, Range<usize>)> {
+        self.tokens.iter().zip(self.spans.iter().cloned())
+    }
 }
 
 impl IntoIterator for TokenStream {
@@ -280,6 +666,17 @@ impl Creator {
     }
 }
 
+impl Extension {
+    pub const PREFIX: &'static str = "X_";
+
+    pub(crate) fn from_cursor(key: String, cursor: &mut Cursor) -> Self {
+        Self {
+            key,
+            value: cursor.current_remaining_line().trim().to_string(),
+        }
+    }
+}
+
 impl BpmDefinition {
     pub(crate) fn from_cursor(cursor: &mut Cursor) -> Result<Self> {
         let first = next_token_f32_or(cursor, "Bpm first")?;
@@ -429,32 +826,40 @@ impl ProgJudgeBpm {
 }
 
 impl BulletPalette {
-    pub(crate) fn from_cursor(cursor: &mut Cursor) -> Result<Self> {
+    pub(crate) fn from_cursor(cursor: &mut Cursor, format: ChartFormat) -> Result<Self> {
         let id = next_token_or(cursor, "BulletPalette id")?.to_owned();
         let shooter = BulletShooter::from_cursor(cursor)?;
         let target_x_offset = next_token_i32_or(cursor, "BulletPalette target_x_offset")?;
         let target = BulletTarget::from_cursor(cursor)?;
         let speed = next_token_f32_or(cursor, "BulletPalette speed")?;
 
-        // Different versions have different syntax.Older versions have damage type here isntead of size, bullet type,
-        // and random position offset.
-        let next_token = cursor.peek_token().unwrap_or("");
-        let (size, ty, random_position_offset, damage_type) =
-            if let Ok(_) = BulletDamageType::from_str(next_token) {
-                (
-                    None,
-                    None,
-                    None,
-                    Some(BulletDamageType::from_cursor(cursor)?),
-                )
-            } else {
-                let size = BulletSize::from_cursor(cursor)?;
-                let ty = BulletType::from_cursor(cursor)?;
-                let random_position_offset =
-                    next_token_i32_or(cursor, "BulletPalette random_position_offset")?;
-
-                (Some(size), Some(ty), Some(random_position_offset), None)
-            };
+        let current = |cursor: &mut Cursor| -> Result<_> {
+            let size = BulletSize::from_cursor(cursor)?;
+            let ty = BulletType::from_cursor(cursor)?;
+            let random_position_offset =
+                next_token_i32_or(cursor, "BulletPalette random_position_offset")?;
+            Ok((Some(size), Some(ty), Some(random_position_offset), None))
+        };
+        let legacy = |cursor: &mut Cursor| -> Result<_> {
+            Ok((
+                None,
+                None,
+                None,
+                Some(BulletDamageType::from_cursor(cursor)?),
+            ))
+        };
+
+        let (size, ty, random_position_offset, damage_type) = match format {
+            #[cfg(feature = "legacy-format")]
+            ChartFormat::Current => current(cursor)?,
+            #[cfg(feature = "legacy-format")]
+            ChartFormat::Legacy => legacy(cursor)?,
+            // The version is not known, so the syntax is recognized from the arguments.
+            ChartFormat::Auto => match cursor.speculate(current) {
+                Ok(arguments) => arguments,
+                Err(error) => cursor.speculate(legacy).map_err(|_| error)?,
+            },
+        };
 
         Ok(Self {
             id,
@@ -583,17 +988,18 @@ impl LaneEvent {
 }
 
 impl Bullet {
-    pub(crate) fn from_cursor(cursor: &mut Cursor) -> Result<Self> {
+    pub(crate) fn from_cursor(cursor: &mut Cursor, format: ChartFormat) -> Result<Self> {
         let pallete_id = next_token_or(cursor, "Bullet pallete_id")?.to_string();
         let time = CommandTime::from_cursor(cursor, "Bullet time")?;
         let x_position = next_token_i32_or(cursor, "Bullet x_position")?;
 
-        let damage_type = BulletDamageType::from_str(cursor.peek_token().unwrap_or(""))
-            .ok()
-            .map(|damage| {
-                cursor.next_token();
-                damage
-            });
+        let damage_type = match format {
+            #[cfg(feature = "legacy-format")]
+            ChartFormat::Legacy | ChartFormat::Current => {
+                Some(BulletDamageType::from_cursor(cursor)?)
+            }
+            ChartFormat::Auto => cursor.speculate(BulletDamageType::from_cursor).ok(),
+        };
 
         Ok(Self {
             pallete_id,
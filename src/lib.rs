@@ -1,2 +1,87 @@
+pub mod build;
+pub mod code;
+pub mod compiled;
+pub mod conformance;
+pub mod diagnostics;
+pub mod export;
+pub mod gameplay;
+pub mod generate;
+pub mod geometry;
+pub mod ir;
 pub mod lex;
 pub mod parse;
+pub mod pattern;
+pub mod simulate;
+pub mod stats;
+pub mod validate;
+pub mod visit;
+pub mod write;
+
+mod load;
+mod trace;
+
+use thiserror::Error;
+
+use lex::LexError;
+use parse::{
+    analysis::parse_raw_ogkr_with_options, raw::parse_source_with_options, ParseError, ParseOptions,
+};
+
+pub use code::ErrorCode;
+pub use load::LoadError;
+pub use parse::analysis::Ogkr;
+
+/// Any error that can occur while going from chart content to a parsed chart.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Lex(LexError),
+    #[error(transparent)]
+    Parse(ParseError),
+}
+
+impl From<LexError> for Error {
+    fn from(error: LexError) -> Self {
+        Self::Lex(error)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Self {
+        match error {
+            ParseError::LexError(error) => Self::Lex(error),
+            error => Self::Parse(error),
+        }
+    }
+}
+
+impl Error {
+    /// Stable identifier of the kind of error.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::Lex(error) => error.code(),
+            Self::Parse(error) => error.code(),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Tokenizes, parses and analyzes chart content.
+pub fn parse(source: &str) -> Result<Ogkr> {
+    parse_with_options(source, &ParseOptions::default())
+}
+
+/// Same as [`parse()`], using custom parsing options.
+pub fn parse_with_options(source: &str, options: &ParseOptions) -> Result<Ogkr> {
+    let raw_ogkr = parse_source_with_options(source, options)?;
+    Ok(parse_raw_ogkr_with_options(raw_ogkr, options)?)
+}
+
+impl std::str::FromStr for Ogkr {
+    type Err = Error;
+
+    fn from_str(source: &str) -> Result<Self> {
+        parse(source)
+    }
+}
@@ -0,0 +1,75 @@
+use std::path::{Path, PathBuf};
+
+use thiserror::Error as ThisError;
+
+use crate::{code::ErrorCode, parse::ParseOptions, parse_with_options, Error, Ogkr};
+
+/// Error loading a chart file, always including the path of the file.
+#[derive(Debug, ThisError)]
+pub enum LoadError {
+    #[error("failed to read {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("{} is not valid UTF-8: {source}", path.display())]
+    Encoding {
+        path: PathBuf,
+        #[source]
+        source: std::string::FromUtf8Error,
+    },
+    #[error("failed to parse {}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        #[source]
+        source: Error,
+    },
+}
+
+impl LoadError {
+    /// Stable identifier of the kind of error, parsing errors keep their own code.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::Io { .. } => ErrorCode::FileRead,
+            Self::Encoding { .. } => ErrorCode::InvalidEncoding,
+            Self::Parse { source, .. } => source.code(),
+        }
+    }
+
+    /// Path of the chart file that failed to load.
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Io { path, .. } | Self::Encoding { path, .. } | Self::Parse { path, .. } => path,
+        }
+    }
+}
+
+impl Ogkr {
+    /// Reads and parses a chart file.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        Self::from_path_with_options(path, &ParseOptions::default())
+    }
+
+    /// Same as [`Ogkr::from_path`], using custom parsing options.
+    pub fn from_path_with_options(
+        path: impl AsRef<Path>,
+        options: &ParseOptions,
+    ) -> Result<Self, LoadError> {
+        let path = path.as_ref();
+
+        let bytes = std::fs::read(path).map_err(|source| LoadError::Io {
+            path: path.to_owned(),
+            source,
+        })?;
+        let source = String::from_utf8(bytes).map_err(|source| LoadError::Encoding {
+            path: path.to_owned(),
+            source,
+        })?;
+
+        parse_with_options(&source, options).map_err(|source| LoadError::Parse {
+            path: path.to_owned(),
+            source,
+        })
+    }
+}
@@ -1,15 +1,36 @@
-use std::collections::{BTreeMap, HashMap};
+use std::{
+    borrow::Borrow,
+    collections::{btree_map, BTreeMap, HashMap},
+    iter::{FlatMap, Flatten, FusedIterator},
+    ops::Range,
+    vec,
+};
 
 use super::{
+    measure::MeasureView,
+    query::Query,
     raw::{
-        BeamSection, ColorfulLaneSection, LaneSection, ObliqueBeamSection, RawComposition,
+        BeamSection, ColorfulLaneSection, LaneSection, NoteRef, ObliqueBeamSection, RawComposition,
         RawNotes, RawOgkr, RawTrack, WallSection,
     },
+    shared::SharedOgkr,
+    spawn::BulletSpawn,
+    store::{IntoItems, Items, TimeMap, TimeStore},
+    timing::TimingConverter,
     BulletDamageType, BulletShooter, BulletSize, BulletTarget, BulletType, EnemyWaveAssignment,
-    FlickDirection, Header, LanePoint, ParseError, Result, WallPoint,
+    Extensions, FlickDirection, Header, HoldPointsPolicy, LanePoint, MissingLanePolicy,
+    MissingPalettePolicy, ParseError, ParseOptions, Result, Totals, WallPoint, Warning, Warnings,
+    SOUND_EXTENSION,
 };
 
-use crate::lex::command;
+use smallvec::SmallVec;
+
+pub use super::lower::RoundTripError;
+
+use crate::{
+    lex::{command, CustomToken},
+    trace::Phase,
+};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct TimingPoint {
@@ -26,6 +47,23 @@ impl TimingPoint {
             beat_offset,
         }
     }
+
+    /// Closest point of a grid dividing measures into `subdivision` parts, e.g. 16 for sixteenth
+    /// notes in 4/4. Rounds up to the next measure past the last grid point.
+    pub fn snap_to(&self, subdivision: u32, resolution: Resolution) -> Self {
+        let ticks = resolution.ticks.max(1) as u64;
+        let subdivision = subdivision.max(1) as u64;
+        // Rounded to the nearest grid index, then to the nearest tick for grids not dividing the
+        // resolution evenly.
+        let index = (self.beat_offset as u64 * subdivision * 2 + ticks) / (ticks * 2);
+        let offset = (index * ticks * 2 + subdivision) / (subdivision * 2);
+        resolution.time_of(resolution.ticks_at(Self::new(self.measure, 0)) + offset)
+    }
+
+    /// Whether the point lies on the grid of [`TimingPoint::snap_to`].
+    pub fn is_on_grid(&self, subdivision: u32, resolution: Resolution) -> bool {
+        self.snap_to(subdivision, resolution) == *self
+    }
 }
 
 impl PartialOrd for TimingPoint {
@@ -51,6 +89,80 @@ impl From<command::CommandTime> for TimingPoint {
     }
 }
 
+impl From<TimingPoint> for command::CommandTime {
+    fn from(time: TimingPoint) -> Self {
+        Self {
+            measure: time.measure,
+            offset: time.beat_offset,
+        }
+    }
+}
+
+/// Resolutions used to convert chart positions into continuous values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Resolution {
+    /// Number of ticks in a measure, see [`TickResolution`](command::TickResolution).
+    pub ticks: u32,
+    /// Width reference value of [`XPosition::offset`], see [`XResolution`](command::XResolution).
+    pub x: u32,
+}
+
+impl Resolution {
+    pub const DEFAULT_TICKS: u32 = 1920;
+    pub const DEFAULT_X: u32 = 4096;
+
+    pub fn from_header(header: &Header) -> Self {
+        let default = Self::default();
+        Self {
+            ticks: header
+                .tick_resolution
+                .map_or(default.ticks, |resolution| resolution.resolution),
+            x: header
+                .x_resolution
+                .map_or(default.x, |resolution| resolution.resolution),
+        }
+    }
+
+    pub fn tick_resolution(&self) -> command::TickResolution {
+        command::TickResolution {
+            resolution: self.ticks,
+        }
+    }
+
+    pub fn x_resolution(&self) -> command::XResolution {
+        command::XResolution { resolution: self.x }
+    }
+
+    /// Absolute number of ticks from the start of the chart.
+    pub fn ticks_at(&self, time: TimingPoint) -> u64 {
+        self.tick_resolution().ticks_at(time.into())
+    }
+
+    /// Timing point `ticks` ticks from the start of the chart, inverse of [`Resolution::ticks_at`].
+    /// Saturates at the last tick of measure `u32::MAX`.
+    pub fn time_of(&self, ticks: u64) -> TimingPoint {
+        let ticks_per_measure = self.ticks.max(1) as u64;
+        match u32::try_from(ticks / ticks_per_measure) {
+            Ok(measure) => TimingPoint::new(measure, (ticks % ticks_per_measure) as u32),
+            Err(_) => TimingPoint::new(u32::MAX, (ticks_per_measure - 1) as u32),
+        }
+    }
+
+    /// Horizontal position in track units.
+    pub fn x_of(&self, x: XPosition) -> f32 {
+        self.x_resolution().x_of(x.position, x.offset)
+    }
+}
+
+impl Default for Resolution {
+    fn default() -> Self {
+        Self {
+            ticks: Self::DEFAULT_TICKS,
+            x: Self::DEFAULT_X,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct XPosition {
     pub position: i32,
@@ -137,6 +249,23 @@ impl Ord for TrackPosition {
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct BulletPaletteId(pub String);
 
+/// Lets palettes be looked up by `&str` without allocating an ID.
+impl Borrow<str> for BulletPaletteId {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Interned palette ID, indexes [`Bullets::palettes`] of the chart it was parsed with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PaletteIdRef(pub u32);
+
+impl PaletteIdRef {
+    pub fn index(&self) -> usize {
+        self.0 as usize
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct BulletPalette {
     pub id: BulletPaletteId,
@@ -152,6 +281,24 @@ pub struct BulletPalette {
     pub damage_type: Option<BulletDamageType>,
 }
 
+impl BulletPalette {
+    /// Stand-in palette for bullets referencing a palette that does not exist, see
+    /// [`MissingPalettePolicy::Placeholder`].
+    pub fn placeholder(id: BulletPaletteId) -> Self {
+        Self {
+            id,
+            shooter: BulletShooter::Center,
+            target: BulletTarget::Player,
+            x_offset: 0,
+            speed: 1.0,
+            size: Some(BulletSize::Normal),
+            bullet_type: Some(BulletType::Circle),
+            random_position_offset: Some(0),
+            damage_type: Some(BulletDamageType::Normal),
+        }
+    }
+}
+
 impl From<command::BulletPalette> for BulletPalette {
     fn from(palette: command::BulletPalette) -> Self {
         Self {
@@ -179,6 +326,11 @@ pub enum LaneType {
     Right,
     /// Not a real "lane" for the player.
     Enemy,
+    /// Stand-in for a lane that notes reference but the chart does not contain, see
+    /// [`MissingLanePolicy::SyntheticLane`]. Only reachable from its notes through
+    /// [`Track::get_lane`], it has no start time index and track queries like
+    /// [`Track::lanes_active_at`] never return it. A lane of a single tap has a single point.
+    Synthetic,
 }
 
 /// Represents both walls and lanes - they have unique IDs.
@@ -222,58 +374,42 @@ impl Lane {
         start: TrackPosition,
         end: TrackPosition,
     ) -> Result<Vec<TrackPosition>> {
-        // let start_index = self
-        //     .points
-        //     .binary_search_by(|point| point.time.cmp(&start.time))
-        //     .unwrap_or_else(|idx| idx);
-        //
-        // let end_index = self
-        //     .points
-        //     .binary_search_by(|point| point.time.cmp(&end.time))
-        //     .unwrap_or_else(|idx| if idx > 0 { idx - 1 } else { idx });
-
-        let (start_index, start_exact) = match self
-            .points
-            .binary_search_by(|point| point.time.cmp(&start.time))
-        {
-            Ok(idx) => (idx, true),
-            Err(idx) => (idx, false),
-        };
+        if end.time < start.time {
+            return Err(ParseError::InvalidTimeRange(format!(
+                "Lane {:?} interval ends at {:?} before it starts at {:?}",
+                self.id, end.time, start.time
+            )));
+        }
 
-        let (end_index, end_exact) = match self
-            .points
-            .binary_search_by(|point| point.time.cmp(&end.time))
-        {
-            Ok(idx) => (idx, true),
-            Err(idx) => (if idx > 0 { idx - 1 } else { idx }, false),
-        };
+        let points = &self.points[self.points_within_time_interval(start.time, end.time)];
+        Ok(HoldPath {
+            start,
+            lane_points: points,
+            end,
+        }
+        .to_vec())
+    }
 
-        if start_index <= end_index && end_index < self.points.len() {
-            let mut result = vec![];
-            if !start_exact {
-                result.push(start);
-            }
-            result.extend_from_slice(&self.points[start_index..=end_index]);
-            if !end_exact {
-                result.push(end);
-            }
-            // println!("{:#?}", &result);
-            assert_ne!(result[0], result[1]);
-            assert_ne!(result[result.len() - 2], result[result.len() - 1]);
-            Ok(result)
+    /// Range of `points` between `start` and `end` inclusive, empty when the interval has no
+    /// duration.
+    pub fn points_within_time_interval(
+        &self,
+        start: TimingPoint,
+        end: TimingPoint,
+    ) -> Range<usize> {
+        if end <= start {
+            return 0..0;
+        }
+
+        let start_index = self.points.partition_point(|point| point.time < start);
+        let end_index = self.points.partition_point(|point| point.time <= end);
+        // XXX: We assume that start and end is always valid, but this may not always be the
+        // case.
+        if start_index < end_index {
+            start_index..end_index
         } else {
-            // XXX: We assume that start and end is always valid, but this may not always be the
-            // case.
-            Ok(vec![start, end])
+            0..0
         }
-        // else {
-        //     println!("lane: {:#?}", self.points);
-        //     println!("start idx {}, end idx {}", start_index, end_index);
-        //     Err(ParseError::SemanticError(format!(
-        //         "Lane {:#?} has invalid time range {:#?} {:#?}",
-        //         self.id, start, end
-        //     )))
-        // }
     }
 
     pub fn from_wall_section(wall_section: WallSection, lane_type: LaneType) -> Result<Self> {
@@ -284,7 +420,7 @@ impl Lane {
                 points: wall_section
                     .points
                     .into_iter()
-                    .map(|p| TrackPosition::from_wall_point(p))
+                    .map(TrackPosition::from_wall_point)
                     .collect(),
             })
         } else {
@@ -303,7 +439,7 @@ impl Lane {
                 points: lane_section
                     .points
                     .into_iter()
-                    .map(|p| TrackPosition::from_lane_point(p))
+                    .map(TrackPosition::from_lane_point)
                     .collect(),
             })
         } else {
@@ -348,13 +484,12 @@ pub struct ColorfulLane {
 
 impl ColorfulLane {
     pub fn from_section(lane_section: ColorfulLaneSection) -> Result<Self> {
-        if let (Some(start), Some(end)) = (lane_section.points.first(), lane_section.points.last())
-        {
+        if let [start, .., end] = lane_section.points.as_slice() {
             Ok(Self {
                 id: ColorfulLaneId(lane_section.group_id),
                 start: (*start).into(),
                 middle: lane_section.points[1..lane_section.points.len() - 1]
-                    .into_iter()
+                    .iter()
                     .map(|p| (*p).into())
                     .collect(),
                 end: (*end).into(),
@@ -420,28 +555,33 @@ impl From<command::LaneEvent> for LaneBlock {
 
 #[derive(Clone, Debug)]
 pub struct Bullet {
-    pub palette_id: BulletPaletteId,
+    /// Palette shooting the bullet, see [`Bullets::palette`].
+    pub palette: PaletteIdRef,
 
     /// End position of the bullet if the player is not the target.
     pub position: TrackPosition,
 
     pub damage_type: BulletDamageType,
+
+    pub extensions: Extensions,
 }
 
 impl Bullet {
-    fn from_bullet_command(bullet: command::Bullet, palette: &BulletPalette) -> Result<Self> {
-        let damage_type = bullet
-            .damage_type
-            .or_else(|| palette.damage_type)
-            .ok_or_else(|| {
-                ParseError::SyntaxError(
-                    "Bullet damage type is not specified in either bullet or palette".to_string(),
-                )
-            })?;
+    fn from_bullet_command(
+        bullet: command::Bullet,
+        palette: &BulletPalette,
+        palette_ref: PaletteIdRef,
+    ) -> Result<Self> {
+        let damage_type = bullet.damage_type.or(palette.damage_type).ok_or_else(|| {
+            ParseError::SyntaxError(
+                "Bullet damage type is not specified in either bullet or palette".to_string(),
+            )
+        })?;
         Ok(Self {
-            palette_id: BulletPaletteId(bullet.pallete_id),
+            palette: palette_ref,
             position: TrackPosition::from_command_info(bullet.time, bullet.x_position, 0),
             damage_type,
+            extensions: Extensions::new(),
         })
     }
 }
@@ -474,12 +614,12 @@ pub struct Beam {
 
 impl Beam {
     pub fn from_section(section: BeamSection) -> Result<Self> {
-        if let (Some(start), Some(end)) = (section.points.first(), section.points.last()) {
+        if let [start, .., end] = section.points.as_slice() {
             Ok(Self {
                 id: BeamId(section.record_id),
                 start: (*start).into(),
                 middle: section.points[1..section.points.len() - 1]
-                    .into_iter()
+                    .iter()
                     .map(|p| (*p).into())
                     .collect(),
                 end: (*end).into(),
@@ -523,12 +663,12 @@ pub struct ObliqueBeam {
 
 impl ObliqueBeam {
     pub fn from_section(section: ObliqueBeamSection) -> Result<Self> {
-        if let (Some(start), Some(end)) = (section.points.first(), section.points.last()) {
+        if let [start, .., end] = section.points.as_slice() {
             Ok(Self {
                 id: ObliqueBeamId(section.record_id),
                 start: (*start).into(),
                 middle: section.points[1..section.points.len() - 1]
-                    .into_iter()
+                    .iter()
                     .map(|p| (*p).into())
                     .collect(),
                 end: (*end).into(),
@@ -542,19 +682,62 @@ impl ObliqueBeam {
     }
 }
 
+/// Kind of a bell, scored differently by the game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BellKind {
+    /// Bell placed on its own.
+    Normal,
+    /// Bell shot like a bullet, with a palette.
+    Bullet,
+}
+
 #[derive(Clone, Debug)]
 pub struct BellNote {
     pub position: TrackPosition,
-    pub bullet_palette: Option<BulletPaletteId>,
-}
-
-impl From<command::Bell> for BellNote {
-    fn from(bell: command::Bell) -> Self {
-        Self {
+    /// Palette of the bullet shot by the bell, see [`Bullets::palette`].
+    pub bullet_palette: Option<PaletteIdRef>,
+
+    pub extensions: Extensions,
+}
+
+impl BellNote {
+    fn from_bell(bell: command::Bell, bullets: &Bullets) -> Result<Self> {
+        let bullet_palette = match &bell.bullet_palette_id {
+            Some(id) => Some(bullets.palette_ref(id).ok_or_else(|| {
+                ParseError::SemanticError(format!(
+                    "Bell {:?} uses invalid palette id {:?}",
+                    &bell, id
+                ))
+            })?),
+            None => None,
+        };
+        Ok(Self {
             position: TrackPosition::from_command_info(bell.time, bell.x_position, 0),
-            bullet_palette: bell.bullet_palette_id.map(|b| BulletPaletteId(b)),
+            bullet_palette,
+            extensions: Extensions::new(),
+        })
+    }
+
+    pub fn kind(&self) -> BellKind {
+        match self.bullet_palette {
+            Some(_) => BellKind::Bullet,
+            None => BellKind::Normal,
         }
     }
+
+    /// Sample played when collecting the bell, see [`TapNote::sound`].
+    pub fn sound(&self) -> Option<&str> {
+        self.extensions.get(SOUND_EXTENSION).map(String::as_str)
+    }
+
+    pub fn set_sound(&mut self, sound: impl Into<String>) {
+        self.extensions
+            .insert(SOUND_EXTENSION.to_string(), sound.into());
+    }
+
+    pub fn remove_sound(&mut self) -> Option<String> {
+        self.extensions.remove(SOUND_EXTENSION)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -562,6 +745,8 @@ pub struct FlickNote {
     pub position: TrackPosition,
     pub direction: FlickDirection,
     pub is_critical: bool,
+
+    pub extensions: Extensions,
 }
 
 impl FlickNote {
@@ -570,6 +755,7 @@ impl FlickNote {
             position: TrackPosition::from_command_info(flick.time, flick.x_position, 0),
             direction: flick.direction,
             is_critical,
+            extensions: Extensions::new(),
         }
     }
 }
@@ -582,6 +768,8 @@ pub struct TapNote {
     pub position: TrackPosition,
 
     pub is_critical: bool,
+
+    pub extensions: Extensions,
 }
 
 impl TapNote {
@@ -591,8 +779,25 @@ impl TapNote {
             lane_type,
             position: TrackPosition::from_command_info(tap.time, tap.x_position, tap.x_offset),
             is_critical,
+            extensions: Extensions::new(),
         }
     }
+
+    /// Sample played when hitting the note in keysounded charts, stored as an `X_SOUND`
+    /// extension so it is kept when the chart is written back.
+    pub fn sound(&self) -> Option<&str> {
+        self.extensions.get(SOUND_EXTENSION).map(String::as_str)
+    }
+
+    /// Assigns the sample played when hitting the note. It must fit on a single line.
+    pub fn set_sound(&mut self, sound: impl Into<String>) {
+        self.extensions
+            .insert(SOUND_EXTENSION.to_string(), sound.into());
+    }
+
+    pub fn remove_sound(&mut self) -> Option<String> {
+        self.extensions.remove(SOUND_EXTENSION)
+    }
 }
 
 /// XXX TODO: Fill in points/positions for hold note based on lane.
@@ -604,10 +809,13 @@ pub struct HoldNote {
     pub start: TrackPosition,
     pub end: TrackPosition,
 
-    /// Includes start and end.
+    /// Includes start and end. Empty when parsed with [`HoldPointsPolicy::Borrow`], see
+    /// [`HoldNote::path`].
     pub points: Vec<TrackPosition>,
 
     pub is_critical: bool,
+
+    pub extensions: Extensions,
 }
 
 impl HoldNote {
@@ -628,10 +836,90 @@ impl HoldNote {
             end,
             points: lane.create_points_within_time_interval(start, end)?,
             is_critical,
+            extensions: Extensions::new(),
+        })
+    }
+
+    /// Points of the hold borrowed from its lane in `track`, the same as `points` without copying
+    /// them. Returns `None` if the lane does not exist.
+    pub fn path<'a>(&self, track: &'a Track) -> Option<HoldPath<'a>> {
+        let lane = track.get_lane(self.lane_id)?;
+        Some(HoldPath {
+            start: self.start,
+            lane_points: &lane.points
+                [lane.points_within_time_interval(self.start.time, self.end.time)],
+            end: self.end,
         })
     }
 }
 
+/// Points of a hold note as a view into its lane, see [`HoldNote::path`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HoldPath<'a> {
+    pub start: TrackPosition,
+    /// Lane points between the start and end of the hold inclusive.
+    pub lane_points: &'a [TrackPosition],
+    pub end: TrackPosition,
+}
+
+impl<'a> HoldPath<'a> {
+    /// Returns iterator of the points of the hold, the lane points take the place of the start
+    /// and end when they share their time.
+    pub fn iter(&self) -> impl Iterator<Item = TrackPosition> + 'a {
+        let start = (self.lane_points.first().map(|point| point.time) != Some(self.start.time))
+            .then_some(self.start);
+        let end = (self.lane_points.last().map(|point| point.time) != Some(self.end.time))
+            .then_some(self.end);
+        start
+            .into_iter()
+            .chain(self.lane_points.iter().copied())
+            .chain(end)
+    }
+
+    pub fn to_vec(&self) -> Vec<TrackPosition> {
+        self.iter().collect()
+    }
+}
+
+/// Track objects indexed by the time they start at.
+type IdMap<Id> = BTreeMap<TimingPoint, Vec<Id>>;
+
+/// Stores sections by ID and indexes them by start time. IDs within a bucket keep chart order,
+/// a section replacing one with the same ID takes the position of the later definition.
+fn index_by_start<Id, T>(
+    sections: impl Iterator<Item = Result<T>>,
+    id: impl Fn(&T) -> Id,
+    start: impl Fn(&T) -> Option<TimingPoint>,
+    mut on_duplicate: impl FnMut(Id),
+) -> Result<(IdMap<Id>, HashMap<Id, T>)>
+where
+    Id: Copy + Eq + std::hash::Hash,
+{
+    let mut data = HashMap::new();
+    let mut order = Vec::new();
+    for section in sections {
+        let section = section?;
+        let section_id = id(&section);
+        if data.insert(section_id, section).is_some() {
+            on_duplicate(section_id);
+        }
+        order.push(section_id);
+    }
+
+    let last: HashMap<Id, usize> = order.iter().enumerate().map(|(i, &id)| (id, i)).collect();
+    let mut sorted = IdMap::new();
+    for (i, section_id) in order.into_iter().enumerate() {
+        if last[&section_id] != i {
+            continue;
+        }
+        if let Some(time) = start(&data[&section_id]) {
+            sorted.entry(time).or_insert_with(Vec::new).push(section_id);
+        }
+    }
+
+    Ok((sorted, data))
+}
+
 /// Physical track layout.
 #[derive(Clone, Debug)]
 pub struct Track {
@@ -640,21 +928,24 @@ pub struct Track {
     pub lanes_center: BTreeMap<TimingPoint, Vec<LaneId>>,
     pub lanes_right: BTreeMap<TimingPoint, Vec<LaneId>>,
 
-    pub colorful_lanes: BTreeMap<TimingPoint, ColorfulLaneId>,
+    pub colorful_lanes: BTreeMap<TimingPoint, Vec<ColorfulLaneId>>,
 
-    pub walls_left: BTreeMap<TimingPoint, LaneId>,
-    pub walls_right: BTreeMap<TimingPoint, LaneId>,
+    pub walls_left: BTreeMap<TimingPoint, Vec<LaneId>>,
+    pub walls_right: BTreeMap<TimingPoint, Vec<LaneId>>,
 
     pub enemy_lanes: BTreeMap<TimingPoint, Vec<LaneId>>,
 
     // pub lanes_all: BTreeMap<TimingPoint, LaneId>,
-    pub beams: BTreeMap<TimingPoint, BeamId>,
-    pub oblique_beams: BTreeMap<TimingPoint, ObliqueBeamId>,
+    pub beams: BTreeMap<TimingPoint, Vec<BeamId>>,
+    pub oblique_beams: BTreeMap<TimingPoint, Vec<ObliqueBeamId>>,
 
     pub lanes_data: HashMap<LaneId, Lane>,
     pub colorful_lanes_data: HashMap<ColorfulLaneId, ColorfulLane>,
     pub beams_data: HashMap<BeamId, Beam>,
     pub oblique_beams_data: HashMap<ObliqueBeamId, ObliqueBeam>,
+
+    /// Used to interpolate positions between points, taken from the header when parsing a chart.
+    pub resolution: Resolution,
 }
 
 impl Track {
@@ -663,28 +954,46 @@ impl Track {
     }
 
     pub fn from_raw(raw: RawTrack) -> Result<Self> {
-        let (lanes_left, lanes_left_data) = Self::map_lanes(raw.lanes_left, LaneType::Left)?;
+        Self::from_raw_with_warnings(raw, &mut Warnings::default())
+    }
+
+    /// Same as [`Track::from_raw`], additionally collecting data that was dropped.
+    pub fn from_raw_with_warnings(raw: RawTrack, warnings: &mut Warnings) -> Result<Self> {
+        let (lanes_left, lanes_left_data) =
+            Self::map_lanes(raw.lanes_left, LaneType::Left, warnings)?;
         let (lanes_center, lanes_center_data) =
-            Self::map_lanes(raw.lanes_center, LaneType::Center)?;
-        let (lanes_right, lanes_right_data) = Self::map_lanes(raw.lanes_right, LaneType::Right)?;
-        let (enemy_lanes, enemy_lanes_data) = Self::map_lanes(raw.enemy_lanes, LaneType::Enemy)?;
-        let (walls_left, walls_left_data) = Self::map_walls(raw.walls_left, LaneType::WallLeft)?;
+            Self::map_lanes(raw.lanes_center, LaneType::Center, warnings)?;
+        let (lanes_right, lanes_right_data) =
+            Self::map_lanes(raw.lanes_right, LaneType::Right, warnings)?;
+        let (enemy_lanes, enemy_lanes_data) =
+            Self::map_lanes(raw.enemy_lanes, LaneType::Enemy, warnings)?;
+        let (walls_left, walls_left_data) =
+            Self::map_walls(raw.walls_left, LaneType::WallLeft, warnings)?;
         let (walls_right, walls_right_data) =
-            Self::map_walls(raw.walls_right, LaneType::WallRight)?;
-
-        let lanes_data = {
-            let mut data = lanes_left_data;
-            data.extend(lanes_center_data);
-            data.extend(lanes_right_data);
-            data.extend(enemy_lanes_data);
-            data.extend(walls_left_data);
-            data.extend(walls_right_data);
+            Self::map_walls(raw.walls_right, LaneType::WallRight, warnings)?;
+
+        // Walls and lanes share the same ID space.
+        let lanes_data = [
+            lanes_center_data,
+            lanes_right_data,
+            enemy_lanes_data,
+            walls_left_data,
+            walls_right_data,
+        ]
+        .into_iter()
+        .flatten()
+        .fold(lanes_left_data, |mut data, (id, lane)| {
+            if data.insert(id, lane).is_some() {
+                warnings.push(Warning::DuplicateLaneId(id.0));
+            }
             data
-        };
+        });
 
-        let (colorful_lanes, colorful_lanes_data) = Self::map_colorful_lanes(raw.colorful_lanes)?;
-        let (beams, beams_data) = Self::map_beams(raw.beams)?;
-        let (oblique_beams, oblique_beams_data) = Self::map_oblique_beams(raw.oblique_beams)?;
+        let (colorful_lanes, colorful_lanes_data) =
+            Self::map_colorful_lanes(raw.colorful_lanes, warnings)?;
+        let (beams, beams_data) = Self::map_beams(raw.beams, warnings)?;
+        let (oblique_beams, oblique_beams_data) =
+            Self::map_oblique_beams(raw.oblique_beams, warnings)?;
 
         Ok(Self {
             lanes_left,
@@ -701,146 +1010,76 @@ impl Track {
             colorful_lanes_data,
             beams_data,
             oblique_beams_data,
+
+            resolution: Resolution::default(),
         })
     }
 
     fn map_lanes(
         lanes: Vec<LaneSection>,
         lane_type: LaneType,
-    ) -> Result<(BTreeMap<TimingPoint, Vec<LaneId>>, HashMap<LaneId, Lane>)> {
-        let lanes_data = lanes
-            .into_iter()
-            .try_fold(HashMap::new(), |mut m, lane_section| {
-                let lane = Lane::from_lane_section(lane_section, lane_type)?;
-
-                if m.contains_key(&lane.id) {
-                    log::warn!("Internal error: found duplicate lane ID {:?}, previous lane with this ID be ignored", lane.id);
-                }
-                m.insert(lane.id, lane);
-
-                Ok(m)
-            })?;
-
-        let lanes_sorted = lanes_data
-            .values()
-            .try_fold(BTreeMap::new(), |mut m, lane| {
-                m.entry(lane.points.first().unwrap().time)
-                    .or_insert_with(Vec::new)
-                    .push(lane.id);
-
-                Ok(m)
-            })?;
-
-        Ok((lanes_sorted, lanes_data))
+        warnings: &mut Warnings,
+    ) -> Result<(IdMap<LaneId>, HashMap<LaneId, Lane>)> {
+        index_by_start(
+            lanes
+                .into_iter()
+                .map(|lane_section| Lane::from_lane_section(lane_section, lane_type)),
+            |lane| lane.id,
+            |lane| lane.points.first().map(|point| point.time),
+            |id| warnings.push(Warning::DuplicateLaneId(id.0)),
+        )
     }
 
     // XXX TODO: Remove `WallSection` since it is simply a lane.
     fn map_walls(
         walls: Vec<WallSection>,
         lane_type: LaneType,
-    ) -> Result<(BTreeMap<TimingPoint, LaneId>, HashMap<LaneId, Lane>)> {
-        let walls_data = walls
-            .into_iter()
-            .try_fold(HashMap::new(), |mut m, wall_section| {
-                let wall = Lane::from_wall_section(wall_section, lane_type)?;
-
-                if m.contains_key(&wall.id) {
-                    log::warn!("Internal error: found duplicate wall ID {:?}, previous wall with this ID be ignored", wall.id);
-                }
-                m.insert(wall.id, wall);
-                Ok(m)
-            })?;
-
-        let walls_sorted = walls_data
-            .values()
-            .try_fold(BTreeMap::new(), |mut m, wall| {
-                // XXX FIXME: remove unwrap here and return Err if required.
-                m.insert(wall.points.first().unwrap().time, wall.id);
-                Ok(m)
-            })?;
-
-        Ok((walls_sorted, walls_data))
+        warnings: &mut Warnings,
+    ) -> Result<(IdMap<LaneId>, HashMap<LaneId, Lane>)> {
+        index_by_start(
+            walls
+                .into_iter()
+                .map(|wall_section| Lane::from_wall_section(wall_section, lane_type)),
+            |wall| wall.id,
+            |wall| wall.points.first().map(|point| point.time),
+            |id| warnings.push(Warning::DuplicateLaneId(id.0)),
+        )
     }
 
     fn map_colorful_lanes(
         lanes: Vec<ColorfulLaneSection>,
-    ) -> Result<(
-        BTreeMap<TimingPoint, ColorfulLaneId>,
-        HashMap<ColorfulLaneId, ColorfulLane>,
-    )> {
-        let lanes_data = lanes
-            .into_iter()
-            .try_fold(HashMap::new(), |mut m, lane_section| {
-                let lane = ColorfulLane::from_section(lane_section)?;
-
-                if m.contains_key(&lane.id) {
-                    log::warn!("Internal error: found duplicate lane ID {:?}, previous lane with this ID be ignored", lane.id);
-                }
-                m.insert(lane.id, lane);
-                Ok(m)
-            })?;
-
-        let lanes_sorted = lanes_data
-            .values()
-            .try_fold(BTreeMap::new(), |mut m, lane| {
-                m.insert(lane.start.position.time, lane.id);
-                Ok(m)
-            })?;
-
-        Ok((lanes_sorted, lanes_data))
+        warnings: &mut Warnings,
+    ) -> Result<(IdMap<ColorfulLaneId>, HashMap<ColorfulLaneId, ColorfulLane>)> {
+        index_by_start(
+            lanes.into_iter().map(ColorfulLane::from_section),
+            |lane| lane.id,
+            |lane| Some(lane.start.position.time),
+            |id| warnings.push(Warning::DuplicateColorfulLaneId(id.0)),
+        )
     }
 
     fn map_beams(
         beams: Vec<BeamSection>,
-    ) -> Result<(BTreeMap<TimingPoint, BeamId>, HashMap<BeamId, Beam>)> {
-        let beams_data = beams
-            .into_iter()
-            .try_fold(HashMap::new(), |mut m, beam_section| {
-                let beam = Beam::from_section(beam_section)?;
-
-                if m.contains_key(&beam.id) {
-                    log::warn!("Internal error: found duplicate beam ID {:?}, previous beam with this ID be ignored", beam.id);
-                }
-                m.insert(beam.id, beam);
-                Ok(m)
-            })?;
-
-        let beams_sorted = beams_data
-            .values()
-            .try_fold(BTreeMap::new(), |mut m, beam| {
-                m.insert(beam.start.position.time, beam.id);
-                Ok(m)
-            })?;
-
-        Ok((beams_sorted, beams_data))
+        warnings: &mut Warnings,
+    ) -> Result<(IdMap<BeamId>, HashMap<BeamId, Beam>)> {
+        index_by_start(
+            beams.into_iter().map(Beam::from_section),
+            |beam| beam.id,
+            |beam| Some(beam.start.position.time),
+            |id| warnings.push(Warning::DuplicateBeamId(id.0)),
+        )
     }
 
     fn map_oblique_beams(
         beams: Vec<ObliqueBeamSection>,
-    ) -> Result<(
-        BTreeMap<TimingPoint, ObliqueBeamId>,
-        HashMap<ObliqueBeamId, ObliqueBeam>,
-    )> {
-        let beams_data = beams
-            .into_iter()
-            .try_fold(HashMap::new(), |mut m, beam_section| {
-                let beam = ObliqueBeam::from_section(beam_section)?;
-
-                if m.contains_key(&beam.id) {
-                    log::warn!("Internal error: found duplicate beam ID {:?}, previous beam with this ID be ignored", beam.id);
-                }
-                m.insert(beam.id, beam);
-                Ok(m)
-            })?;
-
-        let beams_sorted = beams_data
-            .values()
-            .try_fold(BTreeMap::new(), |mut m, beam| {
-                m.insert(beam.start.position.time, beam.id);
-                Ok(m)
-            })?;
-
-        Ok((beams_sorted, beams_data))
+        warnings: &mut Warnings,
+    ) -> Result<(IdMap<ObliqueBeamId>, HashMap<ObliqueBeamId, ObliqueBeam>)> {
+        index_by_start(
+            beams.into_iter().map(ObliqueBeam::from_section),
+            |beam| beam.id,
+            |beam| Some(beam.start.position.time),
+            |id| warnings.push(Warning::DuplicateObliqueBeamId(id.0)),
+        )
     }
 
     pub fn highest_measure(&self) -> Option<u32> {
@@ -863,31 +1102,195 @@ impl Track {
             .map(|tp| tp.time.measure)
             .max()
     }
+
+    /// Moves out the lanes and walls sorted by ID, dropping the rest of the track.
+    pub fn into_lanes(self) -> vec::IntoIter<Lane> {
+        into_values_by_id(self.lanes_data, |id| id.0)
+    }
+
+    /// Moves out the colorful lanes sorted by ID, dropping the rest of the track.
+    pub fn into_colorful_lanes(self) -> vec::IntoIter<ColorfulLane> {
+        into_values_by_id(self.colorful_lanes_data, |id| id.0)
+    }
+
+    /// Moves out the beams sorted by ID, dropping the rest of the track.
+    pub fn into_beams(self) -> vec::IntoIter<Beam> {
+        into_values_by_id(self.beams_data, |id| id.0)
+    }
+
+    /// Moves out the oblique beams sorted by ID, dropping the rest of the track.
+    pub fn into_oblique_beams(self) -> vec::IntoIter<ObliqueBeam> {
+        into_values_by_id(self.oblique_beams_data, |id| id.0)
+    }
+}
+
+fn into_values_by_id<K, V>(map: HashMap<K, V>, id: impl Fn(&K) -> u32) -> vec::IntoIter<V> {
+    let mut entries = map.into_iter().collect::<Vec<_>>();
+    entries.sort_by_key(|(key, _)| id(key));
+    entries
+        .into_iter()
+        .map(|(_, value)| value)
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+/// Merges two time-keyed stores, objects of `from` go after the ones of `into` at the same time.
+fn merge_buckets<T>(mut into: TimeMap<T>, from: TimeMap<T>) -> TimeMap<T> {
+    into.combine(from);
+    into
+}
+
+/// Objects sharing a timing point in the `btree-map` backend. Most timing points hold a single
+/// object, which is stored inline without allocating. Inlining two objects measured slower, see `benches/buckets.rs`.
+pub type Bucket<T> = SmallVec<[T; 1]>;
+
+type AsSlice<'a, B, T> = fn(&'a B) -> &'a [T];
+
+/// Iterator over objects stored by time, in time order and then in the order they were stored.
+#[derive(Clone, Debug)]
+pub struct SortedIter<'a, T, B = Bucket<T>> {
+    inner: FlatMap<btree_map::Values<'a, TimingPoint, B>, &'a [T], AsSlice<'a, B, T>>,
+    len: usize,
+}
+
+impl<'a, T, B: AsRef<[T]>> SortedIter<'a, T, B> {
+    pub fn new(map: &'a BTreeMap<TimingPoint, B>) -> Self {
+        Self {
+            inner: map.values().flat_map(B::as_ref as AsSlice<'a, B, T>),
+            len: map.values().map(|items| items.as_ref().len()).sum(),
+        }
+    }
+}
+
+impl<'a, T, B> Iterator for SortedIter<'a, T, B> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.inner.next()?;
+        self.len -= 1;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T, B> DoubleEndedIterator for SortedIter<'_, T, B> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let item = self.inner.next_back()?;
+        self.len -= 1;
+        Some(item)
+    }
+}
+
+impl<T, B> ExactSizeIterator for SortedIter<'_, T, B> {}
+
+impl<T, B> FusedIterator for SortedIter<'_, T, B> {}
+
+/// Owning counterpart of [`SortedIter`].
+pub struct IntoSortedIter<T, B: IntoIterator<Item = T> = Bucket<T>> {
+    inner: Flatten<btree_map::IntoValues<TimingPoint, B>>,
+    len: usize,
+}
+
+impl<T, B: IntoIterator<Item = T> + AsRef<[T]>> IntoSortedIter<T, B> {
+    pub fn new(map: BTreeMap<TimingPoint, B>) -> Self {
+        let len = map.values().map(|items| items.as_ref().len()).sum();
+        Self {
+            inner: map.into_values().flatten(),
+            len,
+        }
+    }
+}
+
+impl<T, B: IntoIterator<Item = T>> Iterator for IntoSortedIter<T, B> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.inner.next()?;
+        self.len -= 1;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T, B> DoubleEndedIterator for IntoSortedIter<T, B>
+where
+    B: IntoIterator<Item = T>,
+    B::IntoIter: DoubleEndedIterator,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let item = self.inner.next_back()?;
+        self.len -= 1;
+        Some(item)
+    }
+}
+
+impl<T, B: IntoIterator<Item = T>> ExactSizeIterator for IntoSortedIter<T, B> {}
+
+impl<T, B: IntoIterator<Item = T>> FusedIterator for IntoSortedIter<T, B> {}
+
+impl<T: std::fmt::Debug, B> std::fmt::Debug for IntoSortedIter<T, B>
+where
+    B: IntoIterator<Item = T>,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IntoSortedIter")
+            .field("len", &self.len)
+            .finish_non_exhaustive()
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct Notes {
-    pub taps: BTreeMap<TimingPoint, Vec<TapNote>>,
-    pub holds: BTreeMap<TimingPoint, Vec<HoldNote>>,
-    pub bells: BTreeMap<TimingPoint, Vec<BellNote>>,
-    pub flicks: BTreeMap<TimingPoint, Vec<FlickNote>>,
+    pub taps: TimeMap<TapNote>,
+    pub holds: TimeMap<HoldNote>,
+    pub bells: TimeMap<BellNote>,
+    pub flicks: TimeMap<FlickNote>,
 }
 
 impl Notes {
-    pub fn from_raw(raw: RawNotes, track: &Track) -> Result<Self> {
-        let taps = Self::map_tap_notes(raw.taps, track, false)?
-            .into_iter()
-            .chain(Self::map_tap_notes(raw.critical_taps, track, true)?)
-            .collect::<BTreeMap<_, _>>();
-        let holds = Self::map_hold_notes(raw.holds, track, false)?
-            .into_iter()
-            .chain(Self::map_hold_notes(raw.critical_holds, track, true)?)
-            .collect::<BTreeMap<_, _>>();
-        let bells = Self::map_bell_notes(raw.bells)?;
-        let flicks = Self::map_flick_notes(raw.flicks, false)?
-            .into_iter()
-            .chain(Self::map_flick_notes(raw.critical_flicks, true)?)
-            .collect::<BTreeMap<_, _>>();
+    /// Maps raw notes onto the lanes of `track`, bells shooting bullets use the palettes of
+    /// `bullets`.
+    pub fn from_raw(raw: RawNotes, track: &Track, bullets: &Bullets) -> Result<Self> {
+        Self::from_raw_with_options(raw, track, bullets, HoldPointsPolicy::default())
+    }
+
+    pub fn from_raw_with_options(
+        mut raw: RawNotes,
+        track: &Track,
+        bullets: &Bullets,
+        hold_points: HoldPointsPolicy,
+    ) -> Result<Self> {
+        let extensions = &mut raw.extensions;
+        let taps = merge_buckets(
+            Self::map_tap_notes(raw.taps, track, false, |i| {
+                extensions.remove(&NoteRef::Tap(i))
+            })?,
+            Self::map_tap_notes(raw.critical_taps, track, true, |i| {
+                extensions.remove(&NoteRef::CriticalTap(i))
+            })?,
+        );
+        let holds = merge_buckets(
+            Self::map_hold_notes(raw.holds, track, false, hold_points, |i| {
+                extensions.remove(&NoteRef::Hold(i))
+            })?,
+            Self::map_hold_notes(raw.critical_holds, track, true, hold_points, |i| {
+                extensions.remove(&NoteRef::CriticalHold(i))
+            })?,
+        );
+        let bells =
+            Self::map_bell_notes(raw.bells, bullets, |i| extensions.remove(&NoteRef::Bell(i)))?;
+        let flicks = merge_buckets(
+            Self::map_flick_notes(raw.flicks, false, |i| extensions.remove(&NoteRef::Flick(i)))?,
+            Self::map_flick_notes(raw.critical_flicks, true, |i| {
+                extensions.remove(&NoteRef::CriticalFlick(i))
+            })?,
+        );
 
         Ok(Self {
             taps,
@@ -898,8 +1301,8 @@ impl Notes {
     }
 
     /// Returns iterator of tap notes sorted by time.
-    pub fn all_taps(&self) -> impl Iterator<Item = &TapNote> {
-        self.taps.values().flatten()
+    pub fn all_taps(&self) -> Items<'_, TapNote> {
+        self.taps.items()
     }
 
     /// Creates a new array containing tap notes flattened from the sorted map.
@@ -907,9 +1310,14 @@ impl Notes {
         self.all_taps().cloned().collect()
     }
 
+    /// Moves out the tap notes sorted by time, dropping the other notes.
+    pub fn into_all_taps(self) -> IntoItems<TapNote> {
+        self.taps.into_items()
+    }
+
     /// Returns iterator of hold notes sorted by time.
-    pub fn all_holds(&self) -> impl Iterator<Item = &HoldNote> {
-        self.holds.values().flatten()
+    pub fn all_holds(&self) -> Items<'_, HoldNote> {
+        self.holds.items()
     }
 
     /// Creates a new array containing hold notes flattened from the sorted map.
@@ -917,9 +1325,14 @@ impl Notes {
         self.all_holds().cloned().collect()
     }
 
+    /// Moves out the hold notes sorted by time, dropping the other notes.
+    pub fn into_all_holds(self) -> IntoItems<HoldNote> {
+        self.holds.into_items()
+    }
+
     /// Returns iterator of bell notes sorted by time.
-    pub fn all_bells(&self) -> impl Iterator<Item = &BellNote> {
-        self.bells.values().flatten()
+    pub fn all_bells(&self) -> Items<'_, BellNote> {
+        self.bells.items()
     }
 
     /// Creates a new array containing bell notes flattened from the sorted map.
@@ -927,9 +1340,19 @@ impl Notes {
         self.all_bells().cloned().collect()
     }
 
+    /// Moves out the bell notes sorted by time, dropping the other notes.
+    pub fn into_all_bells(self) -> IntoItems<BellNote> {
+        self.bells.into_items()
+    }
+
+    /// Returns iterator of bell notes of the given kind sorted by time.
+    pub fn bells_of_kind(&self, kind: BellKind) -> impl Iterator<Item = &BellNote> {
+        self.all_bells().filter(move |bell| bell.kind() == kind)
+    }
+
     /// Returns iterator of flick notes sorted by time.
-    pub fn all_flicks(&self) -> impl Iterator<Item = &FlickNote> {
-        self.flicks.values().flatten()
+    pub fn all_flicks(&self) -> Items<'_, FlickNote> {
+        self.flicks.items()
     }
 
     /// Creates a new array containing flick notes flattened from the sorted map.
@@ -937,20 +1360,38 @@ impl Notes {
         self.all_flicks().cloned().collect()
     }
 
+    /// Moves out the flick notes sorted by time, dropping the other notes.
+    pub fn into_all_flicks(self) -> IntoItems<FlickNote> {
+        self.flicks.into_items()
+    }
+
+    /// Number of notes of all kinds, bells included.
+    pub fn len(&self) -> usize {
+        self.all_taps().len()
+            + self.all_holds().len()
+            + self.all_bells().len()
+            + self.all_flicks().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     fn map_tap_notes(
         taps: Vec<command::Tap>,
         track: &Track,
         is_critical: bool,
-    ) -> Result<BTreeMap<TimingPoint, Vec<TapNote>>> {
-        taps.into_iter().try_fold(BTreeMap::new(), |mut m, note| {
+        mut extensions: impl FnMut(usize) -> Option<Extensions>,
+    ) -> Result<TimeMap<TapNote>> {
+        let mut taps = taps.into_iter().enumerate();
+        let taps = taps.try_fold(Vec::new(), |mut m, (i, note)| {
             if let Some(lane) = track.get_lane(LaneId(note.lane_group_id)) {
-                let tap_note = TapNote::from_tap(note, lane.lane_type, is_critical);
-                m.entry(tap_note.position.time)
-                    .or_insert_with(Vec::new)
-                    .push(tap_note);
+                let mut tap_note = TapNote::from_tap(note, lane.lane_type, is_critical);
+                tap_note.extensions = extensions(i).unwrap_or_default();
+                m.push((tap_note.position.time, tap_note));
                 Ok(m)
             } else {
-                log::error!(
+                crate::diagnostics::error!(
                     "Tap note {:?} uses invalid lane id {:?}",
                     &note,
                     note.lane_group_id
@@ -960,23 +1401,29 @@ impl Notes {
                     &note, note.lane_group_id
                 )))
             }
-        })
+        })?;
+        Ok(TimeMap::from_items(taps))
     }
 
     fn map_hold_notes(
         holds: Vec<command::Hold>,
         track: &Track,
         is_critical: bool,
-    ) -> Result<BTreeMap<TimingPoint, Vec<HoldNote>>> {
-        holds.into_iter().try_fold(BTreeMap::new(), |mut m, note| {
+        hold_points: HoldPointsPolicy,
+        mut extensions: impl FnMut(usize) -> Option<Extensions>,
+    ) -> Result<TimeMap<HoldNote>> {
+        let mut holds = holds.into_iter().enumerate();
+        let holds = holds.try_fold(Vec::new(), |mut m, (i, note)| {
             if let Some(lane) = track.get_lane(LaneId(note.lane_group_id)) {
-                let hold_note = HoldNote::from_hold_and_lane(note, lane, is_critical)?;
-                m.entry(hold_note.start.time)
-                    .or_insert(Vec::new())
-                    .push(hold_note);
+                let mut hold_note = HoldNote::from_hold_and_lane(note, lane, is_critical)?;
+                if hold_points == HoldPointsPolicy::Borrow {
+                    hold_note.points = vec![];
+                }
+                hold_note.extensions = extensions(i).unwrap_or_default();
+                m.push((hold_note.start.time, hold_note));
                 Ok(m)
             } else {
-                log::error!(
+                crate::diagnostics::error!(
                     "hold note {:?} uses invalid lane id {:?}",
                     &note,
                     note.lane_group_id
@@ -986,48 +1433,61 @@ impl Notes {
                     &note, note.lane_group_id
                 )))
             }
-        })
-    }
-
-    fn map_bell_notes(bells: Vec<command::Bell>) -> Result<BTreeMap<TimingPoint, Vec<BellNote>>> {
-        bells.into_iter().try_fold(BTreeMap::new(), |mut m, note| {
-            // XXX TODO: check that bullet palette exists if provided.
-            let bell_note: BellNote = note.into();
-            m.entry(bell_note.position.time)
-                .or_insert(Vec::new())
-                .push(bell_note);
+        })?;
+        Ok(TimeMap::from_items(holds))
+    }
+
+    fn map_bell_notes(
+        bells: Vec<command::Bell>,
+        bullets: &Bullets,
+        mut extensions: impl FnMut(usize) -> Option<Extensions>,
+    ) -> Result<TimeMap<BellNote>> {
+        let mut bells = bells.into_iter().enumerate();
+        let bells = bells.try_fold(Vec::new(), |mut m, (i, note)| {
+            let mut bell_note = BellNote::from_bell(note, bullets)?;
+            bell_note.extensions = extensions(i).unwrap_or_default();
+            m.push((bell_note.position.time, bell_note));
             Ok(m)
-        })
+        })?;
+        Ok(TimeMap::from_items(bells))
     }
 
     fn map_flick_notes(
         flicks: Vec<command::Flick>,
         is_critical: bool,
-    ) -> Result<BTreeMap<TimingPoint, Vec<FlickNote>>> {
-        flicks.into_iter().try_fold(BTreeMap::new(), |mut m, note| {
-            let flick_note = FlickNote::from_flick(note, is_critical);
-            m.entry(flick_note.position.time)
-                .or_insert(Vec::new())
-                .push(flick_note);
+        mut extensions: impl FnMut(usize) -> Option<Extensions>,
+    ) -> Result<TimeMap<FlickNote>> {
+        let mut flicks = flicks.into_iter().enumerate();
+        let flicks = flicks.try_fold(Vec::new(), |mut m, (i, note)| {
+            let mut flick_note = FlickNote::from_flick(note, is_critical);
+            flick_note.extensions = extensions(i).unwrap_or_default();
+            m.push((flick_note.position.time, flick_note));
             Ok(m)
-        })
+        })?;
+        Ok(TimeMap::from_items(flicks))
     }
 
     pub fn highest_measure(&self) -> Option<u32> {
-        self.taps
-            .keys()
-            .chain(self.holds.keys())
-            .chain(self.bells.keys())
-            .chain(self.flicks.keys())
-            .map(|tp| tp.measure)
-            .max()
+        [
+            self.taps.last_time(),
+            self.holds.last_time(),
+            self.bells.last_time(),
+            self.flicks.last_time(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|tp| tp.measure)
+        .max()
     }
 }
 
 #[derive(Clone, Debug)]
 pub struct Bullets {
-    pub bullet_palette_list: HashMap<BulletPaletteId, BulletPalette>,
-    pub bullets: BTreeMap<TimingPoint, Vec<Bullet>>,
+    /// Palettes sorted by ID, indexed by [`PaletteIdRef`].
+    palettes: Vec<BulletPalette>,
+    /// Interned ID of every palette in `palettes`.
+    palette_refs: HashMap<BulletPaletteId, PaletteIdRef>,
+    pub bullets: TimeMap<Bullet>,
 }
 
 impl Bullets {
@@ -1035,45 +1495,173 @@ impl Bullets {
         palettes: Vec<command::BulletPalette>,
         bullets: Vec<command::Bullet>,
     ) -> Result<Bullets> {
-        let bullet_palette_list = palettes.into_iter().fold(HashMap::new(), |mut m, p| {
+        Self::from_raw_with_options(
+            palettes,
+            bullets,
+            MissingPalettePolicy::Error,
+            &mut Warnings::default(),
+        )
+    }
+
+    /// Same as [`Bullets::from_raw`], applying `policy` to bullets with an unknown palette.
+    pub fn from_raw_with_options(
+        palettes: Vec<command::BulletPalette>,
+        bullets: Vec<command::Bullet>,
+        policy: MissingPalettePolicy,
+        warnings: &mut Warnings,
+    ) -> Result<Bullets> {
+        Self::from_raw_with_extensions(
+            palettes,
+            bullets,
+            BTreeMap::new(),
+            &mut [],
+            policy,
+            warnings,
+        )
+    }
+
+    /// Same as [`Bullets::from_raw_with_options`], attaching extensions by bullet index and
+    /// applying `policy` to the palettes of `bells` too. Dropping the bullet of a bell keeps the
+    /// bell as a normal one.
+    pub(crate) fn from_raw_with_extensions(
+        palettes: Vec<command::BulletPalette>,
+        bullets: Vec<command::Bullet>,
+        mut extensions: BTreeMap<usize, Extensions>,
+        bells: &mut [command::Bell],
+        policy: MissingPalettePolicy,
+        warnings: &mut Warnings,
+    ) -> Result<Bullets> {
+        let mut palettes = palettes.into_iter().fold(HashMap::new(), |mut m, p| {
             let palette = BulletPalette::from(p);
             m.insert(palette.id.clone(), palette);
             m
         });
 
-        let bullets = bullets.into_iter().try_fold(BTreeMap::new(), |mut m, b| {
-            let palette_id = BulletPaletteId(b.pallete_id.clone());
-            if let Some(palette) = bullet_palette_list.get(&palette_id) {
-                let bullet = Bullet::from_bullet_command(b, palette)?;
-                m.entry(bullet.position.time)
-                    .or_insert_with(Vec::new)
-                    .push(bullet);
-                Ok(m)
-            } else {
-                Err(ParseError::SemanticError(format!(
-                    "Bullet commend {:?} invalid palette id {:?}",
-                    &b, &palette_id
-                )))
+        // Placeholders are only known once every bullet and bell has been seen.
+        let used = bullets.iter().map(|b| b.pallete_id.as_str()).chain(
+            bells
+                .iter()
+                .filter_map(|bell| bell.bullet_palette_id.as_deref()),
+        );
+        for id in used {
+            if palettes.contains_key(id) {
+                continue;
             }
+            let palette_id = BulletPaletteId(id.to_string());
+            match policy {
+                MissingPalettePolicy::Error | MissingPalettePolicy::Drop => {}
+                MissingPalettePolicy::Placeholder => {
+                    warnings.push(Warning::PlaceholderPaletteCreated {
+                        palette_id: palette_id.0.clone(),
+                    });
+                    palettes.insert(palette_id.clone(), BulletPalette::placeholder(palette_id));
+                }
+            }
+        }
+
+        let mut palettes = palettes.into_values().collect::<Vec<_>>();
+        palettes.sort_by(|a, b| a.id.0.cmp(&b.id.0));
+        let palette_refs = palettes
+            .iter()
+            .enumerate()
+            .map(|(i, palette)| (palette.id.clone(), PaletteIdRef(i as u32)))
+            .collect::<HashMap<_, _>>();
+
+        if policy == MissingPalettePolicy::Drop {
+            for bell in bells {
+                if let Some(id) = bell
+                    .bullet_palette_id
+                    .take_if(|id| !palette_refs.contains_key(id.as_str()))
+                {
+                    warnings.push(Warning::BulletDroppedMissingPalette {
+                        palette_id: id,
+                        time: bell.time.into(),
+                    });
+                }
+            }
+        }
+
+        let mut bullets = bullets.into_iter().enumerate();
+        let bullets = bullets.try_fold(Vec::new(), |mut m, (i, b)| {
+            let Some(&palette_ref) = palette_refs.get(b.pallete_id.as_str()) else {
+                if policy == MissingPalettePolicy::Drop {
+                    warnings.push(Warning::BulletDroppedMissingPalette {
+                        palette_id: b.pallete_id,
+                        time: b.time.into(),
+                    });
+                    return Ok(m);
+                }
+                return Err(ParseError::SemanticError(format!(
+                    "Bullet commend {:?} invalid palette id {:?}",
+                    &b,
+                    BulletPaletteId(b.pallete_id.clone())
+                )));
+            };
+
+            let palette = &palettes[palette_ref.index()];
+            let mut bullet = Bullet::from_bullet_command(b, palette, palette_ref)?;
+            bullet.extensions = extensions.remove(&i).unwrap_or_default();
+            m.push((bullet.position.time, bullet));
+            Ok(m)
         })?;
+        let bullets = TimeMap::from_items(bullets);
 
         Ok(Self {
-            bullet_palette_list,
+            palettes,
+            palette_refs,
             bullets,
         })
     }
 
+    /// Palettes sorted by ID, indexed by [`PaletteIdRef`].
+    pub fn palettes(&self) -> &[BulletPalette] {
+        &self.palettes
+    }
+
+    /// Palette of an interned ID, without hashing.
+    pub fn palette(&self, palette: PaletteIdRef) -> Option<&BulletPalette> {
+        self.palettes.get(palette.index())
+    }
+
+    /// Interned ID of a palette, `None` if it does not exist.
+    pub fn palette_ref(&self, id: &str) -> Option<PaletteIdRef> {
+        self.palette_refs.get(id).copied()
+    }
+
     pub fn get_bullet_palette(&self, id: &BulletPaletteId) -> Option<&BulletPalette> {
-        self.bullet_palette_list.get(id)
+        self.palette(self.palette_ref(&id.0)?)
     }
 
     /// Returns iterator of bullets sorted by time.
-    pub fn all_bullets(&self) -> impl Iterator<Item = &Bullet> {
-        self.bullets.values().flatten()
+    pub fn all_bullets(&self) -> Items<'_, Bullet> {
+        self.bullets.items()
+    }
+
+    /// Moves out the bullets sorted by time, dropping the palettes.
+    pub fn into_all_bullets(self) -> IntoItems<Bullet> {
+        self.bullets.into_items()
     }
 
     fn highest_measure(&self) -> Option<u32> {
-        self.bullets.keys().map(|tp| tp.measure).max()
+        self.bullets.last_time().map(|tp| tp.measure)
+    }
+}
+
+impl<'a> IntoIterator for &'a Bullets {
+    type Item = &'a Bullet;
+    type IntoIter = Items<'a, Bullet>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.all_bullets()
+    }
+}
+
+impl IntoIterator for Bullets {
+    type Item = Bullet;
+    type IntoIter = IntoItems<Bullet>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.into_all_bullets()
     }
 }
 
@@ -1114,10 +1702,25 @@ impl From<command::MeterChange> for MeterChange {
 #[derive(Clone, Debug)]
 pub struct Soflan {
     pub time: TimingPoint,
+    /// Duration in ticks.
     pub duration: u32,
     pub speed_multiplier: f32,
 }
 
+impl Soflan {
+    /// Time at which the soflan stops applying.
+    pub fn end(&self, resolution: Resolution) -> TimingPoint {
+        resolution.time_of(self.end_ticks(resolution))
+    }
+
+    /// Same as [`Soflan::end`], in ticks from the start of the chart.
+    pub fn end_ticks(&self, resolution: Resolution) -> u64 {
+        resolution
+            .ticks_at(self.time)
+            .saturating_add(self.duration as u64)
+    }
+}
+
 impl From<command::Soflan> for Soflan {
     fn from(soflan: command::Soflan) -> Self {
         Self {
@@ -1130,9 +1733,11 @@ impl From<command::Soflan> for Soflan {
 
 #[derive(Clone, Debug)]
 pub struct Composition {
-    pub bpm_changes: BTreeMap<TimingPoint, BpmChange>,
+    /// BPM changes in chart order, several changes can share a timing point.
+    pub bpm_changes: BTreeMap<TimingPoint, Vec<BpmChange>>,
     pub meter_changes: BTreeMap<TimingPoint, MeterChange>,
-    pub soflans: BTreeMap<TimingPoint, Soflan>,
+    /// Soflans in chart order, several soflans can share a timing point.
+    pub soflans: BTreeMap<TimingPoint, Vec<Soflan>>,
 }
 
 impl Composition {
@@ -1142,7 +1747,9 @@ impl Composition {
             .into_iter()
             .fold(BTreeMap::new(), |mut m, b| {
                 let bpm_change = BpmChange::from(b);
-                m.insert(bpm_change.time, bpm_change);
+                m.entry(bpm_change.time)
+                    .or_insert_with(Vec::new)
+                    .push(bpm_change);
                 m
             });
 
@@ -1157,7 +1764,7 @@ impl Composition {
 
         let soflans = raw.soflans.into_iter().fold(BTreeMap::new(), |mut m, b| {
             let soflan = Soflan::from(b);
-            m.insert(soflan.time, soflan);
+            m.entry(soflan.time).or_insert_with(Vec::new).push(soflan);
             m
         });
 
@@ -1167,9 +1774,19 @@ impl Composition {
             soflans,
         }
     }
+
+    /// Returns iterator of BPM changes sorted by time.
+    pub fn all_bpm_changes(&self) -> SortedIter<'_, BpmChange, Vec<BpmChange>> {
+        SortedIter::new(&self.bpm_changes)
+    }
+
+    /// Returns iterator of soflans sorted by time.
+    pub fn all_soflans(&self) -> SortedIter<'_, Soflan, Vec<Soflan>> {
+        SortedIter::new(&self.soflans)
+    }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct ClickSound {
     pub time: TimingPoint,
 }
@@ -1207,18 +1824,48 @@ pub struct Ogkr {
     pub track: Track,
     pub notes: Notes,
     pub bullets: Bullets,
+    /// Sorted by time, without duplicates.
     pub click_sounds: Vec<ClickSound>,
     pub enemy_wave_assignment: EnemyWaveAssignment,
     pub extra_metadata: ExtraMetadata,
+    /// Extensions of the chart itself, extensions of notes and bullets are part of each object.
+    pub extensions: Extensions,
+    /// Commands read by handlers registered with
+    /// [`LexOptions::custom_command`](crate::lex::LexOptions::custom_command), in chart order.
+    pub custom_commands: Vec<CustomToken>,
+
+    /// Data that was dropped or ignored while parsing.
+    pub warnings: Warnings,
 }
 
 impl Ogkr {
     pub fn from_raw(raw: RawOgkr) -> Result<Self> {
+        Self::from_raw_with_options(raw, &ParseOptions::default())
+    }
+
+    pub fn from_raw_with_options(mut raw: RawOgkr, options: &ParseOptions) -> Result<Self> {
+        let _phase = Phase::enter("analysis", raw.notes.len());
+        let mut warnings = raw.warnings;
+
         let header = raw.header;
         let composition = Composition::from_raw(raw.composition);
-        let track = Track::from_raw(raw.track)?;
-        let notes = Notes::from_raw(raw.notes, &track)?;
-        let bullets = Bullets::from_raw(raw.bullet_pallete_list, raw.bullets)?;
+        let mut track = Track::from_raw_with_warnings(raw.track, &mut warnings)?;
+        track.resolution = Resolution::from_header(&header);
+        resolve_missing_lanes(
+            &mut raw.notes,
+            &mut track,
+            options.missing_lane,
+            &mut warnings,
+        );
+        let bullets = Bullets::from_raw_with_extensions(
+            raw.bullet_pallete_list,
+            raw.bullets,
+            raw.bullet_extensions,
+            &mut raw.notes.bells,
+            options.missing_palette,
+            &mut warnings,
+        )?;
+        let notes = Notes::from_raw_with_options(raw.notes, &track, &bullets, options.hold_points)?;
         let click_sounds = Self::map_click_sounds(raw.click_sounds);
         let enemy_wave_assignment = raw.enemy_wave_assignment;
         let extra_metadata = ExtraMetadata::new(&track, &notes, &bullets);
@@ -1232,15 +1879,243 @@ impl Ogkr {
             click_sounds,
             enemy_wave_assignment,
             extra_metadata,
+            extensions: raw.extensions,
+            custom_commands: raw.custom_commands,
+            warnings,
         })
     }
 
+    /// Moves the chart behind an [`Arc`](std::sync::Arc) for sharing across threads.
+    pub fn into_shared(self) -> SharedOgkr {
+        SharedOgkr::new(self)
+    }
+
+    /// Returns the contents of a single measure.
+    pub fn measure(&self, measure: u32) -> MeasureView<'_> {
+        MeasureView::new(self, measure)
+    }
+
+    /// Starts a query over the notes or bullets of the chart, see [`Query`].
+    pub fn query(&self) -> Query<'_> {
+        Query::new(self)
+    }
+
+    /// Returns iterator over every measure of the chart, including empty ones.
+    pub fn measures(&self) -> impl Iterator<Item = MeasureView<'_>> {
+        (0..=self.extra_metadata.num_measures).map(|measure| self.measure(measure))
+    }
+
+    /// Returns a converter between timing points and milliseconds for this chart.
+    pub fn timing(&self) -> TimingConverter {
+        TimingConverter::new(&self.header, &self.composition)
+    }
+
+    /// Returns bullets indexed by the time they have to be spawned at, see
+    /// [`Bullets::spawn_index`].
+    pub fn bullet_spawns(&self) -> BTreeMap<TimingPoint, Vec<BulletSpawn<'_>>> {
+        self.bullets
+            .spawn_index(&self.track, &self.timing(), Bullets::DEFAULT_TRAVEL_MS)
+    }
+
+    /// Returns the time of every click sound in milliseconds, in order, ready to be scheduled as a
+    /// metronome.
+    pub fn click_times_ms(&self, timing: &TimingConverter) -> Vec<f64> {
+        self.click_sounds
+            .iter()
+            .map(|click| timing.to_ms(click.time))
+            .collect()
+    }
+
+    /// Scroll speed multiplier at `time`, 1 outside of soflans.
+    ///
+    /// A soflan applies from its time until its end, excluded. When several soflans apply, the one
+    /// starting the latest wins, and among soflans starting at the same time the last one of the
+    /// chart.
+    pub fn effective_speed_at(&self, time: TimingPoint) -> f32 {
+        let resolution = self.track.resolution;
+        let ticks = resolution.ticks_at(time);
+        self.composition
+            .soflans
+            .range(..=time)
+            .rev()
+            .flat_map(|(_, soflans)| soflans.iter().rev())
+            .find(|soflan| ticks < soflan.end_ticks(resolution))
+            .map_or(1.0, |soflan| soflan.speed_multiplier)
+    }
+
+    /// Counts the notes of the chart the way the `[TOTAL]` section declares them, for comparison
+    /// with `header.totals`.
+    pub fn count_totals(&self) -> Totals {
+        let is_side = |lane_id: &LaneId| {
+            self.track.lanes_data.get(lane_id).is_some_and(|lane| {
+                matches!(lane.lane_type, LaneType::WallLeft | LaneType::WallRight)
+            })
+        };
+
+        let mut totals = Totals::default();
+        for tap in self.notes.all_taps() {
+            if is_side(&tap.lane_id) {
+                totals.side += 1;
+            } else {
+                totals.tap += 1;
+            }
+        }
+        for hold in self.notes.all_holds() {
+            if is_side(&hold.lane_id) {
+                totals.side_hold += 1;
+            } else {
+                totals.hold += 1;
+            }
+        }
+        totals.flick = self.notes.all_flicks().len() as u32;
+        totals.bell = self.notes.all_bells().len() as u32;
+        totals.notes = totals.tap + totals.side + totals.hold + totals.side_hold + totals.flick;
+        totals
+    }
+
+    /// Removes data identifying the authors of the chart, for distributing charts blind: the
+    /// creator, extensions of the chart itself like `X_EDITOR`, and custom commands of private
+    /// editors. Extensions of notes and bullets are part of the gameplay and kept.
+    pub fn anonymize(&mut self) {
+        self.header.creator = None;
+        self.extensions.clear();
+        self.custom_commands.clear();
+    }
+
+    /// Sorted by time, with duplicates removed.
     fn map_click_sounds(click_sounds: Vec<command::ClickSound>) -> Vec<ClickSound> {
-        click_sounds.into_iter().map(ClickSound::from).collect()
+        let mut click_sounds = click_sounds
+            .into_iter()
+            .map(ClickSound::from)
+            .collect::<Vec<_>>();
+        click_sounds.sort();
+        click_sounds.dedup();
+        click_sounds
+    }
+}
+
+/// Applies `policy` to tap and hold notes whose lane is not part of `track`.
+fn resolve_missing_lanes(
+    notes: &mut RawNotes,
+    track: &mut Track,
+    policy: MissingLanePolicy,
+    warnings: &mut Warnings,
+) {
+    let tap_positions = notes.taps.iter().chain(&notes.critical_taps).map(|tap| {
+        let position = TrackPosition::from_command_info(tap.time, tap.x_position, tap.x_offset);
+        (tap.lane_group_id, [position, position])
+    });
+    let hold_positions = notes.holds.iter().chain(&notes.critical_holds).map(|hold| {
+        (
+            hold.lane_group_id,
+            [
+                TrackPosition::from_command_info(
+                    hold.start_time,
+                    hold.start_x_position,
+                    hold.start_x_offset,
+                ),
+                TrackPosition::from_command_info(
+                    hold.end_time,
+                    hold.end_x_position,
+                    hold.end_x_offset,
+                ),
+            ],
+        )
+    });
+
+    let mut missing = BTreeMap::<u32, Vec<TrackPosition>>::new();
+    for (lane_id, positions) in tap_positions.chain(hold_positions) {
+        if track.get_lane(LaneId(lane_id)).is_none() {
+            missing.entry(lane_id).or_default().extend(positions);
+        }
+    }
+
+    match policy {
+        // Reported when mapping the notes.
+        MissingLanePolicy::Error => {}
+        MissingLanePolicy::Drop => {
+            let mut keep = |lane_id: u32, time: command::CommandTime| {
+                let keep = !missing.contains_key(&lane_id);
+                if !keep {
+                    warnings.push(Warning::NoteDroppedMissingLane {
+                        lane_id,
+                        time: time.into(),
+                    });
+                }
+                keep
+            };
+            let extensions = &mut notes.extensions;
+            retain_notes(&mut notes.taps, extensions, NoteRef::Tap, |tap| {
+                keep(tap.lane_group_id, tap.time)
+            });
+            retain_notes(
+                &mut notes.critical_taps,
+                extensions,
+                NoteRef::CriticalTap,
+                |tap| keep(tap.lane_group_id, tap.time),
+            );
+            retain_notes(&mut notes.holds, extensions, NoteRef::Hold, |hold| {
+                keep(hold.lane_group_id, hold.start_time)
+            });
+            retain_notes(
+                &mut notes.critical_holds,
+                extensions,
+                NoteRef::CriticalHold,
+                |hold| keep(hold.lane_group_id, hold.start_time),
+            );
+        }
+        MissingLanePolicy::SyntheticLane => {
+            for (lane_id, mut points) in missing {
+                points.sort();
+                points.dedup_by_key(|point| point.time);
+
+                warnings.push(Warning::SyntheticLaneCreated { lane_id });
+                track.lanes_data.insert(
+                    LaneId(lane_id),
+                    Lane {
+                        id: LaneId(lane_id),
+                        lane_type: LaneType::Synthetic,
+                        points,
+                    },
+                );
+            }
+        }
     }
 }
 
+/// Same as [`Vec::retain`], moving the extensions of kept notes to their new index.
+fn retain_notes<T>(
+    notes: &mut Vec<T>,
+    extensions: &mut BTreeMap<NoteRef, Extensions>,
+    note_ref: fn(usize) -> NoteRef,
+    mut keep: impl FnMut(&T) -> bool,
+) {
+    let mut kept = 0;
+    let mut index = 0;
+    notes.retain(|note| {
+        let note_extensions = extensions.remove(&note_ref(index));
+        index += 1;
+        if !keep(note) {
+            return false;
+        }
+        if let Some(note_extensions) = note_extensions {
+            extensions.insert(note_ref(kept), note_extensions);
+        }
+        kept += 1;
+        true
+    });
+}
+
 /// XXX TODO: Handle random number generation for some fields, eg. bullets.
 pub fn parse_raw_ogkr(raw: RawOgkr) -> Result<Ogkr> {
     Ogkr::from_raw(raw)
 }
+
+pub fn parse_raw_ogkr_with_options(raw: RawOgkr, options: &ParseOptions) -> Result<Ogkr> {
+    Ogkr::from_raw_with_options(raw, options)
+}
+
+/// Converts `ogkr` back into commands, the inverse of [`parse_raw_ogkr`], see [`Ogkr::to_raw`].
+pub fn lower(ogkr: &Ogkr) -> RawOgkr {
+    ogkr.to_raw()
+}
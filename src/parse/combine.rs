@@ -0,0 +1,499 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::lex::command::{BpmChange, CommandTime, MeterChange};
+
+use super::{
+    analysis::{Ogkr, Resolution, TimingPoint, XPosition},
+    raw::{NoteRef, RawComposition, RawNotes, RawOgkr},
+    timing::TimingConverter,
+    EnemyWaveAssignment, ParseError, Result, Totals,
+};
+
+impl Ogkr {
+    /// Appends charts back to back, each part starting at the measure following the last measure
+    /// of the previous one.
+    ///
+    /// The header and enemy waves are taken from the first part. Lane, beam and colorful lane IDs
+    /// are shifted to stay unique, times are converted to the tick resolution of the first part while
+    /// x offsets are kept, and conflicting bullet palettes are renamed. Every later part starts with a BPM and meter
+    /// change restoring its own initial tempo. Fails with [`ParseError::LimitExceeded`] when the
+    /// shifted measures or IDs do not fit in a `u32`.
+    pub fn concat(parts: &[Ogkr]) -> Result<Ogkr> {
+        let Some((first, rest)) = parts.split_first() else {
+            return Ogkr::from_raw(RawOgkr::default());
+        };
+
+        let mut merged = first.to_raw();
+        merged.header.totals = Totals::default();
+        let resolution = Resolution::from_header(&merged.header);
+        let mut measure_offset = add_measures(first.extra_metadata.num_measures, 1)?;
+
+        for part in rest {
+            let mut raw = part.to_raw();
+            add_initial_tempo(&mut raw, part);
+
+            let remap = Remap::new(&merged, &raw, resolution, measure_offset)?;
+            remap.apply(&mut raw)?;
+            append(&mut merged, raw);
+
+            let part_measures = add_measures(part.extra_metadata.num_measures, 1)?;
+            measure_offset = add_measures(measure_offset, part_measures)?;
+        }
+
+        Ogkr::from_raw(merged)
+    }
+}
+
+/// Adds a BPM and meter change at the start of `raw` unless it already has one, so the tempo of
+/// the part is kept when appended to another chart.
+fn add_initial_tempo(raw: &mut RawOgkr, part: &Ogkr) {
+    let start = CommandTime {
+        measure: 0,
+        offset: 0,
+    };
+    let composition = &mut raw.composition;
+
+    if !composition
+        .bpm_changes
+        .iter()
+        .any(|change| change.time == start)
+    {
+        let bpm = part
+            .header
+            .bpm_definition
+            .map(|definition| definition.first)
+            .filter(|bpm| f32::from_bits(*bpm) > 0.0)
+            .unwrap_or((TimingConverter::DEFAULT_BPM as f32).to_bits());
+        composition
+            .bpm_changes
+            .insert(0, BpmChange { time: start, bpm });
+    }
+
+    if !composition
+        .meter_changes
+        .iter()
+        .any(|change| change.time == start)
+    {
+        let (num_beats, note_value) = part
+            .header
+            .meter_definition
+            .filter(|meter| meter.num_beats > 0 && meter.note_value > 0)
+            .map_or((4, 4), |meter| (meter.num_beats, meter.note_value));
+        composition.meter_changes.insert(
+            0,
+            MeterChange {
+                time: start,
+                num_beats,
+                note_value,
+            },
+        );
+    }
+}
+
+/// Moves the objects of a chart so they can be added to another chart without clashing.
+pub(crate) struct Remap {
+    measure_offset: u32,
+    /// Tick resolution of the added chart and of the chart it is added to.
+    ticks: (u32, u32),
+    lane_offset: u32,
+    colorful_lane_offset: u32,
+    beam_offset: u32,
+    oblique_beam_offset: u32,
+    palette_ids: HashMap<String, String>,
+}
+
+impl Remap {
+    /// Moves `raw` to start at `measure_offset` measures, in the resolution of `into`, using IDs
+    /// that are unused in `into`.
+    pub(crate) fn new(
+        into: &RawOgkr,
+        raw: &RawOgkr,
+        resolution: Resolution,
+        measure_offset: u32,
+    ) -> Result<Self> {
+        let track = &into.track;
+        let lane_offset = track.next_lane_id().ok_or_else(ids_exhausted)?;
+        let colorful_lane_offset = track.next_colorful_lane_id().ok_or_else(ids_exhausted)?;
+        let beam_offset = track.next_beam_id().ok_or_else(ids_exhausted)?;
+        let oblique_beam_offset = track.next_oblique_beam_id().ok_or_else(ids_exhausted)?;
+
+        let mut palette_ids = HashMap::new();
+        for palette in &raw.bullet_pallete_list {
+            let Some(existing) = into
+                .bullet_pallete_list
+                .iter()
+                .find(|existing| existing.id == palette.id)
+            else {
+                continue;
+            };
+            if existing == palette {
+                continue;
+            }
+            let id = (2..)
+                .map(|n| format!("{}{n}", palette.id))
+                .find(|id| {
+                    !into.bullet_pallete_list.iter().any(|p| &p.id == id)
+                        && !raw.bullet_pallete_list.iter().any(|p| &p.id == id)
+                })
+                .unwrap();
+            palette_ids.insert(palette.id.clone(), id);
+        }
+
+        Ok(Self {
+            measure_offset,
+            ticks: (
+                Resolution::from_header(&raw.header).ticks.max(1),
+                resolution.ticks.max(1),
+            ),
+            lane_offset,
+            colorful_lane_offset,
+            beam_offset,
+            oblique_beam_offset,
+            palette_ids,
+        })
+    }
+
+    fn time(&self, time: CommandTime) -> Result<CommandTime> {
+        Ok(CommandTime {
+            measure: add_measures(time.measure, self.measure_offset)?,
+            offset: self.duration(time.offset),
+        })
+    }
+
+    fn duration(&self, ticks: u32) -> u32 {
+        let (from, to) = self.ticks;
+        u32::try_from(ticks as u64 * to as u64 / from as u64).unwrap_or(u32::MAX)
+    }
+
+    fn palette_id(&self, id: &mut String) {
+        if let Some(renamed) = self.palette_ids.get(id) {
+            id.clone_from(renamed);
+        }
+    }
+
+    pub(crate) fn apply(&self, raw: &mut RawOgkr) -> Result<()> {
+        let composition = &mut raw.composition;
+        for change in &mut composition.bpm_changes {
+            change.time = self.time(change.time)?;
+        }
+        for change in &mut composition.meter_changes {
+            change.time = self.time(change.time)?;
+        }
+        for soflan in &mut composition.soflans {
+            soflan.time = self.time(soflan.time)?;
+            soflan.duration = self.duration(soflan.duration);
+        }
+
+        for palette in &mut raw.bullet_pallete_list {
+            self.palette_id(&mut palette.id);
+        }
+        for bullet in &mut raw.bullets {
+            self.palette_id(&mut bullet.pallete_id);
+            bullet.time = self.time(bullet.time)?;
+        }
+        for click in &mut raw.click_sounds {
+            click.time = self.time(click.time)?;
+        }
+
+        let track = &mut raw.track;
+        for section in track
+            .lanes_left
+            .iter_mut()
+            .chain(&mut track.lanes_center)
+            .chain(&mut track.lanes_right)
+            .chain(&mut track.enemy_lanes)
+        {
+            shift_id(&mut section.group_id, self.lane_offset)?;
+            for point in &mut section.points {
+                shift_id(&mut point.group_id, self.lane_offset)?;
+                point.time = self.time(point.time)?;
+            }
+        }
+        for section in track.walls_left.iter_mut().chain(&mut track.walls_right) {
+            shift_id(&mut section.group_id, self.lane_offset)?;
+            for point in &mut section.points {
+                shift_id(&mut point.group_id, self.lane_offset)?;
+                point.time = self.time(point.time)?;
+            }
+        }
+        for event in track
+            .lane_disappearances
+            .iter_mut()
+            .chain(&mut track.lane_blocks)
+        {
+            shift_id(&mut event.group_id, self.lane_offset)?;
+            event.start_time = self.time(event.start_time)?;
+            event.end_time = self.time(event.end_time)?;
+        }
+        for section in &mut track.colorful_lanes {
+            shift_id(&mut section.group_id, self.colorful_lane_offset)?;
+            for point in &mut section.points {
+                shift_id(&mut point.group_id, self.colorful_lane_offset)?;
+                point.time = self.time(point.time)?;
+            }
+        }
+        for section in &mut track.beams {
+            shift_id(&mut section.record_id, self.beam_offset)?;
+            for point in &mut section.points {
+                shift_id(&mut point.record_id, self.beam_offset)?;
+                point.time = self.time(point.time)?;
+            }
+        }
+        for section in &mut track.oblique_beams {
+            shift_id(&mut section.record_id, self.oblique_beam_offset)?;
+            for point in &mut section.points {
+                shift_id(&mut point.record_id, self.oblique_beam_offset)?;
+                point.time = self.time(point.time)?;
+            }
+        }
+
+        let notes = &mut raw.notes;
+        for tap in notes.taps.iter_mut().chain(&mut notes.critical_taps) {
+            shift_id(&mut tap.lane_group_id, self.lane_offset)?;
+            tap.time = self.time(tap.time)?;
+        }
+        for hold in notes.holds.iter_mut().chain(&mut notes.critical_holds) {
+            shift_id(&mut hold.lane_group_id, self.lane_offset)?;
+            hold.start_time = self.time(hold.start_time)?;
+            hold.end_time = self.time(hold.end_time)?;
+        }
+        for flick in notes.flicks.iter_mut().chain(&mut notes.critical_flicks) {
+            flick.time = self.time(flick.time)?;
+        }
+        for bell in &mut notes.bells {
+            if let Some(id) = &mut bell.bullet_palette_id {
+                self.palette_id(id);
+            }
+            bell.time = self.time(bell.time)?;
+        }
+        Ok(())
+    }
+}
+
+fn add_measures(measure: u32, offset: u32) -> Result<u32> {
+    measure
+        .checked_add(offset)
+        .ok_or(ParseError::LimitExceeded {
+            what: "measures",
+            max: u32::MAX as usize,
+        })
+}
+
+fn shift_id(id: &mut u32, offset: u32) -> Result<()> {
+    *id = id.checked_add(offset).ok_or_else(ids_exhausted)?;
+    Ok(())
+}
+
+fn ids_exhausted() -> ParseError {
+    ParseError::LimitExceeded {
+        what: "ids",
+        max: u32::MAX as usize,
+    }
+}
+
+/// Moves every command of `from` into `into`, keeping palettes of `into` that `from` shares.
+pub(crate) fn append(into: &mut RawOgkr, from: RawOgkr) {
+    let composition = &mut into.composition;
+    composition.bpm_changes.extend(from.composition.bpm_changes);
+    composition
+        .meter_changes
+        .extend(from.composition.meter_changes);
+    composition.soflans.extend(from.composition.soflans);
+
+    for palette in from.bullet_pallete_list {
+        if !into.bullet_pallete_list.iter().any(|p| p.id == palette.id) {
+            into.bullet_pallete_list.push(palette);
+        }
+    }
+    let bullet_offset = into.bullets.len();
+    into.bullet_extensions.extend(
+        from.bullet_extensions
+            .into_iter()
+            .map(|(i, extensions)| (i + bullet_offset, extensions)),
+    );
+    into.bullets.extend(from.bullets);
+    into.click_sounds.extend(from.click_sounds);
+    // Arguments of custom commands are opaque, so they are kept as they are.
+    into.custom_commands.extend(from.custom_commands);
+
+    let track = &mut into.track;
+    track.walls_left.extend(from.track.walls_left);
+    track.walls_right.extend(from.track.walls_right);
+    track.lanes_left.extend(from.track.lanes_left);
+    track.lanes_center.extend(from.track.lanes_center);
+    track.lanes_right.extend(from.track.lanes_right);
+    track.colorful_lanes.extend(from.track.colorful_lanes);
+    track.enemy_lanes.extend(from.track.enemy_lanes);
+    track
+        .lane_disappearances
+        .extend(from.track.lane_disappearances);
+    track.lane_blocks.extend(from.track.lane_blocks);
+    track.beams.extend(from.track.beams);
+    track.oblique_beams.extend(from.track.oblique_beams);
+
+    let notes = &mut into.notes;
+    for (note, extensions) in from.notes.extensions {
+        let offset = match note {
+            NoteRef::Bell(_) => notes.bells.len(),
+            NoteRef::Flick(_) => notes.flicks.len(),
+            NoteRef::CriticalFlick(_) => notes.critical_flicks.len(),
+            NoteRef::Tap(_) => notes.taps.len(),
+            NoteRef::CriticalTap(_) => notes.critical_taps.len(),
+            NoteRef::Hold(_) => notes.holds.len(),
+            NoteRef::CriticalHold(_) => notes.critical_holds.len(),
+        };
+        notes
+            .extensions
+            .insert(note.with_index(note.index() + offset), extensions);
+    }
+    notes.bells.extend(from.notes.bells);
+    notes.flicks.extend(from.notes.flicks);
+    notes.critical_flicks.extend(from.notes.critical_flicks);
+    notes.taps.extend(from.notes.taps);
+    notes.critical_taps.extend(from.notes.critical_taps);
+    notes.holds.extend(from.notes.holds);
+    notes.critical_holds.extend(from.notes.critical_holds);
+}
+
+/// Object of an overlaid chart colliding with the chart it is overlaid onto.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OverlayConflict {
+    /// Tap, hold start or flick at the same time and position as another one.
+    Note {
+        time: TimingPoint,
+        x: XPosition,
+    },
+    Bell {
+        time: TimingPoint,
+        x_position: i32,
+    },
+    Bullet {
+        time: TimingPoint,
+        x_position: i32,
+    },
+    /// BPM or meter change that the base chart does not have, the overlay follows the tempo of
+    /// the base chart.
+    Tempo {
+        time: TimingPoint,
+    },
+}
+
+impl Ogkr {
+    /// Overlays notes, bullets and track objects of `other` onto this chart, keeping this
+    /// chart's header, tempo and enemy waves.
+    ///
+    /// IDs and palettes of `other` are remapped like in [`Ogkr::concat`]. Objects of `other`
+    /// landing on an object of the same kind are still added and reported as conflicts, sorted by
+    /// time.
+    pub fn merge_overlay(&self, other: &Ogkr) -> Result<(Ogkr, Vec<OverlayConflict>)> {
+        let mut merged = self.to_raw();
+        merged.header.totals = Totals::default();
+        let resolution = Resolution::from_header(&merged.header);
+
+        let mut overlay = other.to_raw();
+        Remap::new(&merged, &overlay, resolution, 0)?.apply(&mut overlay)?;
+        let mut conflicts = overlay_conflicts(&merged, &overlay);
+        conflicts.sort_by_key(|conflict| match conflict {
+            OverlayConflict::Note { time, .. }
+            | OverlayConflict::Bell { time, .. }
+            | OverlayConflict::Bullet { time, .. }
+            | OverlayConflict::Tempo { time } => *time,
+        });
+
+        overlay.composition = RawComposition::default();
+        overlay.enemy_wave_assignment = EnemyWaveAssignment::default();
+        append(&mut merged, overlay);
+
+        Ok((Ogkr::from_raw(merged)?, conflicts))
+    }
+}
+
+fn overlay_conflicts(base: &RawOgkr, overlay: &RawOgkr) -> Vec<OverlayConflict> {
+    fn note_positions(notes: &RawNotes) -> impl Iterator<Item = (CommandTime, i32, i32)> + '_ {
+        notes
+            .taps
+            .iter()
+            .chain(&notes.critical_taps)
+            .map(|tap| (tap.time, tap.x_position, tap.x_offset))
+            .chain(
+                notes
+                    .holds
+                    .iter()
+                    .chain(&notes.critical_holds)
+                    .map(|hold| (hold.start_time, hold.start_x_position, hold.start_x_offset)),
+            )
+            .chain(
+                notes
+                    .flicks
+                    .iter()
+                    .chain(&notes.critical_flicks)
+                    .map(|flick| (flick.time, flick.x_position, 0)),
+            )
+    }
+
+    let mut conflicts = Vec::new();
+
+    let notes = note_positions(&base.notes).collect::<HashSet<_>>();
+    conflicts.extend(
+        note_positions(&overlay.notes)
+            .filter(|position| notes.contains(position))
+            .map(|(time, position, offset)| OverlayConflict::Note {
+                time: time.into(),
+                x: XPosition::new(position, offset),
+            }),
+    );
+
+    let bells = base
+        .notes
+        .bells
+        .iter()
+        .map(|bell| (bell.time, bell.x_position))
+        .collect::<HashSet<_>>();
+    conflicts.extend(
+        overlay
+            .notes
+            .bells
+            .iter()
+            .filter(|bell| bells.contains(&(bell.time, bell.x_position)))
+            .map(|bell| OverlayConflict::Bell {
+                time: bell.time.into(),
+                x_position: bell.x_position,
+            }),
+    );
+
+    let bullets = base
+        .bullets
+        .iter()
+        .map(|bullet| (bullet.time, bullet.x_position))
+        .collect::<HashSet<_>>();
+    conflicts.extend(
+        overlay
+            .bullets
+            .iter()
+            .filter(|bullet| bullets.contains(&(bullet.time, bullet.x_position)))
+            .map(|bullet| OverlayConflict::Bullet {
+                time: bullet.time.into(),
+                x_position: bullet.x_position,
+            }),
+    );
+
+    let base_composition = &base.composition;
+    let overlay_composition = &overlay.composition;
+    conflicts.extend(
+        overlay_composition
+            .bpm_changes
+            .iter()
+            .filter(|change| !base_composition.bpm_changes.contains(change))
+            .map(|change| change.time)
+            .chain(
+                overlay_composition
+                    .meter_changes
+                    .iter()
+                    .filter(|change| !base_composition.meter_changes.contains(change))
+                    .map(|change| change.time),
+            )
+            .map(|time| OverlayConflict::Tempo { time: time.into() }),
+    );
+
+    conflicts
+}
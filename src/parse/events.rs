@@ -0,0 +1,81 @@
+//! Push-based parsing, streaming commands to callbacks as they are read instead of building a
+//! [`RawOgkr`](super::raw::RawOgkr), for tools that only need a few kinds of commands.
+//!
+//! Commands are checked one by one, the checks needing the whole chart such as section and lane
+//! references are skipped.
+
+use std::ops::{ControlFlow, Range};
+
+use crate::lex::{
+    grammar::{self, Section},
+    stream_tokens,
+    token::Token,
+    LexError,
+};
+
+use super::{ParseError, ParseOptions, Result};
+
+type Callback<'f> = Box<dyn FnMut(&Token, Range<usize>) + 'f>;
+
+/// Callbacks for the commands of some sections, see [`Subscriptions::parse`].
+#[derive(Default)]
+pub struct Subscriptions<'f> {
+    sections: Vec<(Option<Section>, Callback<'f>)>,
+}
+
+impl<'f> Subscriptions<'f> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Calls `callback` with every command of `section` and the source range of its line.
+    pub fn on(mut self, section: Section, callback: impl FnMut(&Token, Range<usize>) + 'f) -> Self {
+        self.sections.push((Some(section), Box::new(callback)));
+        self
+    }
+
+    /// Calls `callback` with every extension and custom command, which do not belong to a section.
+    pub fn on_extensions(mut self, callback: impl FnMut(&Token, Range<usize>) + 'f) -> Self {
+        self.sections.push((None, Box::new(callback)));
+        self
+    }
+
+    /// Reads `source`, calling the subscribed callbacks in chart order. Section names are not
+    /// reported.
+    ///
+    /// Gives the errors of lines skipped in lenient mode. The size and token limits of `options`
+    /// apply, nothing else is kept in memory.
+    pub fn parse(mut self, source: &str, options: &ParseOptions) -> Result<Vec<LexError>> {
+        options.check_file_size(source)?;
+
+        let max_tokens = options.max_tokens.unwrap_or(usize::MAX);
+        let mut count = 0;
+        let (errors, flow) = stream_tokens(source, &options.lex, options.format, |token, span| {
+            if count >= max_tokens {
+                return ControlFlow::Break(());
+            }
+            count += 1;
+
+            let section = match &token {
+                Token::SectionName => return ControlFlow::Continue(()),
+                Token::Extension(_) | Token::Custom(_) => None,
+                token => token.kind().and_then(|kind| grammar::command(kind).section),
+            };
+            for (subscribed, callback) in &mut self.sections {
+                if *subscribed == section {
+                    callback(&token, span.clone());
+                }
+            }
+            ControlFlow::Continue(())
+        })
+        .map_err(ParseError::LexError)?;
+
+        if flow.is_break() {
+            return Err(ParseError::LimitExceeded {
+                what: "tokens",
+                max: max_tokens,
+            });
+        }
+        Ok(errors)
+    }
+}
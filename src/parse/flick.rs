@@ -0,0 +1,97 @@
+use super::{
+    analysis::{FlickNote, Notes, Resolution, TimingPoint, Track, XPosition},
+    store::TimeStore,
+    FlickDirection,
+};
+
+/// Flicks that have to be hit at the same time.
+#[derive(Clone, Copy, Debug)]
+pub struct FlickGroup<'a> {
+    pub time: TimingPoint,
+    pub flicks: &'a [FlickNote],
+}
+
+impl FlickGroup<'_> {
+    /// Returns `true` if the group requires swiping in both directions.
+    pub fn is_mixed(&self) -> bool {
+        self.flicks
+            .windows(2)
+            .any(|pair| pair[0].direction != pair[1].direction)
+    }
+}
+
+/// Flicks at the same time and position requiring opposite directions, which cannot be hit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FlickConflict {
+    pub time: TimingPoint,
+    pub x: XPosition,
+}
+
+/// Input required to hit a flick.
+#[derive(Clone, Copy, Debug)]
+pub struct FlickInput<'a> {
+    pub flick: &'a FlickNote,
+    /// Horizontal position of the flick, in track units.
+    pub x: f32,
+    /// Horizontal swipe needed to hit the flick, in track units, negative to the left.
+    pub swipe: f32,
+}
+
+impl FlickNote {
+    /// Horizontal unit vector of the swipe direction.
+    pub fn swipe_direction(&self) -> f32 {
+        match self.direction {
+            FlickDirection::Left => -1.0,
+            FlickDirection::Right => 1.0,
+        }
+    }
+}
+
+impl Notes {
+    /// Horizontal distance of a flick swipe, in track units.
+    pub const FLICK_SWIPE_DISTANCE: f32 = 2.0 * Track::LANE_HALF_WIDTH;
+
+    /// Returns flicks grouped by the time they have to be hit at, sorted by time.
+    pub fn flick_groups(&self) -> impl Iterator<Item = FlickGroup<'_>> {
+        self.flicks
+            .groups()
+            .map(|(time, flicks)| FlickGroup { time, flicks })
+    }
+
+    /// Returns positions with simultaneous flicks in opposite directions, sorted by time.
+    pub fn flick_conflicts(&self) -> Vec<FlickConflict> {
+        self.flick_groups()
+            .filter(|group| group.is_mixed())
+            .flat_map(|group| {
+                let mut conflicts = group
+                    .flicks
+                    .iter()
+                    .filter(|flick| {
+                        group.flicks.iter().any(|other| {
+                            other.position.x == flick.position.x
+                                && other.direction != flick.direction
+                        })
+                    })
+                    .map(|flick| FlickConflict {
+                        time: group.time,
+                        x: flick.position.x,
+                    })
+                    .collect::<Vec<_>>();
+                conflicts.sort_by_key(|conflict| (conflict.x.position, conflict.x.offset));
+                conflicts.dedup();
+                conflicts
+            })
+            .collect()
+    }
+
+    /// Returns the inputs needed to hit every flick, sorted by time.
+    pub fn flick_inputs(&self, resolution: Resolution) -> Vec<FlickInput<'_>> {
+        self.all_flicks()
+            .map(|flick| FlickInput {
+                flick,
+                x: resolution.x_of(flick.position.x),
+                swipe: flick.swipe_direction() * Self::FLICK_SWIPE_DISTANCE,
+            })
+            .collect()
+    }
+}
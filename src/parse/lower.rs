@@ -0,0 +1,380 @@
+use std::collections::BTreeMap;
+
+use thiserror::Error;
+
+use crate::lex::command;
+
+use super::{
+    analysis::{LaneType, Ogkr, PaletteIdRef},
+    raw::{
+        BeamSection, ColorfulLaneSection, LaneSection, NoteRef, ObliqueBeamSection, RawComposition,
+        RawNotes, RawOgkr, RawTrack, WallSection,
+    },
+    Extensions, ParseError, ParseOptions, Warnings,
+};
+
+/// Content lost or changed by lowering a chart and analyzing it again, see
+/// [`Ogkr::check_round_trip`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Error)]
+pub enum RoundTripError {
+    #[error("lowered chart failed to analyze: {0}")]
+    Analysis(ParseError),
+    #[error(
+        "lowered chart differs at canonical line {line}: expected {expected:?}, found {found:?}"
+    )]
+    Mismatch {
+        /// Line number inside [`Ogkr::to_canonical_string`].
+        line: usize,
+        /// Line of the original chart, empty if it has fewer lines.
+        expected: String,
+        /// Line of the analyzed lowered chart, empty if it has fewer lines.
+        found: String,
+    },
+}
+
+/// First line at which two canonical strings differ, with the differing lines.
+fn first_difference(expected: &str, found: &str) -> Option<(usize, String, String)> {
+    let (mut expected, mut found) = (expected.lines(), found.lines());
+    let mut line = 0;
+    loop {
+        line += 1;
+        match (expected.next(), found.next()) {
+            (None, None) => return None,
+            (a, b) if a != b => {
+                return Some((
+                    line,
+                    a.unwrap_or_default().to_string(),
+                    b.unwrap_or_default().to_string(),
+                ))
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Ogkr {
+    /// Whether both charts analyze to the same content, regardless of how their source was
+    /// written and of their warnings.
+    pub fn is_equivalent(&self, other: &Ogkr) -> bool {
+        self.to_canonical_string() == other.to_canonical_string()
+    }
+
+    /// Lowers the chart and analyzes it again with `options`, checking the analyzed model holds
+    /// nothing the commands cannot express. Useful after editing the analyzed chart, before
+    /// writing it.
+    pub fn check_round_trip(&self, options: &ParseOptions) -> Result<(), RoundTripError> {
+        let lowered = Ogkr::from_raw_with_options(self.to_raw(), options)
+            .map_err(RoundTripError::Analysis)?;
+        match first_difference(&self.to_canonical_string(), &lowered.to_canonical_string()) {
+            Some((line, expected, found)) => Err(RoundTripError::Mismatch {
+                line,
+                expected,
+                found,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Converts the chart back into commands, so it can be edited or serialized.
+    ///
+    /// Lane disappearances and lane blocks are not part of [`Ogkr`] and are lost. Synthetic lanes
+    /// become center lanes, and bullets always specify their damage type.
+    pub fn to_raw(&self) -> RawOgkr {
+        let header = self.header.clone();
+        let composition = RawComposition {
+            bpm_first: header.bpm_definition.map_or(0, |bpm| bpm.first),
+            bpm_changes: self
+                .composition
+                .all_bpm_changes()
+                .map(|change| command::BpmChange {
+                    time: change.time.into(),
+                    bpm: change.bpm,
+                })
+                .collect(),
+            meter_first: header.meter_definition.unwrap_or_default(),
+            meter_changes: self
+                .composition
+                .meter_changes
+                .values()
+                .map(|change| command::MeterChange {
+                    time: change.time.into(),
+                    num_beats: change.num_beats,
+                    note_value: change.note_value,
+                })
+                .collect(),
+            soflans: self
+                .composition
+                .all_soflans()
+                .map(|soflan| command::Soflan {
+                    time: soflan.time.into(),
+                    duration: soflan.duration,
+                    current_speed_multiplier: soflan.speed_multiplier.to_bits(),
+                })
+                .collect(),
+        };
+
+        let palettes = self
+            .bullets
+            .palettes()
+            .iter()
+            .map(|palette| command::BulletPalette {
+                id: palette.id.0.clone(),
+                shooter: palette.shooter,
+                target_x_offset: palette.x_offset,
+                target: palette.target,
+                speed: palette.speed.to_bits(),
+                size: palette.size,
+                ty: palette.bullet_type,
+                random_position_offset: palette.random_position_offset,
+                damage_type: palette.damage_type,
+            })
+            .collect::<Vec<_>>();
+        let bullet_extensions = self
+            .bullets
+            .all_bullets()
+            .enumerate()
+            .filter(|(_, bullet)| !bullet.extensions.is_empty())
+            .map(|(i, bullet)| (i, bullet.extensions.clone()))
+            .collect::<BTreeMap<_, _>>();
+        let bullets = self
+            .bullets
+            .all_bullets()
+            .map(|bullet| command::Bullet {
+                pallete_id: self.palette_id(bullet.palette),
+                time: bullet.position.time.into(),
+                x_position: bullet.position.x.position,
+                damage_type: Some(bullet.damage_type),
+            })
+            .collect();
+
+        RawOgkr {
+            header,
+            composition,
+            bullet_pallete_list: palettes,
+            bullets,
+            click_sounds: self
+                .click_sounds
+                .iter()
+                .map(|click| command::ClickSound {
+                    time: click.time.into(),
+                })
+                .collect(),
+            enemy_wave_assignment: self.enemy_wave_assignment.clone(),
+            track: self.raw_track(),
+            notes: self.raw_notes(),
+            extensions: self.extensions.clone(),
+            bullet_extensions,
+            custom_commands: self.custom_commands.clone(),
+            warnings: Warnings::default(),
+        }
+    }
+
+    fn raw_track(&self) -> RawTrack {
+        let track = &self.track;
+        let mut raw = RawTrack::default();
+
+        let mut lanes = track.lanes_data.values().collect::<Vec<_>>();
+        lanes.sort_by_key(|lane| lane.id.0);
+        for lane in lanes {
+            let group_id = lane.id.0;
+            match lane.lane_type {
+                LaneType::WallLeft | LaneType::WallRight => {
+                    let section = WallSection {
+                        group_id,
+                        points: lane
+                            .points
+                            .iter()
+                            .map(|point| command::WallPoint {
+                                group_id,
+                                time: point.time.into(),
+                                x_position: point.x.position,
+                            })
+                            .collect(),
+                    };
+                    if lane.lane_type == LaneType::WallLeft {
+                        raw.walls_left.push(section);
+                    } else {
+                        raw.walls_right.push(section);
+                    }
+                }
+                lane_type => {
+                    let section = LaneSection {
+                        group_id,
+                        points: lane
+                            .points
+                            .iter()
+                            .map(|point| command::LanePoint {
+                                group_id,
+                                time: point.time.into(),
+                                x_position: point.x.position,
+                            })
+                            .collect(),
+                    };
+                    match lane_type {
+                        LaneType::Left => raw.lanes_left.push(section),
+                        LaneType::Right => raw.lanes_right.push(section),
+                        LaneType::Enemy => raw.enemy_lanes.push(section),
+                        _ => raw.lanes_center.push(section),
+                    }
+                }
+            }
+        }
+
+        let mut colorful_lanes = track.colorful_lanes_data.values().collect::<Vec<_>>();
+        colorful_lanes.sort_by_key(|lane| lane.id.0);
+        raw.colorful_lanes = colorful_lanes
+            .into_iter()
+            .map(|lane| {
+                let group_id = lane.id.0;
+                ColorfulLaneSection {
+                    group_id,
+                    points: std::iter::once(&lane.start)
+                        .chain(&lane.middle)
+                        .chain(std::iter::once(&lane.end))
+                        .map(|point| command::ColorfulLanePoint {
+                            group_id,
+                            time: point.position.time.into(),
+                            x_position: point.position.x.position,
+                            color: point.color.0,
+                            brightness: point.brightness,
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+
+        let mut beams = track.beams_data.values().collect::<Vec<_>>();
+        beams.sort_by_key(|beam| beam.id.0);
+        raw.beams = beams
+            .into_iter()
+            .map(|beam| {
+                let record_id = beam.id.0;
+                BeamSection {
+                    record_id,
+                    points: std::iter::once(&beam.start)
+                        .chain(&beam.middle)
+                        .chain(std::iter::once(&beam.end))
+                        .map(|point| command::BeamPoint {
+                            record_id,
+                            time: point.position.time.into(),
+                            x_position: point.position.x.position,
+                            width: point.width,
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+
+        let mut oblique_beams = track.oblique_beams_data.values().collect::<Vec<_>>();
+        oblique_beams.sort_by_key(|beam| beam.id.0);
+        raw.oblique_beams = oblique_beams
+            .into_iter()
+            .map(|beam| {
+                let record_id = beam.id.0;
+                ObliqueBeamSection {
+                    record_id,
+                    points: std::iter::once(&beam.start)
+                        .chain(&beam.middle)
+                        .chain(std::iter::once(&beam.end))
+                        .map(|point| command::ObliqueBeamPoint {
+                            record_id,
+                            time: point.position.time.into(),
+                            x_position: point.position.x.position,
+                            width: point.width,
+                            shoot_position_x_offset: point.shoot_x_offset,
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+
+        raw
+    }
+
+    /// ID written for an interned palette, palettes are only interned when parsing the chart.
+    fn palette_id(&self, palette: PaletteIdRef) -> String {
+        self.bullets
+            .palette(palette)
+            .map(|palette| palette.id.0.clone())
+            .unwrap_or_default()
+    }
+
+    fn raw_notes(&self) -> RawNotes {
+        let notes = &self.notes;
+        let mut raw = RawNotes::default();
+        let mut extensions = BTreeMap::new();
+        let mut extend = |note: NoteRef, note_extensions: &Extensions| {
+            if !note_extensions.is_empty() {
+                extensions.insert(note, note_extensions.clone());
+            }
+        };
+
+        for tap in notes.all_taps() {
+            let command = command::Tap {
+                lane_group_id: tap.lane_id.0,
+                time: tap.position.time.into(),
+                x_position: tap.position.x.position,
+                x_offset: tap.position.x.offset,
+            };
+            if tap.is_critical {
+                extend(
+                    NoteRef::CriticalTap(raw.critical_taps.len()),
+                    &tap.extensions,
+                );
+                raw.critical_taps.push(command);
+            } else {
+                extend(NoteRef::Tap(raw.taps.len()), &tap.extensions);
+                raw.taps.push(command);
+            }
+        }
+        for hold in notes.all_holds() {
+            let command = command::Hold {
+                lane_group_id: hold.lane_id.0,
+                start_time: hold.start.time.into(),
+                start_x_position: hold.start.x.position,
+                start_x_offset: hold.start.x.offset,
+                end_time: hold.end.time.into(),
+                end_x_position: hold.end.x.position,
+                end_x_offset: hold.end.x.offset,
+            };
+            if hold.is_critical {
+                extend(
+                    NoteRef::CriticalHold(raw.critical_holds.len()),
+                    &hold.extensions,
+                );
+                raw.critical_holds.push(command);
+            } else {
+                extend(NoteRef::Hold(raw.holds.len()), &hold.extensions);
+                raw.holds.push(command);
+            }
+        }
+        for flick in notes.all_flicks() {
+            let command = command::Flick {
+                time: flick.position.time.into(),
+                x_position: flick.position.x.position,
+                direction: flick.direction,
+            };
+            if flick.is_critical {
+                extend(
+                    NoteRef::CriticalFlick(raw.critical_flicks.len()),
+                    &flick.extensions,
+                );
+                raw.critical_flicks.push(command);
+            } else {
+                extend(NoteRef::Flick(raw.flicks.len()), &flick.extensions);
+                raw.flicks.push(command);
+            }
+        }
+        for bell in notes.all_bells() {
+            extend(NoteRef::Bell(raw.bells.len()), &bell.extensions);
+            raw.bells.push(command::Bell {
+                time: bell.position.time.into(),
+                x_position: bell.position.x.position,
+                bullet_palette_id: bell.bullet_palette.map(|palette| self.palette_id(palette)),
+            });
+        }
+
+        raw.extensions = extensions;
+        raw
+    }
+}
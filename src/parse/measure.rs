@@ -0,0 +1,137 @@
+use std::{collections::BTreeMap, ops::Range};
+
+use super::analysis::{
+    BellNote, BpmChange, Bullet, FlickNote, HoldNote, Lane, LaneType, MeterChange, Ogkr, Soflan,
+    TapNote, TimingPoint, TrackPosition,
+};
+use super::store::{TimeMap, TimeStore};
+
+/// Part of a lane that is visible within a measure.
+#[derive(Clone, Copy, Debug)]
+pub struct LaneSegment<'a> {
+    pub lane: &'a Lane,
+
+    /// Lane points covering the measure, including the closest points just outside of it so the
+    /// segment can be drawn up to the measure boundaries.
+    pub points: &'a [TrackPosition],
+}
+
+/// Contents of a single measure, as used when rendering a chart one measure at a time.
+#[derive(Clone, Debug)]
+pub struct MeasureView<'a> {
+    pub measure: u32,
+
+    /// BPM in effect at the start of the measure.
+    pub bpm: Option<&'a BpmChange>,
+    /// Meter in effect at the start of the measure.
+    pub meter: Option<&'a MeterChange>,
+
+    /// BPM changes within the measure, sorted by time.
+    pub bpm_changes: Vec<&'a BpmChange>,
+    /// Meter changes within the measure, sorted by time.
+    pub meter_changes: Vec<&'a MeterChange>,
+    /// Soflans starting within the measure, sorted by time.
+    pub soflans: Vec<&'a Soflan>,
+
+    pub taps: Vec<&'a TapNote>,
+    /// Hold notes starting within the measure.
+    pub holds: Vec<&'a HoldNote>,
+    pub bells: Vec<&'a BellNote>,
+    pub flicks: Vec<&'a FlickNote>,
+    pub bullets: Vec<&'a Bullet>,
+
+    /// Lanes and walls passing through the measure, sorted by start time and lane ID.
+    pub lane_segments: Vec<LaneSegment<'a>>,
+}
+
+impl<'a> MeasureView<'a> {
+    pub(crate) fn new(ogkr: &'a Ogkr, measure: u32) -> Self {
+        let start = TimingPoint::new(measure, 0);
+        let range = start..TimingPoint::new(measure.saturating_add(1), 0);
+        let composition = &ogkr.composition;
+
+        let bpm = composition
+            .bpm_changes
+            .range(..=start)
+            .next_back()
+            .and_then(|(_, changes)| changes.last());
+        let meter = composition
+            .meter_changes
+            .range(..=start)
+            .next_back()
+            .map(|(_, change)| change);
+
+        let mut lane_segments = ogkr
+            .track
+            .lanes_data
+            .values()
+            .filter(|lane| lane.lane_type != LaneType::Synthetic)
+            .filter_map(|lane| lane_segment(lane, &range))
+            .collect::<Vec<_>>();
+        lane_segments.sort_by_key(|segment| (segment.points[0].time, segment.lane.id.0));
+
+        Self {
+            measure,
+            bpm,
+            meter,
+            bpm_changes: in_range(&composition.bpm_changes, &range),
+            meter_changes: composition
+                .meter_changes
+                .range(range.clone())
+                .map(|(_, change)| change)
+                .collect(),
+            soflans: in_range(&composition.soflans, &range),
+            taps: stored_in_range(&ogkr.notes.taps, &range),
+            holds: stored_in_range(&ogkr.notes.holds, &range),
+            bells: stored_in_range(&ogkr.notes.bells, &range),
+            flicks: stored_in_range(&ogkr.notes.flicks, &range),
+            bullets: stored_in_range(&ogkr.bullets.bullets, &range),
+            lane_segments,
+        }
+    }
+
+    /// Returns `true` if the measure does not contain any notes or bullets.
+    pub fn is_empty(&self) -> bool {
+        self.taps.is_empty()
+            && self.holds.is_empty()
+            && self.bells.is_empty()
+            && self.flicks.is_empty()
+            && self.bullets.is_empty()
+    }
+}
+
+fn in_range<'a, T, B: AsRef<[T]>>(
+    map: &'a BTreeMap<TimingPoint, B>,
+    range: &Range<TimingPoint>,
+) -> Vec<&'a T> {
+    map.range(range.clone())
+        .flat_map(|(_, v)| v.as_ref())
+        .collect()
+}
+
+fn stored_in_range<'a, T>(store: &'a TimeMap<T>, range: &Range<TimingPoint>) -> Vec<&'a T> {
+    store
+        .groups_in(range.clone())
+        .flat_map(|(_, items)| items)
+        .collect()
+}
+
+fn lane_segment<'a>(lane: &'a Lane, range: &Range<TimingPoint>) -> Option<LaneSegment<'a>> {
+    let (first, last) = (lane.points.first()?, lane.points.last()?);
+    if first.time >= range.end || last.time < range.start {
+        return None;
+    }
+
+    // Points are sorted by time, extend by one point on each side to cover the boundaries.
+    let begin = lane
+        .points
+        .partition_point(|point| point.time <= range.start)
+        .saturating_sub(1);
+    let end =
+        (lane.points.partition_point(|point| point.time < range.end) + 1).min(lane.points.len());
+
+    Some(LaneSegment {
+        lane,
+        points: &lane.points[begin..end],
+    })
+}
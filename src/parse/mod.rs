@@ -1,11 +1,33 @@
 pub mod analysis;
+pub mod combine;
+pub mod events;
+pub mod flick;
+mod lower;
+pub mod measure;
+#[cfg(feature = "rayon")]
+mod par;
+pub mod query;
 pub mod raw;
+mod repair;
+pub mod shared;
+mod simplify;
+pub mod spawn;
+pub mod store;
+pub mod timing;
+
+use std::collections::{BTreeMap, HashMap};
 
 use thiserror::Error;
 
-use crate::lex::{
-    command::*,
-    token::{Token, TokenStream},
+use analysis::TimingPoint;
+
+use crate::{
+    code::ErrorCode,
+    lex::{
+        command::*,
+        token::{Token, TokenKind, TokenStream},
+        LexError, LexOptions,
+    },
 };
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Error)]
@@ -16,13 +38,351 @@ pub enum ParseError {
     SemanticError(String),
     #[error("semantic error, expected more commands: {0}")]
     SemanticErrorExpectedCommand(String),
+    #[error("invalid time range: {0}")]
+    InvalidTimeRange(String),
+    #[error("limit exceeded: {what} is over the maximum of {max}")]
+    LimitExceeded {
+        /// Name of the exceeded limit.
+        what: &'static str,
+        /// Configured maximum value.
+        max: usize,
+    },
+    #[error("lexing failed: {0}")]
+    LexError(LexError),
+}
+
+impl ParseError {
+    /// Stable identifier of the kind of error, lexing errors keep their own code.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::SyntaxError(_) => ErrorCode::Syntax,
+            Self::SemanticError(_) => ErrorCode::Semantic,
+            Self::SemanticErrorExpectedCommand(_) => ErrorCode::ExpectedCommand,
+            Self::InvalidTimeRange(_) => ErrorCode::InvalidTimeRange,
+            Self::LimitExceeded { .. } => ErrorCode::LimitExceeded,
+            Self::LexError(error) => error.code(),
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, ParseError>;
 
+/// Data the parser dropped or ignored without failing.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Warning {
+    /// A lane or wall reuses the ID of another one, the earlier one is dropped.
+    DuplicateLaneId(u32),
+    /// A colorful lane reuses the ID of another one, the earlier one is dropped.
+    DuplicateColorfulLaneId(u32),
+    /// A beam reuses the ID of another one, the earlier one is dropped.
+    DuplicateBeamId(u32),
+    /// An oblique beam reuses the ID of another one, the earlier one is dropped.
+    DuplicateObliqueBeamId(u32),
+    /// A note references a lane that does not exist and was dropped.
+    NoteDroppedMissingLane { lane_id: u32, time: TimingPoint },
+    /// Notes reference a lane that does not exist, a synthetic lane was created for them.
+    SyntheticLaneCreated { lane_id: u32 },
+    /// A bullet, or the bullet shot by a bell, references a palette that does not exist and was
+    /// dropped.
+    BulletDroppedMissingPalette {
+        palette_id: String,
+        time: TimingPoint,
+    },
+    /// Bullets or bells reference a palette that does not exist, a placeholder palette was
+    /// created.
+    PlaceholderPaletteCreated { palette_id: String },
+    /// A line failed to tokenize in lenient mode and was skipped.
+    SkippedInvalidLine(LexError),
+    /// A header command is repeated with a different value, the last one is kept.
+    ConflictingHeaderCommand(TokenKind),
+    /// Points of a section, identified by its start command, were not in time order.
+    SectionPointsSorted { section: TokenKind, id: u32 },
+    /// An offset past the end of its measure was clamped, see [`RepairOptions::clamp_offsets`].
+    OffsetClamped { time: TimingPoint },
+    /// A wall missing its end point was ended at `time`, see [`RepairOptions::close_walls`].
+    WallEndGenerated {
+        section: TokenKind,
+        id: u32,
+        time: TimingPoint,
+    },
+}
+
+impl Warning {
+    /// Stable identifier of the kind of warning, e.g. for suppression lists.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::DuplicateLaneId(_) => ErrorCode::DuplicateLaneId,
+            Self::DuplicateColorfulLaneId(_) => ErrorCode::DuplicateColorfulLaneId,
+            Self::DuplicateBeamId(_) => ErrorCode::DuplicateBeamId,
+            Self::DuplicateObliqueBeamId(_) => ErrorCode::DuplicateObliqueBeamId,
+            Self::NoteDroppedMissingLane { .. } => ErrorCode::NoteDroppedMissingLane,
+            Self::SyntheticLaneCreated { .. } => ErrorCode::SyntheticLaneCreated,
+            Self::BulletDroppedMissingPalette { .. } => ErrorCode::BulletDroppedMissingPalette,
+            Self::PlaceholderPaletteCreated { .. } => ErrorCode::PlaceholderPaletteCreated,
+            Self::SkippedInvalidLine(_) => ErrorCode::SkippedInvalidLine,
+            Self::ConflictingHeaderCommand(_) => ErrorCode::ConflictingHeaderCommand,
+            Self::SectionPointsSorted { .. } => ErrorCode::SectionPointsSorted,
+            Self::OffsetClamped { .. } => ErrorCode::OffsetClamped,
+            Self::WallEndGenerated { .. } => ErrorCode::WallEndGenerated,
+        }
+    }
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DuplicateLaneId(id) => write!(f, "duplicate lane ID {id}, previous lane dropped"),
+            Self::DuplicateColorfulLaneId(id) => {
+                write!(f, "duplicate colorful lane ID {id}, previous lane dropped")
+            }
+            Self::DuplicateBeamId(id) => write!(f, "duplicate beam ID {id}, previous beam dropped"),
+            Self::DuplicateObliqueBeamId(id) => {
+                write!(f, "duplicate oblique beam ID {id}, previous beam dropped")
+            }
+            Self::NoteDroppedMissingLane { lane_id, time } => write!(
+                f,
+                "note at measure {} offset {} dropped, lane ID {lane_id} does not exist",
+                time.measure, time.beat_offset
+            ),
+            Self::SyntheticLaneCreated { lane_id } => {
+                write!(
+                    f,
+                    "lane ID {lane_id} does not exist, created synthetic lane"
+                )
+            }
+            Self::BulletDroppedMissingPalette { palette_id, time } => write!(
+                f,
+                "bullet at measure {} offset {} dropped, palette {palette_id} does not exist",
+                time.measure, time.beat_offset
+            ),
+            Self::PlaceholderPaletteCreated { palette_id } => write!(
+                f,
+                "palette {palette_id} does not exist, created placeholder palette"
+            ),
+            Self::SkippedInvalidLine(error) => write!(f, "skipped invalid line, {error}"),
+            Self::ConflictingHeaderCommand(kind) => {
+                write!(f, "conflicting {} commands, last one kept", kind.mnemonic())
+            }
+            Self::SectionPointsSorted { section, id } => write!(
+                f,
+                "points of {} section {id} were out of order, sorted",
+                section.mnemonic()
+            ),
+            Self::OffsetClamped { time } => write!(
+                f,
+                "offset {} of measure {} is past the end of the measure, clamped",
+                time.beat_offset, time.measure
+            ),
+            Self::WallEndGenerated { section, id, time } => write!(
+                f,
+                "{} section {id} has no end, ended at measure {} offset {}",
+                section.mnemonic(),
+                time.measure,
+                time.beat_offset
+            ),
+        }
+    }
+}
+
+/// Warnings collected while parsing, in the order they were found.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub struct Warnings {
+    warnings: Vec<Warning>,
+}
+
+impl Warnings {
+    pub(crate) fn push(&mut self, warning: Warning) {
+        crate::diagnostics::warning!("{}", warning);
+        self.warnings.push(warning);
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Warning> {
+        self.warnings.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.warnings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.warnings.is_empty()
+    }
+
+    /// Removes the warnings with any of the given codes.
+    pub fn suppress(&mut self, codes: &[ErrorCode]) {
+        self.warnings
+            .retain(|warning| !codes.contains(&warning.code()));
+    }
+
+    /// Counts the unknown commands skipped in lenient mode by mnemonic, showing which commands
+    /// of newer charts are not supported yet.
+    pub fn unknown_commands(&self) -> HashMap<String, usize> {
+        let mut census = HashMap::new();
+        for warning in &self.warnings {
+            if let Warning::SkippedInvalidLine(LexError::UnknownCommand { token, .. }) = warning {
+                *census.entry(token.clone()).or_default() += 1;
+            }
+        }
+        census
+    }
+}
+
+impl IntoIterator for Warnings {
+    type Item = Warning;
+    type IntoIter = std::vec::IntoIter<Warning>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.warnings.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Warnings {
+    type Item = &'a Warning;
+    type IntoIter = std::slice::Iter<'a, Warning>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.warnings.iter()
+    }
+}
+
+/// Options for parsing, all limits are unbounded by default.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub struct ParseOptions {
+    /// Maximum chart source size in bytes.
+    pub max_file_size: Option<usize>,
+    /// Maximum number of command tokens.
+    pub max_tokens: Option<usize>,
+    /// Maximum number of points in a single wall, lane or beam section.
+    pub max_points_per_section: Option<usize>,
+    /// What to do with tap and hold notes referencing a lane that does not exist.
+    pub missing_lane: MissingLanePolicy,
+    /// What to do with bullets referencing a palette that does not exist.
+    pub missing_palette: MissingPalettePolicy,
+    /// Options for tokenizing the chart source.
+    pub lex: LexOptions,
+    /// What to do with header commands repeated with a different value.
+    pub duplicate_header: DuplicateHeaderPolicy,
+    /// How hold notes store the lane points they go through.
+    pub hold_points: HoldPointsPolicy,
+    /// Fixes applied to corrupted charts, none by default.
+    pub repair: RepairOptions,
+    /// Revision of the chart format, by default the one of the `VERSION` command.
+    pub format: ChartFormat,
+}
+
+/// Fixes for common chart corruptions, each reported with a [`Warning`] when applied.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct RepairOptions {
+    /// Clamp offsets past the end of their measure to its last tick.
+    pub clamp_offsets: bool,
+    /// End walls missing their end point with a point at the same position, at the end of the
+    /// track or one measure after their last point.
+    pub close_walls: bool,
+}
+
+impl RepairOptions {
+    /// Every fix.
+    pub fn all() -> Self {
+        Self {
+            clamp_offsets: true,
+            close_walls: true,
+        }
+    }
+
+    pub fn clamp_offsets(mut self) -> Self {
+        self.clamp_offsets = true;
+        self
+    }
+
+    pub fn close_walls(mut self) -> Self {
+        self.close_walls = true;
+        self
+    }
+}
+
+/// Storage of the lane points hold notes go through.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum HoldPointsPolicy {
+    /// Copy them into [`HoldNote::points`](analysis::HoldNote::points).
+    #[default]
+    Copy,
+    /// Leave [`HoldNote::points`](analysis::HoldNote::points) empty, saving memory on charts with
+    /// many holds. [`HoldNote::path`](analysis::HoldNote::path) borrows them from the lane.
+    Borrow,
+}
+
+/// Handling of notes referencing a lane that does not exist.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum MissingLanePolicy {
+    /// Fail parsing.
+    #[default]
+    Error,
+    /// Drop the note with a warning.
+    Drop,
+    /// Keep the note, attaching it to a synthetic lane going through the notes referencing it,
+    /// see [`LaneType::Synthetic`](analysis::LaneType::Synthetic).
+    SyntheticLane,
+}
+
+/// Handling of bullets and bells referencing a bullet palette that does not exist.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum MissingPalettePolicy {
+    /// Fail parsing.
+    #[default]
+    Error,
+    /// Drop the bullet with a warning, bells are kept without shooting.
+    Drop,
+    /// Keep the bullet, using a placeholder palette with default behaviour.
+    Placeholder,
+}
+
+/// Handling of header commands appearing several times with different values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum DuplicateHeaderPolicy {
+    /// Keep the value of the last command with a warning.
+    #[default]
+    KeepLast,
+    /// Fail parsing.
+    Error,
+}
+
+impl ParseOptions {
+    /// Limits suitable for charts coming from untrusted sources, generously above what real
+    /// charts use.
+    pub fn untrusted() -> Self {
+        Self {
+            max_file_size: Some(16 * 1024 * 1024),
+            max_tokens: Some(1_000_000),
+            max_points_per_section: Some(65_536),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn check_file_size(&self, source: &str) -> Result<()> {
+        match self.max_file_size {
+            Some(max) if source.len() > max => Err(ParseError::LimitExceeded {
+                what: "file size",
+                max,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Data attached through `X_`-prefixed commands, by key without the prefix. Sorted so charts are
+/// written back deterministically.
+pub type Extensions = BTreeMap<String, String>;
+
+/// Extension key of the sample played when hitting a note in keysounded charts, written as
+/// `X_SOUND`. See [`TapNote::sound`](analysis::TapNote::sound).
+pub const SOUND_EXTENSION: &str = "SOUND";
+
 /// XXX TODO: Have a proper parsed version of this where the u32 bits are properly converted to
 /// float.
-#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+#[derive(Clone, Debug, PartialEq, Default)]
 pub struct Header {
     pub version: Option<Version>,
     pub creator: Option<Creator>,
@@ -37,16 +397,70 @@ pub struct Header {
     pub prog_judge_bpm: Option<ProgJudgeBpm>,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+impl Header {
+    /// Separates authors of charts made by several people within the creator line.
+    pub const CREATOR_SEPARATOR: &'static str = " / ";
+
+    pub fn set_creator(&mut self, name: impl Into<String>) {
+        self.creator = Some(Creator { name: name.into() });
+    }
+
+    /// Adds an author after the existing ones.
+    pub fn add_creator(&mut self, name: impl Into<String>) {
+        let name = name.into();
+        match &mut self.creator {
+            Some(creator) if !creator.name.trim().is_empty() => {
+                creator.name.push_str(Self::CREATOR_SEPARATOR);
+                creator.name.push_str(&name);
+            }
+            _ => self.set_creator(name),
+        }
+    }
+
+    /// Returns the authors listed in the creator line.
+    pub fn creators(&self) -> impl Iterator<Item = &str> {
+        self.creator
+            .iter()
+            .flat_map(|creator| creator.name.split(Self::CREATOR_SEPARATOR.trim()))
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+    }
+}
+
+/// Damage taken from bullets and beams, zero when the chart does not specify it.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
 pub struct DamageValues {
-    pub normal: u32,
-    pub hard: u32,
-    pub danger: u32,
-    pub beam: u32,
+    pub normal: f32,
+    pub hard: f32,
+    pub danger: f32,
+    pub beam: f32,
+}
+
+impl DamageValues {
+    /// Values used by the game, which every known chart specifies.
+    pub fn arcade_defaults() -> Self {
+        Self {
+            normal: 1.0,
+            hard: 2.0,
+            danger: 4.0,
+            beam: 2.0,
+        }
+    }
+
+    /// Damage of a bullet of the given type.
+    pub fn bullet(&self, damage_type: BulletDamageType) -> f32 {
+        match damage_type {
+            BulletDamageType::Normal => self.normal,
+            BulletDamageType::Hard => self.hard,
+            BulletDamageType::Danger => self.danger,
+        }
+    }
 }
 
+/// Note counts declared in the `[TOTAL]` section, side notes are the ones on walls.
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
 pub struct Totals {
+    /// Taps, holds and flicks, including side notes but not bells.
     pub notes: u32,
     pub tap: u32,
     pub hold: u32,
@@ -56,19 +470,66 @@ pub struct Totals {
     pub bell: u32,
 }
 
+impl Totals {
+    /// Returns the value declared by the total command of the given kind, `None` if it is not a
+    /// total command.
+    pub fn get(&self, kind: TokenKind) -> Option<u32> {
+        Some(match kind {
+            TokenKind::TotalNotes => self.notes,
+            TokenKind::TotalTapNotes => self.tap,
+            TokenKind::TotalHoldNotes => self.hold,
+            TokenKind::TotalSideNotes => self.side,
+            TokenKind::TotalSideHoldNotes => self.side_hold,
+            TokenKind::TotalFlickNotes => self.flick,
+            TokenKind::TotalBellNotes => self.bell,
+            _ => return None,
+        })
+    }
+
+    /// Returns the total commands whose declared value differs from `other`.
+    pub fn mismatches(&self, other: &Totals) -> Vec<TokenKind> {
+        TokenKind::ALL
+            .iter()
+            .copied()
+            .filter(|&kind| self.get(kind).is_some() && self.get(kind) != other.get(kind))
+            .collect()
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
 pub struct EnemyWaveAssignment {
-    pub wave_1: CommandTime,
-    pub wave_2: CommandTime,
-    pub boss: CommandTime,
+    /// Start of each wave, `None` if the chart does not contain the wave.
+    pub wave_1: Option<CommandTime>,
+    pub wave_2: Option<CommandTime>,
+    pub boss: Option<CommandTime>,
 }
 
 impl EnemyWaveAssignment {
+    pub fn get(&self, wave: EnemyWave) -> Option<CommandTime> {
+        match wave {
+            EnemyWave::Wave1 => self.wave_1,
+            EnemyWave::Wave2 => self.wave_2,
+            EnemyWave::Boss => self.boss,
+        }
+    }
+
+    /// Wave in progress at `time`, the present wave with the latest start at or before it, the
+    /// later wave for waves starting together. Objects before the first wave, or in charts without
+    /// waves, belong to [`EnemyWave::Wave1`].
+    pub fn wave_at(&self, time: TimingPoint) -> EnemyWave {
+        [EnemyWave::Wave1, EnemyWave::Wave2, EnemyWave::Boss]
+            .into_iter()
+            .filter_map(|wave| Some((TimingPoint::from(self.get(wave)?), wave)))
+            .filter(|(start, _)| *start <= time)
+            .max_by_key(|(start, _)| *start)
+            .map_or(EnemyWave::Wave1, |(_, wave)| wave)
+    }
+
     fn update_from_command(&mut self, command: EnemySet) {
         match command.wave {
-            EnemyWave::Wave1 => self.wave_1 = command.time,
-            EnemyWave::Wave2 => self.wave_2 = command.time,
-            EnemyWave::Boss => self.boss = command.time,
+            EnemyWave::Wave1 => self.wave_1 = Some(command.time),
+            EnemyWave::Wave2 => self.wave_2 = Some(command.time),
+            EnemyWave::Boss => self.boss = Some(command.time),
         }
     }
 }
@@ -76,12 +537,28 @@ impl EnemyWaveAssignment {
 pub(crate) struct Commands {
     /// Tokens/commands are in reverse order, simply pop from the end to consume next token.
     tokens: Vec<Token>,
+    max_points_per_section: Option<usize>,
 }
 
 impl Commands {
-    fn new_from_token_stream(token_stream: TokenStream) -> Self {
+    fn new_from_token_stream(token_stream: TokenStream, options: &ParseOptions) -> Self {
         Self {
             tokens: token_stream.into_iter().rev().collect(),
+            max_points_per_section: options.max_points_per_section,
+        }
+    }
+
+    /// Adds a point to a section, respecting the section size limit.
+    pub(crate) fn push_point<T>(&self, points: &mut Vec<T>, point: T) -> Result<()> {
+        match self.max_points_per_section {
+            Some(max) if points.len() >= max => Err(ParseError::LimitExceeded {
+                what: "points per section",
+                max,
+            }),
+            _ => {
+                points.push(point);
+                Ok(())
+            }
         }
     }
 
@@ -91,7 +568,7 @@ impl Commands {
     }
 
     pub(crate) fn err_semantic(&self, message: &str) -> ParseError {
-        log::error!(
+        crate::diagnostics::error!(
             "Semantically wrong command, next command is: {:?}",
             &self.tokens.last(),
         );
@@ -0,0 +1,37 @@
+//! Parallel iterators over parsed collections.
+
+use rayon::prelude::*;
+
+use super::{
+    analysis::{BellNote, Bullet, Bullets, FlickNote, HoldNote, Notes, TapNote},
+    store::TimeStore,
+};
+
+impl Notes {
+    /// Parallel counterpart of [`Notes::all_taps`].
+    pub fn par_all_taps(&self) -> impl ParallelIterator<Item = &TapNote> {
+        self.taps.par_items()
+    }
+
+    /// Parallel counterpart of [`Notes::all_holds`].
+    pub fn par_all_holds(&self) -> impl ParallelIterator<Item = &HoldNote> {
+        self.holds.par_items()
+    }
+
+    /// Parallel counterpart of [`Notes::all_bells`].
+    pub fn par_all_bells(&self) -> impl ParallelIterator<Item = &BellNote> {
+        self.bells.par_items()
+    }
+
+    /// Parallel counterpart of [`Notes::all_flicks`].
+    pub fn par_all_flicks(&self) -> impl ParallelIterator<Item = &FlickNote> {
+        self.flicks.par_items()
+    }
+}
+
+impl Bullets {
+    /// Parallel counterpart of [`Bullets::all_bullets`].
+    pub fn par_all_bullets(&self) -> impl ParallelIterator<Item = &Bullet> {
+        self.bullets.par_items()
+    }
+}
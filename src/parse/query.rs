@@ -0,0 +1,832 @@
+use std::{
+    iter::Peekable,
+    ops::{Range, RangeInclusive},
+    vec,
+};
+
+use super::{
+    analysis::{
+        Beam, BellNote, Bullet, BulletPaletteId, FlickNote, HoldNote, Lane, LaneType, Notes,
+        ObliqueBeam, Ogkr, Resolution, TapNote, TimingPoint, Track, TrackPosition,
+    },
+    store::{TimeMap, TimeStore},
+};
+
+/// Lane present at a queried time.
+#[derive(Clone, Copy, Debug)]
+pub struct ActiveLane<'a> {
+    pub lane: &'a Lane,
+    /// Horizontal position of the lane at the queried time, in track units.
+    pub x: f32,
+}
+
+/// How horizontal positions are interpolated between the points of lanes and holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum Interpolation {
+    /// Straight segments between points, the way the game draws lanes.
+    #[default]
+    Linear,
+    /// Smooth curve through every point, with Catmull-Rom tangents computed over time. Points
+    /// sharing the same time still jump.
+    CatmullRom,
+}
+
+/// Point of a resampled lane, see [`Lane::resample`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LaneVertex {
+    pub time: TimingPoint,
+    /// Horizontal position in track units.
+    pub x: f32,
+}
+
+impl Lane {
+    /// Horizontal position of the lane at `time`, interpolated between its points. Returns `None`
+    /// if the lane does not span `time`.
+    pub fn x_at(&self, time: TimingPoint, resolution: Resolution) -> Option<f32> {
+        interpolate_x(&self.points, time, resolution)
+    }
+
+    /// Same as [`Lane::x_at`], using the given interpolation.
+    pub fn x_at_with(
+        &self,
+        time: TimingPoint,
+        resolution: Resolution,
+        interpolation: Interpolation,
+    ) -> Option<f32> {
+        interpolate_x_with(&self.points, time, resolution, interpolation)
+    }
+
+    /// Samples the lane every `interval_ticks` ticks from its start, for renderers drawing
+    /// polylines. The points of the lane are always part of the result, so the geometry stays
+    /// the same.
+    pub fn resample(&self, interval_ticks: u32, resolution: Resolution) -> Vec<LaneVertex> {
+        resample(
+            &self.points,
+            interval_ticks,
+            resolution,
+            Interpolation::Linear,
+        )
+    }
+
+    /// Same as [`Lane::resample`], using the given interpolation.
+    pub fn resample_with(
+        &self,
+        interval_ticks: u32,
+        resolution: Resolution,
+        interpolation: Interpolation,
+    ) -> Vec<LaneVertex> {
+        resample(&self.points, interval_ticks, resolution, interpolation)
+    }
+}
+
+impl HoldNote {
+    /// Horizontal position of the hold at `time`, following the points of its lane. Returns
+    /// `None` if the hold does not span `time`.
+    pub fn x_at(
+        &self,
+        time: TimingPoint,
+        resolution: Resolution,
+        interpolation: Interpolation,
+    ) -> Option<f32> {
+        interpolate_x_with(&self.points, time, resolution, interpolation)
+    }
+
+    /// Samples the path of the hold every `interval_ticks` ticks from its start, including the
+    /// points of the hold.
+    pub fn sample_path(
+        &self,
+        interval_ticks: u32,
+        resolution: Resolution,
+        interpolation: Interpolation,
+    ) -> Vec<LaneVertex> {
+        resample(&self.points, interval_ticks, resolution, interpolation)
+    }
+}
+
+fn resample(
+    points: &[TrackPosition],
+    interval_ticks: u32,
+    resolution: Resolution,
+    interpolation: Interpolation,
+) -> Vec<LaneVertex> {
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        return vec![];
+    };
+    let (start, end) = (
+        resolution.ticks_at(first.time),
+        resolution.ticks_at(last.time),
+    );
+
+    let mut ticks = (start..=end)
+        .step_by(interval_ticks.max(1) as usize)
+        .chain(points.iter().map(|point| resolution.ticks_at(point.time)))
+        .collect::<Vec<_>>();
+    ticks.sort();
+    ticks.dedup();
+
+    ticks
+        .into_iter()
+        .filter_map(|tick| {
+            let time = resolution.time_of(tick);
+            Some(LaneVertex {
+                time,
+                x: interpolate_x_with(points, time, resolution, interpolation)?,
+            })
+        })
+        .collect()
+}
+
+/// Beam or oblique beam present at a queried time.
+#[derive(Clone, Copy, Debug)]
+pub enum BeamRef<'a> {
+    Beam(&'a Beam),
+    Oblique(&'a ObliqueBeam),
+}
+
+/// Beam segment present at a queried time, with values interpolated between its points.
+#[derive(Clone, Copy, Debug)]
+pub struct ActiveBeam<'a> {
+    pub beam: BeamRef<'a>,
+    /// Horizontal center of the beam where it hits the track, in track units.
+    pub x: f32,
+    pub width: f32,
+    /// Horizontal offset of the beam origin from `x`, always 0 for non-oblique beams.
+    pub shoot_x_offset: f32,
+}
+
+/// Positions of the enemy over the chart, made up of all enemy lanes.
+#[derive(Clone, Debug)]
+pub struct EnemyTrack<'a> {
+    /// Sorted by start time.
+    lanes: Vec<&'a Lane>,
+    resolution: Resolution,
+}
+
+impl<'a> EnemyTrack<'a> {
+    /// Enemy lanes sorted by start time.
+    pub fn lanes(&self) -> &[&'a Lane] {
+        &self.lanes
+    }
+
+    /// Horizontal position of the enemy at `time`, in track units. When enemy lanes overlap the
+    /// one starting last is used. Returns `None` if no enemy lane spans `time`.
+    pub fn position_at(&self, time: TimingPoint) -> Option<f32> {
+        self.lanes
+            .iter()
+            .rev()
+            .find_map(|lane| lane.x_at(time, self.resolution))
+    }
+}
+
+impl Track {
+    /// Distance from a lane's center, in track units, at which a position is still on that lane.
+    pub const LANE_HALF_WIDTH: f32 = 4.0;
+
+    /// Returns all lanes and walls whose points span `time`, sorted by their position. Synthetic
+    /// lanes are not part of the track and never returned.
+    pub fn lanes_active_at(&self, time: TimingPoint) -> Vec<ActiveLane<'_>> {
+        let mut lanes = self
+            .lanes_data
+            .values()
+            .filter(|lane| lane.lane_type != LaneType::Synthetic)
+            .filter_map(|lane| {
+                lane.x_at(time, self.resolution)
+                    .map(|x| ActiveLane { lane, x })
+            })
+            .collect::<Vec<_>>();
+        lanes.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.lane.id.0.cmp(&b.lane.id.0)));
+        lanes
+    }
+
+    /// Returns the playable lane closest to `x` at `time`, if `x` is within
+    /// [`Track::LANE_HALF_WIDTH`] of it. Walls and enemy lanes are never returned.
+    pub fn lane_at_position(&self, time: TimingPoint, x: f32) -> Option<ActiveLane<'_>> {
+        self.lanes_active_at(time)
+            .into_iter()
+            .filter(|active| {
+                !matches!(
+                    active.lane.lane_type,
+                    LaneType::WallLeft | LaneType::WallRight | LaneType::Enemy
+                )
+            })
+            .map(|active| ((active.x - x).abs(), active))
+            .filter(|(distance, _)| *distance <= Self::LANE_HALF_WIDTH)
+            .min_by(|(a, _), (b, _)| a.total_cmp(b))
+            .map(|(_, active)| active)
+    }
+
+    /// Returns the enemy position timeline, see [`EnemyTrack`].
+    pub fn enemy_track(&self) -> EnemyTrack<'_> {
+        let lanes = self
+            .enemy_lanes
+            .values()
+            .flatten()
+            .filter_map(|id| self.get_lane(*id))
+            .collect();
+
+        EnemyTrack {
+            lanes,
+            resolution: self.resolution,
+        }
+    }
+
+    /// Returns all beams and oblique beams whose points span `time`, sorted by their position.
+    pub fn beams_active_at(&self, time: TimingPoint) -> Vec<ActiveBeam<'_>> {
+        let resolution = self.resolution;
+        let beams = self.beams_data.values().filter_map(|beam| {
+            let points = std::iter::once(&beam.start)
+                .chain(&beam.middle)
+                .chain(std::iter::once(&beam.end))
+                .collect::<Vec<_>>();
+            let [x, width] = interpolate(&points, time, resolution, |point| {
+                (
+                    point.position.time,
+                    [resolution.x_of(point.position.x), point.width as f32],
+                )
+            })?;
+
+            Some(ActiveBeam {
+                beam: BeamRef::Beam(beam),
+                x,
+                width,
+                shoot_x_offset: 0.0,
+            })
+        });
+        let oblique_beams = self.oblique_beams_data.values().filter_map(|beam| {
+            let points = std::iter::once(&beam.start)
+                .chain(&beam.middle)
+                .chain(std::iter::once(&beam.end))
+                .collect::<Vec<_>>();
+            let [x, width, shoot_x_offset] = interpolate(&points, time, resolution, |point| {
+                (
+                    point.position.time,
+                    [
+                        resolution.x_of(point.position.x),
+                        point.width as f32,
+                        point.shoot_x_offset as f32,
+                    ],
+                )
+            })?;
+
+            Some(ActiveBeam {
+                beam: BeamRef::Oblique(beam),
+                x,
+                width,
+                shoot_x_offset,
+            })
+        });
+
+        let mut active = beams.chain(oblique_beams).collect::<Vec<_>>();
+        active.sort_by(|a, b| a.x.total_cmp(&b.x));
+        active
+    }
+}
+
+/// Note of any kind, see [`Notes::all`].
+#[derive(Clone, Copy, Debug)]
+pub enum NoteRef<'a> {
+    Tap(&'a TapNote),
+    Hold(&'a HoldNote),
+    Bell(&'a BellNote),
+    Flick(&'a FlickNote),
+}
+
+impl NoteRef<'_> {
+    /// Position of the note, the start for holds.
+    pub fn position(&self) -> TrackPosition {
+        match self {
+            Self::Tap(tap) => tap.position,
+            Self::Hold(hold) => hold.start,
+            Self::Bell(bell) => bell.position,
+            Self::Flick(flick) => flick.position,
+        }
+    }
+
+    pub fn time(&self) -> TimingPoint {
+        self.position().time
+    }
+
+    /// Returns `true` for critical taps, holds and flicks. Bells are never critical.
+    pub fn is_critical(&self) -> bool {
+        match self {
+            Self::Tap(tap) => tap.is_critical,
+            Self::Hold(hold) => hold.is_critical,
+            Self::Bell(_) => false,
+            Self::Flick(flick) => flick.is_critical,
+        }
+    }
+}
+
+/// Owned note of any kind, see [`Notes::into_iter`].
+#[derive(Clone, Debug)]
+pub enum Note {
+    Tap(TapNote),
+    Hold(HoldNote),
+    Bell(BellNote),
+    Flick(FlickNote),
+}
+
+impl Note {
+    pub fn as_ref(&self) -> NoteRef<'_> {
+        match self {
+            Self::Tap(tap) => NoteRef::Tap(tap),
+            Self::Hold(hold) => NoteRef::Hold(hold),
+            Self::Bell(bell) => NoteRef::Bell(bell),
+            Self::Flick(flick) => NoteRef::Flick(flick),
+        }
+    }
+
+    /// Position of the note, the start for holds.
+    pub fn position(&self) -> TrackPosition {
+        self.as_ref().position()
+    }
+
+    pub fn time(&self) -> TimingPoint {
+        self.as_ref().time()
+    }
+}
+
+/// Iterator over notes of all kinds in time order, see [`Notes::all`] and [`Notes::into_iter`].
+pub struct AllNotes<'a, T> {
+    /// Taps, holds, bells and flicks, each already sorted by time.
+    kinds: [Peekable<Box<dyn ExactSizeIterator<Item = T> + 'a>>; 4],
+    time: fn(&T) -> TimingPoint,
+}
+
+impl<'a, T> AllNotes<'a, T> {
+    fn new(
+        kinds: [Box<dyn ExactSizeIterator<Item = T> + 'a>; 4],
+        time: fn(&T) -> TimingPoint,
+    ) -> Self {
+        Self {
+            kinds: kinds.map(Iterator::peekable),
+            time,
+        }
+    }
+}
+
+impl<T> Iterator for AllNotes<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        // Ties go to the kind listed first.
+        let next = self
+            .kinds
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, kind)| Some(((self.time)(kind.peek()?), i)))
+            .min()?
+            .1;
+        self.kinds[next].next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.kinds.iter().map(|kind| kind.len()).sum();
+        (len, Some(len))
+    }
+}
+
+impl<T> ExactSizeIterator for AllNotes<'_, T> {}
+
+impl Notes {
+    /// Returns iterator of all notes sorted by time. Notes at the same time come as taps, holds,
+    /// bells then flicks.
+    pub fn all(&self) -> AllNotes<'_, NoteRef<'_>> {
+        AllNotes::new(
+            [
+                Box::new(self.all_taps().map(NoteRef::Tap)),
+                Box::new(self.all_holds().map(NoteRef::Hold)),
+                Box::new(self.all_bells().map(NoteRef::Bell)),
+                Box::new(self.all_flicks().map(NoteRef::Flick)),
+            ],
+            NoteRef::time,
+        )
+    }
+}
+
+impl<'a> IntoIterator for &'a Notes {
+    type Item = NoteRef<'a>;
+    type IntoIter = AllNotes<'a, NoteRef<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.all()
+    }
+}
+
+impl IntoIterator for Notes {
+    type Item = Note;
+    type IntoIter = AllNotes<'static, Note>;
+
+    /// Moves out all notes sorted by time, in the same order as [`Notes::all`].
+    fn into_iter(self) -> Self::IntoIter {
+        let Self {
+            taps,
+            holds,
+            bells,
+            flicks,
+        } = self;
+        AllNotes::new(
+            [
+                Box::new(taps.into_items().map(Note::Tap)),
+                Box::new(holds.into_items().map(Note::Hold)),
+                Box::new(bells.into_items().map(Note::Bell)),
+                Box::new(flicks.into_items().map(Note::Flick)),
+            ],
+            Note::time,
+        )
+    }
+}
+
+/// Query over the objects of a chart, see [`Ogkr::query`].
+///
+/// Filters narrow the query down and can be chained in any order, e.g.
+/// `ogkr.query().notes().critical().between(4, 7).x_range(-8.0, 8.0)`.
+#[derive(Clone, Copy, Debug)]
+pub struct Query<'a> {
+    ogkr: &'a Ogkr,
+}
+
+impl<'a> Query<'a> {
+    pub(crate) fn new(ogkr: &'a Ogkr) -> Self {
+        Self { ogkr }
+    }
+
+    /// Notes of all kinds, until narrowed down with [`NoteQuery::taps`] and the like.
+    pub fn notes(self) -> NoteQuery<'a> {
+        NoteQuery {
+            ogkr: self.ogkr,
+            kinds: None,
+            critical: None,
+            bounds: Bounds::default(),
+        }
+    }
+
+    pub fn bullets(self) -> BulletQuery<'a> {
+        BulletQuery {
+            ogkr: self.ogkr,
+            palette: None,
+            bounds: Bounds::default(),
+        }
+    }
+}
+
+/// Time and position filters shared by all queries.
+#[derive(Clone, Debug, Default)]
+struct Bounds {
+    time: Option<Range<TimingPoint>>,
+    x: Option<RangeInclusive<f32>>,
+}
+
+impl Bounds {
+    /// Narrows the time range, an empty range past its start when nothing is left.
+    fn between(&mut self, from: TimingPoint, to: TimingPoint) {
+        let time = match self.time.take() {
+            Some(time) => time.start.max(from)..time.end.min(to),
+            None => from..to,
+        };
+        self.time = Some(time.start..time.end.max(time.start));
+    }
+
+    fn x_range(&mut self, from: f32, to: f32) {
+        let (from, to) = (from.min(to), from.max(to));
+        self.x = Some(match self.x.take() {
+            Some(x) => x.start().max(from)..=x.end().min(to),
+            None => from..=to,
+        });
+    }
+
+    /// Objects of `store` within the time range, sorted by time.
+    fn items<'a, T>(&self, store: &'a TimeMap<T>) -> Vec<&'a T> {
+        match &self.time {
+            Some(time) => store
+                .groups_in(time.clone())
+                .flat_map(|(_, items)| items)
+                .collect(),
+            None => store.groups().flat_map(|(_, items)| items).collect(),
+        }
+    }
+
+    fn contains_x(&self, position: TrackPosition, resolution: Resolution) -> bool {
+        self.x
+            .as_ref()
+            .is_none_or(|x| x.contains(&resolution.x_of(position.x)))
+    }
+}
+
+/// Kind of note selected by a [`NoteQuery`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum NoteKind {
+    Tap,
+    Hold,
+    Bell,
+    Flick,
+}
+
+/// Query over notes, see [`Query::notes`]. Holds are matched by their start.
+#[derive(Clone, Debug)]
+pub struct NoteQuery<'a> {
+    ogkr: &'a Ogkr,
+    /// Kinds selected so far, all of them when `None`.
+    kinds: Option<Vec<NoteKind>>,
+    critical: Option<bool>,
+    bounds: Bounds,
+}
+
+impl<'a> NoteQuery<'a> {
+    fn kind(mut self, kind: NoteKind) -> Self {
+        self.kinds.get_or_insert_with(Vec::new).push(kind);
+        self
+    }
+
+    /// Keeps taps, along with any other kind selected the same way.
+    pub fn taps(self) -> Self {
+        self.kind(NoteKind::Tap)
+    }
+
+    /// Keeps holds, along with any other kind selected the same way.
+    pub fn holds(self) -> Self {
+        self.kind(NoteKind::Hold)
+    }
+
+    /// Keeps bells, along with any other kind selected the same way.
+    pub fn bells(self) -> Self {
+        self.kind(NoteKind::Bell)
+    }
+
+    /// Keeps flicks, along with any other kind selected the same way.
+    pub fn flicks(self) -> Self {
+        self.kind(NoteKind::Flick)
+    }
+
+    /// Keeps critical notes only, see [`NoteRef::is_critical`].
+    pub fn critical(mut self) -> Self {
+        self.critical = Some(true);
+        self
+    }
+
+    /// Keeps notes that are not critical only.
+    pub fn normal(mut self) -> Self {
+        self.critical = Some(false);
+        self
+    }
+
+    /// Keeps notes from the start of measure `from` up to the end of measure `to`.
+    pub fn between(self, from: u32, to: u32) -> Self {
+        self.during(TimingPoint::new(from, 0)..TimingPoint::new(to.saturating_add(1), 0))
+    }
+
+    /// Keeps notes within `time`.
+    pub fn during(mut self, time: Range<TimingPoint>) -> Self {
+        self.bounds.between(time.start, time.end);
+        self
+    }
+
+    /// Keeps notes whose horizontal position is within `from` and `to` included, in track units.
+    pub fn x_range(mut self, from: f32, to: f32) -> Self {
+        self.bounds.x_range(from, to);
+        self
+    }
+
+    /// Matching notes sorted by time. Notes at the same time come in the same order as
+    /// [`Notes::all`].
+    pub fn iter(&self) -> vec::IntoIter<NoteRef<'a>> {
+        let notes = &self.ogkr.notes;
+        let selected = |kind| {
+            self.kinds
+                .as_ref()
+                .is_none_or(|kinds| kinds.contains(&kind))
+        };
+
+        let mut found = Vec::new();
+        if selected(NoteKind::Tap) {
+            found.extend(self.bounds.items(&notes.taps).into_iter().map(NoteRef::Tap));
+        }
+        if selected(NoteKind::Hold) {
+            found.extend(
+                self.bounds
+                    .items(&notes.holds)
+                    .into_iter()
+                    .map(NoteRef::Hold),
+            );
+        }
+        if selected(NoteKind::Bell) {
+            found.extend(
+                self.bounds
+                    .items(&notes.bells)
+                    .into_iter()
+                    .map(NoteRef::Bell),
+            );
+        }
+        if selected(NoteKind::Flick) {
+            found.extend(
+                self.bounds
+                    .items(&notes.flicks)
+                    .into_iter()
+                    .map(NoteRef::Flick),
+            );
+        }
+
+        let resolution = self.ogkr.track.resolution;
+        found.retain(|note| {
+            self.critical
+                .is_none_or(|critical| note.is_critical() == critical)
+                && self.bounds.contains_x(note.position(), resolution)
+        });
+        found.sort_by_key(NoteRef::time);
+        found.into_iter()
+    }
+
+    pub fn count(&self) -> usize {
+        self.iter().len()
+    }
+}
+
+impl<'a> IntoIterator for NoteQuery<'a> {
+    type Item = NoteRef<'a>;
+    type IntoIter = vec::IntoIter<NoteRef<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Query over bullets, see [`Query::bullets`].
+#[derive(Clone, Debug)]
+pub struct BulletQuery<'a> {
+    ogkr: &'a Ogkr,
+    palette: Option<BulletPaletteId>,
+    bounds: Bounds,
+}
+
+impl<'a> BulletQuery<'a> {
+    /// Keeps bullets shot with the palette `id`.
+    pub fn palette(mut self, id: &str) -> Self {
+        self.palette = Some(BulletPaletteId(id.to_string()));
+        self
+    }
+
+    /// Keeps bullets from the start of measure `from` up to the end of measure `to`.
+    pub fn between(self, from: u32, to: u32) -> Self {
+        self.during(TimingPoint::new(from, 0)..TimingPoint::new(to.saturating_add(1), 0))
+    }
+
+    /// Keeps bullets within `time`.
+    pub fn during(mut self, time: Range<TimingPoint>) -> Self {
+        self.bounds.between(time.start, time.end);
+        self
+    }
+
+    /// Keeps bullets whose horizontal position is within `from` and `to` included, in track
+    /// units.
+    pub fn x_range(mut self, from: f32, to: f32) -> Self {
+        self.bounds.x_range(from, to);
+        self
+    }
+
+    /// Matching bullets sorted by time.
+    pub fn iter(&self) -> vec::IntoIter<&'a Bullet> {
+        let resolution = self.ogkr.track.resolution;
+        let palette = match &self.palette {
+            Some(id) => match self.ogkr.bullets.palette_ref(&id.0) {
+                Some(palette) => Some(palette),
+                None => return vec![].into_iter(),
+            },
+            None => None,
+        };
+        let mut found = self.bounds.items(&self.ogkr.bullets.bullets);
+        found.retain(|bullet| {
+            palette.is_none_or(|palette| bullet.palette == palette)
+                && self.bounds.contains_x(bullet.position, resolution)
+        });
+        found.into_iter()
+    }
+
+    pub fn count(&self) -> usize {
+        self.iter().len()
+    }
+}
+
+impl<'a> IntoIterator for BulletQuery<'a> {
+    type Item = &'a Bullet;
+    type IntoIter = vec::IntoIter<&'a Bullet>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Linearly interpolates the horizontal position of time-sorted `points` at `time`.
+pub(crate) fn interpolate_x(
+    points: &[TrackPosition],
+    time: TimingPoint,
+    resolution: Resolution,
+) -> Option<f32> {
+    interpolate(points, time, resolution, |point| {
+        (point.time, [resolution.x_of(point.x)])
+    })
+    .map(|[x]| x)
+}
+
+fn interpolate_x_with(
+    points: &[TrackPosition],
+    time: TimingPoint,
+    resolution: Resolution,
+    interpolation: Interpolation,
+) -> Option<f32> {
+    match interpolation {
+        Interpolation::Linear => interpolate_x(points, time, resolution),
+        Interpolation::CatmullRom => catmull_rom_x(points, time, resolution),
+    }
+}
+
+fn catmull_rom_x(
+    points: &[TrackPosition],
+    time: TimingPoint,
+    resolution: Resolution,
+) -> Option<f32> {
+    let tick = resolution.ticks_at(time);
+    let next = next_point(points.len(), tick, |i| resolution.ticks_at(points[i].time))?;
+    let sample = |i: usize| {
+        let point = &points[i];
+        (
+            resolution.ticks_at(point.time) as f64,
+            resolution.x_of(point.x) as f64,
+        )
+    };
+    let (t2, x2) = sample(next);
+    let (t1, x1) = sample(next.saturating_sub(1));
+    if t2 <= tick as f64 || t1 >= t2 {
+        return Some(x2 as f32);
+    }
+
+    // Tangent at a point from its neighbours, one-sided at the ends of the points and next to
+    // points sharing the same time, so jumps do not bend the curve.
+    let tangent = |i: usize| {
+        let point = sample(i);
+        let before = sample(i.saturating_sub(1));
+        let before = if before.0 < point.0 { before } else { point };
+        let after = sample((i + 1).min(points.len() - 1));
+        let after = if after.0 > point.0 { after } else { point };
+        if after.0 > before.0 {
+            (after.1 - before.1) / (after.0 - before.0)
+        } else {
+            0.0
+        }
+    };
+    let (m1, m2) = (tangent(next - 1), tangent(next));
+
+    let dt = t2 - t1;
+    let s = (tick as f64 - t1) / dt;
+    let (s2, s3) = (s * s, s * s * s);
+    let x = (2.0 * s3 - 3.0 * s2 + 1.0) * x1
+        + (s3 - 2.0 * s2 + s) * dt * m1
+        + (-2.0 * s3 + 3.0 * s2) * x2
+        + (s3 - s2) * dt * m2;
+    Some(x as f32)
+}
+
+/// Linearly interpolates the values sampled from time-sorted `points` at `time`. Returns `None` if
+/// `time` is outside of the points.
+fn interpolate<P, const N: usize>(
+    points: &[P],
+    time: TimingPoint,
+    resolution: Resolution,
+    sample: impl Fn(&P) -> (TimingPoint, [f32; N]),
+) -> Option<[f32; N]> {
+    let tick = resolution.ticks_at(time);
+    let next = next_point(points.len(), tick, |i| {
+        resolution.ticks_at(sample(&points[i]).0)
+    })?;
+    let (end_time, end) = sample(&points[next]);
+    let end_ticks = resolution.ticks_at(end_time);
+    let (start_time, start) = sample(&points[next.saturating_sub(1)]);
+    let start_ticks = resolution.ticks_at(start_time);
+    if end_ticks <= tick || start_ticks >= end_ticks {
+        return Some(end);
+    }
+
+    let t = (tick - start_ticks) as f64 / (end_ticks - start_ticks) as f64;
+    Some(std::array::from_fn(|i| {
+        start[i] + (end[i] - start[i]) * t as f32
+    }))
+}
+
+/// Index of the first of `len` points at or after `tick`, comparing ticks rather than timing
+/// points since offsets can exceed a measure. Returns `None` if `tick` is outside of the points.
+fn next_point(len: usize, tick: u64, ticks: impl Fn(usize) -> u64) -> Option<usize> {
+    let last = len.checked_sub(1)?;
+    if tick < ticks(0) || tick > ticks(last) {
+        return None;
+    }
+
+    let (mut low, mut high) = (0, last);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if ticks(mid) < tick {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    Some(low)
+}
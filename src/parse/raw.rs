@@ -1,11 +1,21 @@
-use crate::lex::{
-    command::*,
-    token::{Token, TokenStream},
+use std::collections::BTreeMap;
+
+use crate::{
+    lex::{
+        command::*,
+        grammar::{self, Section},
+        token::{Token, TokenKind, TokenStream},
+        tokenize_limited, tokenize_with_format, CustomToken,
+    },
+    trace::Phase,
 };
 
-use super::{Commands, EnemyWaveAssignment, Header, ParseError, Result};
+use super::{
+    repair, Commands, DuplicateHeaderPolicy, EnemyWaveAssignment, Extensions, Header, ParseError,
+    ParseOptions, RepairOptions, Result, Warning, Warnings,
+};
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct RawOgkr {
     /// Header information and metadata.
     pub header: Header,
@@ -22,6 +32,17 @@ pub struct RawOgkr {
 
     pub track: RawTrack,
     pub notes: RawNotes,
+
+    /// Extensions not following a note or bullet command.
+    pub extensions: Extensions,
+    /// Extensions of bullets, by index in [`RawOgkr::bullets`].
+    pub bullet_extensions: BTreeMap<usize, Extensions>,
+    /// Commands read by handlers registered with
+    /// [`LexOptions::custom_command`](crate::lex::LexOptions::custom_command), in chart order.
+    pub custom_commands: Vec<CustomToken>,
+
+    /// Data that was dropped or ignored while tokenizing and parsing.
+    pub warnings: Warnings,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
@@ -42,11 +63,69 @@ pub struct RawNotes {
     pub critical_taps: Vec<Tap>,
     pub holds: Vec<Hold>,
     pub critical_holds: Vec<Hold>,
+
+    /// Extensions of notes, by the note they follow.
+    pub extensions: BTreeMap<NoteRef, Extensions>,
+}
+
+/// Note command identified by its list and index within [`RawNotes`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum NoteRef {
+    Bell(usize),
+    Flick(usize),
+    CriticalFlick(usize),
+    Tap(usize),
+    CriticalTap(usize),
+    Hold(usize),
+    CriticalHold(usize),
+}
+
+impl NoteRef {
+    pub fn index(&self) -> usize {
+        match *self {
+            Self::Bell(index)
+            | Self::Flick(index)
+            | Self::CriticalFlick(index)
+            | Self::Tap(index)
+            | Self::CriticalTap(index)
+            | Self::Hold(index)
+            | Self::CriticalHold(index) => index,
+        }
+    }
+
+    /// Same note list at another index.
+    pub fn with_index(&self, index: usize) -> Self {
+        match self {
+            Self::Bell(_) => Self::Bell(index),
+            Self::Flick(_) => Self::Flick(index),
+            Self::CriticalFlick(_) => Self::CriticalFlick(index),
+            Self::Tap(_) => Self::Tap(index),
+            Self::CriticalTap(_) => Self::CriticalTap(index),
+            Self::Hold(_) => Self::Hold(index),
+            Self::CriticalHold(_) => Self::CriticalHold(index),
+        }
+    }
+}
+
+impl RawNotes {
+    /// Total number of note commands.
+    pub fn len(&self) -> usize {
+        self.bells.len()
+            + self.flicks.len()
+            + self.critical_flicks.len()
+            + self.taps.len()
+            + self.critical_taps.len()
+            + self.holds.len()
+            + self.critical_holds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
-/// Walls, lanes, and beams are grammar-enforce to be consequetive.
-/// XXX FIXME: Handle non-consequetive, out-of-order, objects. The group id should suffice to
-/// provide grouping information.
+/// Walls, lanes and beams are assembled from their start, next and end commands by group id, the
+/// commands of different sections may be interleaved or out-of-order.
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
 pub struct RawTrack {
     pub walls_left: Vec<WallSection>,
@@ -66,320 +145,503 @@ pub struct RawTrack {
     pub oblique_beams: Vec<ObliqueBeamSection>,
 }
 
+impl RawTrack {
+    /// Smallest ID above the IDs of every wall, lane and enemy lane, which share their IDs, or
+    /// `None` if one of them already uses `u32::MAX`.
+    pub fn next_lane_id(&self) -> Option<u32> {
+        next_id(
+            self.walls_left
+                .iter()
+                .chain(&self.walls_right)
+                .map(|section| section.group_id)
+                .chain(
+                    self.lanes_left
+                        .iter()
+                        .chain(&self.lanes_center)
+                        .chain(&self.lanes_right)
+                        .chain(&self.enemy_lanes)
+                        .map(|section| section.group_id),
+                ),
+        )
+    }
+
+    /// Smallest ID above the IDs of every colorful lane, see [`RawTrack::next_lane_id`].
+    pub fn next_colorful_lane_id(&self) -> Option<u32> {
+        next_id(self.colorful_lanes.iter().map(|section| section.group_id))
+    }
+
+    /// Smallest ID above the IDs of every beam, see [`RawTrack::next_lane_id`].
+    pub fn next_beam_id(&self) -> Option<u32> {
+        next_id(self.beams.iter().map(|section| section.record_id))
+    }
+
+    /// Smallest ID above the IDs of every oblique beam, see [`RawTrack::next_lane_id`].
+    pub fn next_oblique_beam_id(&self) -> Option<u32> {
+        next_id(self.oblique_beams.iter().map(|section| section.record_id))
+    }
+}
+
+fn next_id(ids: impl Iterator<Item = u32>) -> Option<u32> {
+    ids.max().map_or(Some(0), |id| id.checked_add(1))
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
 pub struct WallSection {
     pub group_id: u32,
     pub points: Vec<WallPoint>,
 }
 
-fn next_token_or(commands: &mut Commands, message: &str) -> Result<Token> {
-    commands
-        .next_command()
-        .ok_or_else(|| ParseError::SemanticErrorExpectedCommand(message.to_string()))
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub struct LaneSection {
+    pub group_id: u32,
+    pub points: Vec<LanePoint>,
 }
 
-fn verify_group_id(commands: &Commands, reference_id: u32, new_id: u32) -> Result<()> {
-    if reference_id != new_id {
-        return Err(commands.err_semantic("different group ids for consequetive section"));
-    } else {
-        Ok(())
-    }
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub struct ColorfulLaneSection {
+    pub group_id: u32,
+    pub points: Vec<ColorfulLanePoint>,
 }
 
-// XXX FIXME: Remove code duplication here?.
-impl WallSection {
-    pub(crate) fn wall_left_from_commands(
-        commands: &mut Commands,
-        first_point: WallPoint,
-    ) -> Result<Self> {
-        let group_id = first_point.group_id;
-        let mut points = Vec::new();
-        points.push(first_point);
-
-        loop {
-            match next_token_or(commands, "more commands for left wall section")? {
-                Token::WallLeftNext(wall_point) => {
-                    verify_group_id(commands, group_id, wall_point.group_id)?;
-                    points.push(wall_point);
-                }
-                Token::WallLeftEnd(wall_point) => {
-                    verify_group_id(commands, group_id, wall_point.group_id)?;
-                    points.push(wall_point);
-                    break;
-                }
-                _ => return Err(commands.err_semantic("unexpected command on left wall section")),
-            }
-        }
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub struct BeamSection {
+    pub record_id: u32,
+    pub points: Vec<BeamPoint>,
+    // XXX TODO: Figure out what the width of the first point does.
+    // pub width_id: u32,
+}
 
-        Ok(Self { group_id, points })
-    }
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub struct ObliqueBeamSection {
+    pub record_id: u32,
+    pub points: Vec<ObliqueBeamPoint>,
+}
 
-    pub(crate) fn wall_right_from_commands(
-        commands: &mut Commands,
-        first_point: WallPoint,
-    ) -> Result<Self> {
-        let group_id = first_point.group_id;
-        let mut points = Vec::new();
-        points.push(first_point);
-
-        loop {
-            match next_token_or(commands, "more commands for right wall section")? {
-                Token::WallRightNext(wall_point) => {
-                    verify_group_id(commands, group_id, wall_point.group_id)?;
-                    points.push(wall_point);
-                }
-                Token::WallRightEnd(wall_point) => {
-                    verify_group_id(commands, group_id, wall_point.group_id)?;
-                    points.push(wall_point);
-                    break;
-                }
-                _ => return Err(commands.err_semantic("unexpected command on right wall section")),
+/// Point commands making up a wall, lane or beam section.
+trait SectionPoint {
+    fn section_id(&self) -> u32;
+    fn time(&self) -> CommandTime;
+}
+
+macro_rules! impl_section_point {
+    ($point:ty, $id:ident) => {
+        impl SectionPoint for $point {
+            fn section_id(&self) -> u32 {
+                self.$id
+            }
+
+            fn time(&self) -> CommandTime {
+                self.time
             }
         }
+    };
+}
 
-        Ok(Self { group_id, points })
-    }
+impl_section_point!(WallPoint, group_id);
+impl_section_point!(LanePoint, group_id);
+impl_section_point!(ColorfulLanePoint, group_id);
+impl_section_point!(BeamPoint, record_id);
+impl_section_point!(ObliqueBeamPoint, record_id);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SectionRole {
+    Start,
+    Next,
+    End,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
-pub struct LaneSection {
-    pub group_id: u32,
-    pub points: Vec<LanePoint>,
+struct OpenSection<P> {
+    points: Vec<P>,
+    has_start: bool,
+    has_end: bool,
 }
 
-// XXX FIXME: Remove code duplication here?.
-impl LaneSection {
-    pub(crate) fn lane_left_from_commands(
-        commands: &mut Commands,
-        first_point: LanePoint,
-    ) -> Result<Self> {
-        let group_id = first_point.group_id;
-        let mut points = Vec::new();
-        points.push(first_point);
-
-        loop {
-            match next_token_or(commands, "more commands for left lane section")? {
-                Token::LaneLeftNext(lane_point) => {
-                    verify_group_id(commands, group_id, lane_point.group_id)?;
-                    points.push(lane_point);
-                }
-                Token::LaneLeftEnd(lane_point) => {
-                    verify_group_id(commands, group_id, lane_point.group_id)?;
-                    points.push(lane_point);
-                    break;
-                }
-                _ => return Err(commands.err_semantic("unexpected command on left lane section")),
-            }
-        }
+fn time_key(time: CommandTime) -> (u32, u32) {
+    (time.measure, time.offset)
+}
+
+/// Groups section points by id. A section is complete once both its start and end are found,
+/// the same id can then be reused by a later section.
+struct SectionAssembler<P> {
+    name: &'static str,
+    /// Start command of the sections, identifying them in warnings.
+    kind: TokenKind,
+    open: BTreeMap<u32, OpenSection<P>>,
+    sections: Vec<(u32, Vec<P>)>,
+    /// IDs of completed sections whose points were not in time order.
+    unordered: Vec<u32>,
+}
 
-        Ok(Self { group_id, points })
+impl<P: SectionPoint> SectionAssembler<P> {
+    fn new(name: &'static str, kind: TokenKind) -> Self {
+        Self {
+            name,
+            kind,
+            open: BTreeMap::new(),
+            sections: Vec::new(),
+            unordered: Vec::new(),
+        }
     }
 
-    pub(crate) fn lane_center_from_commands(
-        commands: &mut Commands,
-        first_point: LanePoint,
-    ) -> Result<Self> {
-        let group_id = first_point.group_id;
-        let mut points = Vec::new();
-        points.push(first_point);
-
-        loop {
-            match next_token_or(commands, "more commands for center lane section")? {
-                Token::LaneCenterNext(lane_point) => {
-                    verify_group_id(commands, group_id, lane_point.group_id)?;
-                    points.push(lane_point);
-                }
-                Token::LaneCenterEnd(lane_point) => {
-                    verify_group_id(commands, group_id, lane_point.group_id)?;
-                    points.push(lane_point);
-                    break;
-                }
-                _ => return Err(commands.err_semantic("unexpected command on center lane section")),
+    fn add(&mut self, commands: &Commands, role: SectionRole, point: P) -> Result<()> {
+        let id = point.section_id();
+        let section = self.open.entry(id).or_insert_with(|| OpenSection {
+            points: Vec::new(),
+            has_start: false,
+            has_end: false,
+        });
+
+        match role {
+            SectionRole::Start if section.has_start => {
+                return Err(
+                    commands.err_semantic(&format!("{} section {} started twice", self.name, id))
+                )
             }
+            SectionRole::End if section.has_end => {
+                return Err(
+                    commands.err_semantic(&format!("{} section {} ended twice", self.name, id))
+                )
+            }
+            SectionRole::Start => section.has_start = true,
+            SectionRole::End => section.has_end = true,
+            SectionRole::Next => {}
         }
+        commands.push_point(&mut section.points, point)?;
 
-        Ok(Self { group_id, points })
-    }
-
-    pub(crate) fn lane_right_from_commands(
-        commands: &mut Commands,
-        first_point: LanePoint,
-    ) -> Result<Self> {
-        let group_id = first_point.group_id;
-        let mut points = Vec::new();
-        points.push(first_point);
-
-        loop {
-            match next_token_or(commands, "more commands for right lane section")? {
-                Token::LaneRightNext(lane_point) => {
-                    verify_group_id(commands, group_id, lane_point.group_id)?;
-                    points.push(lane_point);
-                }
-                Token::LaneRightEnd(lane_point) => {
-                    verify_group_id(commands, group_id, lane_point.group_id)?;
-                    points.push(lane_point);
-                    break;
-                }
-                _ => return Err(commands.err_semantic("unexpected command on right lane section")),
+        if section.has_start && section.has_end {
+            if let Some(section) = self.open.remove(&id) {
+                self.complete(id, section.points);
             }
         }
 
-        Ok(Self { group_id, points })
+        Ok(())
     }
 
-    pub(crate) fn enemy_lane_from_commands(
-        commands: &mut Commands,
-        first_point: EnemyLanePoint,
-    ) -> Result<Self> {
-        let group_id = first_point.group_id;
-        let mut points = Vec::new();
-        points.push(first_point.into());
-
-        loop {
-            match next_token_or(commands, "more commands for enemy lane section")? {
-                Token::EnemyLaneNext(lane_point) => {
-                    verify_group_id(commands, group_id, lane_point.group_id)?;
-                    points.push(lane_point.into());
-                }
-                Token::EnemyLaneEnd(lane_point) => {
-                    verify_group_id(commands, group_id, lane_point.group_id)?;
-                    points.push(lane_point.into());
-                    break;
-                }
-                _ => return Err(commands.err_semantic("unexpected command on enemy lane section")),
-            }
+    fn complete(&mut self, id: u32, mut points: Vec<P>) {
+        if !points.is_sorted_by_key(|point| time_key(point.time())) {
+            self.unordered.push(id);
+            points.sort_by_key(|point| time_key(point.time()));
         }
+        self.sections.push((id, points));
+    }
 
-        Ok(Self { group_id, points })
+    /// Time of the last point added so far.
+    fn last_time(&self) -> Option<CommandTime> {
+        self.sections
+            .iter()
+            .map(|(_, points)| points)
+            .chain(self.open.values().map(|section| &section.points))
+            .flatten()
+            .map(|point| point.time())
+            .max_by_key(|time| time_key(*time))
     }
-}
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
-pub struct ColorfulLaneSection {
-    pub group_id: u32,
-    pub points: Vec<ColorfulLanePoint>,
-}
+    /// Returns completed sections in the order they were completed, reporting the ones whose
+    /// points were sorted.
+    fn finish(self, warnings: &mut Warnings) -> Result<Vec<(u32, Vec<P>)>> {
+        if let Some(id) = self.open.keys().next() {
+            return Err(ParseError::SemanticErrorExpectedCommand(format!(
+                "more commands for {} section {}",
+                self.name, id
+            )));
+        }
 
-impl ColorfulLaneSection {
-    pub(crate) fn from_commands(
-        commands: &mut Commands,
-        first_point: ColorfulLanePoint,
-    ) -> Result<Self> {
-        let group_id = first_point.group_id;
-        let mut points = Vec::new();
-        points.push(first_point);
-
-        loop {
-            match next_token_or(commands, "more commands for colorful lane section")? {
-                Token::ColorfulLaneNext(lane_point) => {
-                    verify_group_id(commands, group_id, lane_point.group_id)?;
-                    points.push(lane_point);
-                }
-                Token::ColorfulLaneEnd(lane_point) => {
-                    verify_group_id(commands, group_id, lane_point.group_id)?;
-                    points.push(lane_point);
-                    break;
-                }
-                _ => {
-                    return Err(commands.err_semantic("unexpected command on colorful lane section"))
-                }
-            }
+        for id in self.unordered {
+            warnings.push(Warning::SectionPointsSorted {
+                section: self.kind,
+                id,
+            });
         }
+        Ok(self.sections)
+    }
+}
 
-        Ok(Self { group_id, points })
+impl SectionAssembler<WallPoint> {
+    /// Ends the walls that have a start but no end, see [`RepairOptions::close_walls`].
+    fn close_walls(&mut self, track_end: Option<CommandTime>, warnings: &mut Warnings) {
+        let ids = self
+            .open
+            .iter()
+            .filter(|(_, section)| section.has_start && !section.has_end)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for id in ids {
+            let Some(mut section) = self.open.remove(&id) else {
+                continue;
+            };
+            let Some(&last) = section
+                .points
+                .iter()
+                .max_by_key(|point| time_key(point.time))
+            else {
+                continue;
+            };
+            let time = match track_end.filter(|end| time_key(*end) > time_key(last.time)) {
+                Some(end) => end,
+                None => match last.time.measure.checked_add(1) {
+                    Some(measure) => CommandTime { measure, offset: 0 },
+                    // Nothing can come after the last measure, leave the wall unended.
+                    None => {
+                        self.open.insert(id, section);
+                        continue;
+                    }
+                },
+            };
+            section.points.push(WallPoint { time, ..last });
+            warnings.push(Warning::WallEndGenerated {
+                section: self.kind,
+                id,
+                time: time.into(),
+            });
+            self.complete(id, section.points);
+        }
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
-pub struct BeamSection {
-    pub record_id: u32,
-    pub points: Vec<BeamPoint>,
-    // pub width_id: u32,
+struct TrackAssembler {
+    walls_left: SectionAssembler<WallPoint>,
+    walls_right: SectionAssembler<WallPoint>,
+    lanes_left: SectionAssembler<LanePoint>,
+    lanes_center: SectionAssembler<LanePoint>,
+    lanes_right: SectionAssembler<LanePoint>,
+    colorful_lanes: SectionAssembler<ColorfulLanePoint>,
+    enemy_lanes: SectionAssembler<LanePoint>,
+    beams: SectionAssembler<BeamPoint>,
+    oblique_beams: SectionAssembler<ObliqueBeamPoint>,
 }
 
-impl BeamSection {
-    pub(crate) fn from_commands(commands: &mut Commands, first_point: BeamPoint) -> Result<Self> {
-        let record_id = first_point.record_id;
-
-        // XXX TODO: Figure out what this does.
-        // let width_id = first_point.width;
-
-        let mut points = Vec::new();
-        points.push(first_point);
-
-        loop {
-            match next_token_or(commands, "more commands for enemy lane section")? {
-                Token::BeamNext(beam_point) => {
-                    verify_group_id(commands, record_id, beam_point.record_id)?;
-                    points.push(beam_point);
-                }
-                Token::BeamEnd(beam_point) => {
-                    verify_group_id(commands, record_id, beam_point.record_id)?;
-                    points.push(beam_point);
-                    break;
-                }
-                _ => return Err(commands.err_semantic("unexpected command on enemy lane section")),
-            }
+impl TrackAssembler {
+    fn new() -> Self {
+        Self {
+            walls_left: SectionAssembler::new("left wall", TokenKind::WallLeftStart),
+            walls_right: SectionAssembler::new("right wall", TokenKind::WallRightStart),
+            lanes_left: SectionAssembler::new("left lane", TokenKind::LaneLeftStart),
+            lanes_center: SectionAssembler::new("center lane", TokenKind::LaneCenterStart),
+            lanes_right: SectionAssembler::new("right lane", TokenKind::LaneRightStart),
+            colorful_lanes: SectionAssembler::new("colorful lane", TokenKind::ColorfulLaneStart),
+            enemy_lanes: SectionAssembler::new("enemy lane", TokenKind::EnemyLaneStart),
+            beams: SectionAssembler::new("beam", TokenKind::BeamStart),
+            oblique_beams: SectionAssembler::new("oblique beam", TokenKind::ObliqueBeamStart),
         }
+    }
+
+    /// Adds the token to its section, returning it back if it is not a section point.
+    fn add(&mut self, commands: &Commands, token: Token) -> Result<Option<Token>> {
+        use SectionRole::*;
 
-        Ok(Self {
-            record_id,
-            points,
-            // width_id,
-        })
+        match token {
+            Token::WallLeftStart(point) => self.walls_left.add(commands, Start, point)?,
+            Token::WallLeftNext(point) => self.walls_left.add(commands, Next, point)?,
+            Token::WallLeftEnd(point) => self.walls_left.add(commands, End, point)?,
+            Token::WallRightStart(point) => self.walls_right.add(commands, Start, point)?,
+            Token::WallRightNext(point) => self.walls_right.add(commands, Next, point)?,
+            Token::WallRightEnd(point) => self.walls_right.add(commands, End, point)?,
+            Token::LaneLeftStart(point) => self.lanes_left.add(commands, Start, point)?,
+            Token::LaneLeftNext(point) => self.lanes_left.add(commands, Next, point)?,
+            Token::LaneLeftEnd(point) => self.lanes_left.add(commands, End, point)?,
+            Token::LaneCenterStart(point) => self.lanes_center.add(commands, Start, point)?,
+            Token::LaneCenterNext(point) => self.lanes_center.add(commands, Next, point)?,
+            Token::LaneCenterEnd(point) => self.lanes_center.add(commands, End, point)?,
+            Token::LaneRightStart(point) => self.lanes_right.add(commands, Start, point)?,
+            Token::LaneRightNext(point) => self.lanes_right.add(commands, Next, point)?,
+            Token::LaneRightEnd(point) => self.lanes_right.add(commands, End, point)?,
+            Token::ColorfulLaneStart(point) => self.colorful_lanes.add(commands, Start, point)?,
+            Token::ColorfulLaneNext(point) => self.colorful_lanes.add(commands, Next, point)?,
+            Token::ColorfulLaneEnd(point) => self.colorful_lanes.add(commands, End, point)?,
+            Token::EnemyLaneStart(point) => self.enemy_lanes.add(commands, Start, point.into())?,
+            Token::EnemyLaneNext(point) => self.enemy_lanes.add(commands, Next, point.into())?,
+            Token::EnemyLaneEnd(point) => self.enemy_lanes.add(commands, End, point.into())?,
+            Token::BeamStart(point) => self.beams.add(commands, Start, point)?,
+            Token::BeamNext(point) => self.beams.add(commands, Next, point)?,
+            Token::BeamEnd(point) => self.beams.add(commands, End, point)?,
+            Token::ObliqueBeamStart(point) => self.oblique_beams.add(commands, Start, point)?,
+            Token::ObliqueBeamNext(point) => self.oblique_beams.add(commands, Next, point)?,
+            Token::ObliqueBeamEnd(point) => self.oblique_beams.add(commands, End, point)?,
+            token => return Ok(Some(token)),
+        }
+
+        Ok(None)
     }
-}
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
-pub struct ObliqueBeamSection {
-    pub record_id: u32,
-    pub points: Vec<ObliqueBeamPoint>,
-}
+    /// Time of the last point of any section added so far.
+    fn last_time(&self) -> Option<CommandTime> {
+        [
+            self.walls_left.last_time(),
+            self.walls_right.last_time(),
+            self.lanes_left.last_time(),
+            self.lanes_center.last_time(),
+            self.lanes_right.last_time(),
+            self.colorful_lanes.last_time(),
+            self.enemy_lanes.last_time(),
+            self.beams.last_time(),
+            self.oblique_beams.last_time(),
+        ]
+        .into_iter()
+        .flatten()
+        .max_by_key(|time| time_key(*time))
+    }
 
-impl ObliqueBeamSection {
-    pub(crate) fn from_commands(
-        commands: &mut Commands,
-        first_point: ObliqueBeamPoint,
-    ) -> Result<Self> {
-        let record_id = first_point.record_id;
-
-        // XXX TODO: Figure out what this does.
-        // let width_id = first_point.width;
-
-        let mut points = Vec::new();
-        points.push(first_point);
-
-        loop {
-            match next_token_or(commands, "more commands for enemy lane section")? {
-                Token::ObliqueBeamNext(beam_point) => {
-                    verify_group_id(commands, record_id, beam_point.record_id)?;
-                    points.push(beam_point);
-                }
-                Token::ObliqueBeamEnd(beam_point) => {
-                    verify_group_id(commands, record_id, beam_point.record_id)?;
-                    points.push(beam_point);
-                    break;
-                }
-                _ => return Err(commands.err_semantic("unexpected command on enemy lane section")),
-            }
+    fn finish(
+        mut self,
+        track: &mut RawTrack,
+        repair: &RepairOptions,
+        warnings: &mut Warnings,
+    ) -> Result<()> {
+        fn walls(sections: Vec<(u32, Vec<WallPoint>)>) -> Vec<WallSection> {
+            sections
+                .into_iter()
+                .map(|(group_id, points)| WallSection { group_id, points })
+                .collect()
+        }
+
+        fn lanes(sections: Vec<(u32, Vec<LanePoint>)>) -> Vec<LaneSection> {
+            sections
+                .into_iter()
+                .map(|(group_id, points)| LaneSection { group_id, points })
+                .collect()
         }
 
-        Ok(Self {
-            record_id,
-            points,
-            // width_id,
-        })
+        if repair.close_walls {
+            let track_end = self.last_time();
+            self.walls_left.close_walls(track_end, warnings);
+            self.walls_right.close_walls(track_end, warnings);
+        }
+
+        track.walls_left = walls(self.walls_left.finish(warnings)?);
+        track.walls_right = walls(self.walls_right.finish(warnings)?);
+        track.lanes_left = lanes(self.lanes_left.finish(warnings)?);
+        track.lanes_center = lanes(self.lanes_center.finish(warnings)?);
+        track.lanes_right = lanes(self.lanes_right.finish(warnings)?);
+        track.enemy_lanes = lanes(self.enemy_lanes.finish(warnings)?);
+        track.colorful_lanes = self
+            .colorful_lanes
+            .finish(warnings)?
+            .into_iter()
+            .map(|(group_id, points)| ColorfulLaneSection { group_id, points })
+            .collect();
+        track.beams = self
+            .beams
+            .finish(warnings)?
+            .into_iter()
+            .map(|(record_id, points)| BeamSection { record_id, points })
+            .collect();
+        track.oblique_beams = self
+            .oblique_beams
+            .finish(warnings)?
+            .into_iter()
+            .map(|(record_id, points)| ObliqueBeamSection { record_id, points })
+            .collect();
+
+        Ok(())
     }
 }
 
 /// Parses command tokens.
 pub fn parse_tokens(token_stream: TokenStream) -> Result<RawOgkr> {
-    let mut commands = Commands::new_from_token_stream(token_stream);
+    parse_tokens_with_options(token_stream, &ParseOptions::default())
+}
+
+/// Tokenizes and parses chart content, enforcing the limits in `options`.
+pub fn parse_source_with_options(source: &str, options: &ParseOptions) -> Result<RawOgkr> {
+    options.check_file_size(source)?;
+
+    let token_stream = match options.max_tokens {
+        Some(max) => tokenize_limited(source, max, &options.lex, options.format)
+            .map_err(ParseError::LexError)?
+            .ok_or(ParseError::LimitExceeded {
+                what: "tokens",
+                max,
+            })?,
+        None => tokenize_with_format(source, &options.lex, options.format)
+            .map_err(ParseError::LexError)?,
+    };
+
+    parse_tokens_with_options(token_stream, options)
+}
+
+/// Parses command tokens, enforcing the limits in `options`.
+pub fn parse_tokens_with_options(
+    token_stream: TokenStream,
+    options: &ParseOptions,
+) -> Result<RawOgkr> {
+    if let Some(max) = options.max_tokens {
+        if token_stream.len() > max {
+            return Err(ParseError::LimitExceeded {
+                what: "tokens",
+                max,
+            });
+        }
+    }
+
+    let _phase = Phase::enter("parse", token_stream.len());
     let mut ogkr = RawOgkr::default();
+    for error in token_stream.errors() {
+        ogkr.warnings
+            .push(Warning::SkippedInvalidLine(error.clone()));
+    }
+    let mut commands = Commands::new_from_token_stream(token_stream, options);
+    let mut track = TrackAssembler::new();
+
+    /// Object the following extension commands are attached to.
+    #[derive(Clone, Copy)]
+    enum Extended {
+        Chart,
+        Note(NoteRef),
+        Bullet(usize),
+    }
+    let mut extended = Extended::Chart;
+    // Header commands seen so far, to detect conflicting duplicates.
+    let mut header_commands = BTreeMap::new();
 
-    // Commands can be out-of-order or not grouped by sections, except for walls, lanes and beams
-    // with distance start, next and end commands.
+    // Commands can be out-of-order or not grouped by sections.
     while let Some(token) = commands.next_command() {
+        let Some(token) = track.add(&commands, token)? else {
+            extended = Extended::Chart;
+            continue;
+        };
+
+        let notes = &ogkr.notes;
+        let next_extended = match &token {
+            Token::Extension(_) => extended,
+            Token::Bullet(_) => Extended::Bullet(ogkr.bullets.len()),
+            Token::Bell(_) => Extended::Note(NoteRef::Bell(notes.bells.len())),
+            Token::Flick(_) => Extended::Note(NoteRef::Flick(notes.flicks.len())),
+            Token::CriticalFlick(_) => {
+                Extended::Note(NoteRef::CriticalFlick(notes.critical_flicks.len()))
+            }
+            Token::Tap(_) => Extended::Note(NoteRef::Tap(notes.taps.len())),
+            Token::CriticalTap(_) => {
+                Extended::Note(NoteRef::CriticalTap(notes.critical_taps.len()))
+            }
+            Token::Hold(_) => Extended::Note(NoteRef::Hold(notes.holds.len())),
+            Token::CriticalHold(_) => {
+                Extended::Note(NoteRef::CriticalHold(notes.critical_holds.len()))
+            }
+            _ => Extended::Chart,
+        };
+
+        if let Some(kind) = token.kind().filter(|&kind| {
+            matches!(
+                grammar::command(kind).section,
+                Some(Section::Header | Section::Total)
+            )
+        }) {
+            match header_commands.insert(kind, token.clone()) {
+                Some(previous) if previous != token => match options.duplicate_header {
+                    DuplicateHeaderPolicy::KeepLast => {
+                        ogkr.warnings.push(Warning::ConflictingHeaderCommand(kind))
+                    }
+                    DuplicateHeaderPolicy::Error => {
+                        return Err(ParseError::SemanticError(format!(
+                            "conflicting {} commands",
+                            kind.mnemonic()
+                        )))
+                    }
+                },
+                _ => {}
+            }
+        }
+
         match token {
             Token::SectionName => continue,
 
@@ -399,24 +661,24 @@ pub fn parse_tokens(token_stream: TokenStream) -> Result<RawOgkr> {
             Token::ClickDefinition(click_def) => ogkr.header.click_definition = Some(click_def),
             Token::Tutorial(tutorial) => ogkr.header.tutorial = Some(tutorial),
             Token::BulletDamage(bullet_damage) => {
-                ogkr.header.damage_values.normal = bullet_damage.damage
+                ogkr.header.damage_values.normal = f32::from_bits(bullet_damage.damage)
             }
             Token::HardBulletDamage(hard_bullet_damage) => {
-                ogkr.header.damage_values.hard = hard_bullet_damage.damage
+                ogkr.header.damage_values.hard = f32::from_bits(hard_bullet_damage.damage)
             }
             Token::DangerBulletDamage(danger_bullet_damage) => {
-                ogkr.header.damage_values.danger = danger_bullet_damage.damage
+                ogkr.header.damage_values.danger = f32::from_bits(danger_bullet_damage.damage)
+            }
+            Token::BeamDamage(beam_damage) => {
+                ogkr.header.damage_values.beam = f32::from_bits(beam_damage.damage)
             }
-            Token::BeamDamage(beam_damage) => ogkr.header.damage_values.beam = beam_damage.damage,
             Token::ProgJudgeBpm(prog_judge_bpm) => {
                 ogkr.header.prog_judge_bpm = Some(prog_judge_bpm)
             }
 
             // Totals.
             Token::TotalNotes(total_notes) => ogkr.header.totals.notes = total_notes.value,
-            Token::TotalTapNotes(total_tap_notes) => {
-                ogkr.header.totals.notes = total_tap_notes.value
-            }
+            Token::TotalTapNotes(total_tap_notes) => ogkr.header.totals.tap = total_tap_notes.value,
             Token::TotalHoldNotes(total_hold_notes) => {
                 ogkr.header.totals.hold = total_hold_notes.value
             }
@@ -424,7 +686,7 @@ pub fn parse_tokens(token_stream: TokenStream) -> Result<RawOgkr> {
                 ogkr.header.totals.side = total_side_notes.value
             }
             Token::TotalSideHoldNotes(total_side_hold_notes) => {
-                ogkr.header.totals.side = total_side_hold_notes.value
+                ogkr.header.totals.side_hold = total_side_hold_notes.value
             }
             Token::TotalFlickNotes(total_flick_notes) => {
                 ogkr.header.totals.flick = total_flick_notes.value
@@ -447,84 +709,13 @@ pub fn parse_tokens(token_stream: TokenStream) -> Result<RawOgkr> {
             // Enemy wave assignment.
             Token::EnemySet(enemy_set) => ogkr.enemy_wave_assignment.update_from_command(enemy_set),
 
-            // Walls and lanes.
-            Token::WallLeftStart(wall_point) => {
-                ogkr.track
-                    .walls_left
-                    .push(WallSection::wall_left_from_commands(
-                        &mut commands,
-                        wall_point,
-                    )?)
-            }
-            Token::WallRightStart(wall_point) => {
-                ogkr.track
-                    .walls_right
-                    .push(WallSection::wall_right_from_commands(
-                        &mut commands,
-                        wall_point,
-                    )?)
-            }
-            Token::LaneLeftStart(lane_point) => {
-                ogkr.track
-                    .lanes_left
-                    .push(LaneSection::lane_left_from_commands(
-                        &mut commands,
-                        lane_point,
-                    )?)
-            }
-
-            Token::LaneCenterStart(lane_point) => {
-                ogkr.track
-                    .lanes_center
-                    .push(LaneSection::lane_center_from_commands(
-                        &mut commands,
-                        lane_point,
-                    )?)
-            }
-            Token::LaneRightStart(lane_point) => {
-                ogkr.track
-                    .lanes_right
-                    .push(LaneSection::lane_right_from_commands(
-                        &mut commands,
-                        lane_point,
-                    )?)
-            }
-            Token::ColorfulLaneStart(lane_point) => {
-                ogkr.track
-                    .colorful_lanes
-                    .push(ColorfulLaneSection::from_commands(
-                        &mut commands,
-                        lane_point,
-                    )?)
-            }
-            Token::EnemyLaneStart(lane_point) => {
-                ogkr.track
-                    .enemy_lanes
-                    .push(LaneSection::enemy_lane_from_commands(
-                        &mut commands,
-                        lane_point,
-                    )?)
-            }
+            // Lane events.
             Token::LaneDisappearance(lane_disp) => ogkr.track.lane_disappearances.push(lane_disp),
             Token::LaneBlock(lane_block) => ogkr.track.lane_blocks.push(lane_block),
 
             // Bullets.
             Token::Bullet(bullet) => ogkr.bullets.push(bullet),
 
-            // Beams.
-            Token::BeamStart(beam_point) => ogkr
-                .track
-                .beams
-                .push(BeamSection::from_commands(&mut commands, beam_point)?),
-            Token::ObliqueBeamStart(beam_point) => {
-                ogkr.track
-                    .oblique_beams
-                    .push(ObliqueBeamSection::from_commands(
-                        &mut commands,
-                        beam_point,
-                    )?)
-            }
-
             // Notes.
             Token::Bell(bell) => ogkr.notes.bells.push(bell),
             Token::Flick(flick) => ogkr.notes.flicks.push(flick),
@@ -534,6 +725,17 @@ pub fn parse_tokens(token_stream: TokenStream) -> Result<RawOgkr> {
             Token::Hold(hold) => ogkr.notes.holds.push(hold),
             Token::CriticalHold(critical_hold) => ogkr.notes.critical_holds.push(critical_hold),
 
+            // Extensions.
+            Token::Extension(extension) => {
+                let extensions = match extended {
+                    Extended::Chart => &mut ogkr.extensions,
+                    Extended::Note(note) => ogkr.notes.extensions.entry(note).or_default(),
+                    Extended::Bullet(index) => ogkr.bullet_extensions.entry(index).or_default(),
+                };
+                extensions.insert(extension.key, extension.value);
+            }
+            Token::Custom(custom) => ogkr.custom_commands.push(custom),
+
             // Unexpected commands.
             _ => {
                 return Err(ParseError::SyntaxError(format!(
@@ -542,6 +744,12 @@ pub fn parse_tokens(token_stream: TokenStream) -> Result<RawOgkr> {
                 )))
             }
         }
+        extended = next_extended;
+    }
+
+    track.finish(&mut ogkr.track, &options.repair, &mut ogkr.warnings)?;
+    if options.repair.clamp_offsets {
+        repair::clamp_offsets(&mut ogkr);
     }
 
     Ok(ogkr)
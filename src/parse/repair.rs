@@ -0,0 +1,104 @@
+//! Fixes for corrupted charts that need the whole raw chart, see [`RepairOptions`].
+
+use crate::lex::command::CommandTime;
+
+#[cfg(doc)]
+use super::RepairOptions;
+use super::{analysis::Resolution, raw::RawOgkr, Warning};
+
+/// Clamps offsets past the end of their measure to its last tick, see
+/// [`RepairOptions::clamp_offsets`].
+pub(crate) fn clamp_offsets(raw: &mut RawOgkr) {
+    let last_tick = Resolution::from_header(&raw.header).ticks.max(1) - 1;
+    let mut clamped = vec![];
+    let mut clamp = |time: &mut CommandTime| {
+        if time.offset > last_tick {
+            clamped.push(Warning::OffsetClamped {
+                time: (*time).into(),
+            });
+            time.offset = last_tick;
+        }
+    };
+
+    let composition = &mut raw.composition;
+    for change in &mut composition.bpm_changes {
+        clamp(&mut change.time);
+    }
+    for change in &mut composition.meter_changes {
+        clamp(&mut change.time);
+    }
+    for soflan in &mut composition.soflans {
+        clamp(&mut soflan.time);
+    }
+    for bullet in &mut raw.bullets {
+        clamp(&mut bullet.time);
+    }
+    for click in &mut raw.click_sounds {
+        clamp(&mut click.time);
+    }
+
+    let track = &mut raw.track;
+    for section in track
+        .lanes_left
+        .iter_mut()
+        .chain(&mut track.lanes_center)
+        .chain(&mut track.lanes_right)
+        .chain(&mut track.enemy_lanes)
+    {
+        section
+            .points
+            .iter_mut()
+            .for_each(|point| clamp(&mut point.time));
+    }
+    for section in track.walls_left.iter_mut().chain(&mut track.walls_right) {
+        section
+            .points
+            .iter_mut()
+            .for_each(|point| clamp(&mut point.time));
+    }
+    for section in &mut track.colorful_lanes {
+        section
+            .points
+            .iter_mut()
+            .for_each(|point| clamp(&mut point.time));
+    }
+    for section in &mut track.beams {
+        section
+            .points
+            .iter_mut()
+            .for_each(|point| clamp(&mut point.time));
+    }
+    for section in &mut track.oblique_beams {
+        section
+            .points
+            .iter_mut()
+            .for_each(|point| clamp(&mut point.time));
+    }
+    for event in track
+        .lane_disappearances
+        .iter_mut()
+        .chain(&mut track.lane_blocks)
+    {
+        clamp(&mut event.start_time);
+        clamp(&mut event.end_time);
+    }
+
+    let notes = &mut raw.notes;
+    for tap in notes.taps.iter_mut().chain(&mut notes.critical_taps) {
+        clamp(&mut tap.time);
+    }
+    for hold in notes.holds.iter_mut().chain(&mut notes.critical_holds) {
+        clamp(&mut hold.start_time);
+        clamp(&mut hold.end_time);
+    }
+    for flick in notes.flicks.iter_mut().chain(&mut notes.critical_flicks) {
+        clamp(&mut flick.time);
+    }
+    for bell in &mut raw.notes.bells {
+        clamp(&mut bell.time);
+    }
+
+    for warning in clamped {
+        raw.warnings.push(warning);
+    }
+}
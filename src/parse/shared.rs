@@ -0,0 +1,139 @@
+use std::{ops::Deref, sync::Arc};
+
+use super::{
+    analysis::{Bullets, ClickSound, Composition, Notes, Ogkr, Track},
+    EnemyWaveAssignment, Header,
+};
+
+// The parsed chart is plain owned data, keep it that way so it can be shared between threads.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+
+    assert_send_sync::<Ogkr>();
+    assert_send_sync::<SharedOgkr>();
+    assert_send_sync::<SharedView<Track>>();
+};
+
+/// Cheaply clonable, thread-safe handle to a parsed chart.
+#[derive(Clone, Debug)]
+pub struct SharedOgkr {
+    ogkr: Arc<Ogkr>,
+}
+
+impl SharedOgkr {
+    pub fn new(ogkr: Ogkr) -> Self {
+        Self {
+            ogkr: Arc::new(ogkr),
+        }
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.ogkr.header
+    }
+
+    pub fn composition(&self) -> &Composition {
+        &self.ogkr.composition
+    }
+
+    pub fn track(&self) -> &Track {
+        &self.ogkr.track
+    }
+
+    pub fn notes(&self) -> &Notes {
+        &self.ogkr.notes
+    }
+
+    pub fn bullets(&self) -> &Bullets {
+        &self.ogkr.bullets
+    }
+
+    pub fn click_sounds(&self) -> &[ClickSound] {
+        &self.ogkr.click_sounds
+    }
+
+    pub fn enemy_wave_assignment(&self) -> &EnemyWaveAssignment {
+        &self.ogkr.enemy_wave_assignment
+    }
+
+    /// Owned view of the composition, eg. for an audio thread.
+    pub fn composition_view(&self) -> SharedView<Composition> {
+        SharedView::new(self.ogkr.clone(), |ogkr| &ogkr.composition)
+    }
+
+    /// Owned view of the track, eg. for a render thread.
+    pub fn track_view(&self) -> SharedView<Track> {
+        SharedView::new(self.ogkr.clone(), |ogkr| &ogkr.track)
+    }
+
+    /// Owned view of the notes, eg. for a game logic thread.
+    pub fn notes_view(&self) -> SharedView<Notes> {
+        SharedView::new(self.ogkr.clone(), |ogkr| &ogkr.notes)
+    }
+
+    /// Owned view of the bullets.
+    pub fn bullets_view(&self) -> SharedView<Bullets> {
+        SharedView::new(self.ogkr.clone(), |ogkr| &ogkr.bullets)
+    }
+
+    /// Returns the underlying reference counted chart.
+    pub fn into_inner(self) -> Arc<Ogkr> {
+        self.ogkr
+    }
+}
+
+impl Deref for SharedOgkr {
+    type Target = Ogkr;
+
+    fn deref(&self) -> &Self::Target {
+        &self.ogkr
+    }
+}
+
+impl From<Ogkr> for SharedOgkr {
+    fn from(ogkr: Ogkr) -> Self {
+        Self::new(ogkr)
+    }
+}
+
+impl From<Arc<Ogkr>> for SharedOgkr {
+    fn from(ogkr: Arc<Ogkr>) -> Self {
+        Self { ogkr }
+    }
+}
+
+/// Keeps the whole chart alive while only exposing a part of it.
+pub struct SharedView<T> {
+    ogkr: Arc<Ogkr>,
+    project: fn(&Ogkr) -> &T,
+}
+
+impl<T> SharedView<T> {
+    fn new(ogkr: Arc<Ogkr>, project: fn(&Ogkr) -> &T) -> Self {
+        Self { ogkr, project }
+    }
+
+    /// Returns a handle to the full chart this view belongs to.
+    pub fn chart(&self) -> SharedOgkr {
+        SharedOgkr::from(self.ogkr.clone())
+    }
+}
+
+impl<T> Clone for SharedView<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.ogkr.clone(), self.project)
+    }
+}
+
+impl<T> Deref for SharedView<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        (self.project)(&self.ogkr)
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for SharedView<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(&**self, f)
+    }
+}
@@ -0,0 +1,75 @@
+use super::analysis::{Ogkr, Resolution, TrackPosition};
+
+impl Ogkr {
+    /// Removes lane and wall points lying on the straight line between the points around them,
+    /// within `tolerance` track units horizontally. Returns the number of removed points.
+    ///
+    /// The first and last points of every lane are kept, so the lanes start and end at the same
+    /// times, and every removed point stays within `tolerance` of the simplified lane.
+    pub fn simplify_lanes(&mut self, tolerance: f32) -> usize {
+        let resolution = self.track.resolution;
+        let mut removed = 0;
+        for lane in self.track.lanes_data.values_mut() {
+            let before = lane.points.len();
+            lane.points = simplify(&lane.points, resolution, tolerance.max(0.0));
+            removed += before - lane.points.len();
+        }
+        removed
+    }
+}
+
+/// Keeps a point only when skipping it would move one of the points skipped since the last kept
+/// point further than `tolerance` away from the line.
+fn simplify(
+    points: &[TrackPosition],
+    resolution: Resolution,
+    tolerance: f32,
+) -> Vec<TrackPosition> {
+    let Some((&first, _)) = points.split_first() else {
+        return vec![];
+    };
+
+    let mut kept = vec![first];
+    let mut anchor = 0;
+    for i in 1..points.len().saturating_sub(1) {
+        let next = points[i + 1];
+        let collinear = (anchor + 1..=i).all(|skipped| {
+            deviation(points[anchor], next, points[skipped], resolution)
+                .is_some_and(|deviation| deviation <= tolerance)
+        });
+        if !collinear {
+            kept.push(points[i]);
+            anchor = i;
+        }
+    }
+    if points.len() > 1 {
+        kept.push(points[points.len() - 1]);
+    }
+    kept
+}
+
+/// Horizontal distance between `point` and the line from `start` to `end`, `None` when the line
+/// has no duration or `point` is outside of it.
+fn deviation(
+    start: TrackPosition,
+    end: TrackPosition,
+    point: TrackPosition,
+    resolution: Resolution,
+) -> Option<f32> {
+    let (start_tick, end_tick) = (
+        resolution.ticks_at(start.time),
+        resolution.ticks_at(end.time),
+    );
+    let tick = resolution.ticks_at(point.time);
+    if end_tick <= start_tick || !(start_tick..=end_tick).contains(&tick) {
+        return None;
+    }
+
+    let progress = (tick - start_tick) as f64 / (end_tick - start_tick) as f64;
+    let (start_x, end_x) = (
+        resolution.x_of(start.x) as f64,
+        resolution.x_of(end.x) as f64,
+    );
+    let expected = start_x + (end_x - start_x) * progress;
+    Some((resolution.x_of(point.x) as f64 - expected).abs() as f32)
+}
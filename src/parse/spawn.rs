@@ -0,0 +1,68 @@
+use std::collections::BTreeMap;
+
+use super::{
+    analysis::{Bullet, Bullets, TimingPoint, Track},
+    timing::TimingConverter,
+    BulletShooter,
+};
+
+/// When and where a bullet has to be instantiated to arrive at its specified time and position.
+#[derive(Clone, Copy, Debug)]
+pub struct BulletSpawn<'a> {
+    pub bullet: &'a Bullet,
+
+    pub spawn_time: TimingPoint,
+    /// Milliseconds from the start of the chart, negative if the bullet is shot before the chart
+    /// starts.
+    pub spawn_ms: f64,
+    pub arrival_ms: f64,
+
+    /// Horizontal position of the shooter at spawn time, in track units.
+    pub spawn_x: f32,
+}
+
+impl Bullets {
+    /// Time a bullet with a palette speed of 1 takes to travel from its shooter to the player.
+    pub const DEFAULT_TRAVEL_MS: f64 = 2000.0;
+
+    /// Returns bullets indexed by their spawn time, travelling for `base_travel_ms` divided by
+    /// their palette speed.
+    pub fn spawn_index<'a>(
+        &'a self,
+        track: &Track,
+        timing: &TimingConverter,
+        base_travel_ms: f64,
+    ) -> BTreeMap<TimingPoint, Vec<BulletSpawn<'a>>> {
+        let resolution = timing.resolution();
+        let enemy = track.enemy_track();
+
+        self.all_bullets().fold(BTreeMap::new(), |mut m, bullet| {
+            let palette = self.palette(bullet.palette);
+            let speed = palette
+                .map(|palette| palette.speed as f64)
+                .filter(|speed| speed.is_finite() && *speed > 0.0)
+                .unwrap_or(1.0);
+
+            let arrival_ms = timing.to_ms(bullet.position.time);
+            let spawn_ms = arrival_ms - base_travel_ms / speed;
+            let spawn_time = timing.from_ms(spawn_ms);
+
+            let spawn_x = match palette.map(|palette| palette.shooter) {
+                Some(BulletShooter::Enemy) => enemy.position_at(spawn_time).unwrap_or(0.0),
+                Some(BulletShooter::EndPosition) => resolution.x_of(bullet.position.x),
+                Some(BulletShooter::Center) | None => 0.0,
+            };
+
+            m.entry(spawn_time)
+                .or_insert_with(Vec::new)
+                .push(BulletSpawn {
+                    bullet,
+                    spawn_time,
+                    spawn_ms,
+                    arrival_ms,
+                    spawn_x,
+                });
+            m
+        })
+    }
+}
@@ -0,0 +1,333 @@
+//! Storage of notes and bullets by time.
+//!
+//! Two backends implement [`TimeStore`], [`TimeMap`] picks one for the whole crate:
+//! * [`SortedVec`], the default, a single array sorted by time.
+//! * `BTreeMap<TimingPoint, Bucket<T>>` with the `btree-map` feature.
+//!
+//! `benches/buckets.rs` compares them, the sorted array parses, iterates and clones faster on
+//! both the bundled and generated charts. Code using the [`TimeStore`] methods works with both.
+
+use std::{collections::BTreeMap, ops::Range, slice, vec};
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use super::analysis::{Bucket, IntoSortedIter, SortedIter, TimingPoint};
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Objects sorted by time, objects at the same time stay in insertion order.
+pub trait TimeStore<T>: sealed::Sealed + Default {
+    type Iter<'a>: DoubleEndedIterator<Item = &'a T> + ExactSizeIterator
+    where
+        Self: 'a,
+        T: 'a;
+    type IntoIter: DoubleEndedIterator<Item = T> + ExactSizeIterator;
+
+    /// Builds a store from objects in any order, objects at the same time keep the order of
+    /// `items`.
+    fn from_items(items: impl IntoIterator<Item = (TimingPoint, T)>) -> Self;
+
+    /// Adds `item` after the objects already at `time`. Objects added out of time order can
+    /// move the ones after them, prefer [`TimeStore::from_items`] to build a whole store.
+    fn push(&mut self, time: TimingPoint, item: T);
+
+    /// Moves the objects of `other` after the ones of `self` at the same time.
+    fn combine(&mut self, other: Self);
+
+    /// Number of objects, not of distinct times.
+    fn item_count(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.item_count() == 0
+    }
+
+    /// Returns iterator of objects sorted by time.
+    fn items(&self) -> Self::Iter<'_>;
+
+    fn into_items(self) -> Self::IntoIter;
+
+    /// Returns objects grouped by time, sorted by time.
+    fn groups<'a>(&'a self) -> impl DoubleEndedIterator<Item = (TimingPoint, &'a [T])>
+    where
+        T: 'a;
+
+    /// Same as [`TimeStore::groups`], only for times within `range`, nothing when `range` ends
+    /// before it starts.
+    fn groups_in<'a>(
+        &'a self,
+        range: Range<TimingPoint>,
+    ) -> impl DoubleEndedIterator<Item = (TimingPoint, &'a [T])>
+    where
+        T: 'a;
+
+    fn last_time(&self) -> Option<TimingPoint> {
+        self.groups().next_back().map(|(time, _)| time)
+    }
+
+    /// Parallel counterpart of [`TimeStore::items`], collecting keeps objects sorted by time.
+    #[cfg(feature = "rayon")]
+    fn par_items<'a>(&'a self) -> impl ParallelIterator<Item = &'a T>
+    where
+        T: Sync + 'a;
+}
+
+/// Storage backend used by [`Notes`](super::analysis::Notes) and
+/// [`Bullets`](super::analysis::Bullets).
+#[cfg(feature = "btree-map")]
+pub type TimeMap<T> = BTreeMap<TimingPoint, Bucket<T>>;
+
+/// Storage backend used by [`Notes`](super::analysis::Notes) and
+/// [`Bullets`](super::analysis::Bullets).
+#[cfg(not(feature = "btree-map"))]
+pub type TimeMap<T> = SortedVec<T>;
+
+/// Iterator over the objects of a [`TimeMap`].
+pub type Items<'a, T> = <TimeMap<T> as TimeStore<T>>::Iter<'a>;
+
+/// Owning iterator over the objects of a [`TimeMap`].
+pub type IntoItems<T> = <TimeMap<T> as TimeStore<T>>::IntoIter;
+
+impl<T> sealed::Sealed for BTreeMap<TimingPoint, Bucket<T>> {}
+
+impl<T> TimeStore<T> for BTreeMap<TimingPoint, Bucket<T>> {
+    type Iter<'a>
+        = SortedIter<'a, T>
+    where
+        T: 'a;
+    type IntoIter = IntoSortedIter<T>;
+
+    fn from_items(items: impl IntoIterator<Item = (TimingPoint, T)>) -> Self {
+        let mut store = Self::new();
+        for (time, item) in items {
+            store.push(time, item);
+        }
+        store
+    }
+
+    fn push(&mut self, time: TimingPoint, item: T) {
+        self.entry(time).or_default().push(item);
+    }
+
+    fn combine(&mut self, other: Self) {
+        for (time, items) in other {
+            self.entry(time).or_default().extend(items);
+        }
+    }
+
+    fn item_count(&self) -> usize {
+        self.values().map(|items| items.len()).sum()
+    }
+
+    fn items(&self) -> Self::Iter<'_> {
+        SortedIter::new(self)
+    }
+
+    fn into_items(self) -> Self::IntoIter {
+        IntoSortedIter::new(self)
+    }
+
+    fn groups<'a>(&'a self) -> impl DoubleEndedIterator<Item = (TimingPoint, &'a [T])>
+    where
+        T: 'a,
+    {
+        self.iter().map(|(time, items)| (*time, items.as_slice()))
+    }
+
+    fn groups_in<'a>(
+        &'a self,
+        range: Range<TimingPoint>,
+    ) -> impl DoubleEndedIterator<Item = (TimingPoint, &'a [T])>
+    where
+        T: 'a,
+    {
+        // Ranges ending before their start are empty, like for the sorted array.
+        self.range(range.start..range.end.max(range.start))
+            .map(|(time, items)| (*time, items.as_slice()))
+    }
+
+    #[cfg(feature = "rayon")]
+    fn par_items<'a>(&'a self) -> impl ParallelIterator<Item = &'a T>
+    where
+        T: Sync + 'a,
+    {
+        self.par_iter().flat_map_iter(|(_, items)| items.iter())
+    }
+}
+
+/// Objects and their times in two arrays sorted by time.
+#[derive(Clone, Debug)]
+pub struct SortedVec<T> {
+    times: Vec<TimingPoint>,
+    items: Vec<T>,
+}
+
+impl<T> Default for SortedVec<T> {
+    fn default() -> Self {
+        Self {
+            times: vec![],
+            items: vec![],
+        }
+    }
+}
+
+impl<T> SortedVec<T> {
+    /// Index range of the objects at times within `range`.
+    fn indices(&self, range: Range<TimingPoint>) -> Range<usize> {
+        let start = self.times.partition_point(|time| *time < range.start);
+        let end = self.times.partition_point(|time| *time < range.end);
+        start..end.max(start)
+    }
+
+    fn groups_of(&self, indices: Range<usize>) -> Groups<'_, T> {
+        Groups {
+            times: &self.times,
+            items: &self.items,
+            indices,
+        }
+    }
+}
+
+impl<T> sealed::Sealed for SortedVec<T> {}
+
+impl<T> TimeStore<T> for SortedVec<T> {
+    type Iter<'a>
+        = slice::Iter<'a, T>
+    where
+        T: 'a;
+    type IntoIter = vec::IntoIter<T>;
+
+    fn from_items(items: impl IntoIterator<Item = (TimingPoint, T)>) -> Self {
+        // A single stable sort instead of inserting objects one by one, which is quadratic when
+        // they come in reverse order.
+        let mut pairs = items.into_iter().collect::<Vec<_>>();
+        pairs.sort_by_key(|(time, _)| *time);
+        let (times, items) = pairs.into_iter().unzip();
+        Self { times, items }
+    }
+
+    fn push(&mut self, time: TimingPoint, item: T) {
+        // Objects mostly come in time order, appending is the common case.
+        if self.times.last().is_none_or(|last| *last <= time) {
+            self.times.push(time);
+            self.items.push(item);
+        } else {
+            let index = self.times.partition_point(|other| *other <= time);
+            self.times.insert(index, time);
+            self.items.insert(index, item);
+        }
+    }
+
+    fn combine(&mut self, other: Self) {
+        let mut merged = Self {
+            times: Vec::with_capacity(self.item_count() + other.item_count()),
+            items: Vec::with_capacity(self.item_count() + other.item_count()),
+        };
+        let mut ours = std::mem::take(self).into_pairs().peekable();
+        let mut theirs = other.into_pairs().peekable();
+        loop {
+            let take_ours = match (ours.peek(), theirs.peek()) {
+                (Some((ours, _)), Some((theirs, _))) => ours <= theirs,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+            let (time, item) = if take_ours {
+                ours.next().unwrap()
+            } else {
+                theirs.next().unwrap()
+            };
+            merged.times.push(time);
+            merged.items.push(item);
+        }
+        *self = merged;
+    }
+
+    fn item_count(&self) -> usize {
+        self.items.len()
+    }
+
+    fn items(&self) -> Self::Iter<'_> {
+        self.items.iter()
+    }
+
+    fn into_items(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+
+    fn groups<'a>(&'a self) -> impl DoubleEndedIterator<Item = (TimingPoint, &'a [T])>
+    where
+        T: 'a,
+    {
+        self.groups_of(0..self.items.len())
+    }
+
+    fn groups_in<'a>(
+        &'a self,
+        range: Range<TimingPoint>,
+    ) -> impl DoubleEndedIterator<Item = (TimingPoint, &'a [T])>
+    where
+        T: 'a,
+    {
+        self.groups_of(self.indices(range))
+    }
+
+    fn last_time(&self) -> Option<TimingPoint> {
+        self.times.last().copied()
+    }
+
+    #[cfg(feature = "rayon")]
+    fn par_items<'a>(&'a self) -> impl ParallelIterator<Item = &'a T>
+    where
+        T: Sync + 'a,
+    {
+        self.items.par_iter()
+    }
+}
+
+impl<T> SortedVec<T> {
+    fn into_pairs(self) -> impl Iterator<Item = (TimingPoint, T)> {
+        self.times.into_iter().zip(self.items)
+    }
+}
+
+/// Runs of objects sharing a time in a [`SortedVec`].
+struct Groups<'a, T> {
+    times: &'a [TimingPoint],
+    items: &'a [T],
+    indices: Range<usize>,
+}
+
+impl<'a, T> Iterator for Groups<'a, T> {
+    type Item = (TimingPoint, &'a [T]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.indices.start;
+        let time = *self.times[..self.indices.end].get(start)?;
+        let len = self.times[start..self.indices.end]
+            .iter()
+            .take_while(|other| **other == time)
+            .count();
+        self.indices.start += len;
+        Some((time, &self.items[start..start + len]))
+    }
+}
+
+impl<T> DoubleEndedIterator for Groups<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let end = self.indices.end;
+        if end <= self.indices.start {
+            return None;
+        }
+        let time = self.times[end - 1];
+        let len = self.times[self.indices.start..end]
+            .iter()
+            .rev()
+            .take_while(|other| **other == time)
+            .count();
+        self.indices.end -= len;
+        Some((time, &self.items[end - len..end]))
+    }
+}
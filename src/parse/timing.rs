@@ -0,0 +1,235 @@
+use super::{
+    analysis::{Composition, Ogkr, Resolution, TimingPoint},
+    Header,
+};
+
+/// Converts between chart timing points and milliseconds, following BPM and meter changes.
+#[derive(Clone, Debug)]
+pub struct TimingConverter {
+    resolution: Resolution,
+    /// Sorted by tick, the first segment always starts at tick 0.
+    segments: Vec<TempoSegment>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct TempoSegment {
+    tick: u64,
+    ms: f64,
+    ms_per_measure: f64,
+}
+
+impl TempoSegment {
+    fn ms_after(&self, ticks: u64, resolution: Resolution) -> f64 {
+        // Multiply before dividing to stay exact for whole-millisecond measures.
+        ticks as f64 * self.ms_per_measure / resolution.ticks.max(1) as f64
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Tempo {
+    bpm: f64,
+    num_beats: u32,
+    note_value: u32,
+}
+
+impl Tempo {
+    fn ms_per_measure(&self) -> f64 {
+        // A beat is always a quarter note, a measure lasts `num_beats` notes of `note_value`.
+        60_000.0 * 4.0 * self.num_beats as f64 / (self.bpm * self.note_value as f64)
+    }
+}
+
+impl TimingConverter {
+    pub const DEFAULT_BPM: f64 = 120.0;
+
+    pub fn new(header: &Header, composition: &Composition) -> Self {
+        let resolution = Resolution::from_header(header);
+
+        let mut tempo = Tempo {
+            bpm: header
+                .bpm_definition
+                .map(|definition| f32::from_bits(definition.first) as f64)
+                .filter(|bpm| valid_bpm(*bpm))
+                .unwrap_or(Self::DEFAULT_BPM),
+            num_beats: 4,
+            note_value: 4,
+        };
+        if let Some(meter) = header.meter_definition {
+            if meter.num_beats > 0 && meter.note_value > 0 {
+                tempo.num_beats = meter.num_beats;
+                tempo.note_value = meter.note_value;
+            }
+        }
+
+        let mut times = composition
+            .bpm_changes
+            .keys()
+            .chain(composition.meter_changes.keys())
+            .copied()
+            .collect::<Vec<_>>();
+        times.sort();
+        times.dedup();
+        // Offsets can exceed a measure, so timing point order is not necessarily tick order.
+        times.sort_by_key(|time| resolution.ticks_at(*time));
+
+        let mut segments = vec![TempoSegment {
+            tick: 0,
+            ms: 0.0,
+            ms_per_measure: tempo.ms_per_measure(),
+        }];
+        for time in times {
+            // Simultaneous BPM changes are applied in chart order, the last one wins.
+            if let Some(bpm_change) = composition
+                .bpm_changes
+                .get(&time)
+                .and_then(|changes| changes.last())
+            {
+                let bpm = f32::from_bits(bpm_change.bpm) as f64;
+                if valid_bpm(bpm) {
+                    tempo.bpm = bpm;
+                }
+            }
+            if let Some(meter_change) = composition.meter_changes.get(&time) {
+                if meter_change.num_beats > 0 && meter_change.note_value > 0 {
+                    tempo.num_beats = meter_change.num_beats;
+                    tempo.note_value = meter_change.note_value;
+                }
+            }
+
+            let tick = resolution.ticks_at(time);
+            let previous = segments[segments.len() - 1];
+            let segment = TempoSegment {
+                tick,
+                ms: previous.ms + previous.ms_after(tick - previous.tick, resolution),
+                ms_per_measure: tempo.ms_per_measure(),
+            };
+            if previous.tick == tick {
+                *segments.last_mut().unwrap() = segment;
+            } else {
+                segments.push(segment);
+            }
+        }
+
+        Self {
+            resolution,
+            segments,
+        }
+    }
+
+    pub fn resolution(&self) -> Resolution {
+        self.resolution
+    }
+
+    /// Milliseconds from the start of the chart at `time`.
+    pub fn to_ms(&self, time: TimingPoint) -> f64 {
+        let tick = self.resolution.ticks_at(time);
+        let index = self
+            .segments
+            .partition_point(|segment| segment.tick <= tick)
+            .saturating_sub(1);
+        let segment = &self.segments[index];
+
+        segment.ms + segment.ms_after(tick - segment.tick, self.resolution)
+    }
+
+    /// Closest timing point to `ms` milliseconds from the start of the chart, clamped to the
+    /// start of the chart.
+    pub fn from_ms(&self, ms: f64) -> TimingPoint {
+        let index = self
+            .segments
+            .partition_point(|segment| segment.ms <= ms)
+            .saturating_sub(1);
+        let segment = &self.segments[index];
+
+        let ticks_per_measure = self.resolution.ticks.max(1) as u64;
+        let tick = segment.tick as f64
+            + (ms - segment.ms) * ticks_per_measure as f64 / segment.ms_per_measure;
+        self.resolution.time_of(tick.round().max(0.0) as u64)
+    }
+}
+
+/// Time range during which notes scroll at a constant speed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScrollSegment {
+    pub start_ms: f64,
+    pub end_ms: f64,
+    /// Multiplier of the base scroll speed, 1 outside of soflans.
+    pub speed_multiplier: f32,
+}
+
+/// Scroll speed over the whole chart, as contiguous segments of constant speed.
+///
+/// Soflan ranges are in ticks, BPM changes decide where they start and end in milliseconds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScrollTimeline {
+    /// Sorted, contiguous and starting at 0 ms, adjacent segments have different speeds.
+    segments: Vec<ScrollSegment>,
+}
+
+impl ScrollTimeline {
+    pub fn new(ogkr: &Ogkr) -> Self {
+        let timing = ogkr.timing();
+        let resolution = ogkr.track.resolution;
+
+        let chart_end = resolution
+            .ticks_at(TimingPoint::new(ogkr.extra_metadata.num_measures, 0))
+            .saturating_add(resolution.ticks.max(1) as u64);
+        let mut ticks = vec![0, chart_end];
+        for soflan in ogkr.composition.all_soflans() {
+            ticks.push(resolution.ticks_at(soflan.time));
+            ticks.push(soflan.end_ticks(resolution));
+        }
+        ticks.sort();
+        ticks.dedup();
+
+        let mut segments: Vec<ScrollSegment> = vec![];
+        for range in ticks.windows(2) {
+            let start = resolution.time_of(range[0]);
+            let speed_multiplier = ogkr.effective_speed_at(start);
+            let end_ms = timing.to_ms(resolution.time_of(range[1]));
+            match segments.last_mut() {
+                Some(last) if last.speed_multiplier == speed_multiplier => last.end_ms = end_ms,
+                _ => segments.push(ScrollSegment {
+                    start_ms: timing.to_ms(start),
+                    end_ms,
+                    speed_multiplier,
+                }),
+            }
+        }
+
+        Self { segments }
+    }
+
+    pub fn segments(&self) -> &[ScrollSegment] {
+        &self.segments
+    }
+
+    /// Scrolled distance at `ms` in milliseconds at base speed, for placing notes relative to
+    /// each other. Speed stays the same as the last segment after the chart ends.
+    pub fn position_at(&self, ms: f64) -> f64 {
+        let mut position = 0.0;
+        for (i, segment) in self.segments.iter().enumerate() {
+            if ms <= segment.start_ms {
+                break;
+            }
+            let end_ms = if i + 1 == self.segments.len() {
+                ms
+            } else {
+                ms.min(segment.end_ms)
+            };
+            position += (end_ms - segment.start_ms) * segment.speed_multiplier as f64;
+        }
+        position
+    }
+}
+
+impl Ogkr {
+    /// Returns the scroll speed timeline of this chart.
+    pub fn scroll_timeline(&self) -> ScrollTimeline {
+        ScrollTimeline::new(self)
+    }
+}
+
+pub(crate) fn valid_bpm(bpm: f64) -> bool {
+    bpm.is_finite() && bpm > 0.0
+}
@@ -0,0 +1,151 @@
+//! High-level bullet patterns expanded into bullet commands, for authoring dense bullet sections
+//! with [`ChartBuilder::bullet_pattern`].
+//!
+//! Intervals are counted in beats of the meter at the start of the pattern, so patterns stay on
+//! the beat whatever the tick resolution is.
+
+use crate::{
+    build::{BuildError, ChartBuilder, Result},
+    generate::Spray,
+    lex::command::{
+        Bullet, BulletDamageType, BulletTarget, CommandTime, MeterDefinition, TickResolution,
+    },
+    parse::analysis::Resolution,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BulletPattern {
+    /// `count` bullets shot at once, spread evenly from `x - spread` to `x + spread`.
+    Fan { x: i32, spread: i32, count: u32 },
+    /// `count` bullets shot one after another every `interval` beats, each one `drift` further
+    /// than the previous one.
+    Stream {
+        x: i32,
+        count: u32,
+        interval: f64,
+        drift: i32,
+    },
+    /// `bursts` bursts shot every `interval` beats, each of `count` bullets `spacing` beats apart.
+    /// Requires a palette targeting the player.
+    AimedBurst {
+        x: i32,
+        count: u32,
+        spacing: f64,
+        bursts: u32,
+        interval: f64,
+    },
+}
+
+impl BulletPattern {
+    /// Times and horizontal positions of the bullets of the pattern starting at `start`, sorted
+    /// by time.
+    pub fn expand(
+        &self,
+        start: CommandTime,
+        resolution: TickResolution,
+        meter: MeterDefinition,
+    ) -> Vec<(CommandTime, i32)> {
+        let ticks_per_beat = resolution.ticks_per_beat(meter);
+        let start = resolution.ticks_at(start);
+        let at = |beats: f64| resolution.time_at(start + (beats * ticks_per_beat).round() as u64);
+
+        match *self {
+            Self::Fan { x, spread, count } => Spray {
+                from_x: x - spread,
+                to_x: x + spread,
+                count,
+            }
+            .positions()
+            .map(|x| (at(0.0), x))
+            .collect(),
+            Self::Stream {
+                x,
+                count,
+                interval,
+                drift,
+            } => (0..count)
+                .map(|i| (at(i as f64 * interval), x + drift * i as i32))
+                .collect(),
+            Self::AimedBurst {
+                x,
+                count,
+                spacing,
+                bursts,
+                interval,
+            } => (0..bursts)
+                .flat_map(|burst| {
+                    (0..count).map(move |i| burst as f64 * interval + i as f64 * spacing)
+                })
+                .map(|beats| (at(beats), x))
+                .collect(),
+        }
+    }
+}
+
+impl ChartBuilder {
+    /// Adds the bullets of `pattern` starting at `start`, shot with `palette_id`. `damage_type` may
+    /// only be omitted for palettes using the legacy syntax, see [`ChartBuilder::bullet`].
+    pub fn bullet_pattern(
+        &mut self,
+        palette_id: &str,
+        start: CommandTime,
+        pattern: &BulletPattern,
+        damage_type: Option<BulletDamageType>,
+    ) -> Result<&mut Self> {
+        let raw = self.raw();
+        let palette = raw
+            .bullet_pallete_list
+            .iter()
+            .find(|palette| palette.id == palette_id)
+            .ok_or_else(|| BuildError::UnknownPaletteId(palette_id.to_string()))?;
+        if matches!(pattern, BulletPattern::AimedBurst { .. })
+            && palette.target != BulletTarget::Player
+        {
+            return Err(BuildError::UnaimedPalette(palette_id.to_string()));
+        }
+
+        let key = |time: CommandTime| (time.measure, time.offset);
+        let meter = raw
+            .composition
+            .meter_changes
+            .iter()
+            .filter(|change| key(change.time) <= key(start))
+            .max_by_key(|change| key(change.time))
+            .map(|change| MeterDefinition {
+                num_beats: change.num_beats,
+                note_value: change.note_value,
+            })
+            .or(raw.header.meter_definition)
+            .unwrap_or(MeterDefinition {
+                num_beats: 4,
+                note_value: 4,
+            });
+        let resolution = Resolution::from_header(&raw.header).tick_resolution();
+
+        for (time, x_position) in pattern.expand(start, resolution, meter) {
+            self.bullet(palette_id, time, x_position, damage_type)?;
+        }
+        Ok(self)
+    }
+}
+
+/// Bullet commands of `pattern`, for charts assembled without [`ChartBuilder`].
+pub fn bullets(
+    pattern: &BulletPattern,
+    palette_id: &str,
+    start: CommandTime,
+    resolution: TickResolution,
+    meter: MeterDefinition,
+    damage_type: Option<BulletDamageType>,
+) -> Vec<Bullet> {
+    pattern
+        .expand(start, resolution, meter)
+        .into_iter()
+        .map(|(time, x_position)| Bullet {
+            pallete_id: palette_id.to_string(),
+            time,
+            x_position,
+            damage_type,
+        })
+        .collect()
+}
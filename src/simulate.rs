@@ -0,0 +1,60 @@
+//! Chart playback helpers for overlays and simulators, assuming a perfect play.
+
+use crate::parse::{
+    analysis::{Ogkr, TrackPosition},
+    query::NoteRef,
+};
+
+/// Object adding to the combo, see [`combo_timeline`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ComboObject {
+    /// Milliseconds from the start of the chart.
+    pub ms: f64,
+    /// Start position for holds.
+    pub position: TrackPosition,
+    /// Combo reached when hitting this object, counting from 1.
+    pub combo: u32,
+}
+
+/// Cumulative combo over a chart, see [`combo_timeline`].
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct ComboTimeline {
+    /// Sorted by time.
+    pub objects: Vec<ComboObject>,
+}
+
+impl ComboTimeline {
+    /// Same as the notes total of [`Ogkr::count_totals`].
+    pub fn max_combo(&self) -> u32 {
+        self.objects.len() as u32
+    }
+
+    /// Combo reached by `ms` included.
+    pub fn combo_at(&self, ms: f64) -> u32 {
+        self.objects.partition_point(|object| object.ms <= ms) as u32
+    }
+
+    /// Objects left to hit after `ms`, for "notes remaining" displays.
+    pub fn remaining_at(&self, ms: f64) -> u32 {
+        self.max_combo() - self.combo_at(ms)
+    }
+}
+
+/// Lists the taps, holds and flicks of the chart with the combo reached at each of them. Holds
+/// count once, at their start, and bells do not count.
+pub fn combo_timeline(ogkr: &Ogkr) -> ComboTimeline {
+    let timing = ogkr.timing();
+    let objects = ogkr
+        .notes
+        .all()
+        .filter(|note| !matches!(note, NoteRef::Bell(_)))
+        .zip(1..)
+        .map(|(note, combo)| ComboObject {
+            ms: timing.to_ms(note.time()),
+            position: note.position(),
+            combo,
+        })
+        .collect();
+
+    ComboTimeline { objects }
+}
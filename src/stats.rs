@@ -0,0 +1,1000 @@
+//! Summaries of parsed charts.
+
+use std::{collections::BTreeMap, ops::Range};
+
+use crate::{
+    lex::command::EnemyWave,
+    parse::{
+        analysis::{LaneId, LaneType, Ogkr, TimingPoint, TrackPosition},
+        query::NoteRef,
+        DamageValues,
+    },
+};
+
+/// Object counts and damage of a single enemy wave.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct WaveStats {
+    pub taps: usize,
+    pub holds: usize,
+    pub flicks: usize,
+    pub bells: usize,
+    pub bullets: usize,
+    pub beams: usize,
+
+    /// Damage taken when getting hit by every bullet, and every beam once.
+    pub max_damage: f32,
+}
+
+impl WaveStats {
+    /// Number of taps, holds and flicks.
+    pub fn notes(&self) -> usize {
+        self.taps + self.holds + self.flicks
+    }
+}
+
+/// [`WaveStats`] for every enemy wave.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct PerWaveStats {
+    pub wave_1: WaveStats,
+    pub wave_2: WaveStats,
+    pub boss: WaveStats,
+}
+
+impl PerWaveStats {
+    pub fn get(&self, wave: EnemyWave) -> &WaveStats {
+        match wave {
+            EnemyWave::Wave1 => &self.wave_1,
+            EnemyWave::Wave2 => &self.wave_2,
+            EnemyWave::Boss => &self.boss,
+        }
+    }
+
+    fn get_mut(&mut self, wave: EnemyWave) -> &mut WaveStats {
+        match wave {
+            EnemyWave::Wave1 => &mut self.wave_1,
+            EnemyWave::Wave2 => &mut self.wave_2,
+            EnemyWave::Boss => &mut self.boss,
+        }
+    }
+}
+
+/// Summarizes notes, bells, bullets and damage of every enemy wave. Holds and beams count towards
+/// the wave they start in.
+pub fn per_wave(ogkr: &Ogkr) -> PerWaveStats {
+    per_wave_with_damage(ogkr, &ogkr.header.damage_values)
+}
+
+/// Same as [`per_wave`], using `damage` instead of the damage values of the chart, e.g.
+/// [`DamageValues::arcade_defaults`] for charts not specifying them.
+pub fn per_wave_with_damage(ogkr: &Ogkr, damage: &DamageValues) -> PerWaveStats {
+    let waves = &ogkr.enemy_wave_assignment;
+    let mut stats = PerWaveStats::default();
+
+    for tap in ogkr.notes.all_taps() {
+        stats.get_mut(waves.wave_at(tap.position.time)).taps += 1;
+    }
+    for hold in ogkr.notes.all_holds() {
+        stats.get_mut(waves.wave_at(hold.start.time)).holds += 1;
+    }
+    for flick in ogkr.notes.all_flicks() {
+        stats.get_mut(waves.wave_at(flick.position.time)).flicks += 1;
+    }
+    for bell in ogkr.notes.all_bells() {
+        stats.get_mut(waves.wave_at(bell.position.time)).bells += 1;
+    }
+    for bullet in ogkr.bullets.all_bullets() {
+        let wave = stats.get_mut(waves.wave_at(bullet.position.time));
+        wave.bullets += 1;
+        wave.max_damage += damage.bullet(bullet.damage_type);
+    }
+    let beam_starts = ogkr
+        .track
+        .beams_data
+        .values()
+        .map(|beam| beam.start.position.time)
+        .chain(
+            ogkr.track
+                .oblique_beams_data
+                .values()
+                .map(|beam| beam.start.position.time),
+        );
+    for time in beam_starts {
+        let wave = stats.get_mut(waves.wave_at(time));
+        wave.beams += 1;
+        wave.max_damage += damage.beam;
+    }
+
+    stats
+}
+
+/// Number of objects within a heatmap cell.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct DensityCell {
+    /// Taps, holds and flicks.
+    pub notes: u32,
+    pub bells: u32,
+    pub bullets: u32,
+}
+
+impl DensityCell {
+    pub fn total(&self) -> u32 {
+        self.notes + self.bells + self.bullets
+    }
+}
+
+/// 2D histogram of object density over time and horizontal position.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DensityHeatmap {
+    pub ticks_per_bucket: u32,
+    pub x_bucket_width: f32,
+    /// Horizontal position where the first column starts, in track units.
+    pub x_min: f32,
+    /// Cells containing objects, keyed by time bucket then x bucket. Empty cells are left out,
+    /// so far apart objects do not need a cell for everything in between.
+    pub cells: BTreeMap<(u64, u64), DensityCell>,
+}
+
+impl DensityHeatmap {
+    /// Cell at the given buckets, empty if no object lies in it.
+    pub fn get(&self, time_bucket: u64, x_bucket: u64) -> DensityCell {
+        self.cells
+            .get(&(time_bucket, x_bucket))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Number of time buckets up to the last one containing objects.
+    pub fn rows(&self) -> u64 {
+        self.cells
+            .last_key_value()
+            .map_or(0, |(&(row, _), _)| row.saturating_add(1))
+    }
+
+    /// Number of x buckets up to the rightmost one containing objects.
+    pub fn columns(&self) -> u64 {
+        self.cells
+            .keys()
+            .map(|&(_, column)| column.saturating_add(1))
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Counts notes, bells and bullets into buckets of `ticks_per_bucket` ticks and `x_bucket_width`
+/// track units. Holds count at their start.
+pub fn density_heatmap(ogkr: &Ogkr, ticks_per_bucket: u32, x_bucket_width: f32) -> DensityHeatmap {
+    #[derive(Clone, Copy)]
+    enum Kind {
+        Note,
+        Bell,
+        Bullet,
+    }
+
+    let ticks_per_bucket = ticks_per_bucket.max(1);
+    let x_bucket_width = if x_bucket_width > 0.0 {
+        x_bucket_width
+    } else {
+        1.0
+    };
+    let resolution = ogkr.track.resolution;
+
+    let notes = ogkr
+        .notes
+        .all_taps()
+        .map(|tap| tap.position)
+        .chain(ogkr.notes.all_holds().map(|hold| hold.start))
+        .chain(ogkr.notes.all_flicks().map(|flick| flick.position))
+        .map(|position| (Kind::Note, position));
+    let bells = ogkr
+        .notes
+        .all_bells()
+        .map(|bell| (Kind::Bell, bell.position));
+    let bullets = ogkr
+        .bullets
+        .all_bullets()
+        .map(|bullet| (Kind::Bullet, bullet.position));
+    let objects = notes
+        .chain(bells)
+        .chain(bullets)
+        .map(|(kind, position)| {
+            (
+                kind,
+                resolution.ticks_at(position.time) / ticks_per_bucket as u64,
+                resolution.x_of(position.x),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let x_min = objects
+        .iter()
+        .map(|(_, _, x)| *x)
+        .reduce(f32::min)
+        .map_or(0.0, |x| (x / x_bucket_width).floor() * x_bucket_width);
+    let x_bucket = |x: f32| ((x - x_min) / x_bucket_width) as u64;
+
+    let mut cells = BTreeMap::<_, DensityCell>::new();
+    for (kind, row, x) in objects {
+        let cell = cells.entry((row, x_bucket(x))).or_default();
+        match kind {
+            Kind::Note => cell.notes += 1,
+            Kind::Bell => cell.bells += 1,
+            Kind::Bullet => cell.bullets += 1,
+        }
+    }
+
+    DensityHeatmap {
+        ticks_per_bucket,
+        x_bucket_width,
+        x_min,
+        cells,
+    }
+}
+
+/// Grids most charts are written in, in measure subdivisions: quarter notes up to 64th notes,
+/// along with triplets.
+pub const COMMON_SUBDIVISIONS: [u32; 8] = [4, 8, 12, 16, 24, 32, 48, 64];
+
+/// Time of notes lying on none of the checked grids.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct OffGridNote {
+    pub time: TimingPoint,
+    /// Closest point on the finest checked grid.
+    pub snapped: TimingPoint,
+    /// Notes at `time`, holds count at their start and at their end.
+    pub notes: u32,
+}
+
+/// Finds the times of notes and bells lying on none of the grids of `subdivisions`, sorted by
+/// time. See [`COMMON_SUBDIVISIONS`] for typical grids.
+pub fn off_grid_notes(ogkr: &Ogkr, subdivisions: &[u32]) -> Vec<OffGridNote> {
+    let resolution = ogkr.track.resolution;
+
+    let mut times = BTreeMap::<TimingPoint, u32>::new();
+    for time in note_times(ogkr) {
+        *times.entry(time).or_default() += 1;
+    }
+
+    let finest = subdivisions.iter().copied().max().unwrap_or(1);
+    times
+        .into_iter()
+        .filter(|(time, _)| {
+            !subdivisions
+                .iter()
+                .any(|&subdivision| time.is_on_grid(subdivision, resolution))
+        })
+        .map(|(time, notes)| OffGridNote {
+            time,
+            snapped: time.snap_to(finest, resolution),
+            notes,
+        })
+        .collect()
+}
+
+/// Times of notes and bells, holds have both their start and end.
+fn note_times(ogkr: &Ogkr) -> impl Iterator<Item = TimingPoint> + '_ {
+    let notes = &ogkr.notes;
+    notes
+        .all_taps()
+        .map(|tap| tap.position.time)
+        .chain(
+            notes
+                .all_holds()
+                .flat_map(|hold| [hold.start.time, hold.end.time]),
+        )
+        .chain(notes.all_flicks().map(|flick| flick.position.time))
+        .chain(notes.all_bells().map(|bell| bell.position.time))
+}
+
+/// Rhythmic grid the notes of a measure are written in, in measure subdivisions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Grid {
+    /// Power of two subdivisions, e.g. 16 for sixteenth notes in 4/4.
+    Straight(u32),
+    /// Subdivisions by 3 times a power of two, e.g. 12 for eighth note triplets in 4/4.
+    Triplet(u32),
+    /// Some notes only lie on the straight grid and others only on the triplet one.
+    Mixed { straight: u32, triplet: u32 },
+    /// Some notes lie on neither grid up to [`Grid::STRAIGHT`] and [`Grid::TRIPLET`].
+    Irregular,
+}
+
+impl Grid {
+    /// Straight subdivisions considered, from coarsest to finest.
+    pub const STRAIGHT: [u32; 7] = [1, 2, 4, 8, 16, 32, 64];
+    /// Triplet subdivisions considered, from coarsest to finest.
+    pub const TRIPLET: [u32; 5] = [3, 6, 12, 24, 48];
+
+    /// Subdivision to snap to for editing the measure, the finest one containing every note.
+    /// `None` for irregular measures.
+    pub fn subdivision(&self) -> Option<u32> {
+        match *self {
+            Self::Straight(subdivision) | Self::Triplet(subdivision) => Some(subdivision),
+            Self::Mixed { straight, triplet } => Some(lcm(straight, triplet)),
+            Self::Irregular => None,
+        }
+    }
+}
+
+fn lcm(a: u32, b: u32) -> u32 {
+    let gcd = |mut a: u32, mut b: u32| {
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        a
+    };
+    a / gcd(a, b) * b
+}
+
+/// Infers the grid of every measure containing notes or bells, keyed by measure.
+pub fn measure_grids(ogkr: &Ogkr) -> BTreeMap<u32, Grid> {
+    let resolution = ogkr.track.resolution;
+    let mut measures = BTreeMap::<u32, Vec<TimingPoint>>::new();
+    for time in note_times(ogkr) {
+        measures.entry(time.measure).or_default().push(time);
+    }
+
+    let coarsest = |time: &TimingPoint, subdivisions: &[u32]| {
+        subdivisions
+            .iter()
+            .copied()
+            .find(|&subdivision| time.is_on_grid(subdivision, resolution))
+    };
+    measures
+        .into_iter()
+        .map(|(measure, times)| {
+            let straight = times
+                .iter()
+                .map(|time| coarsest(time, &Grid::STRAIGHT))
+                .try_fold(1, |finest, subdivision| Some(finest.max(subdivision?)));
+            if let Some(straight) = straight {
+                return (measure, Grid::Straight(straight));
+            }
+
+            // Fine triplet grids contain straight ones, so the triplet grid is only as fine as the
+            // notes off the straight grids need, the other notes must fit a straight grid.
+            let triplet = times
+                .iter()
+                .filter(|time| coarsest(time, &Grid::STRAIGHT).is_none())
+                .map(|time| coarsest(time, &Grid::TRIPLET))
+                .try_fold(Grid::TRIPLET[0], |finest, subdivision| {
+                    Some(finest.max(subdivision?))
+                });
+            let Some(triplet) = triplet else {
+                return (measure, Grid::Irregular);
+            };
+            let straight = times
+                .iter()
+                .filter(|time| !time.is_on_grid(triplet, resolution))
+                .map(|time| coarsest(time, &Grid::STRAIGHT))
+                .try_fold(None, |finest: Option<u32>, subdivision| {
+                    Some(finest.max(Some(subdivision?)))
+                });
+            let grid = match straight {
+                Some(Some(straight)) => Grid::Mixed { straight, triplet },
+                Some(None) => Grid::Triplet(triplet),
+                None => Grid::Irregular,
+            };
+            (measure, grid)
+        })
+        .collect()
+}
+
+/// Thresholds used by [`note_spacing`].
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpacingOptions {
+    /// Notes closer than this horizontally, in track units, are in the same region.
+    pub region_width: f32,
+    /// Notes in the same region closer than this in time, in milliseconds, are stacked.
+    pub stack_ms: f64,
+}
+
+impl Default for SpacingOptions {
+    fn default() -> Self {
+        Self {
+            region_width: 4.0,
+            stack_ms: 30.0,
+        }
+    }
+}
+
+impl SpacingOptions {
+    pub fn region_width(mut self, region_width: f32) -> Self {
+        self.region_width = region_width;
+        self
+    }
+
+    pub fn stack_ms(mut self, stack_ms: f64) -> Self {
+        self.stack_ms = stack_ms;
+        self
+    }
+}
+
+/// Two objects drawn on top of each other.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StackedPair {
+    pub first: TrackPosition,
+    pub second: TrackPosition,
+    pub gap_ms: f64,
+}
+
+/// Result of [`note_spacing`].
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct SpacingReport {
+    /// Smallest time between a note and the next one in the same region, `None` if no two notes
+    /// share a region.
+    pub min_gap_ms: Option<f64>,
+    /// Pairs of consecutive notes of a region closer than [`SpacingOptions::stack_ms`], sorted by
+    /// the time of the first one.
+    pub stacked: Vec<StackedPair>,
+}
+
+/// Measures how close consecutive taps, hold starts, flicks and bells are within horizontal
+/// regions of the track, flagging the ones too close to be read apart.
+pub fn note_spacing(ogkr: &Ogkr, options: &SpacingOptions) -> SpacingReport {
+    let timing = ogkr.timing();
+    let resolution = ogkr.track.resolution;
+    let notes = &ogkr.notes;
+
+    let mut objects = notes
+        .all_taps()
+        .map(|tap| tap.position)
+        .chain(notes.all_holds().map(|hold| hold.start))
+        .chain(notes.all_flicks().map(|flick| flick.position))
+        .chain(notes.all_bells().map(|bell| bell.position))
+        .map(|position| {
+            (
+                timing.to_ms(position.time),
+                resolution.x_of(position.x),
+                position,
+            )
+        })
+        .collect::<Vec<_>>();
+    objects.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut report = SpacingReport::default();
+    for (i, &(ms, x, first)) in objects.iter().enumerate() {
+        let Some(&(next_ms, _, second)) = objects[i + 1..]
+            .iter()
+            .find(|(_, next_x, _)| (next_x - x).abs() < options.region_width)
+        else {
+            continue;
+        };
+        let gap_ms = next_ms - ms;
+        report.min_gap_ms = Some(report.min_gap_ms.map_or(gap_ms, |min| min.min(gap_ms)));
+        if gap_ms < options.stack_ms {
+            report.stacked.push(StackedPair {
+                first,
+                second,
+                gap_ms,
+            });
+        }
+    }
+    report
+}
+
+/// Limits used by [`hand_conflicts`].
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct HandLimits {
+    /// Notes that can be pressed or held at the same time.
+    pub hands: u32,
+    /// Whether notes on walls need a hand, players using the side buttons with the same hands as
+    /// the main ones.
+    pub count_side_notes: bool,
+    /// Whether flicks need a hand.
+    pub count_flicks: bool,
+}
+
+impl Default for HandLimits {
+    fn default() -> Self {
+        Self {
+            hands: 2,
+            count_side_notes: true,
+            count_flicks: true,
+        }
+    }
+}
+
+impl HandLimits {
+    pub fn hands(mut self, hands: u32) -> Self {
+        self.hands = hands;
+        self
+    }
+
+    pub fn count_side_notes(mut self, count: bool) -> Self {
+        self.count_side_notes = count;
+        self
+    }
+
+    pub fn count_flicks(mut self, count: bool) -> Self {
+        self.count_flicks = count;
+        self
+    }
+}
+
+/// Moment needing more hands than allowed by [`HandLimits`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct HandConflict {
+    pub time: TimingPoint,
+    /// Notes starting at `time` plus holds still held from before.
+    pub notes: u32,
+}
+
+/// Finds the times at which more notes need to be hit than the player has hands. Holds occupy a
+/// hand until they end, a note may be hit exactly when a hold ends.
+pub fn hand_conflicts(ogkr: &Ogkr, limits: &HandLimits) -> Vec<HandConflict> {
+    let is_side = |lane_id: &LaneId| {
+        ogkr.track
+            .lanes_data
+            .get(lane_id)
+            .is_some_and(|lane| matches!(lane.lane_type, LaneType::WallLeft | LaneType::WallRight))
+    };
+    let counted = |lane_id: &LaneId| limits.count_side_notes || !is_side(lane_id);
+
+    let mut starts = BTreeMap::<TimingPoint, u32>::new();
+    for tap in ogkr.notes.all_taps().filter(|tap| counted(&tap.lane_id)) {
+        *starts.entry(tap.position.time).or_default() += 1;
+    }
+    let holds = ogkr
+        .notes
+        .all_holds()
+        .filter(|hold| counted(&hold.lane_id))
+        .collect::<Vec<_>>();
+    for hold in &holds {
+        *starts.entry(hold.start.time).or_default() += 1;
+    }
+    if limits.count_flicks {
+        for flick in ogkr.notes.all_flicks() {
+            *starts.entry(flick.position.time).or_default() += 1;
+        }
+    }
+
+    starts
+        .into_iter()
+        .filter_map(|(time, count)| {
+            let held = holds
+                .iter()
+                .filter(|hold| hold.start.time < time && time < hold.end.time)
+                .count() as u32;
+            let notes = count + held;
+            (notes > limits.hands).then_some(HandConflict { time, notes })
+        })
+        .collect()
+}
+
+/// What makes a measure hard to play, see [`measure_difficulty`]. Rates are per second so
+/// measures of different lengths compare.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct MeasureDifficulty {
+    pub measure: u32,
+    /// Taps, holds, flicks and bells per second.
+    pub density: f64,
+    /// Track units per second the player moves following the notes, counting the move from the
+    /// previous note for the first note of the measure.
+    pub lever_movement: f64,
+    /// Bullets reaching the player per second.
+    pub bullet_pressure: f64,
+}
+
+/// Breaks down what makes each measure containing notes, bells or bullets hard, sorted by
+/// measure, so editors can highlight the sections driving the difficulty of a chart.
+///
+/// The player is assumed to be at every note and bell in turn, holds moving them from their start
+/// to their end.
+pub fn measure_difficulty(ogkr: &Ogkr) -> Vec<MeasureDifficulty> {
+    let timing = ogkr.timing();
+    let resolution = ogkr.track.resolution;
+    let mut measures = BTreeMap::<u32, MeasureDifficulty>::new();
+
+    let mut stops = vec![];
+    for note in ogkr.notes.all() {
+        measures.entry(note.time().measure).or_default().density += 1.0;
+        match note {
+            NoteRef::Hold(hold) => stops.extend([hold.start, hold.end]),
+            note => stops.push(note.position()),
+        }
+    }
+    stops.sort_by_key(|position| position.time);
+    for pair in stops.windows(2) {
+        let distance = (resolution.x_of(pair[1].x) - resolution.x_of(pair[0].x)).abs();
+        measures
+            .entry(pair[1].time.measure)
+            .or_default()
+            .lever_movement += distance as f64;
+    }
+
+    for bullet in ogkr.bullets.all_bullets() {
+        measures
+            .entry(bullet.position.time.measure)
+            .or_default()
+            .bullet_pressure += 1.0;
+    }
+
+    let ticks_per_measure = resolution.ticks.max(1);
+    let mut measures = measures
+        .into_iter()
+        .map(|(measure, difficulty)| MeasureDifficulty {
+            measure,
+            ..difficulty
+        })
+        .collect::<Vec<_>>();
+    for measure in &mut measures {
+        let start = timing.to_ms(TimingPoint::new(measure.measure, 0));
+        // An offset of a whole measure, as the next measure may not be representable.
+        let end = timing.to_ms(TimingPoint::new(measure.measure, ticks_per_measure));
+        let seconds = (end - start) / 1000.0;
+        if seconds > 0.0 {
+            measure.density /= seconds;
+            measure.lever_movement /= seconds;
+            measure.bullet_pressure /= seconds;
+        }
+    }
+    measures
+}
+
+/// Limits used by [`wall_switches`].
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LeverLimits {
+    /// Fastest plausible lever movement, in track units per second.
+    pub max_speed: f32,
+}
+
+impl Default for LeverLimits {
+    fn default() -> Self {
+        Self { max_speed: 96.0 }
+    }
+}
+
+impl LeverLimits {
+    pub fn max_speed(mut self, max_speed: f32) -> Self {
+        self.max_speed = max_speed;
+        self
+    }
+}
+
+/// Move from a note on one wall to a note on the other one faster than allowed by
+/// [`LeverLimits`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WallSwitch {
+    /// Position of the first note, the end for holds.
+    pub from: TrackPosition,
+    pub to: TrackPosition,
+    pub gap_ms: f64,
+    /// Lever speed needed to make the move, in track units per second. Infinite for notes on
+    /// both walls at the same time.
+    pub required_speed: f32,
+}
+
+/// Finds consecutive notes on opposite walls, each requiring the player at that extreme of the
+/// track, that are too close in time to move between, sorted by time. A wall hold keeps the
+/// player on its wall until it ends.
+pub fn wall_switches(ogkr: &Ogkr, limits: &LeverLimits) -> Vec<WallSwitch> {
+    let timing = ogkr.timing();
+    let resolution = ogkr.track.resolution;
+    let side = |lane_type| match lane_type {
+        LaneType::WallLeft => Some(false),
+        LaneType::WallRight => Some(true),
+        _ => None,
+    };
+
+    let notes = &ogkr.notes;
+    let mut walls = notes
+        .all_taps()
+        .filter_map(|tap| Some((side(tap.lane_type)?, tap.position, tap.position)))
+        .chain(
+            notes
+                .all_holds()
+                .filter_map(|hold| Some((side(hold.lane_type)?, hold.start, hold.end))),
+        )
+        .map(|(is_right, start, end)| (timing.to_ms(start.time), is_right, start, end))
+        .collect::<Vec<_>>();
+    walls.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    walls
+        .windows(2)
+        .filter(|pair| pair[0].1 != pair[1].1)
+        .filter_map(|pair| {
+            let (_, _, _, from) = pair[0];
+            let (_, _, to, _) = pair[1];
+            let gap_ms = timing.to_ms(to.time) - timing.to_ms(from.time);
+            let distance = (resolution.x_of(to.x) - resolution.x_of(from.x)).abs();
+            let required_speed = if gap_ms > 0.0 {
+                (distance as f64 * 1000.0 / gap_ms) as f32
+            } else {
+                f32::INFINITY
+            };
+            (required_speed > limits.max_speed).then_some(WallSwitch {
+                from,
+                to,
+                gap_ms: gap_ms.max(0.0),
+                required_speed,
+            })
+        })
+        .collect()
+}
+
+/// Length distribution of the hold notes of a chart, see [`hold_durations`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct HoldDurations {
+    pub count: usize,
+    pub min_ticks: u64,
+    pub max_ticks: u64,
+    pub total_ticks: u64,
+    pub min_ms: f64,
+    pub max_ms: f64,
+    pub total_ms: f64,
+    pub ticks_per_bucket: u32,
+    /// Number of holds by length, bucket `i` counts holds lasting from `i * ticks_per_bucket`
+    /// up to `(i + 1) * ticks_per_bucket` ticks. Empty buckets are left out.
+    pub histogram: BTreeMap<u64, u32>,
+}
+
+impl HoldDurations {
+    pub fn mean_ticks(&self) -> f64 {
+        self.total_ticks as f64 / self.count as f64
+    }
+
+    pub fn mean_ms(&self) -> f64 {
+        self.total_ms / self.count as f64
+    }
+}
+
+/// Computes hold lengths in ticks and milliseconds, `None` if the chart has no holds.
+pub fn hold_durations(ogkr: &Ogkr, ticks_per_bucket: u32) -> Option<HoldDurations> {
+    let timing = ogkr.timing();
+    let resolution = ogkr.track.resolution;
+    let ticks_per_bucket = ticks_per_bucket.max(1);
+
+    let mut durations: Option<HoldDurations> = None;
+    for hold in ogkr.notes.all_holds() {
+        let ticks = resolution
+            .ticks_at(hold.end.time)
+            .saturating_sub(resolution.ticks_at(hold.start.time));
+        let ms = timing.to_ms(hold.end.time) - timing.to_ms(hold.start.time);
+
+        let durations = durations.get_or_insert(HoldDurations {
+            count: 0,
+            min_ticks: ticks,
+            max_ticks: ticks,
+            total_ticks: 0,
+            min_ms: ms,
+            max_ms: ms,
+            total_ms: 0.0,
+            ticks_per_bucket,
+            histogram: BTreeMap::new(),
+        });
+        durations.count += 1;
+        durations.min_ticks = durations.min_ticks.min(ticks);
+        durations.max_ticks = durations.max_ticks.max(ticks);
+        durations.total_ticks += ticks;
+        durations.min_ms = durations.min_ms.min(ms);
+        durations.max_ms = durations.max_ms.max(ms);
+        durations.total_ms += ms;
+
+        *durations
+            .histogram
+            .entry(ticks / ticks_per_bucket as u64)
+            .or_default() += 1;
+    }
+    durations
+}
+
+/// Difficulty a chart of a song is written for, from easiest to hardest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Difficulty {
+    Basic,
+    Advanced,
+    Expert,
+    Master,
+    Lunatic,
+}
+
+/// Stats of a single chart within a [`DifficultyComparison`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct DifficultyStats {
+    pub difficulty: Difficulty,
+    pub taps: usize,
+    pub holds: usize,
+    pub flicks: usize,
+    pub bells: usize,
+    pub bullets: usize,
+    /// Taps, holds and flicks per second, from the first to the last of them.
+    pub notes_per_second: f64,
+    /// Taps, holds and flicks in each measure containing any, keyed by measure.
+    pub notes_per_measure: BTreeMap<u32, usize>,
+}
+
+impl DifficultyStats {
+    /// Number of taps, holds and flicks.
+    pub fn notes(&self) -> usize {
+        self.taps + self.holds + self.flicks
+    }
+
+    /// Highest number of notes within a single measure.
+    pub fn peak_measure_notes(&self) -> usize {
+        self.notes_per_measure.values().copied().max().unwrap_or(0)
+    }
+}
+
+/// Measures written the same in two consecutive difficulties.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SharedSection {
+    pub lower: Difficulty,
+    pub higher: Difficulty,
+    pub measures: Range<u32>,
+}
+
+/// Result of [`compare_difficulties`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct DifficultyComparison {
+    /// Sorted by difficulty.
+    pub charts: Vec<DifficultyStats>,
+    /// Sorted by difficulty, then by measure.
+    pub shared_sections: Vec<SharedSection>,
+}
+
+impl DifficultyComparison {
+    pub fn get(&self, difficulty: Difficulty) -> Option<&DifficultyStats> {
+        self.charts
+            .iter()
+            .find(|stats| stats.difficulty == difficulty)
+    }
+}
+
+/// Compares the charts of a song, e.g. to check that note counts and density grow with the
+/// difficulty. Measures are aligned across charts, so the charts should share their timing.
+///
+/// Shared sections are runs of measures holding the same notes and bells, at the same times and
+/// positions, in two consecutive difficulties. Holds are compared by their start, measures without
+/// notes are never shared.
+pub fn compare_difficulties(charts: &[(Difficulty, &Ogkr)]) -> DifficultyComparison {
+    let mut charts = charts.to_vec();
+    charts.sort_by_key(|(difficulty, _)| *difficulty);
+
+    let stats = charts
+        .iter()
+        .map(|&(difficulty, ogkr)| difficulty_stats(difficulty, ogkr))
+        .collect();
+
+    let layouts = charts
+        .iter()
+        .map(|(_, ogkr)| measure_layouts(ogkr))
+        .collect::<Vec<_>>();
+    let mut shared_sections = vec![];
+    for (pair, layouts) in charts.windows(2).zip(layouts.windows(2)) {
+        let mut runs: Vec<Range<u32>> = vec![];
+        for (&measure, lower) in &layouts[0] {
+            if layouts[1].get(&measure) != Some(lower) {
+                continue;
+            }
+            // Only measures with notes are in the layouts, a run continues only if the previous
+            // measure was shared.
+            match runs.last_mut() {
+                Some(run) if run.end == measure => run.end = measure.saturating_add(1),
+                _ => runs.push(measure..measure.saturating_add(1)),
+            }
+        }
+        shared_sections.extend(runs.into_iter().map(|measures| SharedSection {
+            lower: pair[0].0,
+            higher: pair[1].0,
+            measures,
+        }));
+    }
+
+    DifficultyComparison {
+        charts: stats,
+        shared_sections,
+    }
+}
+
+fn difficulty_stats(difficulty: Difficulty, ogkr: &Ogkr) -> DifficultyStats {
+    let notes = &ogkr.notes;
+    let mut notes_per_measure = BTreeMap::new();
+    let (mut first, mut last) = (None, None);
+    for note in notes.all().filter(|note| !matches!(note, NoteRef::Bell(_))) {
+        let time = note.time();
+        *notes_per_measure.entry(time.measure).or_default() += 1;
+        first.get_or_insert(time);
+        last = Some(time);
+    }
+
+    let count = notes.all_taps().len() + notes.all_holds().len() + notes.all_flicks().len();
+    let notes_per_second = match first.zip(last) {
+        Some((first, last)) if last > first => {
+            let timing = ogkr.timing();
+            count as f64 * 1000.0 / (timing.to_ms(last) - timing.to_ms(first))
+        }
+        _ => 0.0,
+    };
+
+    DifficultyStats {
+        difficulty,
+        taps: notes.all_taps().len(),
+        holds: notes.all_holds().len(),
+        flicks: notes.all_flicks().len(),
+        bells: notes.all_bells().len(),
+        bullets: ogkr.bullets.all_bullets().len(),
+        notes_per_second,
+        notes_per_measure,
+    }
+}
+
+/// Kind, time, position and criticality of every note and bell, grouped by measure.
+type NoteLayout = Vec<(u8, TimingPoint, i32, i32, bool)>;
+
+fn measure_layouts(ogkr: &Ogkr) -> BTreeMap<u32, NoteLayout> {
+    let mut layouts = BTreeMap::<u32, NoteLayout>::new();
+    for note in ogkr.notes.all() {
+        let kind = match note {
+            NoteRef::Tap(_) => 0,
+            NoteRef::Hold(_) => 1,
+            NoteRef::Bell(_) => 2,
+            NoteRef::Flick(_) => 3,
+        };
+        let position = note.position();
+        layouts.entry(position.time.measure).or_default().push((
+            kind,
+            position.time,
+            position.x.position,
+            position.x.offset,
+            note.is_critical(),
+        ));
+    }
+    for layout in layouts.values_mut() {
+        layout.sort();
+    }
+    layouts
+}
+
+/// Part of a chart to play on song select, see [`Ogkr::suggest_preview_window`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PreviewWindow {
+    /// Start of the measure the window starts at.
+    pub start: TimingPoint,
+    /// Milliseconds from the start of the chart.
+    pub start_ms: f64,
+    pub end_ms: f64,
+    /// Taps, holds and flicks within the window.
+    pub notes: usize,
+}
+
+impl Ogkr {
+    /// Finds the window of `duration_secs` seconds holding the most notes, so preview clips can
+    /// be chosen automatically. Windows start on measures, ties go to the earliest one.
+    ///
+    /// Returns `None` if the chart has no taps, holds or flicks.
+    pub fn suggest_preview_window(&self, duration_secs: f64) -> Option<PreviewWindow> {
+        let timing = self.timing();
+        let duration_ms = duration_secs.max(0.0) * 1000.0;
+        let mut note_ms = self
+            .notes
+            .all()
+            .filter(|note| !matches!(note, NoteRef::Bell(_)))
+            .map(|note| timing.to_ms(note.time()))
+            .collect::<Vec<_>>();
+        note_ms.sort_by(f64::total_cmp);
+        let last = note_ms.last()?.max(0.0);
+
+        (0..=self.extra_metadata.num_measures)
+            .map(|measure| {
+                let start = TimingPoint::new(measure, 0);
+                let start_ms = timing.to_ms(start);
+                let end_ms = start_ms + duration_ms;
+                let notes = note_ms.partition_point(|&ms| ms < end_ms)
+                    - note_ms.partition_point(|&ms| ms < start_ms);
+                PreviewWindow {
+                    start,
+                    start_ms,
+                    end_ms,
+                    notes,
+                }
+            })
+            .take_while(|window| window.start_ms <= last)
+            .fold(None, |best: Option<PreviewWindow>, window| match best {
+                Some(best) if best.notes >= window.notes => Some(best),
+                _ => Some(window),
+            })
+    }
+}
@@ -0,0 +1,53 @@
+//! Optional instrumentation of the parsing phases, enabled with the `tracing` feature. Without the
+//! feature everything here compiles down to nothing.
+
+/// Span covering one parsing phase, recording its input/output sizes and elapsed time.
+#[cfg(feature = "tracing")]
+pub(crate) struct Phase {
+    span: tracing::span::EnteredSpan,
+    start: std::time::Instant,
+}
+
+#[cfg(feature = "tracing")]
+impl Phase {
+    pub(crate) fn enter(name: &'static str, input: usize) -> Self {
+        let span = tracing::info_span!(
+            "ogkr",
+            phase = name,
+            input,
+            output = tracing::field::Empty,
+            elapsed_us = tracing::field::Empty,
+        )
+        .entered();
+
+        Self {
+            span,
+            start: std::time::Instant::now(),
+        }
+    }
+
+    pub(crate) fn record_output(&self, output: usize) {
+        self.span.record("output", output);
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Drop for Phase {
+    fn drop(&mut self) {
+        let elapsed_us = self.start.elapsed().as_micros() as u64;
+        self.span.record("elapsed_us", elapsed_us);
+        tracing::debug!(elapsed_us, "phase finished");
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct Phase;
+
+#[cfg(not(feature = "tracing"))]
+impl Phase {
+    pub(crate) fn enter(_name: &'static str, _input: usize) -> Self {
+        Self
+    }
+
+    pub(crate) fn record_output(&self, _output: usize) {}
+}
@@ -0,0 +1,591 @@
+//! Structural checks of analyzed charts.
+
+use std::{collections::BTreeMap, fmt};
+
+use crate::parse::{
+    analysis::{
+        BeamId, ColorfulLaneId, HoldNote, LaneId, LaneType, Notes, ObliqueBeamId, TimingPoint,
+        Track,
+    },
+    timing::valid_bpm,
+};
+use crate::{code::ErrorCode, Ogkr};
+
+/// Wall, lane or beam section of a track.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SectionId {
+    /// Wall or lane, they share the same ID space.
+    Lane(LaneId),
+    ColorfulLane(ColorfulLaneId),
+    Beam(BeamId),
+    ObliqueBeam(ObliqueBeamId),
+}
+
+impl fmt::Display for SectionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Lane(id) => write!(f, "lane {}", id.0),
+            Self::ColorfulLane(id) => write!(f, "colorful lane {}", id.0),
+            Self::Beam(id) => write!(f, "beam {}", id.0),
+            Self::ObliqueBeam(id) => write!(f, "oblique beam {}", id.0),
+        }
+    }
+}
+
+/// Returns the point times of every section of the track, sorted by section ID.
+pub(crate) fn section_times(track: &Track) -> Vec<(SectionId, Vec<TimingPoint>)> {
+    let mut sections = track
+        .lanes_data
+        .values()
+        .map(|lane| {
+            (
+                SectionId::Lane(lane.id),
+                lane.points.iter().map(|point| point.time).collect(),
+            )
+        })
+        .chain(track.colorful_lanes_data.values().map(|lane| {
+            (
+                SectionId::ColorfulLane(lane.id),
+                std::iter::once(&lane.start)
+                    .chain(&lane.middle)
+                    .chain(std::iter::once(&lane.end))
+                    .map(|point| point.position.time)
+                    .collect(),
+            )
+        }))
+        .chain(track.beams_data.values().map(|beam| {
+            (
+                SectionId::Beam(beam.id),
+                std::iter::once(&beam.start)
+                    .chain(&beam.middle)
+                    .chain(std::iter::once(&beam.end))
+                    .map(|point| point.position.time)
+                    .collect(),
+            )
+        }))
+        .chain(track.oblique_beams_data.values().map(|beam| {
+            (
+                SectionId::ObliqueBeam(beam.id),
+                std::iter::once(&beam.start)
+                    .chain(&beam.middle)
+                    .chain(std::iter::once(&beam.end))
+                    .map(|point| point.position.time)
+                    .collect(),
+            )
+        }))
+        .collect::<Vec<_>>();
+    sections.sort_by_key(|(id, _)| section_order(id));
+    sections
+}
+
+fn section_order(id: &SectionId) -> (u8, u32) {
+    match *id {
+        SectionId::Lane(id) => (0, id.0),
+        SectionId::ColorfulLane(id) => (1, id.0),
+        SectionId::Beam(id) => (2, id.0),
+        SectionId::ObliqueBeam(id) => (3, id.0),
+    }
+}
+
+/// Reason for a chart failing [`Ogkr::quick_check`].
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum QuickCheckFailure {
+    /// The chart has no wall of the given type, either [`LaneType::WallLeft`] or
+    /// [`LaneType::WallRight`].
+    MissingWall(LaneType),
+    /// Points of the section go back in time.
+    UnorderedPoints(SectionId),
+    /// Neither the header nor the composition define a valid BPM.
+    NoBpm,
+}
+
+impl fmt::Display for QuickCheckFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingWall(LaneType::WallLeft) => write!(f, "chart has no left wall"),
+            Self::MissingWall(_) => write!(f, "chart has no right wall"),
+            Self::UnorderedPoints(section) => write!(f, "points of {section} go back in time"),
+            Self::NoBpm => write!(f, "chart has no valid BPM"),
+        }
+    }
+}
+
+/// Result of [`Ogkr::quick_check`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub struct QuickCheck {
+    pub failures: Vec<QuickCheckFailure>,
+}
+
+impl QuickCheck {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl Ogkr {
+    /// Cheap structural checks to reject corrupt charts before heavier processing. Charts passing
+    /// them can still have issues.
+    pub fn quick_check(&self) -> QuickCheck {
+        let mut failures = vec![];
+
+        for wall in [LaneType::WallLeft, LaneType::WallRight] {
+            let present = self
+                .track
+                .lanes_data
+                .values()
+                .any(|lane| lane.lane_type == wall && !lane.points.is_empty());
+            if !present {
+                failures.push(QuickCheckFailure::MissingWall(wall));
+            }
+        }
+
+        failures.extend(
+            section_times(&self.track)
+                .into_iter()
+                .filter(|(_, times)| times.windows(2).any(|pair| pair[0] > pair[1]))
+                .map(|(section, _)| QuickCheckFailure::UnorderedPoints(section)),
+        );
+
+        let has_bpm = self
+            .header
+            .bpm_definition
+            .map(|definition| definition.first)
+            .into_iter()
+            .chain(self.composition.all_bpm_changes().map(|change| change.bpm))
+            .any(|bpm| valid_bpm(f32::from_bits(bpm) as f64));
+        if !has_bpm {
+            failures.push(QuickCheckFailure::NoBpm);
+        }
+
+        QuickCheck { failures }
+    }
+}
+
+impl SectionId {
+    /// Name of the kind of section, as used in [`ValidationReport::to_json`].
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Lane(_) => "lane",
+            Self::ColorfulLane(_) => "colorful_lane",
+            Self::Beam(_) => "beam",
+            Self::ObliqueBeam(_) => "oblique_beam",
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        match *self {
+            Self::Lane(id) => id.0,
+            Self::ColorfulLane(id) => id.0,
+            Self::Beam(id) => id.0,
+            Self::ObliqueBeam(id) => id.0,
+        }
+    }
+}
+
+/// How serious an [`Issue`] is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The chart cannot be played as intended.
+    Error,
+    /// The chart is playable but likely contains a mistake.
+    Warning,
+}
+
+/// Problem found by [`Ogkr::validate`].
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Issue {
+    /// A point of the section is not strictly after the previous one, positions along the section
+    /// are ambiguous.
+    UnorderedPoints {
+        section: SectionId,
+        time: TimingPoint,
+    },
+    /// A hold starts before the previous hold on the same lane ends, both are identified by their
+    /// start time.
+    OverlappingHolds {
+        lane_id: LaneId,
+        first: TimingPoint,
+        second: TimingPoint,
+    },
+    /// Flicks at the same time and position require opposite directions, see
+    /// [`Notes::flick_conflicts`].
+    ContradictoryFlicks { time: TimingPoint, x_position: i32 },
+    /// A soflan starts before the previous one ends, only the later one applies in the overlap, see
+    /// [`Ogkr::effective_speed_at`].
+    OverlappingSoflans {
+        first: TimingPoint,
+        second: TimingPoint,
+    },
+    /// A soflan lasts past the end of the last measure with objects.
+    SoflanPastEnd {
+        start: TimingPoint,
+        end: TimingPoint,
+    },
+}
+
+impl Issue {
+    /// Catalog code of the kind of issue, see [`ErrorCode`].
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::UnorderedPoints { .. } => ErrorCode::UnorderedPoints,
+            Self::OverlappingHolds { .. } => ErrorCode::OverlappingHolds,
+            Self::ContradictoryFlicks { .. } => ErrorCode::ContradictoryFlicks,
+            Self::OverlappingSoflans { .. } => ErrorCode::OverlappingSoflans,
+            Self::SoflanPastEnd { .. } => ErrorCode::SoflanPastEnd,
+        }
+    }
+
+    /// Name of the kind of issue in snake case, stable across releases like its code.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::UnorderedPoints { .. } => "unordered_points",
+            Self::OverlappingHolds { .. } => "overlapping_holds",
+            Self::ContradictoryFlicks { .. } => "contradictory_flicks",
+            Self::OverlappingSoflans { .. } => "overlapping_soflans",
+            Self::SoflanPastEnd { .. } => "soflan_past_end",
+        }
+    }
+
+    /// First and last chart time involved in the issue.
+    pub fn span(&self) -> (TimingPoint, TimingPoint) {
+        match *self {
+            Self::UnorderedPoints { time, .. } | Self::ContradictoryFlicks { time, .. } => {
+                (time, time)
+            }
+            Self::OverlappingHolds { first, second, .. }
+            | Self::OverlappingSoflans { first, second } => (first, second),
+            Self::SoflanPastEnd { start, end } => (start, end),
+        }
+    }
+
+    /// Section the issue is about, holds are identified by their lane. `None` for issues about
+    /// the composition.
+    pub fn object(&self) -> Option<SectionId> {
+        match *self {
+            Self::UnorderedPoints { section, .. } => Some(section),
+            Self::OverlappingHolds { lane_id, .. } => Some(SectionId::Lane(lane_id)),
+            Self::ContradictoryFlicks { .. }
+            | Self::OverlappingSoflans { .. }
+            | Self::SoflanPastEnd { .. } => None,
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::UnorderedPoints { .. } | Self::OverlappingHolds { .. } => Severity::Error,
+            Self::ContradictoryFlicks { .. }
+            | Self::OverlappingSoflans { .. }
+            | Self::SoflanPastEnd { .. } => Severity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnorderedPoints { section, time } => write!(
+                f,
+                "point of {section} at measure {} offset {} is not after the previous point",
+                time.measure, time.beat_offset
+            ),
+            Self::OverlappingHolds {
+                lane_id,
+                first,
+                second,
+            } => write!(
+                f,
+                "hold on lane {} at measure {} offset {} overlaps the hold at measure {} offset {}",
+                lane_id.0, second.measure, second.beat_offset, first.measure, first.beat_offset
+            ),
+            Self::ContradictoryFlicks { time, x_position } => write!(
+                f,
+                "flicks at measure {} offset {} position {x_position} have opposite directions",
+                time.measure, time.beat_offset
+            ),
+            Self::OverlappingSoflans { first, second } => write!(
+                f,
+                "soflan at measure {} offset {} overlaps the soflan at measure {} offset {}",
+                second.measure, second.beat_offset, first.measure, first.beat_offset
+            ),
+            Self::SoflanPastEnd { start, end } => write!(
+                f,
+                "soflan at measure {} offset {} lasts past the end of the chart until measure {} offset {}",
+                start.measure, start.beat_offset, end.measure, end.beat_offset
+            ),
+        }
+    }
+}
+
+/// Issues found by [`Ogkr::validate`], in the order of the rules finding them.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub struct ValidationReport {
+    pub issues: Vec<Issue>,
+}
+
+impl ValidationReport {
+    /// Whether the chart has no issue of [`Severity::Error`].
+    pub fn is_valid(&self) -> bool {
+        self.issues
+            .iter()
+            .all(|issue| issue.severity() != Severity::Error)
+    }
+
+    /// Removes the issues with any of the given codes.
+    pub fn suppress(&mut self, codes: &[ErrorCode]) {
+        self.issues.retain(|issue| !codes.contains(&issue.code()));
+    }
+
+    /// Serializes the report as a single line of JSON, for tools gating on chart validity:
+    ///
+    /// ```json
+    /// {"valid":false,"issues":[{"code":"OGKR0302","name":"overlapping_holds","severity":"error",
+    /// "message":"...","span":{"start":{"measure":1,"offset":0},"end":{"measure":1,"offset":960}},
+    /// "object":{"kind":"lane","id":0}}]}
+    /// ```
+    ///
+    /// Codes and names come from [`Issue::code`] and [`Issue::name`], object kinds from
+    /// [`SectionId::kind`], the object is `null` for issues not about a section. Messages are
+    /// meant for humans and may change.
+    pub fn to_json(&self) -> String {
+        let mut out = format!("{{\"valid\":{},\"issues\":[", self.is_valid());
+        for (i, issue) in self.issues.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let severity = match issue.severity() {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+            let (start, end) = issue.span();
+            out.push_str(&format!(
+                "{{\"code\":\"{}\",\"name\":\"{}\",\"severity\":\"{severity}\",\"message\":",
+                issue.code(),
+                issue.name()
+            ));
+            push_json_string(&mut out, &issue.to_string());
+            out.push_str(&format!(
+                ",\"span\":{{\"start\":{},\"end\":{}}},\"object\":",
+                json_time(start),
+                json_time(end),
+            ));
+            match issue.object() {
+                Some(object) => out.push_str(&format!(
+                    "{{\"kind\":\"{}\",\"id\":{}}}}}",
+                    object.kind(),
+                    object.id()
+                )),
+                None => out.push_str("null}"),
+            }
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+fn json_time(time: TimingPoint) -> String {
+    format!(
+        "{{\"measure\":{},\"offset\":{}}}",
+        time.measure, time.beat_offset
+    )
+}
+
+fn push_json_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+impl Ogkr {
+    /// Runs every validation rule on the chart.
+    pub fn validate(&self) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        unordered_points(&self.track, &mut report);
+        overlapping_holds(&self.notes, &mut report);
+        contradictory_flicks(&self.notes, &mut report);
+        soflan_ranges(self, &mut report);
+        report
+    }
+
+    /// Sorts the points of every section by time, for tracks that were not built by the parser.
+    /// Returns the sections that had to be sorted.
+    ///
+    /// Points sharing the same time keep their order and are still reported by
+    /// [`Ogkr::validate`].
+    pub fn sort_section_points(&mut self) -> Vec<SectionId> {
+        let track = &mut self.track;
+        let mut sorted = vec![];
+
+        for lane in track.lanes_data.values_mut() {
+            if sort_by_time(&mut lane.points, |point| point.time) {
+                sorted.push(SectionId::Lane(lane.id));
+                let map = match lane.lane_type {
+                    LaneType::WallLeft => &mut track.walls_left,
+                    LaneType::WallRight => &mut track.walls_right,
+                    LaneType::Left => &mut track.lanes_left,
+                    LaneType::Center => &mut track.lanes_center,
+                    LaneType::Right => &mut track.lanes_right,
+                    LaneType::Enemy => &mut track.enemy_lanes,
+                    LaneType::Synthetic => continue,
+                };
+                rekey(map, lane.id, lane.points[0].time);
+            }
+        }
+        for lane in track.colorful_lanes_data.values_mut() {
+            let mut points = std::iter::once(lane.start.clone())
+                .chain(lane.middle.iter().cloned())
+                .chain(std::iter::once(lane.end.clone()))
+                .collect::<Vec<_>>();
+            if sort_by_time(&mut points, |point| point.position.time) {
+                sorted.push(SectionId::ColorfulLane(lane.id));
+                (lane.start, lane.middle, lane.end) = split_section(points);
+                rekey(&mut track.colorful_lanes, lane.id, lane.start.position.time);
+            }
+        }
+        for beam in track.beams_data.values_mut() {
+            let mut points = std::iter::once(beam.start.clone())
+                .chain(beam.middle.iter().cloned())
+                .chain(std::iter::once(beam.end.clone()))
+                .collect::<Vec<_>>();
+            if sort_by_time(&mut points, |point| point.position.time) {
+                sorted.push(SectionId::Beam(beam.id));
+                (beam.start, beam.middle, beam.end) = split_section(points);
+                rekey(&mut track.beams, beam.id, beam.start.position.time);
+            }
+        }
+        for beam in track.oblique_beams_data.values_mut() {
+            let mut points = std::iter::once(beam.start.clone())
+                .chain(beam.middle.iter().cloned())
+                .chain(std::iter::once(beam.end.clone()))
+                .collect::<Vec<_>>();
+            if sort_by_time(&mut points, |point| point.position.time) {
+                sorted.push(SectionId::ObliqueBeam(beam.id));
+                (beam.start, beam.middle, beam.end) = split_section(points);
+                rekey(&mut track.oblique_beams, beam.id, beam.start.position.time);
+            }
+        }
+
+        sorted.sort_by_key(section_order);
+        sorted
+    }
+}
+
+fn unordered_points(track: &Track, report: &mut ValidationReport) {
+    for (section, times) in section_times(track) {
+        if let Some(pair) = times.windows(2).find(|pair| pair[0] >= pair[1]) {
+            report.issues.push(Issue::UnorderedPoints {
+                section,
+                time: pair[1],
+            });
+        }
+    }
+}
+
+fn overlapping_holds(notes: &Notes, report: &mut ValidationReport) {
+    let mut lanes = BTreeMap::<u32, Vec<&HoldNote>>::new();
+    for hold in notes.all_holds() {
+        lanes.entry(hold.lane_id.0).or_default().push(hold);
+    }
+
+    for holds in lanes.values_mut() {
+        holds.sort_by_key(|hold| hold.start.time);
+        // Hold ending the latest so far, a hold may start exactly when it ends.
+        let mut latest: Option<&HoldNote> = None;
+        for &hold in holds.iter() {
+            match latest {
+                Some(previous) if hold.start.time < previous.end.time => {
+                    report.issues.push(Issue::OverlappingHolds {
+                        lane_id: hold.lane_id,
+                        first: previous.start.time,
+                        second: hold.start.time,
+                    });
+                    if hold.end.time > previous.end.time {
+                        latest = Some(hold);
+                    }
+                }
+                _ => latest = Some(hold),
+            }
+        }
+    }
+}
+
+fn contradictory_flicks(notes: &Notes, report: &mut ValidationReport) {
+    for conflict in notes.flick_conflicts() {
+        report.issues.push(Issue::ContradictoryFlicks {
+            time: conflict.time,
+            x_position: conflict.x.position,
+        });
+    }
+}
+
+fn soflan_ranges(ogkr: &Ogkr, report: &mut ValidationReport) {
+    let resolution = ogkr.track.resolution;
+    // Compared in ticks, the measure after the last one may not be representable.
+    let chart_end = resolution
+        .ticks_at(TimingPoint::new(ogkr.extra_metadata.num_measures, 0))
+        .saturating_add(resolution.ticks.max(1) as u64);
+
+    let mut latest: Option<(TimingPoint, u64)> = None;
+    for soflan in ogkr
+        .composition
+        .all_soflans()
+        .filter(|soflan| soflan.duration > 0)
+    {
+        let (start, end) = (soflan.time, soflan.end_ticks(resolution));
+        match latest {
+            Some((first, previous_end)) if resolution.ticks_at(start) < previous_end => {
+                report.issues.push(Issue::OverlappingSoflans {
+                    first,
+                    second: start,
+                });
+                if end > previous_end {
+                    latest = Some((start, end));
+                }
+            }
+            _ => latest = Some((start, end)),
+        }
+        if end > chart_end {
+            report.issues.push(Issue::SoflanPastEnd {
+                start,
+                end: resolution.time_of(end),
+            });
+        }
+    }
+}
+
+/// Stable sort by time, returns whether the order changed.
+fn sort_by_time<T>(points: &mut [T], time: impl Fn(&T) -> TimingPoint) -> bool {
+    if points
+        .windows(2)
+        .all(|pair| time(&pair[0]) <= time(&pair[1]))
+    {
+        return false;
+    }
+    points.sort_by_key(time);
+    true
+}
+
+/// Splits at least two sorted points into start, middle and end.
+fn split_section<T>(mut points: Vec<T>) -> (T, Vec<T>, T) {
+    let end = points.pop().expect("sections have at least two points");
+    let start = points.remove(0);
+    (start, points, end)
+}
+
+/// Moves `id` to its new start time, wherever it was before.
+fn rekey<Id: PartialEq>(map: &mut BTreeMap<TimingPoint, Vec<Id>>, id: Id, start: TimingPoint) {
+    map.retain(|_, ids| {
+        ids.retain(|other| *other != id);
+        !ids.is_empty()
+    });
+    map.entry(start).or_default().push(id);
+}
@@ -0,0 +1,145 @@
+//! Visitors over the commands of a token stream or the objects of an analyzed chart, so passes
+//! like counters, linters and exporters only implement the methods for what they look at.
+//!
+//! Every method does nothing by default.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    ops::Range,
+};
+
+use crate::{
+    lex::{
+        command::{Bell, Bullet as BulletCommand, BulletPalette, Extension},
+        grammar::{self, Section},
+        token::{Token, TokenStream},
+        CustomToken,
+    },
+    parse::analysis::{
+        Beam, BellNote, BpmChange, Bullet, ClickSound, ColorfulLane, FlickNote, HoldNote, Lane,
+        MeterChange, ObliqueBeam, Ogkr, Soflan, TapNote, TimingPoint,
+    },
+};
+
+/// Visitor over the tokens of a chart, in chart order.
+pub trait TokenVisitor {
+    /// Called for every token with the source range of its line, before the method for its
+    /// section.
+    fn visit_token(&mut self, _token: &Token, _span: Range<usize>) {}
+
+    /// `[NAME]` section lines.
+    fn visit_section_name(&mut self) {}
+    fn visit_header(&mut self, _token: &Token) {}
+    fn visit_bullet_palette(&mut self, _palette: &BulletPalette) {}
+    /// BPM, meter and soflan changes.
+    fn visit_composition(&mut self, _token: &Token) {}
+    /// Points of lanes and walls, lane disappearances and lane blocks.
+    fn visit_lane(&mut self, _token: &Token) {}
+    /// Points of beams and oblique beams.
+    fn visit_beam(&mut self, _token: &Token) {}
+    fn visit_bullet(&mut self, _bullet: &BulletCommand) {}
+    fn visit_bell(&mut self, _bell: &Bell) {}
+    /// Taps, holds and flicks.
+    fn visit_note(&mut self, _token: &Token) {}
+    fn visit_total(&mut self, _token: &Token) {}
+    fn visit_extension(&mut self, _extension: &Extension) {}
+    fn visit_custom(&mut self, _custom: &CustomToken) {}
+}
+
+/// Calls `visitor` for every token of `tokens`.
+pub fn walk_tokens(tokens: &TokenStream, visitor: &mut impl TokenVisitor) {
+    for (token, span) in tokens.iter_spanned() {
+        visitor.visit_token(token, span);
+
+        match token {
+            Token::SectionName => visitor.visit_section_name(),
+            Token::BulletPalette(palette) => visitor.visit_bullet_palette(palette),
+            Token::Bullet(bullet) => visitor.visit_bullet(bullet),
+            Token::Bell(bell) => visitor.visit_bell(bell),
+            Token::Extension(extension) => visitor.visit_extension(extension),
+            Token::Custom(custom) => visitor.visit_custom(custom),
+            token => match token.kind().and_then(|kind| grammar::command(kind).section) {
+                Some(Section::Header) => visitor.visit_header(token),
+                Some(Section::Composition) => visitor.visit_composition(token),
+                Some(Section::Lane) => visitor.visit_lane(token),
+                Some(Section::Beam) => visitor.visit_beam(token),
+                Some(Section::Notes) => visitor.visit_note(token),
+                Some(Section::Total) => visitor.visit_total(token),
+                _ => {}
+            },
+        }
+    }
+}
+
+/// Visitor over the objects of an analyzed chart.
+pub trait ChartVisitor {
+    fn visit_bpm_change(&mut self, _change: &BpmChange) {}
+    fn visit_meter_change(&mut self, _change: &MeterChange) {}
+    fn visit_soflan(&mut self, _soflan: &Soflan) {}
+    fn visit_click_sound(&mut self, _click: &ClickSound) {}
+    /// Lanes, walls and enemy lanes.
+    fn visit_lane(&mut self, _lane: &Lane) {}
+    fn visit_colorful_lane(&mut self, _lane: &ColorfulLane) {}
+    fn visit_beam(&mut self, _beam: &Beam) {}
+    fn visit_oblique_beam(&mut self, _beam: &ObliqueBeam) {}
+    fn visit_tap(&mut self, _tap: &TapNote) {}
+    fn visit_hold(&mut self, _hold: &HoldNote) {}
+    fn visit_flick(&mut self, _flick: &FlickNote) {}
+    fn visit_bell(&mut self, _bell: &BellNote) {}
+    fn visit_bullet(&mut self, _bullet: &Bullet) {}
+}
+
+/// Calls `visitor` for every object of `ogkr`: composition changes, click sounds, track objects,
+/// notes and bullets, in that order. Objects of a kind come in time order, objects starting at
+/// the same time in ID order.
+pub fn walk_chart(ogkr: &Ogkr, visitor: &mut impl ChartVisitor) {
+    let composition = &ogkr.composition;
+    composition
+        .all_bpm_changes()
+        .for_each(|change| visitor.visit_bpm_change(change));
+    composition
+        .meter_changes
+        .values()
+        .for_each(|change| visitor.visit_meter_change(change));
+    composition
+        .all_soflans()
+        .for_each(|soflan| visitor.visit_soflan(soflan));
+    ogkr.click_sounds
+        .iter()
+        .for_each(|click| visitor.visit_click_sound(click));
+
+    let track = &ogkr.track;
+    let mut lanes = track.lanes_data.values().collect::<Vec<_>>();
+    lanes.sort_by_key(|lane| (lane.points.first().map(|point| point.time), lane.id.0));
+    lanes.into_iter().for_each(|lane| visitor.visit_lane(lane));
+    by_start(&track.colorful_lanes, &track.colorful_lanes_data, |id| id.0)
+        .for_each(|lane| visitor.visit_colorful_lane(lane));
+    by_start(&track.beams, &track.beams_data, |id| id.0).for_each(|beam| visitor.visit_beam(beam));
+    by_start(&track.oblique_beams, &track.oblique_beams_data, |id| id.0)
+        .for_each(|beam| visitor.visit_oblique_beam(beam));
+
+    let notes = &ogkr.notes;
+    notes.all_taps().for_each(|tap| visitor.visit_tap(tap));
+    notes.all_holds().for_each(|hold| visitor.visit_hold(hold));
+    notes
+        .all_flicks()
+        .for_each(|flick| visitor.visit_flick(flick));
+    notes.all_bells().for_each(|bell| visitor.visit_bell(bell));
+    ogkr.bullets
+        .all_bullets()
+        .for_each(|bullet| visitor.visit_bullet(bullet));
+}
+
+/// Objects of `data` ordered by their start time in `starts`, then by ID.
+fn by_start<'a, I: Copy + Eq + Hash, T>(
+    starts: &'a BTreeMap<TimingPoint, Vec<I>>,
+    data: &'a HashMap<I, T>,
+    key: impl Fn(I) -> u32,
+) -> impl Iterator<Item = &'a T> {
+    starts.values().flat_map(move |ids| {
+        let mut ids = ids.clone();
+        ids.sort_by_key(|&id| key(id));
+        ids.into_iter().filter_map(|id| data.get(&id))
+    })
+}
@@ -0,0 +1,566 @@
+//! Serialization of charts back into the text format.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write},
+};
+
+use crate::{
+    lex::command::*,
+    parse::{
+        analysis::Ogkr,
+        raw::{
+            BeamSection, ColorfulLaneSection, LaneSection, NoteRef, ObliqueBeamSection, RawOgkr,
+            WallSection,
+        },
+        Extensions, Header, Totals,
+    },
+};
+
+/// Writes `raw` in the chart text format, grouping commands into the usual sections.
+pub fn write_raw_ogkr(raw: &RawOgkr) -> String {
+    let mut out = String::new();
+    write_raw_ogkr_to(&mut out, raw).expect("writing to a String cannot fail");
+    out
+}
+
+/// Same as [`write_raw_ogkr`], writing into any [`fmt::Write`].
+pub fn write_raw_ogkr_to(out: &mut impl Write, raw: &RawOgkr) -> fmt::Result {
+    let mut w = Writer { out };
+
+    w.section("HEADER")?;
+    w.header(&raw.header)?;
+    w.extensions(Some(&raw.extensions))?;
+
+    w.section("B_PALETTE")?;
+    for palette in &raw.bullet_pallete_list {
+        w.bullet_palette(palette)?;
+    }
+
+    w.section("COMPOSITION")?;
+    for bpm_change in &raw.composition.bpm_changes {
+        w.line("BPM", |w| {
+            w.time(bpm_change.time)?;
+            w.float(bpm_change.bpm)
+        })?;
+    }
+    for meter_change in &raw.composition.meter_changes {
+        w.line("MET", |w| {
+            w.time(meter_change.time)?;
+            w.field(meter_change.num_beats)?;
+            w.field(meter_change.note_value)
+        })?;
+    }
+    for soflan in &raw.composition.soflans {
+        w.line("SFL", |w| {
+            w.time(soflan.time)?;
+            w.field(soflan.duration)?;
+            w.float(soflan.current_speed_multiplier)
+        })?;
+    }
+    for click_sound in &raw.click_sounds {
+        w.line("CLK", |w| w.time(click_sound.time))?;
+    }
+    let waves = &raw.enemy_wave_assignment;
+    for (time, wave) in [
+        (waves.wave_1, "WAVE1"),
+        (waves.wave_2, "WAVE2"),
+        (waves.boss, "BOSS"),
+    ] {
+        if let Some(time) = time {
+            w.line("EST", |w| {
+                w.time(time)?;
+                w.field(wave)
+            })?;
+        }
+    }
+
+    let track = &raw.track;
+    w.section("LANE")?;
+    w.wall_sections(["WLS", "WLN", "WLE"], &track.walls_left)?;
+    w.wall_sections(["WRS", "WRN", "WRE"], &track.walls_right)?;
+    w.lane_sections(["LLS", "LLN", "LLE"], &track.lanes_left)?;
+    w.lane_sections(["LCS", "LCN", "LCE"], &track.lanes_center)?;
+    w.lane_sections(["LRS", "LRN", "LRE"], &track.lanes_right)?;
+    w.lane_sections(["ENS", "ENN", "ENE"], &track.enemy_lanes)?;
+    w.colorful_lane_sections(&track.colorful_lanes)?;
+    for event in &track.lane_disappearances {
+        w.lane_event("LDP", event)?;
+    }
+    for event in &track.lane_blocks {
+        w.lane_event("LBK", event)?;
+    }
+
+    w.section("BEAM")?;
+    w.beam_sections(&track.beams)?;
+    w.oblique_beam_sections(&track.oblique_beams)?;
+
+    w.section("BULLET")?;
+    for (i, bullet) in raw.bullets.iter().enumerate() {
+        w.line("BLT", |w| {
+            w.field(&bullet.pallete_id)?;
+            w.time(bullet.time)?;
+            w.field(bullet.x_position)?;
+            if let Some(damage_type) = bullet.damage_type {
+                w.field(damage_type_mnemonic(damage_type))?;
+            }
+            Ok(())
+        })?;
+        w.extensions(raw.bullet_extensions.get(&i))?;
+    }
+
+    let notes = &raw.notes;
+    w.section("BELL")?;
+    for (i, bell) in notes.bells.iter().enumerate() {
+        w.line("BEL", |w| {
+            w.time(bell.time)?;
+            w.field(bell.x_position)?;
+            if let Some(palette_id) = &bell.bullet_palette_id {
+                w.field(palette_id)?;
+            }
+            Ok(())
+        })?;
+        w.extensions(notes.extensions.get(&NoteRef::Bell(i)))?;
+    }
+
+    w.section("NOTES")?;
+    for (command, taps, note_ref) in [
+        ("TAP", &notes.taps, NoteRef::Tap as fn(usize) -> NoteRef),
+        ("CTP", &notes.critical_taps, NoteRef::CriticalTap),
+    ] {
+        for (i, tap) in taps.iter().enumerate() {
+            w.line(command, |w| {
+                w.field(tap.lane_group_id)?;
+                w.time(tap.time)?;
+                w.field(tap.x_position)?;
+                w.field(tap.x_offset)
+            })?;
+            w.extensions(notes.extensions.get(&note_ref(i)))?;
+        }
+    }
+    for (command, flicks, note_ref) in [
+        ("FLK", &notes.flicks, NoteRef::Flick as fn(usize) -> NoteRef),
+        ("CFK", &notes.critical_flicks, NoteRef::CriticalFlick),
+    ] {
+        for (i, flick) in flicks.iter().enumerate() {
+            w.line(command, |w| {
+                w.time(flick.time)?;
+                w.field(flick.x_position)?;
+                w.field(match flick.direction {
+                    FlickDirection::Left => "L",
+                    FlickDirection::Right => "R",
+                })
+            })?;
+            w.extensions(notes.extensions.get(&note_ref(i)))?;
+        }
+    }
+    for (command, holds, note_ref) in [
+        ("HLD", &notes.holds, NoteRef::Hold as fn(usize) -> NoteRef),
+        ("CHD", &notes.critical_holds, NoteRef::CriticalHold),
+    ] {
+        for (i, hold) in holds.iter().enumerate() {
+            w.line(command, |w| {
+                w.field(hold.lane_group_id)?;
+                w.time(hold.start_time)?;
+                w.field(hold.start_x_position)?;
+                w.field(hold.start_x_offset)?;
+                w.time(hold.end_time)?;
+                w.field(hold.end_x_position)?;
+                w.field(hold.end_x_offset)
+            })?;
+            w.extensions(notes.extensions.get(&note_ref(i)))?;
+        }
+    }
+
+    if raw.header.totals != Totals::default() {
+        w.section("TOTAL")?;
+        w.totals(&raw.header.totals)?;
+    }
+
+    // Custom commands do not belong to any section, they go last to keep sections contiguous.
+    for custom in &raw.custom_commands {
+        w.line(&custom.mnemonic, |w| {
+            custom
+                .command
+                .args()
+                .into_iter()
+                .try_for_each(|arg| w.field(arg))
+        })?;
+    }
+
+    Ok(())
+}
+
+impl Ogkr {
+    /// Serializes the chart in a deterministic form for snapshot tests, charts analyzing to the
+    /// same content give the same string regardless of how their source was written.
+    ///
+    /// The output is [`write_raw_ogkr`] of [`Ogkr::to_raw`], so sections and command kinds come in
+    /// the order of that writer. Within a command kind:
+    /// - palettes, lanes and beams are ordered by ID, the points of a section by time,
+    /// - notes, bells and bullets are ordered by time, then by every other field in command order,
+    /// - BPM, meter and soflan changes are ordered by time, simultaneous changes keep the order of
+    ///   the chart as the last one takes effect.
+    ///
+    /// Extensions stay attached to their command.
+    pub fn to_canonical_string(&self) -> String {
+        let mut raw = self.to_raw();
+
+        let notes = &mut raw.notes;
+        let extensions = &mut notes.extensions;
+        for (taps, note_ref) in [
+            (&mut notes.taps, NoteRef::Tap as fn(usize) -> NoteRef),
+            (&mut notes.critical_taps, NoteRef::CriticalTap),
+        ] {
+            reorder(taps, extensions, note_ref, |tap| {
+                (
+                    time_key(tap.time),
+                    tap.lane_group_id,
+                    tap.x_position,
+                    tap.x_offset,
+                )
+            });
+        }
+        for (holds, note_ref) in [
+            (&mut notes.holds, NoteRef::Hold as fn(usize) -> NoteRef),
+            (&mut notes.critical_holds, NoteRef::CriticalHold),
+        ] {
+            reorder(holds, extensions, note_ref, |hold| {
+                (
+                    time_key(hold.start_time),
+                    hold.lane_group_id,
+                    hold.start_x_position,
+                    hold.start_x_offset,
+                    time_key(hold.end_time),
+                    hold.end_x_position,
+                    hold.end_x_offset,
+                )
+            });
+        }
+        for (flicks, note_ref) in [
+            (&mut notes.flicks, NoteRef::Flick as fn(usize) -> NoteRef),
+            (&mut notes.critical_flicks, NoteRef::CriticalFlick),
+        ] {
+            reorder(flicks, extensions, note_ref, |flick| {
+                (
+                    time_key(flick.time),
+                    flick.x_position,
+                    flick.direction == FlickDirection::Right,
+                )
+            });
+        }
+        reorder(&mut notes.bells, extensions, NoteRef::Bell, |bell| {
+            (
+                time_key(bell.time),
+                bell.x_position,
+                bell.bullet_palette_id.clone(),
+            )
+        });
+        reorder(
+            &mut raw.bullets,
+            &mut raw.bullet_extensions,
+            |i| i,
+            |bullet| {
+                (
+                    time_key(bullet.time),
+                    bullet.pallete_id.clone(),
+                    bullet.x_position,
+                    bullet.damage_type.map(|damage_type| damage_type as u8),
+                )
+            },
+        );
+
+        write_raw_ogkr(&raw)
+    }
+}
+
+fn time_key(time: CommandTime) -> (u32, u32) {
+    (time.measure, time.offset)
+}
+
+/// Stable sorts `commands` by `key`, moving their extensions along.
+fn reorder<T: Clone, R: Ord, K: Ord>(
+    commands: &mut Vec<T>,
+    extensions: &mut BTreeMap<R, Extensions>,
+    reference: impl Fn(usize) -> R,
+    key: impl Fn(&T) -> K,
+) {
+    let mut order = (0..commands.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| key(&commands[i]));
+
+    let moved = order
+        .iter()
+        .enumerate()
+        .filter_map(|(new, &old)| {
+            extensions
+                .remove(&reference(old))
+                .map(|extensions| (reference(new), extensions))
+        })
+        .collect::<Vec<_>>();
+    extensions.extend(moved);
+    *commands = order.into_iter().map(|i| commands[i].clone()).collect();
+}
+
+pub(crate) fn shooter_mnemonic(shooter: BulletShooter) -> &'static str {
+    match shooter {
+        BulletShooter::EndPosition => "UPS",
+        BulletShooter::Enemy => "ENE",
+        BulletShooter::Center => "CEN",
+    }
+}
+
+pub(crate) fn target_mnemonic(target: BulletTarget) -> &'static str {
+    match target {
+        BulletTarget::Player => "PLR",
+        BulletTarget::FixedPosition => "FIX",
+    }
+}
+
+pub(crate) fn damage_type_mnemonic(damage_type: BulletDamageType) -> &'static str {
+    match damage_type {
+        BulletDamageType::Normal => "NML",
+        BulletDamageType::Hard => "STR",
+        BulletDamageType::Danger => "DNG",
+    }
+}
+
+struct Writer<'a, W> {
+    out: &'a mut W,
+}
+
+impl<W: Write> Writer<'_, W> {
+    fn section(&mut self, name: &str) -> fmt::Result {
+        writeln!(self.out, "[{name}]")
+    }
+
+    /// Writes a command line, `fields` writes the tab-prefixed arguments.
+    fn line(
+        &mut self,
+        command: &str,
+        fields: impl FnOnce(&mut Self) -> fmt::Result,
+    ) -> fmt::Result {
+        self.out.write_str(command)?;
+        fields(self)?;
+        self.out.write_char('\n')
+    }
+
+    /// Writes extension commands, attached to the command written just before.
+    fn extensions(&mut self, extensions: Option<&Extensions>) -> fmt::Result {
+        for (key, value) in extensions.into_iter().flatten() {
+            let command = format!("{}{key}", Extension::PREFIX);
+            self.line(&command, |w| w.field(value))?;
+        }
+        Ok(())
+    }
+
+    fn field(&mut self, value: impl fmt::Display) -> fmt::Result {
+        write!(self.out, "\t{value}")
+    }
+
+    fn time(&mut self, time: CommandTime) -> fmt::Result {
+        self.field(time.measure)?;
+        self.field(time.offset)
+    }
+
+    /// Writes an f32 stored as bits, with a fixed number of decimals when that does not lose
+    /// precision.
+    fn float(&mut self, bits: u32) -> fmt::Result {
+        let value = f32::from_bits(bits);
+        let fixed = format!("{value:.6}");
+        if fixed
+            .parse::<f32>()
+            .is_ok_and(|parsed| parsed.to_bits() == bits)
+        {
+            self.field(fixed)
+        } else {
+            self.field(value)
+        }
+    }
+
+    fn header(&mut self, header: &Header) -> fmt::Result {
+        if let Some(version) = header.version {
+            self.line("VERSION", |w| {
+                w.field(version.major)?;
+                w.field(version.minor)?;
+                w.field(version.release)
+            })?;
+        }
+        if let Some(creator) = &header.creator {
+            self.line("CREATOR", |w| w.field(&creator.name))?;
+        }
+        if let Some(bpm) = header.bpm_definition {
+            self.line("BPM_DEF", |w| {
+                w.float(bpm.first)?;
+                w.float(bpm.common)?;
+                w.float(bpm.minimum)?;
+                w.float(bpm.maximum)
+            })?;
+        }
+        if let Some(meter) = header.meter_definition {
+            self.line("MET_DEF", |w| {
+                w.field(meter.num_beats)?;
+                w.field(meter.note_value)
+            })?;
+        }
+        if let Some(resolution) = header.tick_resolution {
+            self.line("TRESOLUTION", |w| w.field(resolution.resolution))?;
+        }
+        if let Some(resolution) = header.x_resolution {
+            self.line("XRESOLUTION", |w| w.field(resolution.resolution))?;
+        }
+        if let Some(click) = header.click_definition {
+            self.line("CLK_DEF", |w| w.field(click.value))?;
+        }
+        if let Some(prog_judge_bpm) = header.prog_judge_bpm {
+            self.line("PROGJUDGE_BPM", |w| w.float(prog_judge_bpm.value))?;
+        }
+        if let Some(tutorial) = header.tutorial {
+            self.line("TUTORIAL", |w| w.field(tutorial.value))?;
+        }
+
+        let damage = &header.damage_values;
+        for (command, value) in [
+            ("BULLET_DAMAGE", damage.normal),
+            ("HARDBULLET_DAMAGE", damage.hard),
+            ("DANGERBULLET_DAMAGE", damage.danger),
+            ("BEAM_DAMAGE", damage.beam),
+        ] {
+            if value != 0.0 {
+                self.line(command, |w| w.float(value.to_bits()))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn totals(&mut self, totals: &Totals) -> fmt::Result {
+        for (command, value) in [
+            ("T_TOTAL", totals.notes),
+            ("T_TAP", totals.tap),
+            ("T_HOLD", totals.hold),
+            ("T_SIDE", totals.side),
+            ("T_SHOLD", totals.side_hold),
+            ("T_FLICK", totals.flick),
+            ("T_BELL", totals.bell),
+        ] {
+            self.line(command, |w| w.field(value))?;
+        }
+        Ok(())
+    }
+
+    fn bullet_palette(&mut self, palette: &BulletPalette) -> fmt::Result {
+        self.line("BPL", |w| {
+            w.field(&palette.id)?;
+            w.field(shooter_mnemonic(palette.shooter))?;
+            w.field(palette.target_x_offset)?;
+            w.field(target_mnemonic(palette.target))?;
+            w.float(palette.speed)?;
+
+            // Palettes in the legacy syntax specify the damage type instead.
+            if let Some(damage_type) = palette.damage_type {
+                return w.field(damage_type_mnemonic(damage_type));
+            }
+            w.field(match palette.size.unwrap_or(BulletSize::Normal) {
+                BulletSize::Normal => "N",
+                BulletSize::Large => "L",
+            })?;
+            w.field(match palette.ty.unwrap_or(BulletType::Circle) {
+                BulletType::Circle => "CIR",
+                BulletType::Square => "SQR",
+                BulletType::Needle => "NDL",
+            })?;
+            w.field(palette.random_position_offset.unwrap_or(0))
+        })
+    }
+
+    /// Writes section points as start, next and end commands.
+    fn points<P>(
+        &mut self,
+        commands: [&str; 3],
+        points: &[P],
+        mut fields: impl FnMut(&mut Self, &P) -> fmt::Result,
+    ) -> fmt::Result {
+        let last = points.len().saturating_sub(1);
+        for (i, point) in points.iter().enumerate() {
+            let command = match i {
+                0 => commands[0],
+                i if i == last => commands[2],
+                _ => commands[1],
+            };
+            self.line(command, |w| fields(w, point))?;
+        }
+        Ok(())
+    }
+
+    fn wall_sections(&mut self, commands: [&str; 3], sections: &[WallSection]) -> fmt::Result {
+        for section in sections {
+            self.points(commands, &section.points, |w, point| {
+                w.field(point.group_id)?;
+                w.time(point.time)?;
+                w.field(point.x_position)
+            })?;
+        }
+        Ok(())
+    }
+
+    fn lane_sections(&mut self, commands: [&str; 3], sections: &[LaneSection]) -> fmt::Result {
+        for section in sections {
+            self.points(commands, &section.points, |w, point| {
+                w.field(point.group_id)?;
+                w.time(point.time)?;
+                w.field(point.x_position)
+            })?;
+        }
+        Ok(())
+    }
+
+    fn colorful_lane_sections(&mut self, sections: &[ColorfulLaneSection]) -> fmt::Result {
+        for section in sections {
+            self.points(["CLS", "CLN", "CLE"], &section.points, |w, point| {
+                w.field(point.group_id)?;
+                w.time(point.time)?;
+                w.field(point.x_position)?;
+                w.field(point.color)?;
+                w.field(point.brightness)
+            })?;
+        }
+        Ok(())
+    }
+
+    fn lane_event(&mut self, command: &str, event: &LaneEvent) -> fmt::Result {
+        self.line(command, |w| {
+            w.field(event.group_id)?;
+            w.time(event.start_time)?;
+            w.field(event.start_x_position)?;
+            w.field(event.start_x_offset)?;
+            w.time(event.end_time)?;
+            w.field(event.end_x_position)?;
+            w.field(event.end_x_offset)
+        })
+    }
+
+    fn beam_sections(&mut self, sections: &[BeamSection]) -> fmt::Result {
+        for section in sections {
+            self.points(["BMS", "BMN", "BME"], &section.points, |w, point| {
+                w.field(point.record_id)?;
+                w.time(point.time)?;
+                w.field(point.x_position)?;
+                w.field(point.width)
+            })?;
+        }
+        Ok(())
+    }
+
+    fn oblique_beam_sections(&mut self, sections: &[ObliqueBeamSection]) -> fmt::Result {
+        for section in sections {
+            self.points(["OBS", "OBN", "OBE"], &section.points, |w, point| {
+                w.field(point.record_id)?;
+                w.time(point.time)?;
+                w.field(point.x_position)?;
+                w.field(point.width)?;
+                w.field(point.shoot_position_x_offset)
+            })?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,290 @@
+use ogkr::{
+    build::{BuildError, BulletPaletteBuilder, ChartBuilder, LaneKind, WallSide},
+    lex::command::{
+        BulletDamageType, BulletShooter, BulletSize, CommandTime, MeterDefinition, Tap, Version,
+    },
+    parse::raw::parse_source_with_options,
+    write::write_raw_ogkr,
+    Ogkr,
+};
+
+#[test]
+fn test_bullet_palette_builder() {
+    let palette = BulletPaletteBuilder::new("A")
+        .shooter(BulletShooter::Enemy)
+        .speed(1.5)
+        .size(BulletSize::Large)
+        .build()
+        .unwrap();
+    assert_eq!(palette.speed, 1.5f32.to_bits());
+    assert_eq!(palette.size, Some(BulletSize::Large));
+    assert!(palette.ty.is_some());
+    assert_eq!(palette.damage_type, None);
+
+    assert!(matches!(
+        BulletPaletteBuilder::new("A").speed(0.0).build(),
+        Err(BuildError::InvalidPaletteSpeed { .. })
+    ));
+    assert!(matches!(
+        BulletPaletteBuilder::new("A").speed(f32::NAN).build(),
+        Err(BuildError::InvalidPaletteSpeed { .. })
+    ));
+    assert_eq!(
+        BulletPaletteBuilder::new("A")
+            .size(BulletSize::Large)
+            .damage_type(BulletDamageType::Hard)
+            .build()
+            .unwrap_err(),
+        BuildError::MixedPaletteSyntax("A".to_string())
+    );
+}
+
+#[test]
+fn test_chart_builder_palettes() {
+    let mut builder = ChartBuilder::new();
+    builder
+        .bullet_palette(BulletPaletteBuilder::new("A"))
+        .unwrap();
+
+    assert_eq!(
+        builder
+            .bullet_palette(BulletPaletteBuilder::new("A"))
+            .unwrap_err(),
+        BuildError::DuplicatePaletteId("A".to_string())
+    );
+    assert_eq!(
+        builder
+            .bullet_palette(BulletPaletteBuilder::new("B").damage_type(BulletDamageType::Normal))
+            .unwrap_err(),
+        BuildError::InconsistentPaletteSyntax("B".to_string())
+    );
+    assert_eq!(
+        builder
+            .bullet("A", CommandTime::new(1, 0), 0, None)
+            .unwrap_err(),
+        BuildError::MissingDamageType("A".to_string())
+    );
+    assert_eq!(
+        builder
+            .bullet(
+                "C",
+                CommandTime::new(1, 0),
+                0,
+                Some(BulletDamageType::Normal)
+            )
+            .unwrap_err(),
+        BuildError::UnknownPaletteId("C".to_string())
+    );
+    builder
+        .bullet(
+            "A",
+            CommandTime::new(1, 0),
+            0,
+            Some(BulletDamageType::Normal),
+        )
+        .unwrap();
+
+    let ogkr = builder.build_ogkr().unwrap();
+    assert_eq!(ogkr.bullets.all_bullets().count(), 1);
+}
+
+#[test]
+fn test_chart_builder_write() {
+    let mut builder = ChartBuilder::new();
+    builder
+        .version(1, 8, 0)
+        .creator("builder")
+        .resolution(1920, 4096)
+        .bpm(CommandTime::new(0, 0), 150.0)
+        .lane(
+            LaneKind::Center,
+            0,
+            [(CommandTime::new(0, 0), 0), (CommandTime::new(4, 0), 8)],
+        )
+        .tap(
+            Tap {
+                lane_group_id: 0,
+                time: CommandTime::new(1, 0),
+                x_position: 2,
+                x_offset: 0,
+            },
+            true,
+        )
+        .bullet_palette(BulletPaletteBuilder::new("A").speed(2.0))
+        .unwrap()
+        .bullet(
+            "A",
+            CommandTime::new(2, 0),
+            4,
+            Some(BulletDamageType::Danger),
+        )
+        .unwrap();
+
+    let source = builder.write().unwrap();
+    let ogkr: Ogkr = source.parse().unwrap();
+    assert_eq!(ogkr.header.creator.unwrap().name, "builder");
+    assert_eq!(ogkr.notes.all_taps().count(), 1);
+    assert_eq!(ogkr.bullets.all_bullets().count(), 1);
+    assert_eq!(ogkr.bullets.palettes()[0].speed, 2.0);
+}
+
+#[test]
+fn test_chart_builder_version_syntax() {
+    let legacy = || BulletPaletteBuilder::new("A").damage_type(BulletDamageType::Hard);
+
+    let mut builder = ChartBuilder::new();
+    builder.version(1, 8, 0).bullet_palette(legacy()).unwrap();
+    assert_eq!(
+        builder.write().unwrap_err(),
+        BuildError::PaletteSyntaxForVersion {
+            id: "A".to_string(),
+            version: Version::new(1, 8, 0),
+        }
+    );
+    builder.version(1, 0, 0);
+    assert!(builder.build().is_ok());
+
+    let mut builder = ChartBuilder::new();
+    builder
+        .version(1, 0, 0)
+        .bullet_palette(BulletPaletteBuilder::new("A"))
+        .unwrap();
+    assert!(matches!(
+        builder.build(),
+        Err(BuildError::PaletteSyntaxForVersion { .. })
+    ));
+
+    // Without a version either syntax is written.
+    let mut builder = ChartBuilder::new();
+    builder.bullet_palette(legacy()).unwrap();
+    assert!(builder.build().is_ok());
+}
+
+#[test]
+fn test_chart_builder_incomplete_section() {
+    let mut builder = ChartBuilder::new();
+    builder
+        .lane(
+            LaneKind::Center,
+            0,
+            [(CommandTime::new(0, 0), 0), (CommandTime::new(4, 0), 0)],
+        )
+        .beam(3, [(CommandTime::new(1, 0), 0, 2)]);
+    assert_eq!(
+        builder.build_ogkr().unwrap_err(),
+        BuildError::IncompleteSection(3)
+    );
+    assert_eq!(
+        builder.write().unwrap_err(),
+        BuildError::IncompleteSection(3)
+    );
+}
+
+#[test]
+fn test_chart_builder_ids() {
+    let mut builder = ChartBuilder::new();
+    builder
+        .wall(
+            WallSide::Left,
+            3,
+            [(CommandTime::new(0, 0), -24), (CommandTime::new(4, 0), -24)],
+        )
+        .beam(
+            0,
+            [
+                (CommandTime::new(1, 0), 0, 2),
+                (CommandTime::new(2, 0), 0, 2),
+            ],
+        );
+
+    let right_wall = builder.alloc_lane_id().unwrap();
+    let lane = builder.alloc_lane_id().unwrap();
+    assert_eq!((right_wall, lane), (4, 5));
+    builder
+        .wall(
+            WallSide::Right,
+            right_wall,
+            [(CommandTime::new(0, 0), 24), (CommandTime::new(4, 0), 24)],
+        )
+        .lane(
+            LaneKind::Enemy,
+            10,
+            [(CommandTime::new(0, 0), 0), (CommandTime::new(4, 0), 0)],
+        );
+    assert_eq!(builder.alloc_lane_id(), Some(11));
+    assert_eq!(builder.alloc_beam_id(), Some(1));
+    assert_eq!(builder.alloc_oblique_beam_id(), Some(0));
+    assert_eq!(builder.alloc_oblique_beam_id(), Some(1));
+
+    let mut builder = ChartBuilder::from_raw(builder.build().unwrap());
+    assert_eq!(builder.alloc_lane_id(), Some(11));
+
+    builder.beam(
+        u32::MAX - 1,
+        [
+            (CommandTime::new(1, 0), 0, 2),
+            (CommandTime::new(2, 0), 0, 2),
+        ],
+    );
+    assert_eq!(builder.alloc_beam_id(), Some(u32::MAX));
+    assert_eq!(builder.alloc_beam_id(), None);
+    builder.lane(
+        LaneKind::Enemy,
+        u32::MAX,
+        [(CommandTime::new(0, 0), 0), (CommandTime::new(4, 0), 0)],
+    );
+    assert_eq!(builder.alloc_lane_id(), None);
+    assert_eq!(builder.build().unwrap().track.next_lane_id(), None);
+}
+
+#[test]
+fn test_empty_template() {
+    let meter = MeterDefinition {
+        num_beats: 3,
+        note_value: 4,
+    };
+    let ogkr = Ogkr::empty_template(180.0, meter, 12);
+    assert!(ogkr.validate().issues.is_empty());
+    assert_eq!(ogkr.track.lanes_data.len(), 3);
+    assert_eq!(ogkr.track.walls_left.len(), 1);
+    assert_eq!(ogkr.track.walls_right.len(), 1);
+    assert_eq!(ogkr.track.lanes_center.len(), 1);
+    assert!(ogkr.notes.is_empty());
+    assert_eq!(
+        ogkr.enemy_wave_assignment.wave_2,
+        Some(CommandTime::new(4, 0))
+    );
+    assert_eq!(
+        ogkr.enemy_wave_assignment.boss,
+        Some(CommandTime::new(8, 0))
+    );
+
+    let source = ChartBuilder::empty_template(180.0, meter, 12)
+        .write()
+        .unwrap();
+    let parsed: Ogkr = source.parse().unwrap();
+    assert_eq!(parsed.header.meter_definition, Some(meter));
+    assert_eq!(
+        parsed
+            .header
+            .bpm_definition
+            .map(|bpm| f32::from_bits(bpm.first)),
+        Some(180.0)
+    );
+    assert_eq!(parsed.track.highest_measure(), Some(12));
+}
+
+#[test]
+fn test_write_round_trip() {
+    for source in [
+        include_str!("../charts/1.ogkr"),
+        include_str!("../charts/2.ogkr"),
+        include_str!("../charts/3.ogkr"),
+    ] {
+        let options = Default::default();
+        let written = write_raw_ogkr(&parse_source_with_options(source, &options).unwrap());
+        let rewritten = write_raw_ogkr(&parse_source_with_options(&written, &options).unwrap());
+        assert_eq!(written, rewritten);
+        assert!(written.parse::<Ogkr>().is_ok());
+    }
+}
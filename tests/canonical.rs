@@ -0,0 +1,58 @@
+use ogkr::Ogkr;
+
+#[test]
+fn test_canonical_order() {
+    let source = "
+        LCS 0 0 0 0
+        LCE 0 4 0 0
+        BPL A CEN 0 PLR 1.0 L CIR 0
+        TAP 0 1 0 8 0
+        TAP 0 1 0 0 0
+        X_TAG first
+        FLK 1 0 8 R
+        FLK 1 0 0 L
+        BLT A 2 0 8 DNG
+        BLT A 2 0 0 NML
+    ";
+    let reordered = "
+        BPL A CEN 0 PLR 1.0 L CIR 0
+        LCS 0 0 0 0
+        LCE 0 4 0 0
+        BLT A 2 0 0 NML
+        BLT A 2 0 8 DNG
+        FLK 1 0 0 L
+        FLK 1 0 8 R
+        TAP 0 1 0 0 0
+        X_TAG first
+        TAP 0 1 0 8 0
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let canonical = ogkr.to_canonical_string();
+    assert_eq!(
+        canonical,
+        reordered.parse::<Ogkr>().unwrap().to_canonical_string()
+    );
+
+    let taps = canonical
+        .lines()
+        .skip_while(|line| !line.starts_with("TAP"))
+        .take(3)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        taps,
+        ["TAP\t0\t1\t0\t0\t0", "X_TAG\tfirst", "TAP\t0\t1\t0\t8\t0"]
+    );
+}
+
+#[test]
+fn test_canonical_stable() {
+    for source in [
+        include_str!("../charts/1.ogkr"),
+        include_str!("../charts/2.ogkr"),
+        include_str!("../charts/3.ogkr"),
+    ] {
+        let canonical = source.parse::<Ogkr>().unwrap().to_canonical_string();
+        let reparsed: Ogkr = canonical.parse().unwrap();
+        assert_eq!(reparsed.to_canonical_string(), canonical);
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::HashSet;
+
+use ogkr::{parse::ParseError, ErrorCode, Ogkr};
+
+#[test]
+fn test_error_catalog() {
+    let numbers = ErrorCode::ALL
+        .iter()
+        .map(|code| code.number())
+        .collect::<Vec<_>>();
+    assert!(numbers.windows(2).all(|pair| pair[0] < pair[1]));
+
+    for &code in ErrorCode::ALL {
+        assert!(!code.description().is_empty());
+        assert_eq!(code.to_string().parse::<ErrorCode>(), Ok(code));
+    }
+    assert_eq!(
+        ErrorCode::ALL
+            .iter()
+            .map(|code| code.to_string())
+            .collect::<HashSet<_>>()
+            .len(),
+        ErrorCode::ALL.len()
+    );
+
+    assert_eq!(ErrorCode::OverlappingHolds.to_string(), "OGKR0302");
+    assert_eq!("ogkr0001".parse(), Ok(ErrorCode::UnknownCommand));
+    assert!("OGKR9999".parse::<ErrorCode>().is_err());
+    assert!("0001".parse::<ErrorCode>().is_err());
+}
+
+#[test]
+fn test_error_codes() {
+    let error = "FOO 0 0".parse::<Ogkr>().unwrap_err();
+    assert_eq!(error.code(), ErrorCode::UnknownCommand);
+
+    let error = "
+        LCS 0 0 0 0
+        LCE 0 1 0 0
+        TAP 5 0 0 0 0
+    "
+    .parse::<Ogkr>()
+    .unwrap_err();
+    assert_eq!(error.code(), ErrorCode::Semantic);
+
+    let error = ParseError::LimitExceeded {
+        what: "tokens",
+        max: 1,
+    };
+    assert_eq!(error.code(), ErrorCode::LimitExceeded);
+}
+
+#[test]
+fn test_suppressed_warnings() {
+    let mut ogkr: Ogkr = "
+        LCS 0 0 0 0
+        LCE 0 1 0 0
+        LCS 0 0 0 4
+        LCE 0 1 0 4
+    "
+    .parse()
+    .unwrap();
+    let codes = ogkr
+        .warnings
+        .iter()
+        .map(|warning| warning.code())
+        .collect::<Vec<_>>();
+    assert_eq!(codes, [ErrorCode::DuplicateLaneId]);
+
+    ogkr.warnings.suppress(&[ErrorCode::DuplicateBeamId]);
+    assert_eq!(ogkr.warnings.len(), 1);
+    ogkr.warnings.suppress(&[ErrorCode::DuplicateLaneId]);
+    assert!(ogkr.warnings.is_empty());
+}
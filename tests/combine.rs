@@ -0,0 +1,145 @@
+use ogkr::{
+    parse::{
+        analysis::{TimingPoint, XPosition},
+        combine::OverlayConflict,
+        ParseError,
+    },
+    Ogkr,
+};
+
+const FIRST: &str = "
+    BPM 0 0 120.000000
+    BPL A UPS 0 PLR 1.0 N CIR 0
+    LCS 0 0 0 0
+    LCE 0 1 0 0
+    TAP 0 0 0 0 0
+    BLT A 1 0 0 NML
+";
+
+const SECOND: &str = "
+    [HEADER]
+    BPM_DEF 240.000 240.000 240.000 240.000
+    [B_PALETTE]
+    BPL A UPS 0 PLR 2.0 N CIR 0
+    [NOTES]
+    LCS 0 0 0 8
+    LCE 0 2 0 8
+    TAP 0 1 960 8 0
+    BLT A 0 0 0 NML
+";
+
+#[test]
+fn test_concat() {
+    let first: Ogkr = FIRST.parse().unwrap();
+    let second: Ogkr = SECOND.parse().unwrap();
+    let ogkr = Ogkr::concat(&[first, second]).unwrap();
+
+    // The first part ends at measure 1, the second one starts at measure 2.
+    let taps = ogkr
+        .notes
+        .all_taps()
+        .map(|tap| (tap.lane_id.0, tap.position.time))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        taps,
+        [(0, TimingPoint::new(0, 0)), (1, TimingPoint::new(3, 960))]
+    );
+    assert_eq!(ogkr.track.lanes_data.len(), 2);
+
+    // The second part keeps its own tempo.
+    let timing = ogkr.timing();
+    assert_eq!(timing.to_ms(TimingPoint::new(2, 0)), 4000.0);
+    assert_eq!(timing.to_ms(TimingPoint::new(3, 960)), 5500.0);
+
+    // Palettes with the same ID but different content are renamed.
+    let bullets = ogkr
+        .bullets
+        .all_bullets()
+        .map(|bullet| {
+            let palette = ogkr.bullets.palette(bullet.palette).unwrap();
+            (palette.id.0.as_str(), bullet.position.time)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        bullets,
+        [
+            ("A", TimingPoint::new(1, 0)),
+            ("A2", TimingPoint::new(2, 0))
+        ]
+    );
+}
+
+#[test]
+fn test_concat_empty() {
+    let ogkr = Ogkr::concat(&[]).unwrap();
+    assert_eq!(ogkr.notes.all_taps().count(), 0);
+}
+
+#[test]
+fn test_concat_exhausted_ids() {
+    let first: Ogkr = "LCS 4294967295 0 0 0\nLCE 4294967295 1 0 0\n"
+        .parse()
+        .unwrap();
+    let second: Ogkr = SECOND.parse().unwrap();
+    assert!(matches!(
+        Ogkr::concat(&[first.clone(), second.clone()]),
+        Err(ParseError::LimitExceeded { what: "ids", .. })
+    ));
+    assert!(first.merge_overlay(&second).is_err());
+}
+
+#[test]
+fn test_concat_exhausted_measures() {
+    let first: Ogkr = "LCS 0 0 0 0\nLCE 0 4294967294 0 0\n".parse().unwrap();
+    let second: Ogkr = SECOND.parse().unwrap();
+    assert!(matches!(
+        Ogkr::concat(&[first, second]),
+        Err(ParseError::LimitExceeded {
+            what: "measures",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_merge_overlay() {
+    let base: Ogkr = FIRST.parse().unwrap();
+    let overlay: Ogkr = "
+        BPM 0 0 150.000000
+        BPL A UPS 0 PLR 1.0 N CIR 0
+        LCS 0 0 0 0
+        LCE 0 1 0 0
+        TAP 0 0 0 0 0
+        TAP 0 0 480 0 0
+        BLT A 1 0 0 NML
+        BEL 0 0 0
+    "
+    .parse()
+    .unwrap();
+
+    let (ogkr, conflicts) = base.merge_overlay(&overlay).unwrap();
+
+    assert_eq!(ogkr.notes.all_taps().count(), 3);
+    assert_eq!(ogkr.notes.all_bells().count(), 1);
+    assert_eq!(ogkr.track.lanes_data.len(), 2);
+    // Identical palettes are shared.
+    assert_eq!(ogkr.bullets.palettes().len(), 1);
+    assert_eq!(ogkr.composition.all_bpm_changes().count(), 1);
+
+    assert_eq!(
+        conflicts,
+        [
+            OverlayConflict::Note {
+                time: TimingPoint::new(0, 0),
+                x: XPosition::new(0, 0),
+            },
+            OverlayConflict::Tempo {
+                time: TimingPoint::new(0, 0),
+            },
+            OverlayConflict::Bullet {
+                time: TimingPoint::new(1, 0),
+                x_position: 0,
+            },
+        ]
+    );
+}
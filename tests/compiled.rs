@@ -0,0 +1,92 @@
+use ogkr::{
+    compiled::{CompiledNoteKind, CompiledPoint},
+    lex::command::FlickDirection,
+    parse::analysis::{LaneId, PaletteIdRef},
+    Ogkr,
+};
+
+#[test]
+fn test_compiled_chart() {
+    let source = "
+        BPM_DEF 120.000 120.000 120.000 120.000
+        TRESOLUTION 1920
+        BPL A CEN 0 PLR 1.0 L CIR 0
+        BPL B CEN 0 PLR 2.0 L CIR 0
+        SFL 1 0 1920 2.000000
+        WLS 0 0 0 -24
+        WLE 0 4 0 -24
+        LCS 1 0 0 0
+        LCE 1 4 0 12
+        CLK 0 0
+        TAP 1 0 960 0 0
+        HLD 1 1 0 0 0 2 0 0 0
+        FLK 0 960 24 R
+        BEL 1 0 -12
+        BLT A 2 0 0 NML
+        BLT B 0 960 0 NML
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let chart = ogkr.compile();
+
+    assert_eq!(
+        chart.lanes.iter().map(|lane| lane.id).collect::<Vec<_>>(),
+        [LaneId(0), LaneId(1)]
+    );
+    assert_eq!(
+        chart.lanes[1].points[1],
+        CompiledPoint {
+            ms: 8000.0,
+            scroll: 10000.0,
+            x: 0.75,
+        }
+    );
+
+    let notes = chart
+        .notes
+        .iter()
+        .map(|note| {
+            (
+                note.kind,
+                note.start.ms,
+                note.end.ms,
+                note.start.x,
+                note.lane,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        notes,
+        [
+            (CompiledNoteKind::Tap, 1000.0, 1000.0, 0.5, Some(1)),
+            (
+                CompiledNoteKind::Flick(FlickDirection::Right),
+                1000.0,
+                1000.0,
+                1.0,
+                None
+            ),
+            (CompiledNoteKind::Hold, 2000.0, 4000.0, 0.5, Some(1)),
+            (CompiledNoteKind::Bell, 2000.0, 2000.0, 0.25, None),
+        ]
+    );
+    assert_eq!(chart.notes[2].end.scroll, 6000.0);
+    assert_eq!(chart.notes_between(1000.0, 2000.0).len(), 2);
+    assert_eq!(chart.notes_between(3000.0, 1000.0).len(), 0);
+
+    let bullets = chart
+        .bullets
+        .iter()
+        .map(|bullet| (bullet.spawn_ms, bullet.arrival.ms, bullet.palette))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        bullets,
+        [
+            (0.0, 1000.0, PaletteIdRef(1)),
+            (2000.0, 4000.0, PaletteIdRef(0))
+        ]
+    );
+    assert_eq!(chart.palette(PaletteIdRef(1)).unwrap().speed, 2.0);
+    assert_eq!(chart.bullets_between(1.0, 2001.0).len(), 1);
+    assert_eq!(chart.clicks_ms, [0.0]);
+    assert_eq!(chart.scroll.len(), 3);
+}
@@ -0,0 +1,37 @@
+use ogkr::Ogkr;
+
+#[test]
+fn test_simultaneous_composition_events() {
+    let source = "
+        BPM 0 0 120.000000
+        BPM 2 0 150.000000
+        BPM 2 0 180.000000
+        SFL 1 0 960 0.500000
+        SFL 1 0 1920 2.000000
+        SFL 3 0 960 1.000000
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let composition = &ogkr.composition;
+
+    assert_eq!(composition.bpm_changes.len(), 2);
+    assert_eq!(
+        composition
+            .all_bpm_changes()
+            .map(|b| (b.time.measure, b.bpm))
+            .collect::<Vec<_>>(),
+        vec![
+            (0, 120.0f32.to_bits()),
+            (2, 150.0f32.to_bits()),
+            (2, 180.0f32.to_bits())
+        ]
+    );
+
+    assert_eq!(composition.soflans.len(), 2);
+    assert_eq!(
+        composition
+            .all_soflans()
+            .map(|s| (s.time.measure, s.duration, s.speed_multiplier))
+            .collect::<Vec<_>>(),
+        vec![(1, 960, 0.5), (1, 1920, 2.0), (3, 960, 1.0)]
+    );
+}
@@ -0,0 +1,30 @@
+use ogkr::conformance;
+
+#[test]
+fn test_run_dir() {
+    let dir = std::env::temp_dir().join(format!("ogkr-conformance-{}", std::process::id()));
+    let nested = dir.join("song");
+    std::fs::create_dir_all(&nested).unwrap();
+    std::fs::write(dir.join("1.ogkr"), include_str!("../charts/1.ogkr")).unwrap();
+    std::fs::write(nested.join("broken.ogkr"), "WLS 0 0 0 0").unwrap();
+    std::fs::write(
+        nested.join("warned.ogkr"),
+        "LCS 0 0 0 0\nLCE 0 1 0 0\nLCS 0 2 0 0\nLCE 0 3 0 0",
+    )
+    .unwrap();
+    std::fs::write(nested.join("notes.txt"), "not a chart").unwrap();
+
+    let summary = conformance::run_dir(&dir).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(summary.files.len(), 3);
+    assert_eq!(summary.passed(), 2);
+    assert_eq!(summary.warned(), 1);
+    assert_eq!(summary.failed(), 1);
+    let failures = summary.failures().collect::<Vec<_>>();
+    assert_eq!(failures.len(), 1);
+    assert!(failures[0].path.ends_with("song/broken.ogkr"));
+    assert!(summary.files[0].is_clean());
+
+    assert!(conformance::run_dir(dir.join("missing")).is_err());
+}
@@ -0,0 +1,121 @@
+use ogkr::{
+    lex::{
+        token::Token, tokenize_with_options, CustomCommand, LexError, LexOptions, RawLineReader,
+    },
+    parse::{raw::parse_source_with_options, ParseOptions},
+    parse_with_options,
+    write::write_raw_ogkr,
+};
+
+/// Made-up `SPD <measure> <offset> <speed> [label]` command of a private editor.
+#[derive(Debug, PartialEq)]
+struct Speed {
+    time: (u32, u32),
+    speed: f32,
+    label: String,
+}
+
+impl CustomCommand for Speed {
+    fn parse(reader: &mut RawLineReader<'_>) -> ogkr::lex::Result<Self> {
+        Ok(Self {
+            time: (
+                reader.parse_token("Speed measure")?,
+                reader.parse_token("Speed offset")?,
+            ),
+            speed: reader.parse_token("Speed speed")?,
+            label: reader.rest_of_line().to_string(),
+        })
+    }
+
+    fn args(&self) -> Vec<String> {
+        let mut args = vec![
+            self.time.0.to_string(),
+            self.time.1.to_string(),
+            self.speed.to_string(),
+        ];
+        if !self.label.is_empty() {
+            args.push(self.label.clone());
+        }
+        args
+    }
+}
+
+fn options() -> ParseOptions {
+    let mut options = ParseOptions::default();
+    options.lex = LexOptions::default().custom_command::<Speed>("SPD");
+    options
+}
+
+const SOURCE: &str = "
+    LCS 0 0 0 0
+    LCE 0 4 0 0
+    SPD 1 0 1.5 fast
+    TAP 0 1 0 0 0
+    SPD 2 480 0.5
+";
+
+#[test]
+fn test_custom_commands() {
+    assert!(SOURCE.parse::<ogkr::Ogkr>().is_err());
+
+    let ogkr = parse_with_options(SOURCE, &options()).unwrap();
+    let speeds = ogkr
+        .custom_commands
+        .iter()
+        .map(|custom| {
+            assert_eq!(custom.mnemonic, "SPD");
+            custom.downcast_ref::<Speed>().unwrap()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        speeds,
+        [
+            &Speed {
+                time: (1, 0),
+                speed: 1.5,
+                label: "fast".to_string()
+            },
+            &Speed {
+                time: (2, 480),
+                speed: 0.5,
+                label: String::new()
+            }
+        ]
+    );
+    assert_eq!(ogkr.notes.all_taps().count(), 1);
+
+    let written = write_raw_ogkr(&ogkr.to_raw());
+    assert!(written.ends_with("SPD\t1\t0\t1.5\tfast\nSPD\t2\t480\t0.5\n"));
+    let reparsed = parse_source_with_options(&written, &options()).unwrap();
+    assert_eq!(reparsed.custom_commands, ogkr.custom_commands);
+}
+
+#[test]
+fn test_custom_command_errors() {
+    let options = options().lex;
+    match tokenize_with_options("SPD 1 0 fast\n", &options).unwrap_err() {
+        LexError::ExpectedToken {
+            message,
+            col,
+            token,
+            ..
+        } => assert_eq!((message, col, token.as_str()), ("Speed speed", 9, "fast")),
+        error => panic!("unexpected error {error:?}"),
+    }
+
+    let tokens =
+        tokenize_with_options("SPD 1 0 fast\nSPD 1 0 2\n", &options.clone().lenient()).unwrap();
+    assert_eq!(tokens.len(), 1);
+    assert_eq!(tokens.errors().len(), 1);
+
+    // Built-in commands cannot be overridden.
+    let options = LexOptions::default().custom_command::<Speed>("TAP");
+    let tokens = tokenize_with_options("TAP 0 1 0 0 0\n", &options).unwrap();
+    assert!(matches!(tokens.iter().next(), Some(Token::Tap(_))));
+
+    assert_eq!(
+        options,
+        LexOptions::default().custom_command::<Speed>("TAP")
+    );
+    assert_ne!(options, LexOptions::default());
+}
@@ -0,0 +1,51 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use ogkr::{
+    diagnostics::{self, DiagnosticsSink, Level},
+    Ogkr,
+};
+
+#[derive(Default)]
+struct Collect(Mutex<Vec<(Level, String)>>);
+
+impl DiagnosticsSink for Collect {
+    fn emit(&self, level: Level, message: fmt::Arguments<'_>) {
+        self.0.lock().unwrap().push((level, message.to_string()));
+    }
+}
+
+#[test]
+fn test_diagnostics_sink() {
+    let sink = Arc::new(Collect::default());
+    diagnostics::set_sink(sink.clone());
+
+    let source = "
+        WLS 0 0 0 -24
+        WLE 0 1 0 -24
+        LCS 0 0 0 0
+        LCE 0 1 0 0
+        TAP 5 0 0 0 0
+    ";
+    assert!(source.parse::<Ogkr>().is_err());
+    let ogkr: Ogkr = "
+        LCS 0 0 0 0
+        LCE 0 1 0 0
+        LCS 0 0 0 4
+        LCE 0 1 0 4
+    "
+    .parse()
+    .unwrap();
+    diagnostics::reset_sink();
+
+    let messages = sink.0.lock().unwrap();
+    assert!(messages
+        .iter()
+        .any(|(level, message)| *level == Level::Error && message.contains("invalid lane id")));
+    for warning in &ogkr.warnings {
+        assert!(messages.contains(&(Level::Warn, warning.to_string())));
+    }
+    assert!(!ogkr.warnings.is_empty());
+}
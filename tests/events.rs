@@ -0,0 +1,59 @@
+use ogkr::{
+    lex::{grammar::Section, token::Token},
+    parse::{events::Subscriptions, ParseError, ParseOptions},
+};
+
+const CHART: &str = include_str!("../charts/1.ogkr");
+
+#[test]
+fn test_bpm_changes() {
+    let mut bpms = Vec::new();
+    let errors = Subscriptions::new()
+        .on(Section::Composition, |token, span| {
+            if let Token::BpmChange(change) = token {
+                bpms.push((f32::from_bits(change.bpm), &CHART[span]));
+            }
+        })
+        .parse(CHART, &ParseOptions::default())
+        .unwrap();
+
+    assert!(errors.is_empty());
+    assert_eq!(bpms, [(150.0, "BPM\t0\t0\t150.000")]);
+}
+
+#[test]
+fn test_sections_in_order() {
+    let mut header = Vec::new();
+    let mut total = Vec::new();
+    let mut extensions = 0;
+    Subscriptions::new()
+        .on(Section::Header, |_, span| header.push(span.start))
+        .on(Section::Total, |_, span| total.push(span.start))
+        .on_extensions(|_, _| extensions += 1)
+        .parse(CHART, &ParseOptions::default())
+        .unwrap();
+
+    assert_eq!(header.len(), 13);
+    assert!(header.is_sorted());
+    assert!(header.last() < total.first());
+    assert_eq!(extensions, 0);
+}
+
+#[test]
+fn test_limits() {
+    let mut options = ParseOptions::default();
+    options.max_tokens = Some(10);
+
+    let mut count = 0;
+    assert_eq!(
+        Subscriptions::new()
+            .on(Section::Header, |_, _| count += 1)
+            .parse(CHART, &options)
+            .unwrap_err(),
+        ParseError::LimitExceeded {
+            what: "tokens",
+            max: 10
+        }
+    );
+    assert_eq!(count, 9);
+}
@@ -0,0 +1,164 @@
+use ogkr::{export, Ogkr};
+
+#[test]
+fn test_csv() {
+    let source = "
+        BPM 0 0 120.000000
+        LCS 0 0 0 0
+        LCE 0 4 0 0
+        TAP 0 1 0 0 0
+        CTP 0 0 960 4 2048
+        HLD 0 2 0 0 0 3 0 0 0
+        FLK 1 480 -8 R
+        BEL 0 0 8
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+
+    assert_eq!(
+        export::csv(&ogkr),
+        "type,time_ms,measure,offset,x,lane,critical
+bell,0.000,0,0,8,,false
+tap,1000.000,0,960,4.5,0,true
+tap,2000.000,1,0,0,0,false
+flick,2500.000,1,480,-8,,false
+hold,4000.000,2,0,0,0,false
+hold_end,6000.000,3,0,0,0,false
+"
+    );
+}
+
+#[test]
+fn test_hitsounds() {
+    let source = "
+        BPM 0 0 120.000000
+        LCS 0 0 0 0
+        LCE 0 4 0 0
+        CTP 0 0 0 0 0
+        HLD 0 1 0 0 0 2 0 0 0
+        BEL 0 0 8
+        CLK 0 960
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+
+    let hitsounds = export::hitsounds(&ogkr, 4)
+        .into_iter()
+        .map(|hitsound| (hitsound.ms, hitsound.sample.name()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        hitsounds,
+        [
+            (0.0, "critical_tap"),
+            (0.0, "bell"),
+            (1000.0, "click"),
+            (2000.0, "hold"),
+            (2500.0, "hold_tick"),
+            (3000.0, "hold_tick"),
+            (3500.0, "hold_tick"),
+        ]
+    );
+
+    let without_ticks = export::hitsounds(&ogkr, 0);
+    assert!(without_ticks
+        .iter()
+        .all(|hitsound| hitsound.sample != export::HitsoundSample::HoldTick));
+}
+
+#[test]
+fn test_vsrg() {
+    let source = "
+        BPM 0 0 120.000000
+        LCS 0 0 0 0
+        LCE 0 4 16 0
+        TAP 0 0 0 0 0
+        TAP 0 0 0 0 0
+        TAP 0 1 0 16 0
+        HLD 0 2 0 8 0 3 0 12 0
+        FLK 1 480 -8 R
+        BEL 0 0 8
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let chart = export::VsrgChart::new(&ogkr, 4).unwrap();
+
+    assert_eq!(
+        chart.notes,
+        [
+            export::VsrgNote {
+                column: 0,
+                start_ms: 0.0,
+                end_ms: None,
+            },
+            export::VsrgNote {
+                column: 3,
+                start_ms: 2000.0,
+                end_ms: None,
+            },
+            export::VsrgNote {
+                column: 2,
+                start_ms: 4000.0,
+                end_ms: Some(6000.0),
+            },
+        ]
+    );
+    assert_eq!(
+        chart.dropped,
+        export::VsrgDropped {
+            flicks: 1,
+            bells: 1,
+            overlapping_notes: 1,
+            ..Default::default()
+        }
+    );
+
+    let osu = chart.osu_mania();
+    assert!(osu.contains("CircleSize:4\n"));
+    assert!(osu.contains("[TimingPoints]\n0,500,4,0,0,100,1,0\n"));
+    assert!(osu.ends_with(
+        "[HitObjects]
+64,192,0,1,0,0:0:0:0:
+448,192,2000,1,0,0:0:0:0:
+320,192,4000,128,0,6000:0:0:0:0:
+"
+    ));
+
+    for columns in [0, export::VsrgChart::MAX_COLUMNS + 1, u32::MAX] {
+        assert_eq!(
+            export::VsrgChart::new(&ogkr, columns),
+            Err(export::VsrgError::InvalidColumns(columns))
+        );
+    }
+}
+
+#[cfg(feature = "midi")]
+#[test]
+fn test_midi() {
+    let source = "
+        BPM 0 0 120.000000
+        LCS 0 0 0 0
+        LCE 0 4 0 0
+        TAP 0 0 0 0 0
+        HLD 0 0 960 0 0 1 0 0 0
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let midi = export::midi(&ogkr);
+
+    assert_eq!(&midi[..4], b"MThd");
+    assert_eq!(&midi[8..14], &[0, 0, 0, 1, 0x03, 0xE8]);
+    assert_eq!(&midi[14..18], b"MTrk");
+
+    let track = &midi[22..];
+    assert_eq!(
+        u32::from_be_bytes(midi[18..22].try_into().unwrap()) as usize,
+        track.len()
+    );
+    // Tempo, then a tap at 0 ms lasting 50 ms and a hold from 1000 ms to 2000 ms.
+    let expected = [
+        &[0x00, 0xFF, 0x51, 0x03, 0x0F, 0x42, 0x40][..],
+        &[0x00, 0x90, export::pitch::TAP, 100],
+        &[0x32, 0x80, export::pitch::TAP, 0],
+        &[0x87, 0x36, 0x90, export::pitch::HOLD, 100],
+        &[0x87, 0x68, 0x80, export::pitch::HOLD, 0],
+        &[0x00, 0xFF, 0x2F, 0x00],
+    ]
+    .concat();
+    assert_eq!(track, expected);
+}
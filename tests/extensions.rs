@@ -0,0 +1,121 @@
+use ogkr::{
+    parse::{raw::parse_source_with_options, store::TimeStore, MissingLanePolicy, ParseOptions},
+    write::write_raw_ogkr,
+    Ogkr,
+};
+
+const SOURCE: &str = "
+    [HEADER]
+    X_EDITOR some editor 1.0
+    [B_PALETTE]
+    BPL A UPS 0 PLR 1.0 N CIR 0
+    [NOTES]
+    LCS 0 0 0 0
+    X_LAYER lanes
+    LCE 0 4 0 0
+    TAP 0 0 0 0 0
+    TAP 0 1 0 0 0
+    X_COLOR red
+    X_GROUP 2
+    CHD 0 2 0 0 0 3 0 0 0
+    X_GROUP 3
+    BLT A 1 0 0 NML
+    X_PATTERN spiral
+";
+
+fn extensions(pairs: &[(&str, &str)]) -> ogkr::parse::Extensions {
+    pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+fn check(ogkr: &Ogkr) {
+    assert_eq!(
+        ogkr.extensions,
+        extensions(&[("EDITOR", "some editor 1.0"), ("LAYER", "lanes")])
+    );
+
+    let taps = ogkr.notes.all_taps().collect::<Vec<_>>();
+    assert!(taps[0].extensions.is_empty());
+    assert_eq!(
+        taps[1].extensions,
+        extensions(&[("COLOR", "red"), ("GROUP", "2")])
+    );
+
+    let hold = ogkr.notes.all_holds().next().unwrap();
+    assert_eq!(hold.extensions, extensions(&[("GROUP", "3")]));
+
+    let bullet = ogkr.bullets.all_bullets().next().unwrap();
+    assert_eq!(bullet.extensions, extensions(&[("PATTERN", "spiral")]));
+}
+
+#[test]
+fn test_extensions() {
+    check(&SOURCE.parse().unwrap());
+}
+
+#[test]
+fn test_extensions_round_trip() {
+    let ogkr: Ogkr = SOURCE.parse().unwrap();
+    let written = write_raw_ogkr(&ogkr.to_raw());
+    assert!(written.contains("X_COLOR\tred\n"));
+
+    check(&written.parse().unwrap());
+}
+
+#[test]
+fn test_extensions_of_dropped_notes() {
+    let source = "
+        LCS 0 0 0 0
+        LCE 0 4 0 0
+        TAP 9 0 0 0 0
+        X_COLOR blue
+        TAP 0 1 0 0 0
+        X_COLOR red
+    ";
+    let mut options = ParseOptions::default();
+    options.missing_lane = MissingLanePolicy::Drop;
+    let raw = parse_source_with_options(source, &options).unwrap();
+    let ogkr = Ogkr::from_raw_with_options(raw, &options).unwrap();
+
+    let taps = ogkr.notes.all_taps().collect::<Vec<_>>();
+    assert_eq!(taps.len(), 1);
+    assert_eq!(taps[0].extensions, extensions(&[("COLOR", "red")]));
+}
+
+#[test]
+fn test_sounds() {
+    let source = "
+        LCS 0 0 0 0
+        LCE 0 4 0 0
+        TAP 0 0 0 0 0
+        X_SOUND kick.wav
+        TAP 0 1 0 0 0
+        BEL 2 0 0
+        X_SOUND chime 02.ogg
+    ";
+    let mut ogkr: Ogkr = source.parse().unwrap();
+
+    let taps = ogkr.notes.all_taps().collect::<Vec<_>>();
+    assert_eq!(taps[0].sound(), Some("kick.wav"));
+    assert_eq!(taps[1].sound(), None);
+    let bell = ogkr.notes.all_bells().next().unwrap();
+    assert_eq!(bell.sound(), Some("chime 02.ogg"));
+
+    for mut tap in std::mem::take(&mut ogkr.notes.taps).into_items() {
+        tap.set_sound("snare.wav");
+        ogkr.notes.taps.push(tap.position.time, tap);
+    }
+    let written: Ogkr = write_raw_ogkr(&ogkr.to_raw()).parse().unwrap();
+    let sounds = written
+        .notes
+        .all_taps()
+        .map(|tap| tap.sound())
+        .collect::<Vec<_>>();
+    assert_eq!(sounds, [Some("snare.wav"), Some("snare.wav")]);
+    assert_eq!(
+        written.notes.all_bells().next().unwrap().sound(),
+        Some("chime 02.ogg")
+    );
+}
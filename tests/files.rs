@@ -1,6 +1,7 @@
 use ogkr::{
     lex::tokenize,
     parse::{analysis::parse_raw_ogkr, raw::parse_tokens},
+    Error, LoadError, Ogkr,
 };
 
 fn test_file(source: &str) {
@@ -23,3 +24,26 @@ fn test_2() {
 fn test_3() {
     test_file(include_str!("../charts/3.ogkr"));
 }
+
+#[test]
+fn test_one_shot_parse() {
+    let source = include_str!("../charts/1.ogkr");
+    let ogkr: Ogkr = source.parse().expect("must be parsed");
+    assert_eq!(
+        ogkr.notes.all_taps().count(),
+        ogkr::parse(source).unwrap().notes.all_taps().count()
+    );
+
+    assert!(matches!(ogkr::parse("TAP 0"), Err(Error::Lex(_))));
+    assert!(matches!(ogkr::parse("WLS 0 0 0 0"), Err(Error::Parse(_))));
+}
+
+#[test]
+fn test_from_path() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/charts/2.ogkr");
+    assert!(Ogkr::from_path(path).is_ok());
+
+    let error = Ogkr::from_path("charts/missing.ogkr").unwrap_err();
+    assert!(matches!(error, LoadError::Io { .. }));
+    assert!(error.to_string().contains("charts/missing.ogkr"));
+}
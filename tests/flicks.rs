@@ -0,0 +1,69 @@
+use ogkr::{
+    parse::analysis::TimingPoint,
+    validate::{Issue, Severity},
+    Ogkr,
+};
+
+const SOURCE: &str = "
+    FLK 0 0 0 L
+    FLK 1 0 -8 L
+    FLK 1 0 8 R
+    CFK 2 0 4 L
+    FLK 2 0 4 R
+";
+
+#[test]
+fn test_flick_groups() {
+    let ogkr: Ogkr = SOURCE.parse().unwrap();
+
+    let groups = ogkr
+        .notes
+        .flick_groups()
+        .map(|group| (group.time.measure, group.flicks.len(), group.is_mixed()))
+        .collect::<Vec<_>>();
+    assert_eq!(groups, vec![(0, 1, false), (1, 2, true), (2, 2, true)]);
+}
+
+#[test]
+fn test_flick_conflicts() {
+    let ogkr: Ogkr = SOURCE.parse().unwrap();
+
+    let conflicts = ogkr.notes.flick_conflicts();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].time, TimingPoint::new(2, 0));
+    assert_eq!(conflicts[0].x.position, 4);
+
+    // Reported as a lint, the flicks are kept.
+    assert!(ogkr.warnings.is_empty());
+    let report = ogkr.validate();
+    assert_eq!(
+        report.issues,
+        [Issue::ContradictoryFlicks {
+            time: TimingPoint::new(2, 0),
+            x_position: 4
+        }]
+    );
+    assert_eq!(report.issues[0].severity(), Severity::Warning);
+}
+
+#[test]
+fn test_flick_inputs() {
+    let ogkr: Ogkr = SOURCE.parse().unwrap();
+
+    let inputs = ogkr
+        .notes
+        .flick_inputs(ogkr.track.resolution)
+        .iter()
+        .map(|input| (input.x, input.swipe))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        inputs,
+        vec![
+            (0.0, -8.0),
+            (-8.0, -8.0),
+            (8.0, 8.0),
+            (4.0, 8.0),
+            (4.0, -8.0)
+        ]
+    );
+}
@@ -0,0 +1,107 @@
+use ogkr::lex::{command::BulletDamageType, token::Token, tokenize};
+#[cfg(feature = "legacy-format")]
+use ogkr::{
+    lex::{
+        command::{ChartFormat, Version},
+        LexError,
+    },
+    parse::{raw::parse_source_with_options, ParseOptions},
+    Ogkr,
+};
+
+const PALETTE: &str = "BPL A UPS 0 PLR 1.0 N CIR 0\n";
+
+#[cfg(feature = "legacy-format")]
+fn parse_with_format(source: &str, format: ChartFormat) -> ogkr::Result<Ogkr> {
+    let mut options = ParseOptions::default();
+    options.format = format;
+    ogkr::parse_with_options(source, &options)
+}
+
+#[cfg(feature = "legacy-format")]
+#[test]
+fn test_current_format() {
+    assert_eq!(
+        ChartFormat::of_version(&Version::BULLET_DAMAGE_IN_BLT),
+        ChartFormat::Current
+    );
+
+    let source = format!("VERSION 1 8 0\n{PALETTE}BLT A 0 0 0\n");
+    match tokenize(&source).unwrap_err() {
+        LexError::ExpectedToken { line, token, .. } => assert_eq!((line, token.as_str()), (3, "")),
+        error => panic!("unexpected error {error:?}"),
+    }
+
+    // Without a version, the damage type stays optional until analysis.
+    let source = format!("{PALETTE}BLT A 0 0 0\n");
+    assert!(tokenize(&source).is_ok());
+    assert!(parse_with_format(&source, ChartFormat::Current).is_err());
+
+    // Legacy palettes are not recognized once the version is known.
+    assert!(tokenize("VERSION 1 8 0\nBPL A UPS 0 PLR 1.0 NML\n").is_err());
+}
+
+#[cfg(feature = "legacy-format")]
+#[test]
+fn test_legacy_format() {
+    assert_eq!(
+        ChartFormat::of_version(&Version::new(1, 0, 0)),
+        ChartFormat::Legacy
+    );
+
+    // Bullets have a damage type, overriding the one of their palette.
+    let source = "VERSION 1 0 0\nBPL A UPS 0 PLR 1.0 STR\nBLT A 0 0 0 NML\nBLT A 1 0 0 STR\n";
+    let ogkr: Ogkr = source.parse().unwrap();
+    assert_eq!(
+        ogkr.bullets
+            .all_bullets()
+            .map(|bullet| bullet.damage_type)
+            .collect::<Vec<_>>(),
+        [BulletDamageType::Normal, BulletDamageType::Hard]
+    );
+    assert!(tokenize("VERSION 1 0 0\nBPL A UPS 0 PLR 1.0 STR\nBLT A 0 0 0\n").is_err());
+
+    // Current syntax is not recognized once the version is known.
+    let source = format!("VERSION 1 0 0\n{PALETTE}");
+    assert!(tokenize(&source).is_err());
+
+    // Forcing the format overrides the version.
+    let source = "VERSION 1 8 0\nBPL A UPS 0 PLR 1.0 NML\nBLT A 0 0 0 NML\n";
+    assert!(source.parse::<Ogkr>().is_err());
+    assert!(parse_with_format(source, ChartFormat::Legacy).is_ok());
+
+    let legacy = include_str!("../charts/2.ogkr");
+    assert!(parse_with_format(legacy, ChartFormat::Current).is_err());
+    let raw = parse_source_with_options(legacy, &ParseOptions::default()).unwrap();
+    assert!(raw
+        .bullets
+        .iter()
+        .all(|bullet| bullet.damage_type.is_some()));
+    assert!(raw
+        .bullet_pallete_list
+        .iter()
+        .all(|palette| palette.size.is_none() && palette.damage_type.is_some()));
+}
+
+#[test]
+fn test_recognized_format() {
+    let source = format!("BPL A UPS 0 PLR 1.0 NML\n{PALETTE}BLT A 0 0 0\nBLT A 0 0 0 STR\n");
+    let damage_types = tokenize(&source)
+        .unwrap()
+        .iter()
+        .map(|token| match token {
+            Token::BulletPalette(palette) => palette.damage_type,
+            Token::Bullet(bullet) => bullet.damage_type,
+            token => panic!("unexpected token {token:?}"),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        damage_types,
+        [
+            Some(BulletDamageType::Normal),
+            None,
+            None,
+            Some(BulletDamageType::Hard)
+        ]
+    );
+}
@@ -0,0 +1,125 @@
+use ogkr::{
+    gameplay::{self, BeamDamage, ResolvedBullet},
+    lex::command::BulletSize,
+    parse::DamageValues,
+    Ogkr,
+};
+
+const SOURCE: &str = "
+    BPM_DEF 120.000 120.000 120.000 120.000
+    BPM 0 0 120.000
+    MET 0 0 4 4
+    BPL A CEN 0 PLR 1.0 L CIR 0
+    BPL B CEN 2 FIX 2.0 N CIR 0
+    BLT A 2 0 0 NML
+    BLT B 2 0 -16 NML
+";
+
+#[test]
+fn test_resolved_bullets() {
+    let ogkr: Ogkr = SOURCE.parse().unwrap();
+    let resolve = |palette_id: &str| {
+        let palette = ogkr.bullets.palette_ref(palette_id);
+        let spawn = ogkr
+            .bullet_spawns()
+            .into_values()
+            .flatten()
+            .find(|spawn| Some(spawn.bullet.palette) == palette)
+            .unwrap();
+        ResolvedBullet::new(
+            &spawn,
+            ogkr.bullets.palette(spawn.bullet.palette),
+            ogkr.track.resolution,
+            8.0,
+        )
+    };
+
+    // Aimed at the player, shot two seconds before arriving at measure 2.
+    let aimed = resolve("A");
+    assert_eq!((aimed.spawn_ms, aimed.arrival_ms), (2000.0, 4000.0));
+    assert_eq!(aimed.x_at(1999.0), None);
+    assert_eq!(aimed.x_at(3000.0), Some(4.0));
+    assert_eq!(aimed.x_at(4000.0), Some(8.0));
+
+    // Twice as fast, to its fixed position plus the palette offset.
+    let fixed = resolve("B");
+    assert_eq!(fixed.spawn_ms, 3000.0);
+    assert_eq!(fixed.arrival_x, -14.0);
+}
+
+#[test]
+fn test_collisions() {
+    let bullet = ResolvedBullet {
+        spawn_ms: 0.0,
+        arrival_ms: 1000.0,
+        spawn_x: 0.0,
+        arrival_x: 0.0,
+    };
+
+    assert!(gameplay::collides(0.0, 1000.0, &bullet, BulletSize::Normal));
+    assert!(gameplay::collides(4.0, 1000.0, &bullet, BulletSize::Normal));
+    assert!(!gameplay::collides(
+        4.5,
+        1000.0,
+        &bullet,
+        BulletSize::Normal
+    ));
+    assert!(gameplay::collides(4.5, 1000.0, &bullet, BulletSize::Large));
+    // Bullets only hit when crossing the player.
+    assert!(!gameplay::collides(0.0, 500.0, &bullet, BulletSize::Large));
+    assert!(gameplay::collides(0.0, 1030.0, &bullet, BulletSize::Normal));
+    assert!(!gameplay::collides(
+        0.0,
+        1040.0,
+        &bullet,
+        BulletSize::Normal
+    ));
+}
+
+#[test]
+fn test_beam_damage() {
+    let source = "
+        BPM_DEF 120.000 120.000 120.000 120.000
+        BPM 0 0 120.000
+        MET 0 0 4 4
+        WLS 0 0 0 -24
+        WLE 0 4 0 -24
+        WRS 1 0 0 24
+        WRE 1 4 0 24
+        BMS 0 1 0 0 4
+        BME 0 2 0 0 4
+        BMS 1 3 0 0 48
+        BME 1 3 960 0 48
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let damage = DamageValues::arcade_defaults();
+
+    // Measures last 2 seconds, ticks fall every 100 ms from start to end included.
+    assert_eq!(
+        gameplay::beam_damage(&ogkr, &damage),
+        BeamDamage {
+            avoidable: 21.0 * 2.0,
+            unavoidable: 11.0 * 2.0,
+        }
+    );
+    assert_eq!(gameplay::beam_damage_taken(&ogkr, &damage, |_| 10.0), 22.0);
+    assert_eq!(
+        gameplay::beam_damage_taken(&ogkr, &damage, |ms| if ms < 6000.0 { 0.0 } else { 10.0 }),
+        64.0
+    );
+
+    // Only ticks with an active beam are counted, however far apart the beams are.
+    let source = "
+        BPM 0 0 120.000
+        MET 0 0 4 4
+        BMS 0 1 0 0 4
+        BME 0 2 0 0 4
+        BMS 1 100000000 0 0 4
+        BME 1 100000001 0 0 4
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    assert_eq!(
+        gameplay::beam_damage(&ogkr, &damage).avoidable,
+        2.0 * 21.0 * 2.0
+    );
+}
@@ -0,0 +1,82 @@
+use ogkr::{
+    build::ChartBuilder,
+    generate::{grid, stress_chart, taps, Spray, Zigzag},
+    lex::command::CommandTime,
+    parse::analysis::Resolution,
+    Ogkr,
+};
+
+#[test]
+fn test_grid() {
+    let times = grid(Resolution::default(), 3, 1..3).collect::<Vec<_>>();
+    assert_eq!(
+        times,
+        [
+            CommandTime::new(1, 0),
+            CommandTime::new(1, 640),
+            CommandTime::new(1, 1280),
+            CommandTime::new(2, 0),
+            CommandTime::new(2, 640),
+            CommandTime::new(2, 1280)
+        ]
+    );
+
+    let taps = taps(4, times, |time| time.measure as i32).collect::<Vec<_>>();
+    assert_eq!(taps.len(), 6);
+    assert!(taps.iter().all(|tap| tap.lane_group_id == 4));
+    assert_eq!(taps[3].x_position, 2);
+}
+
+#[test]
+fn test_zigzag() {
+    let resolution = Resolution::default();
+    let zigzag = Zigzag {
+        left: -8,
+        right: 8,
+        period: 960,
+    };
+    assert_eq!(
+        zigzag.points(resolution, CommandTime::new(1, 0), CommandTime::new(2, 480)),
+        [
+            (CommandTime::new(1, 0), -8),
+            (CommandTime::new(1, 960), 8),
+            (CommandTime::new(2, 0), -8),
+            (CommandTime::new(2, 480), 0)
+        ]
+    );
+    assert_eq!(
+        zigzag.x_at(
+            resolution,
+            CommandTime::new(1, 0),
+            CommandTime::new(1, 1440)
+        ),
+        0
+    );
+}
+
+#[test]
+fn test_spray() {
+    let spray = Spray {
+        from_x: -10,
+        to_x: 10,
+        count: 3,
+    };
+    assert_eq!(spray.positions().collect::<Vec<_>>(), [-10, 0, 10]);
+
+    let mut builder = ChartBuilder::new();
+    assert!(spray
+        .add_to(&mut builder, "A", [CommandTime::new(0, 0)], None)
+        .is_err());
+}
+
+#[test]
+fn test_stress_chart() {
+    let builder = stress_chart(8, 16);
+    let ogkr = builder.build_ogkr().unwrap();
+    assert_eq!(ogkr.notes.all_taps().count(), 8 * 16);
+    assert_eq!(ogkr.bullets.all_bullets().count(), 8 * 5);
+    assert!(ogkr.validate().is_valid());
+
+    let parsed: Ogkr = builder.write().unwrap().parse().unwrap();
+    assert_eq!(parsed.notes.len(), ogkr.notes.len());
+}
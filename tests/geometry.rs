@@ -0,0 +1,45 @@
+use ogkr::{
+    geometry::{Geometry, Point, TimeAxis},
+    parse::analysis::{TimingPoint, TrackPosition, XPosition},
+    Ogkr,
+};
+
+#[test]
+fn test_project() {
+    let source = "
+        BPM_DEF 120.000 120.000 120.000 120.000
+        TRESOLUTION 1920
+        XRESOLUTION 4096
+        SFL 1 0 1920 2.000000
+        WLS 0 0 0 -24
+        WLE 0 3 0 -24
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let geometry = Geometry::new(&ogkr);
+    let position = |measure, x, offset| {
+        TrackPosition::new(TimingPoint::new(measure, 0), XPosition::new(x, offset))
+    };
+
+    assert_eq!(
+        geometry.project(position(2, -24, 0), TimeAxis::Seconds),
+        Point { t: 4.0, x: 0.0 }
+    );
+    assert_eq!(
+        geometry.project(position(2, 0, 2048), TimeAxis::Scroll),
+        Point {
+            t: 6.0,
+            x: 0.5 + 0.5 / 48.0
+        }
+    );
+    assert_eq!(geometry.scroll(TimingPoint::new(1, 0)), 2.0);
+    assert_eq!(geometry.normalize_x(XPosition::new(24, 0)), 1.0);
+
+    let geometry = geometry.x_range(0.0, 8.0);
+    assert_eq!(geometry.normalize_x(XPosition::new(-8, 0)), -1.0);
+    assert_eq!(
+        Geometry::new(&ogkr)
+            .x_range(4.0, 4.0)
+            .normalize_x(XPosition::new(4, 0)),
+        0.0
+    );
+}
@@ -0,0 +1,54 @@
+use ogkr::lex::{
+    grammar::{self, ArgKind, Section},
+    token::TokenKind,
+    tokenize,
+};
+
+fn sample(kind: ArgKind) -> &'static str {
+    match kind {
+        ArgKind::UnsignedInteger => "1",
+        ArgKind::Integer => "-1",
+        ArgKind::Float => "1.5",
+        ArgKind::Identifier => "A",
+        ArgKind::Text => "some text",
+        ArgKind::Keyword(keywords) => keywords[0],
+    }
+}
+
+#[test]
+fn test_grammar_matches_lexer() {
+    for command in grammar::commands() {
+        let mnemonic = match command.kind {
+            TokenKind::Extension => "X_KEY",
+            kind => kind.mnemonic(),
+        };
+        let args = command
+            .args
+            .iter()
+            .map(|arg| sample(arg.kind))
+            .collect::<Vec<_>>();
+
+        let line = [mnemonic].iter().chain(&args).copied().collect::<Vec<_>>();
+        let tokens = tokenize(&line.join("\t")).unwrap();
+        assert_eq!(tokens.len(), 1, "{line:?}");
+        assert_eq!(tokens.iter().next().unwrap().kind(), Some(command.kind));
+
+        if command.min_args() > 0 && !matches!(command.args[0].kind, ArgKind::Text) {
+            let line = &line[..command.min_args()];
+            assert!(tokenize(&line.join("\t")).is_err(), "{line:?}");
+        }
+    }
+}
+
+#[test]
+fn test_command_grammar() {
+    let hold = grammar::command(TokenKind::CriticalHold);
+    assert_eq!(hold.section, Some(Section::Notes));
+    assert_eq!(hold.args.len(), 9);
+    assert_eq!(hold.args[0].name, "lane_group_id");
+
+    let bell = grammar::command(TokenKind::Bell);
+    assert_eq!((bell.min_args(), bell.max_args()), (3, 4));
+    assert_eq!(Section::BulletPalette.name(), "B_PALETTE");
+    assert_eq!(grammar::command(TokenKind::Extension).section, None);
+}
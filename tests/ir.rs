@@ -0,0 +1,102 @@
+use ogkr::{
+    ir::{self, IrError, LaneKind, NoteKind, Point},
+    Ogkr,
+};
+
+const SOURCE: &str = "
+    BPM 0 0 120.000000
+    BPM 1 0 240.000000
+    MET 0 0 4 4
+    LCS 0 0 0 0
+    LCE 0 4 0 16
+    WLS 1 0 0 -24
+    WLE 1 4 0 -24
+    TAP 0 0 960 4 2048
+    CHD 0 1 0 0 0 2 0 8 0
+    FLK 1 480 -8 R
+    BEL 0 0 8
+";
+
+#[test]
+fn test_from_ogkr() {
+    let ogkr: Ogkr = SOURCE.parse().unwrap();
+    let chart = ir::Chart::from(&ogkr);
+
+    assert_eq!(chart.bpm_changes.len(), 2);
+    assert_eq!(chart.bpm_changes[1].seconds, 2.0);
+    assert_eq!(chart.bpm_changes[1].bpm, 240.0);
+
+    assert_eq!(
+        chart.lanes.iter().map(|lane| lane.kind).collect::<Vec<_>>(),
+        [LaneKind::Center, LaneKind::WallLeft]
+    );
+    assert_eq!(
+        chart.lanes[0].points[1],
+        Point {
+            seconds: 5.0,
+            x: 16.0
+        }
+    );
+
+    let notes = chart
+        .notes
+        .iter()
+        .map(|note| (note.seconds, note.x, note.kind, note.is_critical))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        notes,
+        [
+            (0.0, 8.0, NoteKind::Bell, false),
+            (1.0, 4.5, NoteKind::Tap { lane: Some(0) }, false),
+            (
+                2.0,
+                0.0,
+                NoteKind::Hold {
+                    lane: Some(0),
+                    end: Point {
+                        seconds: 3.0,
+                        x: 8.0
+                    },
+                },
+                true,
+            ),
+            (
+                2.25,
+                -8.0,
+                NoteKind::Flick {
+                    direction: ogkr::lex::command::FlickDirection::Right,
+                },
+                false,
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_round_trip() {
+    let ogkr: Ogkr = SOURCE.parse().unwrap();
+    let chart = ir::Chart::from(&ogkr);
+    let converted = Ogkr::try_from(&chart).unwrap();
+
+    assert_eq!(ir::Chart::from(&converted), chart);
+    assert_eq!(
+        converted.notes.all_holds().next().unwrap().end,
+        ogkr.notes.all_holds().next().unwrap().end
+    );
+}
+
+#[test]
+fn test_missing_lane() {
+    let mut chart = ir::Chart::from(&SOURCE.parse::<Ogkr>().unwrap());
+    let tap = chart
+        .notes
+        .iter_mut()
+        .find(|note| matches!(note.kind, NoteKind::Tap { .. }))
+        .unwrap();
+    tap.kind = NoteKind::Tap { lane: None };
+
+    assert_eq!(
+        Ogkr::try_from(chart).unwrap_err(),
+        IrError::MissingLane { seconds: 1.0 }
+    );
+}
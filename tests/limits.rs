@@ -0,0 +1,51 @@
+use ogkr::parse::{raw::parse_source_with_options, ParseError, ParseOptions};
+
+const CHART: &str = include_str!("../charts/1.ogkr");
+
+#[test]
+fn test_default_options_unbounded() {
+    assert!(parse_source_with_options(CHART, &ParseOptions::default()).is_ok());
+    assert!(parse_source_with_options(CHART, &ParseOptions::untrusted()).is_ok());
+}
+
+#[test]
+fn test_max_file_size() {
+    let mut options = ParseOptions::default();
+    options.max_file_size = Some(16);
+
+    assert_eq!(
+        parse_source_with_options(CHART, &options).unwrap_err(),
+        ParseError::LimitExceeded {
+            what: "file size",
+            max: 16
+        }
+    );
+}
+
+#[test]
+fn test_max_tokens() {
+    let mut options = ParseOptions::default();
+    options.max_tokens = Some(10);
+
+    assert_eq!(
+        parse_source_with_options(CHART, &options).unwrap_err(),
+        ParseError::LimitExceeded {
+            what: "tokens",
+            max: 10
+        }
+    );
+}
+
+#[test]
+fn test_max_points_per_section() {
+    let mut options = ParseOptions::default();
+    options.max_points_per_section = Some(2);
+
+    assert_eq!(
+        parse_source_with_options(CHART, &options).unwrap_err(),
+        ParseError::LimitExceeded {
+            what: "points per section",
+            max: 2
+        }
+    );
+}
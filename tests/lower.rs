@@ -0,0 +1,68 @@
+use ogkr::{
+    parse::{
+        analysis::{lower, parse_raw_ogkr, LaneId, RoundTripError, TimingPoint},
+        MissingLanePolicy, ParseOptions,
+    },
+    write::write_raw_ogkr,
+    Ogkr,
+};
+
+const SOURCE: &str = "
+    LCS 0 0 0 0
+    LCE 0 4 0 0
+    LCS 1 0 0 8
+    LCE 1 4 0 8
+    TAP 0 1 0 0 0
+    TAP 1 2 0 8 0
+";
+
+#[test]
+fn test_lower() {
+    let ogkr: Ogkr = SOURCE.parse().unwrap();
+    assert_eq!(
+        write_raw_ogkr(&lower(&ogkr)),
+        write_raw_ogkr(&ogkr.to_raw())
+    );
+
+    let lowered = parse_raw_ogkr(lower(&ogkr)).unwrap();
+    assert!(lowered.is_equivalent(&ogkr));
+    assert!(ogkr.check_round_trip(&ParseOptions::default()).is_ok());
+
+    let written: Ogkr = write_raw_ogkr(&lower(&ogkr)).parse().unwrap();
+    assert!(written.is_equivalent(&ogkr));
+    assert!(!written.is_equivalent(&"LCS 0 0 0 0\nLCE 0 4 0 0\n".parse().unwrap()));
+}
+
+#[test]
+fn test_round_trip_errors() {
+    // Removing a lane leaves its tap without one.
+    let mut ogkr: Ogkr = SOURCE.parse().unwrap();
+    ogkr.track
+        .lanes_center
+        .get_mut(&TimingPoint::new(0, 0))
+        .unwrap()
+        .retain(|&lane| lane != LaneId(1));
+    assert_eq!(
+        ogkr.track.lanes_data.remove(&LaneId(1)).unwrap().points[0]
+            .x
+            .position,
+        8
+    );
+
+    assert!(matches!(
+        ogkr.check_round_trip(&ParseOptions::default()),
+        Err(RoundTripError::Analysis(_))
+    ));
+
+    let mut options = ParseOptions::default();
+    options.missing_lane = MissingLanePolicy::Drop;
+    match ogkr.check_round_trip(&options).unwrap_err() {
+        RoundTripError::Mismatch {
+            expected, found, ..
+        } => {
+            assert!(expected.starts_with("TAP\t1\t2\t0"), "{expected}");
+            assert_eq!(found, "");
+        }
+        error => panic!("unexpected error {error:?}"),
+    }
+}
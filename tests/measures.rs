@@ -0,0 +1,45 @@
+use ogkr::Ogkr;
+
+#[test]
+fn test_measure_views() {
+    let source = "
+        BPM 0 0 120.000000
+        BPM 2 480 180.000000
+        MET 0 0 4 4
+        LCS 0 0 0 0
+        LCN 0 1 960 8
+        LCE 0 3 0 0
+        TAP 0 0 0 0 0
+        TAP 0 1 0 4 0
+        CTP 0 1 0 4 0
+        FLK 1 480 0 L
+        BEL 2 0 4
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+
+    let measures = ogkr.measures().collect::<Vec<_>>();
+    assert_eq!(measures.len(), 4);
+
+    assert_eq!(measures[0].taps.len(), 1);
+    assert_eq!(measures[1].taps.len(), 2);
+    assert_eq!(measures[1].flicks.len(), 1);
+    assert_eq!(measures[2].bells.len(), 1);
+    assert!(measures[3].is_empty());
+
+    assert_eq!(measures[2].bpm.unwrap().bpm, 120.0f32.to_bits());
+    assert_eq!(measures[2].bpm_changes.len(), 1);
+    assert_eq!(measures[3].bpm.unwrap().bpm, 180.0f32.to_bits());
+    assert!(measures.iter().all(|m| m.meter.is_some()));
+
+    let segment_times = |measure: usize| {
+        measures[measure].lane_segments[0]
+            .points
+            .iter()
+            .map(|p| (p.time.measure, p.time.beat_offset))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(segment_times(0), vec![(0, 0), (1, 960)]);
+    assert_eq!(segment_times(1), vec![(0, 0), (1, 960), (3, 0)]);
+    assert_eq!(segment_times(2), vec![(1, 960), (3, 0)]);
+    assert_eq!(segment_times(3), vec![(3, 0)]);
+}
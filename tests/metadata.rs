@@ -0,0 +1,88 @@
+use ogkr::{
+    lex::{command::Version, CustomCommand, LexOptions, RawLineReader},
+    parse::{Header, ParseOptions},
+    write::write_raw_ogkr,
+    Ogkr,
+};
+
+/// Made-up `SIG <name>` command of a private editor, signing the chart.
+#[derive(Debug)]
+struct Signature(String);
+
+impl CustomCommand for Signature {
+    fn parse(reader: &mut RawLineReader<'_>) -> ogkr::lex::Result<Self> {
+        Ok(Self(reader.rest_of_line().to_string()))
+    }
+
+    fn args(&self) -> Vec<String> {
+        vec![self.0.clone()]
+    }
+}
+
+#[test]
+fn test_creators() {
+    let mut header = Header::default();
+    assert_eq!(header.creators().count(), 0);
+
+    header.add_creator("first");
+    header.add_creator("second");
+    assert_eq!(header.creator.as_ref().unwrap().name, "first / second");
+    assert_eq!(header.creators().collect::<Vec<_>>(), ["first", "second"]);
+
+    header.set_creator("third");
+    assert_eq!(header.creators().collect::<Vec<_>>(), ["third"]);
+}
+
+#[test]
+fn test_anonymize() {
+    let mut options = ParseOptions::default();
+    options.lex = LexOptions::default().custom_command::<Signature>("SIG");
+    let mut ogkr = ogkr::parse_with_options(
+        "
+        [HEADER]
+        CREATOR someone
+        X_EDITOR private editor of someone
+        [NOTES]
+        SIG someone
+        BEL 0 0 0
+        X_SOUND bell.wav
+        ",
+        &options,
+    )
+    .unwrap();
+    assert_eq!(ogkr.header.creators().collect::<Vec<_>>(), ["someone"]);
+
+    ogkr.anonymize();
+    assert!(ogkr.header.creator.is_none());
+    assert!(ogkr.extensions.is_empty());
+    assert!(ogkr.custom_commands.is_empty());
+    assert_eq!(
+        ogkr.notes.all_bells().next().unwrap().sound(),
+        Some("bell.wav")
+    );
+    assert!(!write_raw_ogkr(&ogkr.to_raw()).contains("someone"));
+}
+
+#[test]
+fn test_version() {
+    let version: Version = "1.7.0".parse().unwrap();
+    assert_eq!(version, Version::new(1, 7, 0));
+    assert_eq!(version.to_string(), "1.7.0");
+    for invalid in ["", "1.7", "1.7.0.1", "1.x.0", "1 7 0"] {
+        assert!(invalid.parse::<Version>().is_err(), "{invalid}");
+    }
+
+    assert!(Version::new(1, 6, 9) < Version::new(1, 7, 0));
+    assert!(Version::new(1, 10, 0) > Version::new(1, 9, 5));
+    assert!(Version::new(2, 0, 0) > Version::new(1, 99, 99));
+
+    for (source, supports) in [
+        (include_str!("../charts/1.ogkr"), true),
+        (include_str!("../charts/2.ogkr"), false),
+        (include_str!("../charts/3.ogkr"), true),
+    ] {
+        let ogkr: Ogkr = source.parse().unwrap();
+        let version = ogkr.header.version.unwrap();
+        assert_eq!(version.supports_bullet_damage_in_blt(), supports);
+    }
+}
@@ -0,0 +1,63 @@
+use ogkr::{
+    parse::{
+        analysis::{LaneType, TimingPoint},
+        MissingLanePolicy, ParseOptions, Warning,
+    },
+    Error,
+};
+
+const SOURCE: &str = "
+    LCS 0 0 0 0
+    LCE 0 4 0 0
+    TAP 0 1 0 0 0
+    TAP 7 1 0 8 0
+    HLD 7 2 0 8 0 3 0 8 0
+";
+
+fn options(missing_lane: MissingLanePolicy) -> ParseOptions {
+    let mut options = ParseOptions::default();
+    options.missing_lane = missing_lane;
+    options
+}
+
+#[test]
+fn test_missing_lane_error() {
+    assert!(matches!(ogkr::parse(SOURCE), Err(Error::Parse(_))));
+}
+
+#[test]
+fn test_missing_lane_drop() {
+    let ogkr = ogkr::parse_with_options(SOURCE, &options(MissingLanePolicy::Drop)).unwrap();
+
+    assert_eq!(ogkr.notes.all_taps().count(), 1);
+    assert_eq!(ogkr.notes.all_holds().count(), 0);
+    assert_eq!(ogkr.warnings.len(), 2);
+    assert!(ogkr
+        .warnings
+        .iter()
+        .all(|w| matches!(w, Warning::NoteDroppedMissingLane { lane_id: 7, .. })));
+}
+
+#[test]
+fn test_missing_lane_synthetic() {
+    let ogkr =
+        ogkr::parse_with_options(SOURCE, &options(MissingLanePolicy::SyntheticLane)).unwrap();
+
+    assert_eq!(ogkr.notes.all_taps().count(), 2);
+    assert_eq!(ogkr.notes.all_holds().count(), 1);
+    assert_eq!(
+        ogkr.warnings.iter().collect::<Vec<_>>(),
+        vec![&Warning::SyntheticLaneCreated { lane_id: 7 }]
+    );
+
+    let hold = ogkr.notes.all_holds().next().unwrap();
+    assert_eq!(hold.lane_type, LaneType::Synthetic);
+    assert_eq!(hold.points.len(), 2);
+
+    // Synthetic lanes are only reachable from their notes.
+    assert!(ogkr.track.get_lane(hold.lane_id).is_some());
+    let time = TimingPoint::new(2, 960);
+    assert_eq!(ogkr.track.lanes_active_at(time).len(), 1);
+    assert!(ogkr.track.lane_at_position(time, 8.0).is_none());
+    assert_eq!(ogkr.measure(2).lane_segments.len(), 1);
+}
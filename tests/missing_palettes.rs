@@ -0,0 +1,107 @@
+use ogkr::{
+    parse::{
+        analysis::{BulletPaletteId, PaletteIdRef},
+        MissingPalettePolicy, ParseOptions, Warning,
+    },
+    Error,
+};
+
+const SOURCE: &str = "
+    BPL A UPS 0 PLR 1.0 N CIR 0
+    BLT A 0 0 0 NML
+    BLT Z 1 0 0 NML
+    BLT Z 2 0 0
+";
+
+fn options(missing_palette: MissingPalettePolicy) -> ParseOptions {
+    let mut options = ParseOptions::default();
+    options.missing_palette = missing_palette;
+    options
+}
+
+#[test]
+fn test_missing_palette_error() {
+    assert!(matches!(ogkr::parse(SOURCE), Err(Error::Parse(_))));
+}
+
+#[test]
+fn test_missing_palette_drop() {
+    let ogkr = ogkr::parse_with_options(SOURCE, &options(MissingPalettePolicy::Drop)).unwrap();
+
+    assert_eq!(ogkr.bullets.all_bullets().count(), 1);
+    assert_eq!(ogkr.warnings.len(), 2);
+}
+
+#[test]
+fn test_missing_palette_placeholder() {
+    let ogkr =
+        ogkr::parse_with_options(SOURCE, &options(MissingPalettePolicy::Placeholder)).unwrap();
+
+    assert_eq!(ogkr.bullets.all_bullets().count(), 3);
+    assert_eq!(
+        ogkr.warnings.iter().collect::<Vec<_>>(),
+        vec![&Warning::PlaceholderPaletteCreated {
+            palette_id: "Z".to_string()
+        }]
+    );
+    assert!(ogkr
+        .bullets
+        .get_bullet_palette(&BulletPaletteId("Z".to_string()))
+        .is_some());
+}
+
+#[test]
+fn test_interned_palettes() {
+    let ogkr =
+        ogkr::parse_with_options(SOURCE, &options(MissingPalettePolicy::Placeholder)).unwrap();
+    let bullets = &ogkr.bullets;
+
+    assert_eq!(
+        bullets
+            .palettes()
+            .iter()
+            .map(|palette| palette.id.0.as_str())
+            .collect::<Vec<_>>(),
+        ["A", "Z"]
+    );
+    assert_eq!(bullets.palette_ref("Z"), Some(PaletteIdRef(1)));
+    assert_eq!(bullets.palette_ref("B"), None);
+
+    assert_eq!(
+        bullets
+            .all_bullets()
+            .map(|bullet| bullets.palette(bullet.palette).unwrap().id.0.as_str())
+            .collect::<Vec<_>>(),
+        ["A", "Z", "Z"]
+    );
+    assert!(bullets.palette(PaletteIdRef(2)).is_none());
+}
+
+#[test]
+fn test_missing_bell_palette() {
+    let source = "BPL A UPS 0 PLR 1.0 N CIR 0\nBEL 0 0 0 A\nBEL 1 0 0 Y\n";
+    assert!(ogkr::parse(source).is_err());
+
+    let ogkr = ogkr::parse_with_options(source, &options(MissingPalettePolicy::Drop)).unwrap();
+    let palettes = ogkr
+        .notes
+        .all_bells()
+        .map(|bell| bell.bullet_palette)
+        .collect::<Vec<_>>();
+    assert_eq!(palettes, [Some(PaletteIdRef(0)), None]);
+    assert_eq!(
+        ogkr.warnings.iter().collect::<Vec<_>>(),
+        vec![&Warning::BulletDroppedMissingPalette {
+            palette_id: "Y".to_string(),
+            time: ogkr::parse::analysis::TimingPoint::new(1, 0),
+        }]
+    );
+
+    let ogkr =
+        ogkr::parse_with_options(source, &options(MissingPalettePolicy::Placeholder)).unwrap();
+    assert_eq!(
+        ogkr.notes.all_bells().nth(1).unwrap().bullet_palette,
+        ogkr.bullets.palette_ref("Y")
+    );
+    assert!(ogkr.bullets.palette_ref("Y").is_some());
+}
@@ -0,0 +1,111 @@
+use ogkr::{
+    parse::{analysis::LaneId, HoldPointsPolicy, ParseOptions},
+    Ogkr,
+};
+
+#[test]
+fn test_simultaneous_normal_and_critical_notes() {
+    let source = "
+        LCS 0 0 0 0
+        LCE 0 4 0 0
+        TAP 0 1 0 0 0
+        CTP 0 1 0 4 0
+        HLD 0 2 0 0 0 3 0 0 0
+        CHD 0 2 0 4 0 3 0 4 0
+        FLK 1 0 0 L
+        CFK 1 0 4 R
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+
+    let taps = ogkr.notes.create_all_taps();
+    assert_eq!(taps.len(), 2);
+    assert_eq!(taps.iter().filter(|tap| tap.is_critical).count(), 1);
+    assert_eq!(ogkr.notes.all_holds().count(), 2);
+    assert_eq!(ogkr.notes.all_flicks().count(), 2);
+}
+
+#[test]
+fn test_note_counts_match_chart() {
+    let ogkr: Ogkr = include_str!("../charts/1.ogkr").parse().unwrap();
+
+    assert_eq!(ogkr.notes.all_taps().count(), 3);
+    assert_eq!(ogkr.notes.all_holds().count(), 2);
+    assert_eq!(ogkr.notes.all_bells().count(), 2);
+    assert_eq!(ogkr.notes.all_flicks().count(), 2);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_par_iterators() {
+    use rayon::prelude::*;
+
+    let ogkr: Ogkr = include_str!("../charts/1.ogkr").parse().unwrap();
+    let notes = &ogkr.notes;
+
+    let times = |iter: Vec<ogkr::parse::analysis::TrackPosition>| {
+        iter.into_iter().map(|p| p.time).collect::<Vec<_>>()
+    };
+    assert_eq!(
+        times(notes.par_all_taps().map(|tap| tap.position).collect()),
+        times(notes.all_taps().map(|tap| tap.position).collect())
+    );
+    assert_eq!(
+        times(notes.par_all_holds().map(|hold| hold.start).collect()),
+        times(notes.all_holds().map(|hold| hold.start).collect())
+    );
+    assert_eq!(notes.par_all_bells().count(), notes.all_bells().len());
+    assert_eq!(notes.par_all_flicks().count(), notes.all_flicks().len());
+    assert_eq!(
+        ogkr.bullets.par_all_bullets().count(),
+        ogkr.bullets.all_bullets().len()
+    );
+}
+
+#[test]
+fn test_hold_paths() {
+    let mut options = ParseOptions::default();
+    options.hold_points = HoldPointsPolicy::Borrow;
+
+    for source in [
+        include_str!("../charts/1.ogkr"),
+        include_str!("../charts/2.ogkr"),
+        include_str!("../charts/3.ogkr"),
+    ] {
+        let copied: Ogkr = source.parse().unwrap();
+        let borrowed = ogkr::parse_with_options(source, &options).unwrap();
+
+        let holds = copied.notes.all_holds().zip(borrowed.notes.all_holds());
+        assert_eq!(holds.len(), copied.notes.all_holds().len());
+        for (copied_hold, borrowed_hold) in holds {
+            assert!(borrowed_hold.points.is_empty());
+            let path = borrowed_hold.path(&borrowed.track).unwrap();
+            assert_eq!(path.to_vec(), copied_hold.points);
+            assert_eq!(
+                copied_hold.path(&copied.track).unwrap().to_vec(),
+                copied_hold.points
+            );
+        }
+    }
+
+    let source = "
+        LCS 0 0 0 0
+        LCN 0 1 0 8
+        LCN 0 2 0 0
+        LCE 0 4 0 0
+        HLD 0 1 0 8 0 1 960 4 0
+        HLD 0 3 0 0 0 3 0 0 0
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let paths = ogkr
+        .notes
+        .all_holds()
+        .map(|hold| hold.path(&ogkr.track).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        paths[0].lane_points,
+        &ogkr.track.lanes_data[&LaneId(0)].points[1..2]
+    );
+    assert_eq!(paths[0].iter().count(), 2);
+    assert!(paths[1].lane_points.is_empty());
+    assert_eq!(paths[1].iter().count(), 2);
+}
@@ -0,0 +1,113 @@
+use ogkr::{
+    lex::{command::*, tokenize},
+    parse::{
+        analysis::parse_raw_ogkr,
+        raw::{parse_tokens, ColorfulLaneSection, LaneSection, RawOgkr},
+    },
+};
+
+/// Runs the full pipeline, only caring that it does not panic.
+fn parse_all(source: &str) {
+    if let Ok(tokens) = tokenize(source) {
+        if let Ok(raw_ogkr) = parse_tokens(tokens) {
+            let _ = parse_raw_ogkr(raw_ogkr);
+        }
+    }
+}
+
+fn lane_point(group_id: u32, measure: u32) -> LanePoint {
+    LanePoint {
+        group_id,
+        time: CommandTime { measure, offset: 0 },
+        x_position: 0,
+    }
+}
+
+#[test]
+fn test_remaining_line_at_end_of_file() {
+    assert!(tokenize("CREATOR foo").is_ok());
+    assert!(tokenize("VERSION 1 2 3\nCREATOR").is_ok());
+    assert!(tokenize("BEL 0 0 0").is_ok());
+    assert!(tokenize("BEL 0 0 0 A\r\n").is_ok());
+}
+
+#[test]
+fn test_garbage_input() {
+    for source in [
+        "",
+        "[",
+        "\n\n\r\n",
+        "\u{0}",
+        "TAP",
+        "HLD 0 0",
+        "BPL A UPS 0 PLR",
+    ] {
+        parse_all(source);
+    }
+}
+
+#[test]
+fn test_truncated_charts() {
+    for source in [
+        include_str!("../charts/1.ogkr"),
+        include_str!("../charts/2.ogkr"),
+        include_str!("../charts/3.ogkr"),
+    ] {
+        for (end, _) in source.char_indices() {
+            parse_all(&source[..end]);
+        }
+    }
+}
+
+#[test]
+fn test_single_point_sections() {
+    let mut raw = RawOgkr::default();
+    raw.track.colorful_lanes.push(ColorfulLaneSection {
+        group_id: 0,
+        points: vec![ColorfulLanePoint {
+            group_id: 0,
+            time: CommandTime::default(),
+            x_position: 0,
+            color: 0,
+            brightness: 0,
+        }],
+    });
+    assert!(parse_raw_ogkr(raw).is_err());
+
+    let mut raw = RawOgkr::default();
+    raw.track.lanes_center.push(LaneSection {
+        group_id: 0,
+        points: vec![lane_point(0, 0)],
+    });
+    assert!(parse_raw_ogkr(raw).is_err());
+}
+
+#[test]
+fn test_degenerate_holds() {
+    let hold = |start_measure, end_measure| Hold {
+        lane_group_id: 0,
+        start_time: CommandTime {
+            measure: start_measure,
+            offset: 0,
+        },
+        start_x_position: 0,
+        start_x_offset: 0,
+        end_time: CommandTime {
+            measure: end_measure,
+            offset: 0,
+        },
+        end_x_position: 0,
+        end_x_offset: 0,
+    };
+
+    let mut raw = RawOgkr::default();
+    raw.track.lanes_center.push(LaneSection {
+        group_id: 0,
+        points: vec![lane_point(0, 0), lane_point(0, 1), lane_point(0, 2)],
+    });
+    raw.notes.holds.push(hold(1, 1));
+    assert!(parse_raw_ogkr(raw.clone()).is_ok());
+
+    raw.notes.holds.push(hold(2, 1));
+    assert!(parse_raw_ogkr(raw).is_err());
+}
@@ -0,0 +1,132 @@
+use ogkr::{
+    build::{BuildError, BulletPaletteBuilder, ChartBuilder},
+    lex::command::{BulletDamageType, BulletTarget, CommandTime, MeterDefinition, TickResolution},
+    pattern::{bullets, BulletPattern},
+};
+
+const RESOLUTION: TickResolution = TickResolution { resolution: 1920 };
+const COMMON_TIME: MeterDefinition = MeterDefinition {
+    num_beats: 4,
+    note_value: 4,
+};
+
+#[test]
+fn test_expanded_patterns() {
+    let fan = BulletPattern::Fan {
+        x: 4,
+        spread: 8,
+        count: 3,
+    };
+    assert_eq!(
+        fan.expand(CommandTime::new(1, 0), RESOLUTION, COMMON_TIME),
+        [
+            (CommandTime::new(1, 0), -4),
+            (CommandTime::new(1, 0), 4),
+            (CommandTime::new(1, 0), 12)
+        ]
+    );
+
+    let stream = BulletPattern::Stream {
+        x: 0,
+        count: 3,
+        interval: 1.5,
+        drift: -2,
+    };
+    assert_eq!(
+        stream.expand(CommandTime::new(1, 960), RESOLUTION, COMMON_TIME),
+        [
+            (CommandTime::new(1, 960), 0),
+            (CommandTime::new(1, 1680), -2),
+            (CommandTime::new(2, 480), -4)
+        ]
+    );
+
+    let burst = BulletPattern::AimedBurst {
+        x: 0,
+        count: 2,
+        spacing: 0.25,
+        bursts: 2,
+        interval: 2.0,
+    };
+    let three_four = MeterDefinition {
+        num_beats: 3,
+        note_value: 4,
+    };
+    assert_eq!(
+        burst
+            .expand(CommandTime::new(0, 0), RESOLUTION, three_four)
+            .into_iter()
+            .map(|(time, _)| time)
+            .collect::<Vec<_>>(),
+        [
+            CommandTime::new(0, 0),
+            CommandTime::new(0, 160),
+            CommandTime::new(0, 1280),
+            CommandTime::new(0, 1440)
+        ]
+    );
+
+    let commands = bullets(
+        &fan,
+        "A",
+        CommandTime::new(0, 0),
+        RESOLUTION,
+        COMMON_TIME,
+        Some(BulletDamageType::Hard),
+    );
+    assert_eq!(commands.len(), 3);
+    assert!(commands.iter().all(|bullet| bullet.pallete_id == "A"));
+}
+
+#[test]
+fn test_builder_patterns() {
+    let mut builder = ChartBuilder::new();
+    builder
+        .meter(CommandTime::new(0, 0), 4, 4)
+        .meter(CommandTime::new(2, 0), 3, 4)
+        .bullet_palette(BulletPaletteBuilder::new("A"))
+        .unwrap()
+        .bullet_palette(BulletPaletteBuilder::new("B").target(BulletTarget::FixedPosition))
+        .unwrap();
+
+    let burst = BulletPattern::AimedBurst {
+        x: 0,
+        count: 3,
+        spacing: 1.0,
+        bursts: 1,
+        interval: 0.0,
+    };
+    assert_eq!(
+        builder
+            .bullet_pattern(
+                "B",
+                CommandTime::new(0, 0),
+                &burst,
+                Some(BulletDamageType::Normal)
+            )
+            .unwrap_err(),
+        BuildError::UnaimedPalette("B".to_string())
+    );
+    builder
+        .bullet_pattern(
+            "A",
+            CommandTime::new(2, 0),
+            &burst,
+            Some(BulletDamageType::Normal),
+        )
+        .unwrap();
+    let times = builder
+        .raw()
+        .bullets
+        .iter()
+        .map(|bullet| bullet.time)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        times,
+        [
+            CommandTime::new(2, 0),
+            CommandTime::new(2, 640),
+            CommandTime::new(2, 1280)
+        ]
+    );
+}
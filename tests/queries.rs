@@ -0,0 +1,424 @@
+use ogkr::{
+    parse::{
+        analysis::{BellKind, LaneId, TimingPoint},
+        query::{Interpolation, Note, NoteRef},
+    },
+    Ogkr,
+};
+
+const TRACK: &str = "
+    WLS 0 0 0 -24
+    WLE 0 4 0 -24
+    LLS 1 0 0 -16
+    LLE 1 2 0 -8
+    LCS 2 1 0 0
+    LCN 2 2 0 0
+    LCE 2 3 0 8
+    WRS 3 0 0 24
+    WRE 3 4 0 24
+";
+
+#[test]
+fn test_lanes_active_at() {
+    let ogkr: Ogkr = TRACK.parse().unwrap();
+    let track = &ogkr.track;
+
+    let active = |measure, offset| {
+        track
+            .lanes_active_at(TimingPoint::new(measure, offset))
+            .iter()
+            .map(|active| (active.lane.id.0, active.x))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(active(0, 0), vec![(0, -24.0), (1, -16.0), (3, 24.0)]);
+    assert_eq!(
+        active(1, 0),
+        vec![(0, -24.0), (1, -12.0), (2, 0.0), (3, 24.0)]
+    );
+    assert_eq!(active(2, 960), vec![(0, -24.0), (2, 4.0), (3, 24.0)]);
+    assert_eq!(active(5, 0), vec![]);
+}
+
+#[test]
+fn test_lanes_active_at_oversized_offset() {
+    let source = "
+        TRESOLUTION 1920
+        LCS 0 0 0 0
+        LCN 0 0 3000 8
+        LCE 0 1 0 0
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let track = &ogkr.track;
+    let lane = &track.lanes_data[&LaneId(0)];
+
+    // Measure 0 offset 3000 lies after the end of the lane at measure 1.
+    let time = TimingPoint::new(0, 3500);
+    assert!(track.lanes_active_at(time).is_empty());
+    for interpolation in [Interpolation::Linear, Interpolation::CatmullRom] {
+        assert_eq!(lane.x_at_with(time, track.resolution, interpolation), None);
+    }
+    assert_eq!(
+        lane.x_at(TimingPoint::new(0, 1500), track.resolution),
+        Some(4.0)
+    );
+}
+
+#[test]
+fn test_lane_at_position() {
+    let ogkr: Ogkr = TRACK.parse().unwrap();
+    let track = &ogkr.track;
+
+    let lane_at = |measure, offset, x| {
+        track
+            .lane_at_position(TimingPoint::new(measure, offset), x)
+            .map(|active| active.lane.id.0)
+    };
+
+    assert_eq!(lane_at(1, 0, -11.0), Some(1));
+    assert_eq!(lane_at(1, 0, -3.0), Some(2));
+    assert_eq!(lane_at(1, 0, -6.0), None);
+    // Walls are not lanes.
+    assert_eq!(lane_at(1, 0, -24.0), None);
+    assert_eq!(lane_at(3, 0, -16.0), None);
+}
+
+#[test]
+fn test_beams_active_at() {
+    let source = "
+        BMS 0 1 0 0 4
+        BMN 0 2 0 8 4
+        BME 0 3 0 8 8
+        OBS 1 2 0 -8 2 4
+        OBE 1 4 0 -16 2 8
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let track = &ogkr.track;
+
+    let active = |measure, offset| {
+        track
+            .beams_active_at(TimingPoint::new(measure, offset))
+            .iter()
+            .map(|active| (active.x, active.width, active.shoot_x_offset))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(active(0, 0), vec![]);
+    assert_eq!(active(1, 960), vec![(4.0, 4.0, 0.0)]);
+    assert_eq!(active(2, 0), vec![(-8.0, 2.0, 4.0), (8.0, 4.0, 0.0)]);
+    assert_eq!(active(2, 960), vec![(-10.0, 2.0, 5.0), (8.0, 6.0, 0.0)]);
+    assert_eq!(active(4, 0), vec![(-16.0, 2.0, 8.0)]);
+}
+
+#[test]
+fn test_enemy_track() {
+    let source = "
+        ENS 0 0 0 0
+        ENE 0 2 0 16
+        ENS 1 3 0 -8
+        ENE 1 4 0 -8
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let enemy = ogkr.track.enemy_track();
+
+    assert_eq!(enemy.lanes().len(), 2);
+    assert_eq!(enemy.position_at(TimingPoint::new(1, 0)), Some(8.0));
+    assert_eq!(enemy.position_at(TimingPoint::new(2, 960)), None);
+    assert_eq!(enemy.position_at(TimingPoint::new(3, 960)), Some(-8.0));
+}
+
+#[test]
+fn test_resample() {
+    let source = "
+        TRESOLUTION 1920
+        LCS 0 0 0 0
+        LCN 0 1 0 8
+        LCE 0 2 0 -8
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let resolution = ogkr.track.resolution;
+    let lane = &ogkr.track.lanes_data[&LaneId(0)];
+
+    let vertices = lane
+        .resample(480, resolution)
+        .into_iter()
+        .map(|vertex| vertex.x)
+        .collect::<Vec<_>>();
+    assert_eq!(vertices, [0.0, 2.0, 4.0, 6.0, 8.0, 4.0, 0.0, -4.0, -8.0]);
+
+    // Points of the lane are kept between samples.
+    let ticks = lane
+        .resample(1000, resolution)
+        .into_iter()
+        .map(|vertex| resolution.ticks_at(vertex.time))
+        .collect::<Vec<_>>();
+    assert_eq!(ticks, [0, 1000, 1920, 2000, 3000, 3840]);
+
+    assert_eq!(lane.resample(0, resolution).len(), 3841);
+}
+
+#[test]
+fn test_catmull_rom() {
+    let source = "
+        TRESOLUTION 1920
+        LCS 0 0 0 0
+        LCN 0 1 0 8
+        LCE 0 2 0 0
+        HLD 0 0 960 0 0 1 960 0 0
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let resolution = ogkr.track.resolution;
+    let lane = &ogkr.track.lanes_data[&LaneId(0)];
+
+    let at = |measure, offset, interpolation| {
+        lane.x_at_with(TimingPoint::new(measure, offset), resolution, interpolation)
+            .unwrap()
+    };
+    assert_eq!(at(0, 960, Interpolation::Linear), 4.0);
+    // Passes through the points, bulging towards the peak in between.
+    assert_eq!(at(1, 0, Interpolation::CatmullRom), 8.0);
+    assert_eq!(at(2, 0, Interpolation::CatmullRom), 0.0);
+    assert!(at(0, 960, Interpolation::CatmullRom) > 4.0);
+    assert!(at(1, 960, Interpolation::CatmullRom) > 4.0);
+    assert_eq!(
+        at(0, 960, Interpolation::CatmullRom),
+        at(1, 960, Interpolation::CatmullRom)
+    );
+    assert_eq!(
+        lane.x_at_with(
+            TimingPoint::new(2, 1),
+            resolution,
+            Interpolation::CatmullRom
+        ),
+        None
+    );
+
+    let hold = ogkr.notes.all_holds().next().unwrap();
+    let path = hold.sample_path(960, resolution, Interpolation::CatmullRom);
+    assert_eq!(path.len(), 3);
+    assert_eq!(path[1].x, 8.0);
+    // Holds start and end where their command says, between them they follow the lane points.
+    assert_eq!(path[0].x, 0.0);
+    assert_eq!(
+        hold.x_at(TimingPoint::new(1, 0), resolution, Interpolation::Linear),
+        Some(8.0)
+    );
+}
+
+#[test]
+fn test_all_notes() {
+    let source = "
+        LCS 0 0 0 0
+        LCE 0 4 0 0
+        FLK 0 0 0 L
+        TAP 0 1 0 0 0
+        BEL 1 0 4
+        HLD 0 0 960 0 0 1 0 0 0
+        TAP 0 2 0 0 0
+        CTP 0 0 960 4 0
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+
+    let notes = ogkr
+        .notes
+        .all()
+        .map(|note| {
+            let kind = match note {
+                NoteRef::Tap(_) => "tap",
+                NoteRef::Hold(_) => "hold",
+                NoteRef::Bell(_) => "bell",
+                NoteRef::Flick(_) => "flick",
+            };
+            (kind, note.time())
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        notes,
+        [
+            ("flick", TimingPoint::new(0, 0)),
+            ("tap", TimingPoint::new(0, 960)),
+            ("hold", TimingPoint::new(0, 960)),
+            ("tap", TimingPoint::new(1, 0)),
+            ("bell", TimingPoint::new(1, 0)),
+            ("tap", TimingPoint::new(2, 0)),
+        ]
+    );
+}
+
+#[test]
+fn test_note_counts() {
+    let source = "
+        LCS 0 0 0 0
+        LCE 0 4 0 0
+        TAP 0 0 0 0 0
+        CTP 0 0 0 4 0
+        TAP 0 1 0 0 0
+        HLD 0 2 0 0 0 3 0 0 0
+        BEL 1 0 4
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let notes = &ogkr.notes;
+    assert_eq!(notes.len(), 5);
+    assert!(!notes.is_empty());
+
+    let mut taps = notes.all_taps();
+    assert_eq!(taps.len(), 3);
+    assert_eq!(
+        taps.next_back().map(|tap| tap.position.time),
+        Some(TimingPoint::new(1, 0))
+    );
+    taps.next();
+    assert_eq!(taps.size_hint(), (1, Some(1)));
+    assert_eq!(notes.all_flicks().len(), 0);
+    assert_eq!(ogkr.bullets.all_bullets().len(), 0);
+}
+
+#[test]
+fn test_into_iterators() {
+    let source = "
+        BPL A CEN 0 PLR 1.0 L CIR 0
+        LCS 0 0 0 0
+        LCE 0 4 0 0
+        LLS 1 0 0 -8
+        LLE 1 4 0 -8
+        BMS 2 0 0 0 4
+        BME 2 1 0 0 4
+        TAP 0 1 0 0 0
+        HLD 1 0 960 -8 0 1 0 -8 0
+        FLK 0 0 0 L
+        BLT A 2 0 0 NML
+        BLT A 1 0 8 NML
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+
+    let times = (&ogkr.notes)
+        .into_iter()
+        .map(|note| note.time())
+        .collect::<Vec<_>>();
+    let notes = ogkr.notes.clone().into_iter();
+    assert_eq!(notes.len(), 3);
+    let owned = notes.collect::<Vec<_>>();
+    assert_eq!(owned.iter().map(Note::time).collect::<Vec<_>>(), times);
+    assert!(matches!(owned[1], Note::Hold(ref hold) if hold.lane_id == LaneId(1)));
+
+    let taps = ogkr.notes.clone().into_all_taps();
+    assert_eq!(
+        taps.map(|tap| tap.position.time).collect::<Vec<_>>(),
+        [TimingPoint::new(1, 0)]
+    );
+
+    assert_eq!(
+        ogkr.bullets
+            .clone()
+            .into_iter()
+            .map(|bullet| bullet.position.time)
+            .collect::<Vec<_>>(),
+        [TimingPoint::new(1, 0), TimingPoint::new(2, 0)]
+    );
+    assert_eq!((&ogkr.bullets).into_iter().len(), 2);
+
+    let lanes = ogkr.track.clone().into_lanes().map(|lane| lane.id);
+    assert_eq!(lanes.collect::<Vec<_>>(), [LaneId(0), LaneId(1)]);
+    assert_eq!(ogkr.track.into_beams().len(), 1);
+}
+
+#[test]
+fn test_query_builder() {
+    let source = "
+        BPL A CEN 0 PLR 1.0 L CIR 0
+        BPL B CEN 0 FIX 1.0 L CIR 0
+        LCS 0 0 0 0
+        LCE 0 8 0 0
+        TAP 0 0 0 0 0
+        CTP 0 1 0 0 0
+        CTP 0 2 0 0 0
+        CHD 0 3 0 0 0 4 0 0 0
+        CFK 3 0 16 L
+        BEL 3 0 0
+        CTP 0 6 0 0 0
+        BLT A 2 0 0 NML
+        BLT B 3 0 8 NML
+        BLT A 4 0 24 NML
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+
+    let times = |query: ogkr::parse::query::NoteQuery| {
+        query
+            .into_iter()
+            .map(|note| note.time().measure)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(times(ogkr.query().notes()), [0, 1, 2, 3, 3, 3, 6]);
+    assert_eq!(
+        times(ogkr.query().notes().critical().between(2, 3)),
+        [2, 3, 3]
+    );
+    assert_eq!(
+        times(
+            ogkr.query()
+                .notes()
+                .critical()
+                .between(2, 3)
+                .x_range(-8.0, 8.0)
+        ),
+        [2, 3]
+    );
+    assert_eq!(
+        times(ogkr.query().notes().between(1, 4).between(3, 8)),
+        [3, 3, 3]
+    );
+    // Empty ranges match nothing, with either storage backend.
+    assert_eq!(times(ogkr.query().notes().between(5, 2)), []);
+    assert_eq!(times(ogkr.query().notes().between(0, 1).between(3, 4)), []);
+    assert_eq!(times(ogkr.query().notes().holds().bells()), [3, 3]);
+    assert_eq!(ogkr.query().notes().normal().count(), 2);
+    assert!(matches!(
+        ogkr.query().notes().flicks().iter().next(),
+        Some(NoteRef::Flick(flick)) if flick.is_critical
+    ));
+
+    assert_eq!(ogkr.query().bullets().palette("A").count(), 2);
+    assert_eq!(
+        ogkr.query()
+            .bullets()
+            .x_range(24.0, 0.0)
+            .during(TimingPoint::new(3, 0)..TimingPoint::new(4, 0))
+            .into_iter()
+            .map(|bullet| ogkr.bullets.palette(bullet.palette).unwrap().id.0.as_str())
+            .collect::<Vec<_>>(),
+        ["B"]
+    );
+    assert_eq!(ogkr.query().bullets().palette("C").count(), 0);
+}
+
+#[test]
+fn test_bell_kinds() {
+    let source = "
+        BPL A CEN 0 PLR 1.0 L CIR 0
+        BEL 0 0 0
+        BEL 1 0 8 A
+        BEL 2 0 -8
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let notes = &ogkr.notes;
+
+    let measures = |kind| {
+        notes
+            .bells_of_kind(kind)
+            .map(|bell| bell.position.time.measure)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(measures(BellKind::Normal), [0, 2]);
+    assert_eq!(measures(BellKind::Bullet), [1]);
+    assert_eq!(
+        notes.all_bells().nth(1).unwrap().bullet_palette,
+        ogkr.bullets.palette_ref("A")
+    );
+    assert_eq!(
+        notes
+            .all_bells()
+            .map(|bell| bell.kind())
+            .collect::<Vec<_>>(),
+        [BellKind::Normal, BellKind::Bullet, BellKind::Normal]
+    );
+}
@@ -0,0 +1,89 @@
+use ogkr::lex::{LexError, RawLine, RawLineReader};
+
+#[test]
+fn test_lines() {
+    let source = "\u{feff}[HEADER]\r\n  X_MYTOOL a  b \r\rTAP 0 0 0 4 0\n";
+    let lines = RawLineReader::new(source).collect::<Vec<_>>();
+    assert_eq!(
+        lines,
+        [
+            RawLine {
+                line: 1,
+                offset: 3,
+                text: "[HEADER]"
+            },
+            RawLine {
+                line: 2,
+                offset: 15,
+                text: "X_MYTOOL a  b"
+            },
+            RawLine {
+                line: 4,
+                offset: 31,
+                text: "TAP 0 0 0 4 0"
+            },
+        ]
+    );
+    assert_eq!(lines[1].mnemonic(), "X_MYTOOL");
+    assert_eq!(
+        lines[1].tokens().collect::<Vec<_>>(),
+        ["X_MYTOOL", "a", "b"]
+    );
+}
+
+#[test]
+fn test_custom_command() {
+    // A made-up `SPD <measure> <offset> <speed> [label]` command.
+    let source = "SPD 1 0 1.5 fast\nSPD 2 0 slow\n";
+    let mut reader = RawLineReader::new(source);
+
+    assert_eq!(reader.next_token(), Some("SPD"));
+    let time = (
+        reader.parse_token::<u32>("measure").unwrap(),
+        reader.parse_token::<u32>("offset").unwrap(),
+    );
+    let speed = reader.parse_token::<f32>("speed").unwrap();
+    assert_eq!((time, speed, reader.rest_of_line()), ((1, 0), 1.5, "fast"));
+    reader.end_command().unwrap();
+
+    reader.next_token();
+    let checkpoint = reader.checkpoint();
+    let speed = reader.speculate(|reader| {
+        reader.parse_token::<u32>("measure")?;
+        reader.parse_token::<u32>("offset")?;
+        reader.parse_token::<f32>("speed")
+    });
+    assert!(speed.is_err());
+    assert_eq!(reader.peek_token(), Some("2"));
+
+    reader.parse_token::<u32>("measure").unwrap();
+    reader.parse_token::<u32>("offset").unwrap();
+    match reader.parse_token::<f32>("speed").unwrap_err() {
+        LexError::ExpectedToken {
+            line, col, token, ..
+        } => assert_eq!((line, col, token.as_str()), (2, 9, "slow")),
+        error => panic!("unexpected error {error:?}"),
+    }
+    assert_eq!((reader.line(), reader.col(), reader.offset()), (2, 9, 25));
+
+    reader.rewind(checkpoint);
+    assert_eq!(reader.next_line().unwrap().text, "2 0 slow");
+    assert!(reader.is_end());
+}
+
+#[test]
+fn test_strict_reader() {
+    let mut reader = RawLineReader::new("SPD 1 0 1.5 \n").strict_whitespace();
+    reader.next_token();
+    for _ in 0..3 {
+        reader.next_token();
+    }
+    assert!(matches!(
+        reader.end_command().unwrap_err(),
+        LexError::IrregularWhitespace { offset: 11, .. }
+    ));
+    assert_eq!(
+        reader.expected("nothing").to_string(),
+        "expected token nothing not found at line 1, col 9, found \"1.5\""
+    );
+}
@@ -0,0 +1,99 @@
+use ogkr::{
+    lex::token::TokenKind,
+    parse::{analysis::TimingPoint, ParseOptions, RepairOptions, Warning},
+    parse_with_options, ErrorCode, Ogkr,
+};
+
+fn repaired(source: &str, repair: RepairOptions) -> Ogkr {
+    let mut options = ParseOptions::default();
+    options.repair = repair;
+    parse_with_options(source, &options).unwrap()
+}
+
+#[test]
+fn test_sorted_section_points() {
+    let source = "
+        WLS 0 0 0 -24
+        WLN 0 2 0 -24
+        WLN 0 1 0 -24
+        WLE 0 3 0 -24
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let wall = &ogkr.track.lanes_data[&ogkr.track.walls_left[&TimingPoint::new(0, 0)][0]];
+    assert!(wall.points.is_sorted_by_key(|point| point.time));
+    let warnings = ogkr.warnings.into_iter().collect::<Vec<_>>();
+    assert_eq!(
+        warnings,
+        [Warning::SectionPointsSorted {
+            section: TokenKind::WallLeftStart,
+            id: 0
+        }]
+    );
+    assert_eq!(warnings[0].code(), ErrorCode::SectionPointsSorted);
+}
+
+#[test]
+fn test_clamped_offsets() {
+    let source = "
+        TRESOLUTION 1920
+        LCS 0 0 0 0
+        LCE 0 3 0 0
+        TAP 0 1 5000 0 0
+    ";
+    let ogkr = repaired(source, RepairOptions::default().clamp_offsets());
+    assert_eq!(
+        ogkr.notes.all_taps().next().unwrap().position.time,
+        TimingPoint::new(1, 1919)
+    );
+    assert_eq!(
+        ogkr.warnings.into_iter().collect::<Vec<_>>(),
+        [Warning::OffsetClamped {
+            time: TimingPoint::new(1, 5000)
+        }]
+    );
+}
+
+#[test]
+fn test_closed_walls() {
+    let source = "
+        WLS 0 0 0 -24
+        WLN 0 2 0 -24
+        LCS 1 0 0 0
+        LCE 1 4 0 0
+        WRS 2 5 0 24
+    ";
+    assert!(source.parse::<Ogkr>().is_err());
+
+    let ogkr = repaired(source, RepairOptions::default().close_walls());
+    assert_eq!(
+        ogkr.warnings.into_iter().collect::<Vec<_>>(),
+        [
+            Warning::WallEndGenerated {
+                section: TokenKind::WallLeftStart,
+                id: 0,
+                time: TimingPoint::new(5, 0),
+            },
+            Warning::WallEndGenerated {
+                section: TokenKind::WallRightStart,
+                id: 2,
+                time: TimingPoint::new(6, 0),
+            },
+        ]
+    );
+
+    let wall = &ogkr.track.lanes_data[&ogkr.track.walls_left[&TimingPoint::new(0, 0)][0]];
+    let last = wall.points.last().unwrap();
+    assert_eq!((last.time, last.x.position), (TimingPoint::new(5, 0), -24));
+}
+
+#[test]
+fn test_closed_walls_at_last_measure() {
+    let source = "
+        WLS 0 4294967295 0 -24
+        LCS 1 0 0 0
+        LCE 1 4 0 0
+    ";
+    let mut options = ParseOptions::default();
+    options.repair = RepairOptions::default().close_walls();
+    assert!(parse_with_options(source, &options).is_err());
+}
@@ -0,0 +1,223 @@
+use ogkr::{
+    lex::tokenize,
+    parse::{
+        analysis::{BeamId, LaneId},
+        raw::parse_tokens,
+        ParseError,
+    },
+    Ogkr,
+};
+
+#[test]
+fn test_interleaved_sections() {
+    let source = "
+        LCS 0 0 0 0
+        LCS 1 0 0 8
+        LCN 0 1 0 4
+        BMS 2 0 0 0 4
+        LCE 1 2 0 8
+        BME 2 1 0 0 4
+        LCE 0 2 0 0
+    ";
+    let raw = parse_tokens(tokenize(source).unwrap()).unwrap();
+
+    let lanes = &raw.track.lanes_center;
+    assert_eq!(lanes.len(), 2);
+    assert_eq!(lanes[0].group_id, 1);
+    assert_eq!(lanes[1].group_id, 0);
+    assert_eq!(
+        lanes[1]
+            .points
+            .iter()
+            .map(|p| (p.time.measure, p.time.offset))
+            .collect::<Vec<_>>(),
+        vec![(0, 0), (1, 0), (2, 0)]
+    );
+    assert_eq!(raw.track.beams.len(), 1);
+}
+
+#[test]
+fn test_out_of_order_points() {
+    let source = "
+        WLE 0 4 0 -24
+        WLN 0 2 0 -20
+        WLS 0 0 0 -24
+    ";
+    let raw = parse_tokens(tokenize(source).unwrap()).unwrap();
+
+    let walls = &raw.track.walls_left;
+    assert_eq!(walls.len(), 1);
+    assert_eq!(
+        walls[0]
+            .points
+            .iter()
+            .map(|p| (p.time.measure, p.time.offset))
+            .collect::<Vec<_>>(),
+        vec![(0, 0), (2, 0), (4, 0)]
+    );
+}
+
+#[test]
+fn test_reused_group_id() {
+    let source = "
+        LLS 0 0 0 0
+        LLE 0 1 0 0
+        LLS 0 2 0 0
+        LLE 0 3 0 0
+    ";
+    let raw = parse_tokens(tokenize(source).unwrap()).unwrap();
+    assert_eq!(raw.track.lanes_left.len(), 2);
+}
+
+#[test]
+fn test_unfinished_section() {
+    let source = "
+        LRS 0 0 0 0
+        LRN 0 1 0 0
+    ";
+    assert!(matches!(
+        parse_tokens(tokenize(source).unwrap()),
+        Err(ParseError::SemanticErrorExpectedCommand(_))
+    ));
+
+    let source = "
+        LRS 0 0 0 0
+        LRS 0 1 0 0
+    ";
+    assert!(matches!(
+        parse_tokens(tokenize(source).unwrap()),
+        Err(ParseError::SemanticError(_))
+    ));
+}
+
+#[test]
+fn test_simultaneous_track_objects() {
+    let source = "
+        WLS 0 0 0 -24
+        WLE 0 2 0 -24
+        WLS 1 0 0 -20
+        WLE 1 2 0 -20
+        WRS 2 0 0 24
+        WRE 2 2 0 24
+        WRS 3 0 0 20
+        WRE 3 2 0 20
+        CLS 4 1 0 -4 3 1
+        CLE 4 2 0 -4 3 1
+        CLS 5 1 0 4 3 1
+        CLE 5 2 0 4 3 1
+        BMS 6 1 0 0 4
+        BME 6 2 0 0 4
+        BMS 7 1 0 8 4
+        BME 7 2 0 8 4
+        OBS 8 1 0 -8 2 4
+        OBE 8 2 0 -8 2 4
+        OBS 9 1 0 8 2 4
+        OBE 9 2 0 8 2 4
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let track = &ogkr.track;
+
+    assert_eq!(
+        track.walls_left.values().map(Vec::len).collect::<Vec<_>>(),
+        vec![2]
+    );
+    assert_eq!(
+        track.walls_right.values().map(Vec::len).collect::<Vec<_>>(),
+        vec![2]
+    );
+    assert_eq!(
+        track
+            .colorful_lanes
+            .values()
+            .map(Vec::len)
+            .collect::<Vec<_>>(),
+        vec![2]
+    );
+    assert_eq!(
+        track.beams.values().map(Vec::len).collect::<Vec<_>>(),
+        vec![2]
+    );
+    assert_eq!(
+        track
+            .oblique_beams
+            .values()
+            .map(Vec::len)
+            .collect::<Vec<_>>(),
+        vec![2]
+    );
+}
+
+#[test]
+fn test_simultaneous_track_objects_keep_chart_order() {
+    let source = "
+        WLS 7 0 0 -24
+        WLE 7 2 0 -24
+        WLS 2 0 0 -20
+        WLE 2 2 0 -20
+        WLS 5 0 0 -16
+        WLE 5 2 0 -16
+        BMS 9 1 0 0 4
+        BME 9 2 0 0 4
+        BMS 1 1 0 8 4
+        BME 1 2 0 8 4
+        BMS 4 1 0 -8 4
+        BME 4 2 0 -8 4
+    ";
+    for _ in 0..8 {
+        let ogkr: Ogkr = source.parse().unwrap();
+        let track = &ogkr.track;
+        assert_eq!(
+            track.walls_left.values().collect::<Vec<_>>(),
+            [&vec![LaneId(7), LaneId(2), LaneId(5)]]
+        );
+        assert_eq!(
+            track.beams.values().collect::<Vec<_>>(),
+            [&vec![BeamId(9), BeamId(1), BeamId(4)]]
+        );
+    }
+}
+
+#[test]
+fn test_simplify_lanes() {
+    let source = "
+        WLS 0 0 0 -24
+        WLN 0 1 0 -24
+        WLN 0 2 0 -24
+        WLE 0 3 0 -24
+        LCS 1 0 0 0
+        LCN 1 1 0 4
+        LCN 1 1 960 7
+        LCN 1 2 0 8
+        LCN 1 3 0 0
+        LCE 1 4 0 0
+    ";
+    let mut ogkr: Ogkr = source.parse().unwrap();
+    let positions = |ogkr: &Ogkr, id| {
+        ogkr.track.lanes_data[&LaneId(id)]
+            .points
+            .iter()
+            .map(|point| (point.time.measure, point.time.beat_offset, point.x.position))
+            .collect::<Vec<_>>()
+    };
+
+    let mut strict = ogkr.clone();
+    assert_eq!(strict.simplify_lanes(0.0), 2);
+    assert_eq!(positions(&strict, 0), [(0, 0, -24), (3, 0, -24)]);
+    assert_eq!(
+        positions(&strict, 1),
+        [
+            (0, 0, 0),
+            (1, 0, 4),
+            (1, 960, 7),
+            (2, 0, 8),
+            (3, 0, 0),
+            (4, 0, 0)
+        ]
+    );
+
+    assert_eq!(ogkr.simplify_lanes(1.0), 4);
+    assert_eq!(
+        positions(&ogkr, 1),
+        [(0, 0, 0), (2, 0, 8), (3, 0, 0), (4, 0, 0)]
+    );
+}
@@ -0,0 +1,31 @@
+use std::thread;
+
+use ogkr::{
+    lex::tokenize,
+    parse::{analysis::parse_raw_ogkr, raw::parse_tokens, shared::SharedOgkr},
+};
+
+fn shared_chart(source: &str) -> SharedOgkr {
+    let tokens = tokenize(source).expect("must be tokenized");
+    let raw_ogkr = parse_tokens(tokens).expect("must be parsed");
+    parse_raw_ogkr(raw_ogkr)
+        .expect("must be parsed")
+        .into_shared()
+}
+
+#[test]
+fn test_share_across_threads() {
+    let chart = shared_chart(include_str!("../charts/1.ogkr"));
+    let num_taps = chart.notes().all_taps().count();
+
+    let notes = chart.notes_view();
+    let track = chart.track_view();
+    let handles = [
+        thread::spawn(move || notes.all_taps().count()),
+        thread::spawn(move || track.lanes_data.len()),
+    ];
+    let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+    assert_eq!(results[0], num_taps);
+    assert_eq!(results[1], chart.track.lanes_data.len());
+}
@@ -0,0 +1,39 @@
+use ogkr::{parse::analysis::TimingPoint, simulate, Ogkr};
+
+#[test]
+fn test_combo_timeline() {
+    let source = "
+        BPM_DEF 120.000 120.000 120.000 120.000
+        BPM 0 0 120.000
+        MET 0 0 4 4
+        LCS 0 0 0 0
+        LCE 0 4 0 0
+        TAP 0 0 0 0 0
+        HLD 0 1 0 0 0 2 0 0 0
+        BEL 1 0 8
+        CFK 1 960 4 R
+        TAP 0 3 0 0 0
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let timeline = simulate::combo_timeline(&ogkr);
+
+    assert_eq!(timeline.max_combo(), ogkr.count_totals().notes);
+    assert_eq!(
+        timeline
+            .objects
+            .iter()
+            .map(|object| (object.ms, object.position.time, object.combo))
+            .collect::<Vec<_>>(),
+        [
+            (0.0, TimingPoint::new(0, 0), 1),
+            (2000.0, TimingPoint::new(1, 0), 2),
+            (3000.0, TimingPoint::new(1, 960), 3),
+            (6000.0, TimingPoint::new(3, 0), 4),
+        ]
+    );
+
+    assert_eq!(timeline.combo_at(-1.0), 0);
+    assert_eq!(timeline.combo_at(2000.0), 2);
+    assert_eq!(timeline.remaining_at(2500.0), 2);
+    assert_eq!(timeline.remaining_at(6000.0), 0);
+}
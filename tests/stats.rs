@@ -0,0 +1,596 @@
+use ogkr::{
+    lex::command::EnemyWave,
+    parse::{
+        analysis::{TimingPoint, TrackPosition, XPosition},
+        DamageValues,
+    },
+    stats::{
+        self, DensityCell, Difficulty, Grid, HandConflict, HandLimits, HoldDurations, LeverLimits,
+        MeasureDifficulty, OffGridNote, SharedSection, SpacingOptions, StackedPair,
+    },
+    Ogkr,
+};
+
+#[test]
+fn test_per_wave() {
+    let source = "
+        BULLET_DAMAGE 1.000
+        HARDBULLET_DAMAGE 2.000
+        DANGERBULLET_DAMAGE 4.000
+        BEAM_DAMAGE 3.000
+        EST 0 0 WAVE1
+        EST 2 0 WAVE2
+        EST 4 0 BOSS
+        BPL A CEN 0 PLR 1.0 L CIR 0
+        LCS 0 0 0 0
+        LCE 0 6 0 0
+        TAP 0 0 0 0 0
+        TAP 0 1 0 0 0
+        HLD 0 3 0 0 0 4 0 0 0
+        FLK 2 0 0 L
+        BEL 5 0 0
+        BLT A 1 0 0 NML
+        BLT A 2 0 0 STR
+        BLT A 4 0 0 DNG
+        BLT A 5 0 0 DNG
+        BMS 0 4 0 0 4
+        BME 0 5 0 0 4
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let stats = stats::per_wave(&ogkr);
+
+    assert_eq!(stats.wave_1.notes(), 2);
+    assert_eq!(stats.wave_1.bullets, 1);
+    assert_eq!(stats.wave_1.max_damage, 1.0);
+
+    assert_eq!(stats.wave_2.notes(), 2);
+    assert_eq!(stats.wave_2.max_damage, 2.0);
+
+    assert_eq!(stats.boss.notes(), 0);
+    assert_eq!(stats.boss.bells, 1);
+    assert_eq!(stats.boss.bullets, 2);
+    assert_eq!(stats.boss.beams, 1);
+    assert_eq!(stats.boss.max_damage, 11.0);
+}
+
+#[test]
+fn test_missing_waves() {
+    let source = "
+        EST 2 0 WAVE2
+        LCS 0 0 0 0
+        LCE 0 4 0 0
+        TAP 0 0 0 0 0
+        TAP 0 3 0 0 0
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+
+    let waves = &ogkr.enemy_wave_assignment;
+    assert_eq!(waves.wave_1, None);
+    assert!(waves.wave_2.is_some());
+    assert_eq!(waves.boss, None);
+
+    let stats = stats::per_wave(&ogkr);
+    assert_eq!(stats.wave_1.taps, 1);
+    assert_eq!(stats.wave_2.taps, 1);
+    assert_eq!(stats.boss.taps, 0);
+}
+
+#[test]
+fn test_wave_at() {
+    let source = "
+        EST 1 0 WAVE1
+        EST 4 0 WAVE2
+        EST 2 0 BOSS
+        LCS 0 0 0 0
+        LCE 0 6 0 0
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let waves = &ogkr.enemy_wave_assignment;
+
+    let wave_at = |measure| waves.wave_at(TimingPoint::new(measure, 0));
+    assert_eq!(wave_at(0), EnemyWave::Wave1);
+    assert_eq!(wave_at(1), EnemyWave::Wave1);
+    assert_eq!(wave_at(3), EnemyWave::Boss);
+    assert_eq!(wave_at(5), EnemyWave::Wave2);
+}
+
+#[test]
+fn test_density_heatmap() {
+    let source = "
+        BPL A CEN 0 PLR 1.0 L CIR 0
+        LCS 0 0 0 0
+        LCE 0 4 0 0
+        TAP 0 0 0 -8 0
+        TAP 0 0 960 -6 0
+        FLK 1 0 8 L
+        BEL 1 960 8
+        BLT A 2 0 0 NML
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let heatmap = stats::density_heatmap(&ogkr, 1920, 4.0);
+
+    assert_eq!(heatmap.x_min, -8.0);
+    assert_eq!(heatmap.rows(), 3);
+    assert_eq!(heatmap.columns(), 5);
+    assert_eq!(heatmap.get(0, 0).notes, 2);
+    assert_eq!(heatmap.get(1, 4).notes, 1);
+    assert_eq!(heatmap.get(1, 4).bells, 1);
+    assert_eq!(heatmap.get(2, 2).bullets, 1);
+    assert_eq!(heatmap.get(0, 3), DensityCell::default());
+    assert_eq!(
+        heatmap.cells.values().map(DensityCell::total).sum::<u32>(),
+        5
+    );
+}
+
+#[test]
+fn test_density_heatmap_far_apart() {
+    let source = "
+        LCS 0 0 0 0
+        LCE 0 4 0 0
+        BEL 0 0 -2000000000
+        BEL 4000000 0 2000000000
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let heatmap = stats::density_heatmap(&ogkr, 1, 1.0);
+
+    assert_eq!(heatmap.cells.len(), 2);
+    assert_eq!(heatmap.get(0, 0).bells, 1);
+    assert_eq!(heatmap.rows(), 4000000 * 1920 + 1);
+}
+
+#[test]
+fn test_hand_conflicts() {
+    let source = "
+        WLS 0 0 0 -24
+        WLE 0 8 0 -24
+        LCS 1 0 0 0
+        LCE 1 8 0 0
+        TAP 1 0 0 0 0
+        TAP 1 0 0 4 0
+        TAP 0 1 0 -24 0
+        TAP 1 1 0 0 0
+        FLK 1 0 8 L
+        HLD 1 2 0 0 0 3 0 0 0
+        TAP 1 2 960 0 0
+        TAP 1 2 960 4 0
+        TAP 1 3 0 0 0
+        TAP 1 3 0 4 0
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+
+    let conflicts = stats::hand_conflicts(&ogkr, &HandLimits::default());
+    assert_eq!(
+        conflicts,
+        [
+            HandConflict {
+                time: TimingPoint::new(1, 0),
+                notes: 3,
+            },
+            HandConflict {
+                time: TimingPoint::new(2, 960),
+                notes: 3,
+            },
+        ]
+    );
+
+    let limits = HandLimits::default()
+        .count_side_notes(false)
+        .count_flicks(false);
+    assert_eq!(
+        stats::hand_conflicts(&ogkr, &limits),
+        [HandConflict {
+            time: TimingPoint::new(2, 960),
+            notes: 3,
+        }]
+    );
+    assert!(stats::hand_conflicts(&ogkr, &HandLimits::default().hands(3)).is_empty());
+}
+
+#[test]
+fn test_damage_values() {
+    let ogkr: Ogkr = include_str!("../charts/1.ogkr").parse().unwrap();
+    assert_eq!(ogkr.header.damage_values, DamageValues::arcade_defaults());
+
+    let source = "
+        EST 0 0 WAVE1
+        BPL A CEN 0 PLR 1.0 L CIR 0
+        BLT A 1 0 0 NML
+        BLT A 1 0 0 DNG
+        BMS 0 1 0 0 4
+        BME 0 2 0 0 4
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    assert_eq!(ogkr.header.damage_values, DamageValues::default());
+    assert_eq!(stats::per_wave(&ogkr).wave_1.max_damage, 0.0);
+
+    let stats = stats::per_wave_with_damage(&ogkr, &DamageValues::arcade_defaults());
+    assert_eq!(stats.wave_1.bullets, 2);
+    assert_eq!(stats.wave_1.max_damage, 7.0);
+}
+
+#[test]
+fn test_off_grid_notes() {
+    let source = "
+        LCS 0 0 0 0
+        LCE 0 4 0 0
+        TAP 0 0 480 0 0
+        TAP 0 0 640 0 0
+        TAP 0 1 7 0 0
+        TAP 0 1 7 4 0
+        HLD 0 2 0 0 0 2 1010 0 0
+        BEL 3 50 0
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+
+    let off_grid = stats::off_grid_notes(&ogkr, &stats::COMMON_SUBDIVISIONS);
+    assert_eq!(
+        off_grid,
+        [
+            OffGridNote {
+                time: TimingPoint::new(1, 7),
+                snapped: TimingPoint::new(1, 0),
+                notes: 2,
+            },
+            OffGridNote {
+                time: TimingPoint::new(2, 1010),
+                snapped: TimingPoint::new(2, 1020),
+                notes: 1,
+            },
+            OffGridNote {
+                time: TimingPoint::new(3, 50),
+                snapped: TimingPoint::new(3, 60),
+                notes: 1,
+            },
+        ]
+    );
+
+    let off_grid = stats::off_grid_notes(&ogkr, &[16]);
+    assert_eq!(off_grid[0].time, TimingPoint::new(0, 640));
+    assert_eq!(off_grid.len(), 4);
+}
+
+#[test]
+fn test_measure_grids() {
+    let source = "
+        LCS 0 0 0 0
+        LCE 0 5 0 0
+        TAP 0 0 0 0 0
+        TAP 0 0 480 0 0
+        TAP 0 0 600 0 0
+        TAP 0 1 0 0 0
+        TAP 0 1 640 0 0
+        TAP 0 1 1280 0 0
+        TAP 0 2 120 0 0
+        TAP 0 2 640 0 0
+        TAP 0 4 7 0 0
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+
+    let grids = stats::measure_grids(&ogkr);
+    assert_eq!(
+        grids.clone().into_iter().collect::<Vec<_>>(),
+        [
+            (0, Grid::Straight(16)),
+            (1, Grid::Triplet(3)),
+            (
+                2,
+                Grid::Mixed {
+                    straight: 16,
+                    triplet: 3
+                }
+            ),
+            (4, Grid::Irregular),
+        ]
+    );
+    assert_eq!(grids[&2].subdivision(), Some(48));
+    assert_eq!(grids[&4].subdivision(), None);
+}
+
+#[test]
+fn test_note_spacing() {
+    let source = "
+        BPM_DEF 120.000 120.000 120.000 120.000
+        TRESOLUTION 1920
+        LCS 1 0 0 0
+        LCE 1 4 0 0
+        TAP 1 0 0 0 0
+        TAP 1 0 15 1 0
+        TAP 1 0 960 8 0
+        FLK 1 0 0 L
+        TAP 1 1 0 16 0
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+
+    let report = stats::note_spacing(&ogkr, &SpacingOptions::default());
+    assert_eq!(report.min_gap_ms, Some(15.625));
+    assert_eq!(
+        report.stacked,
+        [StackedPair {
+            first: TrackPosition::new(TimingPoint::new(0, 0), XPosition::new(0, 0)),
+            second: TrackPosition::new(TimingPoint::new(0, 15), XPosition::new(1, 0)),
+            gap_ms: 15.625,
+        }]
+    );
+
+    let report = stats::note_spacing(&ogkr, &SpacingOptions::default().region_width(1.0));
+    assert_eq!(report.min_gap_ms, Some(2000.0));
+    assert!(report.stacked.is_empty());
+
+    let report = stats::note_spacing(&ogkr, &SpacingOptions::default().stack_ms(10.0));
+    assert!(report.stacked.is_empty());
+}
+
+#[test]
+fn test_hold_durations() {
+    let source = "
+        BPM_DEF 120.000 120.000 120.000 120.000
+        TRESOLUTION 1920
+        BPM 2 0 240.000000
+        LCS 0 0 0 0
+        LCE 0 4 0 0
+        HLD 0 0 0 0 0 0 480 0 0
+        HLD 0 1 0 0 0 2 0 0 0
+        HLD 0 2 0 0 0 3 0 0 0
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+
+    let durations = stats::hold_durations(&ogkr, 960).unwrap();
+    assert_eq!(
+        durations,
+        HoldDurations {
+            count: 3,
+            min_ticks: 480,
+            max_ticks: 1920,
+            total_ticks: 4320,
+            min_ms: 500.0,
+            max_ms: 2000.0,
+            total_ms: 3500.0,
+            ticks_per_bucket: 960,
+            histogram: [(0, 1), (2, 2)].into(),
+        }
+    );
+    assert_eq!(durations.mean_ticks(), 1440.0);
+
+    let ogkr: Ogkr = "LCS 0 0 0 0\nLCE 0 4 0 0".parse().unwrap();
+    assert_eq!(stats::hold_durations(&ogkr, 960), None);
+}
+
+#[test]
+fn test_compare_difficulties() {
+    let basic: Ogkr = "
+        BPM_DEF 120.000 120.000 120.000 120.000
+        BPM 0 0 120.000
+        MET 0 0 4 4
+        LCS 0 0 0 0
+        LCE 0 4 0 0
+        TAP 0 0 0 0 0
+        TAP 0 1 0 0 0
+        TAP 0 2 0 0 0
+    "
+    .parse()
+    .unwrap();
+    let master: Ogkr = "
+        BPM_DEF 120.000 120.000 120.000 120.000
+        BPM 0 0 120.000
+        MET 0 0 4 4
+        LCS 0 0 0 0
+        LCE 0 4 0 0
+        TAP 0 0 0 0 0
+        TAP 0 1 0 0 0
+        TAP 0 2 0 0 0
+        TAP 0 2 960 0 0
+        CTP 0 3 0 0 0
+        CTP 0 3 960 0 0
+        BEL 4 0 0
+    "
+    .parse()
+    .unwrap();
+
+    let comparison =
+        stats::compare_difficulties(&[(Difficulty::Master, &master), (Difficulty::Basic, &basic)]);
+    let difficulties = comparison
+        .charts
+        .iter()
+        .map(|stats| stats.difficulty)
+        .collect::<Vec<_>>();
+    assert_eq!(difficulties, [Difficulty::Basic, Difficulty::Master]);
+
+    let basic = comparison.get(Difficulty::Basic).unwrap();
+    assert_eq!(basic.notes(), 3);
+    assert_eq!(
+        basic
+            .notes_per_measure
+            .clone()
+            .into_iter()
+            .collect::<Vec<_>>(),
+        [(0, 1), (1, 1), (2, 1)]
+    );
+    // Three notes over two 4/4 measures at 120 BPM.
+    assert_eq!(basic.notes_per_second, 0.75);
+
+    let master = comparison.get(Difficulty::Master).unwrap();
+    assert_eq!(master.notes(), 6);
+    assert_eq!(master.bells, 1);
+    assert_eq!(master.peak_measure_notes(), 2);
+    assert!(comparison.get(Difficulty::Lunatic).is_none());
+
+    assert_eq!(
+        comparison.shared_sections,
+        [SharedSection {
+            lower: Difficulty::Basic,
+            higher: Difficulty::Master,
+            measures: 0..2,
+        }]
+    );
+}
+
+#[test]
+fn test_compare_difficulties_far_apart() {
+    let source = "
+        LCS 0 0 0 0
+        LCE 0 4000000001 0 0
+        TAP 0 0 0 0 0
+        TAP 0 4000000000 0 0 0
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let comparison =
+        stats::compare_difficulties(&[(Difficulty::Basic, &ogkr), (Difficulty::Expert, &ogkr)]);
+
+    assert_eq!(comparison.charts[0].notes_per_measure.len(), 2);
+    assert_eq!(
+        comparison.shared_sections,
+        [
+            SharedSection {
+                lower: Difficulty::Basic,
+                higher: Difficulty::Expert,
+                measures: 0..1,
+            },
+            SharedSection {
+                lower: Difficulty::Basic,
+                higher: Difficulty::Expert,
+                measures: 4000000000..4000000001,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_preview_window() {
+    let source = "
+        BPM_DEF 120.000 120.000 120.000 120.000
+        BPM 0 0 120.000
+        MET 0 0 4 4
+        LCS 0 0 0 0
+        LCE 0 8 0 0
+        TAP 0 0 0 0 0
+        TAP 0 3 0 0 0
+        TAP 0 3 480 0 0
+        HLD 0 3 960 0 0 3 1440 0 0
+        FLK 3 1440 0 L
+        TAP 0 4 0 0 0
+        TAP 0 4 960 0 0
+        BEL 6 0 0
+        BEL 6 960 0
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+
+    // Measures last 2 seconds.
+    let window = ogkr.suggest_preview_window(4.0).unwrap();
+    assert_eq!(window.start, TimingPoint::new(3, 0));
+    assert_eq!((window.start_ms, window.end_ms), (6000.0, 10000.0));
+    assert_eq!(window.notes, 6);
+
+    assert_eq!(
+        ogkr.suggest_preview_window(60.0).unwrap().start,
+        TimingPoint::new(0, 0)
+    );
+    assert!("BEL 0 0 0"
+        .parse::<Ogkr>()
+        .unwrap()
+        .suggest_preview_window(4.0)
+        .is_none());
+}
+
+#[test]
+fn test_wall_switches() {
+    let source = "
+        BPM_DEF 120.000 120.000 120.000 120.000
+        BPM 0 0 120.000
+        MET 0 0 4 4
+        WLS 0 0 0 -24
+        WLE 0 4 0 -24
+        WRS 1 0 0 24
+        WRE 1 4 0 24
+        LCS 2 0 0 0
+        LCE 2 4 0 0
+        TAP 0 1 0 -24 0
+        TAP 2 1 120 0 0
+        TAP 1 1 240 24 0
+        TAP 0 2 0 -24 0
+        HLD 1 3 0 24 0 3 960 24 0
+        TAP 0 3 1200 -24 0
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+
+    // Measures last 2 seconds, crossing the 48 units between the walls takes 0.5 s at the
+    // default limit.
+    let switches = stats::wall_switches(&ogkr, &LeverLimits::default())
+        .into_iter()
+        .map(|switch| {
+            (
+                switch.from.time,
+                switch.to.time,
+                switch.gap_ms,
+                switch.required_speed,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        switches,
+        [
+            (
+                TimingPoint::new(1, 0),
+                TimingPoint::new(1, 240),
+                250.0,
+                192.0
+            ),
+            (
+                TimingPoint::new(3, 960),
+                TimingPoint::new(3, 1200),
+                250.0,
+                192.0
+            ),
+        ]
+    );
+
+    assert!(stats::wall_switches(&ogkr, &LeverLimits::default().max_speed(200.0)).is_empty());
+}
+
+#[test]
+fn test_measure_difficulty() {
+    let source = "
+        BPM_DEF 120.000 120.000 120.000 120.000
+        BPM 0 0 120.000
+        MET 0 0 4 4
+        BPL A CEN 0 PLR 1.0 L CIR 0
+        LCS 0 0 0 0
+        LCE 0 2 0 0
+        TAP 0 0 0 0 0
+        TAP 0 0 960 8 0
+        HLD 0 1 0 -8 0 1 960 8 0
+        BLT A 1 480 0 NML
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+
+    // Measures last 2 seconds.
+    assert_eq!(
+        stats::measure_difficulty(&ogkr),
+        [
+            MeasureDifficulty {
+                measure: 0,
+                density: 1.0,
+                lever_movement: 4.0,
+                bullet_pressure: 0.0,
+            },
+            MeasureDifficulty {
+                measure: 1,
+                density: 0.5,
+                lever_movement: 16.0,
+                bullet_pressure: 0.5,
+            },
+        ]
+    );
+
+    let ogkr: Ogkr = "LCS 0 0 0 0\nLCE 0 4294967295 0 0\nTAP 0 4294967295 0 0 0"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        stats::measure_difficulty(&ogkr),
+        [MeasureDifficulty {
+            measure: u32::MAX,
+            density: 0.5,
+            ..Default::default()
+        }]
+    );
+}
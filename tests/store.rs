@@ -0,0 +1,67 @@
+use ogkr::parse::{
+    analysis::TimingPoint,
+    store::{TimeMap, TimeStore},
+};
+
+#[test]
+fn test_time_store() {
+    let mut store = TimeMap::default();
+    store.push(TimingPoint::new(1, 0), "b");
+    store.push(TimingPoint::new(0, 0), "a");
+    store.push(TimingPoint::new(1, 0), "c");
+    store.push(TimingPoint::new(2, 960), "d");
+
+    assert_eq!(store.item_count(), 4);
+    assert_eq!(
+        store.items().copied().collect::<Vec<_>>(),
+        ["a", "b", "c", "d"]
+    );
+    assert_eq!(store.last_time(), Some(TimingPoint::new(2, 960)));
+    assert_eq!(
+        store
+            .groups_in(TimingPoint::new(1, 0)..TimingPoint::new(2, 960))
+            .collect::<Vec<_>>(),
+        [(TimingPoint::new(1, 0), &["b", "c"][..])]
+    );
+    assert_eq!(
+        store
+            .groups_in(TimingPoint::new(2, 0)..TimingPoint::new(1, 0))
+            .count(),
+        0
+    );
+    assert_eq!(
+        store
+            .groups()
+            .rev()
+            .map(|(time, _)| time)
+            .collect::<Vec<_>>(),
+        [
+            TimingPoint::new(2, 960),
+            TimingPoint::new(1, 0),
+            TimingPoint::new(0, 0)
+        ]
+    );
+
+    let mut other = TimeMap::default();
+    other.push(TimingPoint::new(1, 0), "e");
+    other.push(TimingPoint::new(3, 0), "f");
+    store.combine(other);
+    assert_eq!(
+        store.into_items().collect::<Vec<_>>(),
+        ["a", "b", "c", "e", "d", "f"]
+    );
+    assert!(TimeMap::<&str>::default().is_empty());
+}
+
+#[test]
+fn test_time_store_from_items() {
+    let store = TimeMap::from_items(
+        [(2, "c"), (1, "a"), (2, "d"), (0, "x"), (1, "b")]
+            .map(|(measure, item)| (TimingPoint::new(measure, 0), item)),
+    );
+    assert_eq!(
+        store.items().copied().collect::<Vec<_>>(),
+        ["x", "a", "b", "c", "d"]
+    );
+    assert_eq!(store.groups().count(), 3);
+}
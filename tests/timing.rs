@@ -0,0 +1,204 @@
+use ogkr::{
+    lex::command::{CommandTime, MeterDefinition, TickResolution, XResolution},
+    parse::analysis::{Resolution, TimingPoint, XPosition},
+    Ogkr,
+};
+
+#[test]
+fn test_timing_conversion() {
+    let source = "
+        BPM_DEF 120.000 120.000 120.000 120.000
+        TRESOLUTION 1920
+        BPM 2 0 240.000000
+        MET 3 0 3 4
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let timing = ogkr.timing();
+
+    // 4/4 at 120 BPM, a measure lasts two seconds.
+    assert_eq!(timing.to_ms(TimingPoint::new(0, 0)), 0.0);
+    assert_eq!(timing.to_ms(TimingPoint::new(1, 960)), 3000.0);
+    // 240 BPM from measure 2.
+    assert_eq!(timing.to_ms(TimingPoint::new(3, 0)), 5000.0);
+    // 3/4 from measure 3.
+    assert_eq!(timing.to_ms(TimingPoint::new(4, 0)), 5750.0);
+
+    for time in [(0, 0), (1, 960), (2, 480), (3, 1), (4, 0), (10, 1919)] {
+        let time = TimingPoint::new(time.0, time.1);
+        assert_eq!(timing.from_ms(timing.to_ms(time)), time);
+    }
+    assert_eq!(timing.from_ms(-100.0), TimingPoint::new(0, 0));
+}
+
+#[test]
+fn test_timing_conversion_oversized_offset() {
+    let source = "
+        BPM_DEF 120.000 120.000 120.000 120.000
+        TRESOLUTION 1920
+        BPM 0 3000 150.000000
+        BPM 1 0 180.000000
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let timing = ogkr.timing();
+
+    // The change at measure 0 offset 3000 comes after the one at measure 1.
+    assert_eq!(timing.to_ms(TimingPoint::new(1, 0)), 2000.0);
+    assert_eq!(timing.to_ms(TimingPoint::new(0, 3000)), 2750.0);
+    assert_eq!(timing.to_ms(TimingPoint::new(2, 0)), 3450.0);
+}
+
+#[test]
+fn test_bullet_spawns() {
+    let source = "
+        BPM_DEF 120.000 120.000 120.000 120.000
+        BPL A CEN 0 PLR 1.0 L CIR 0
+        BPL B CEN 0 PLR 2.0 L CIR 0
+        BLT A 4 0 0 NML
+        BLT B 4 0 8 NML
+        BLT A 0 960 0 NML
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let spawns = ogkr.bullet_spawns();
+
+    let spawn_times = spawns
+        .values()
+        .flatten()
+        .map(|spawn| {
+            (
+                ogkr.bullets
+                    .palette(spawn.bullet.palette)
+                    .unwrap()
+                    .id
+                    .0
+                    .as_str(),
+                spawn.spawn_time.measure,
+                spawn.spawn_time.beat_offset,
+                spawn.spawn_ms,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        spawn_times,
+        vec![
+            ("A", 0, 0, -1000.0),
+            ("A", 3, 0, 6000.0),
+            ("B", 3, 960, 7000.0)
+        ]
+    );
+}
+
+#[test]
+fn test_click_times() {
+    let source = "
+        BPM_DEF 120.000 120.000 120.000 120.000
+        TRESOLUTION 1920
+        CLK 0 960
+        CLK 0 0
+        CLK 0 960
+        CLK 1 0
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    assert_eq!(
+        ogkr.click_sounds
+            .iter()
+            .map(|click| click.time)
+            .collect::<Vec<_>>(),
+        [
+            TimingPoint::new(0, 0),
+            TimingPoint::new(0, 960),
+            TimingPoint::new(1, 0)
+        ]
+    );
+    assert_eq!(ogkr.click_times_ms(&ogkr.timing()), [0.0, 1000.0, 2000.0]);
+}
+
+#[test]
+fn test_resolutions() {
+    let ticks = TickResolution { resolution: 1920 };
+    assert_eq!(
+        ticks.ticks_at(CommandTime {
+            measure: 2,
+            offset: 480
+        }),
+        4320
+    );
+    let meter = |num_beats| MeterDefinition {
+        num_beats,
+        note_value: 4,
+    };
+    assert_eq!(ticks.ticks_per_beat(meter(4)), 480.0);
+    assert_eq!(ticks.ticks_per_beat(meter(3)), 640.0);
+    assert_eq!(ticks.ticks_per_beat(meter(0)), 1920.0);
+
+    let x = XResolution { resolution: 4096 };
+    assert_eq!(x.normalize_x(2048), 0.5);
+    assert_eq!(x.x_of(-8, 1024), -7.75);
+    assert_eq!(XResolution { resolution: 0 }.normalize_x(3), 3.0);
+
+    let resolution = Resolution::default();
+    assert_eq!(resolution.tick_resolution(), ticks);
+    assert_eq!(resolution.x_resolution(), x);
+    assert_eq!(resolution.x_of(XPosition::new(-8, 1024)), -7.75);
+    assert_eq!(resolution.ticks_at(TimingPoint::new(2, 480)), 4320);
+}
+
+#[test]
+fn test_scroll_timeline() {
+    let source = "
+        BPM_DEF 120.000 120.000 120.000 120.000
+        TRESOLUTION 1920
+        BPM 1 0 240.000000
+        SFL 0 960 1920 2.000000
+        SFL 2 0 960 2.000000
+        LCS 0 0 0 0
+        LCE 0 2 0 0
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let timeline = ogkr.scroll_timeline();
+
+    let segments = timeline
+        .segments()
+        .iter()
+        .map(|segment| (segment.start_ms, segment.end_ms, segment.speed_multiplier))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        segments,
+        [
+            (0.0, 1000.0, 1.0),
+            (1000.0, 2500.0, 2.0),
+            (2500.0, 3000.0, 1.0),
+            (3000.0, 3500.0, 2.0),
+            (3500.0, 4000.0, 1.0),
+        ]
+    );
+
+    assert_eq!(timeline.position_at(-10.0), 0.0);
+    assert_eq!(timeline.position_at(500.0), 500.0);
+    assert_eq!(timeline.position_at(2000.0), 3000.0);
+    assert_eq!(timeline.position_at(3500.0), 5500.0);
+    assert_eq!(timeline.position_at(4000.0), 6000.0);
+    assert_eq!(timeline.position_at(4500.0), 6500.0);
+}
+
+#[test]
+fn test_snapping() {
+    let resolution = Resolution::default();
+    let time = TimingPoint::new(1, 130);
+    assert_eq!(time.snap_to(16, resolution), TimingPoint::new(1, 120));
+    assert_eq!(time.snap_to(12, resolution), TimingPoint::new(1, 160));
+    assert_eq!(time.snap_to(4, resolution), TimingPoint::new(1, 0));
+    assert_eq!(
+        TimingPoint::new(1, 1900).snap_to(16, resolution),
+        TimingPoint::new(2, 0)
+    );
+    // 1920 ticks do not divide into 7, grid points round to the nearest tick.
+    assert_eq!(
+        TimingPoint::new(0, 270).snap_to(7, resolution),
+        TimingPoint::new(0, 274)
+    );
+
+    assert!(TimingPoint::new(3, 480).is_on_grid(4, resolution));
+    assert!(TimingPoint::new(3, 160).is_on_grid(12, resolution));
+    assert!(!TimingPoint::new(3, 160).is_on_grid(16, resolution));
+    assert!(TimingPoint::new(3, 274).is_on_grid(7, resolution));
+}
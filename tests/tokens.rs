@@ -0,0 +1,338 @@
+use ogkr::{
+    lex::{
+        command::{BulletDamageType, BulletSize, BulletType, Creator, Extension},
+        line_col,
+        token::{Token, TokenKind},
+        tokenize, tokenize_with_options, LexError, LexOptions,
+    },
+    parse::{raw::parse_source_with_options, ParseOptions, Warning},
+    ErrorCode,
+};
+
+#[test]
+fn test_mnemonic_round_trip() {
+    for &kind in TokenKind::ALL {
+        assert_eq!(TokenKind::from_mnemonic(kind.mnemonic()), Some(kind));
+    }
+    assert_eq!(TokenKind::from_mnemonic("TAP"), Some(TokenKind::Tap));
+    assert_eq!(
+        TokenKind::from_mnemonic("XTP"),
+        Some(TokenKind::CriticalTap)
+    );
+    assert_eq!(
+        TokenKind::from_mnemonic("XHD"),
+        Some(TokenKind::CriticalHold)
+    );
+    assert_eq!(
+        TokenKind::from_mnemonic("X_EDITOR"),
+        Some(TokenKind::Extension)
+    );
+    assert_eq!(TokenKind::from_mnemonic("tap"), None);
+    assert_eq!(TokenKind::from_mnemonic("NOPE"), None);
+}
+
+#[test]
+fn test_token_kind() {
+    let tokens =
+        tokenize("[HEADER]\nBPM_DEF 120 120 120 120\nXTP 0 0 0 0 0\nX_EDITOR foo\n").unwrap();
+    assert_eq!(
+        tokens.iter().map(|token| token.kind()).collect::<Vec<_>>(),
+        [
+            None,
+            Some(TokenKind::BpmDefinition),
+            Some(TokenKind::CriticalTap),
+            Some(TokenKind::Extension),
+        ]
+    );
+}
+
+#[test]
+fn test_token_spans() {
+    let source = "[NOTES]\r\nTAP\t0 1 0 4 0  \r\nBEL 0 0 8 A\nCREATOR  someone  \n";
+    let tokens = tokenize(source).unwrap();
+
+    let spans = tokens
+        .iter_spanned()
+        .map(|(_, span)| &source[span])
+        .collect::<Vec<_>>();
+    assert_eq!(
+        spans,
+        [
+            "[NOTES]",
+            "TAP\t0 1 0 4 0",
+            "BEL 0 0 8 A",
+            "CREATOR  someone"
+        ]
+    );
+    assert_eq!(tokens.span(1), Some(9..22));
+    assert_eq!(tokens.span(4), None);
+}
+
+#[test]
+fn test_error_positions() {
+    let source = "TAP 0 0 0 4 0\nNOPE 1 2\n";
+    match tokenize(source).unwrap_err() {
+        LexError::UnknownCommand { offset, token, .. } => {
+            assert_eq!((offset, token.as_str()), (14, "NOPE"));
+        }
+        error => panic!("unexpected error {error:?}"),
+    }
+
+    let source = "TAP 0 0 zero 4 0\n";
+    match tokenize(source).unwrap_err() {
+        LexError::ExpectedToken { offset, token, .. } => {
+            assert_eq!((offset, token.as_str()), (8, "zero"));
+        }
+        error => panic!("unexpected error {error:?}"),
+    }
+
+    match tokenize("TAP 0 0 0").unwrap_err() {
+        LexError::ExpectedToken { offset, token, .. } => {
+            assert_eq!((offset, token.as_str()), (9, ""));
+        }
+        error => panic!("unexpected error {error:?}"),
+    }
+}
+
+#[test]
+fn test_exotic_whitespace() {
+    let source = "\u{feff}[HEADER]\r\nVERSION\t1\u{3000}7\u{3000}0\u{3000}\r\nCREATOR\u{3000}someone\u{3000}\r\n";
+    let tokens = tokenize(source).unwrap();
+
+    let spans = tokens
+        .iter_spanned()
+        .map(|(_, span)| &source[span])
+        .collect::<Vec<_>>();
+    assert_eq!(
+        spans,
+        [
+            "[HEADER]",
+            "VERSION\t1\u{3000}7\u{3000}0",
+            "CREATOR\u{3000}someone"
+        ]
+    );
+    assert_eq!(
+        tokens.iter().nth(2),
+        Some(&Token::Creator(Creator {
+            name: "someone".to_string()
+        }))
+    );
+
+    match tokenize("\u{feff}NOPE").unwrap_err() {
+        LexError::UnknownCommand { col, offset, .. } => assert_eq!((col, offset), (1, 3)),
+        error => panic!("unexpected error {error:?}"),
+    }
+}
+
+#[test]
+fn test_lex_options() {
+    let source = "tap 0 0 0 4 0\nTOUCH 0 0 0 4 0\ntouch 0 0 0 4 0\nx_editor foo\n";
+    assert!(tokenize(source).is_err());
+
+    let options = LexOptions::default().alias("TOUCH", TokenKind::CriticalTap);
+    assert!(matches!(
+        tokenize_with_options(source, &options).unwrap_err(),
+        LexError::UnknownCommand { .. }
+    ));
+
+    let options = options.case_insensitive();
+    let tokens = tokenize_with_options(source, &options).unwrap();
+    assert_eq!(
+        tokens.iter().map(|token| token.kind()).collect::<Vec<_>>(),
+        [
+            Some(TokenKind::Tap),
+            Some(TokenKind::CriticalTap),
+            Some(TokenKind::CriticalTap),
+            Some(TokenKind::Extension),
+        ]
+    );
+    assert_eq!(
+        tokens.iter().last(),
+        Some(&Token::Extension(Extension {
+            key: "editor".to_string(),
+            value: "foo".to_string()
+        }))
+    );
+
+    let mut parse_options = ParseOptions::default();
+    parse_options.lex = options;
+    assert!(parse_source_with_options("Tap 0 0 0 4 0", &parse_options).is_ok());
+}
+
+#[test]
+fn test_lenient() {
+    let source = "TAP 0 0 0\nTAP 0 1 0 4 0\nNOPE 1\nTAP 0 2 zero 4 0\nTAP 0 3 0 4 0\n";
+    assert!(tokenize(source).is_err());
+
+    let tokens = tokenize_with_options(source, &LexOptions::default().lenient()).unwrap();
+    assert_eq!(tokens.len(), 2);
+    assert_eq!(
+        tokens
+            .iter_spanned()
+            .map(|(_, span)| &source[span])
+            .collect::<Vec<_>>(),
+        ["TAP 0 1 0 4 0", "TAP 0 3 0 4 0"]
+    );
+    assert_eq!(
+        tokens
+            .errors()
+            .iter()
+            .map(|error| match error {
+                LexError::ExpectedToken { token, .. } | LexError::UnknownCommand { token, .. } => {
+                    token.as_str()
+                }
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>(),
+        ["TAP", "NOPE", "zero"]
+    );
+
+    let mut options = ParseOptions::default();
+    options.lex = LexOptions::default().lenient();
+    let raw = parse_source_with_options(source, &options).unwrap();
+    assert_eq!(raw.notes.taps.len(), 2);
+    assert!(raw
+        .warnings
+        .iter()
+        .all(|warning| matches!(warning, Warning::SkippedInvalidLine(_))));
+    assert_eq!(raw.warnings.len(), 3);
+}
+
+#[test]
+fn test_flexible_whitespace() {
+    let source =
+        "[HEADER]\rCREATOR someone\r\rTAP  0\t\t1 0   4 0   \rBEL 0 0 8 A\rTAP 0 2 0 4 0 0 junk\r";
+    let tokens = tokenize(source).unwrap();
+    assert_eq!(
+        tokens.iter().nth(1),
+        Some(&Token::Creator(Creator {
+            name: "someone".to_string()
+        }))
+    );
+    assert_eq!(
+        tokens
+            .iter_spanned()
+            .skip(2)
+            .map(|(_, span)| &source[span])
+            .collect::<Vec<_>>(),
+        ["TAP  0\t\t1 0   4 0", "BEL 0 0 8 A", "TAP 0 2 0 4 0"]
+    );
+
+    match tokenize("TAP 0 0 0 4 0\rTAP 0 1 0 4 0\r\nNOPE").unwrap_err() {
+        LexError::UnknownCommand { line, col, .. } => assert_eq!((line, col), (3, 1)),
+        error => panic!("unexpected error {error:?}"),
+    }
+}
+
+#[test]
+fn test_strict_whitespace() {
+    let options = LexOptions::default().strict_whitespace();
+    let source = "[HEADER]\r\nVERSION\t1\t7\t0\nTAP 0 1 0 4 0\r\rBEL 0 0 8 A\r";
+    assert_eq!(tokenize_with_options(source, &options).unwrap().len(), 4);
+
+    for (source, expected_offset) in [
+        ("TAP 0 1  0 4 0\n", 7),
+        ("TAP 0 1 0 4 0 \n", 13),
+        ("TAP 0 1 0 4 0\n  TAP 0 2 0 4 0\n", 13),
+        ("TAP 0 1 0 4 0\n \nTAP 0 2 0 4 0\n", 13),
+    ] {
+        let error = tokenize_with_options(source, &options).unwrap_err();
+        assert_eq!(error.code(), ErrorCode::IrregularWhitespace);
+        match error {
+            LexError::IrregularWhitespace { offset, .. } => assert_eq!(offset, expected_offset),
+            error => panic!("unexpected error {error:?}"),
+        }
+    }
+
+    match tokenize_with_options("TAP 0 1 0 4 0\nTAP 0 2 0 4 0 1 2\n", &options).unwrap_err() {
+        LexError::TrailingContent {
+            line,
+            col,
+            offset,
+            token,
+        } => assert_eq!((line, col, offset, token.as_str()), (2, 15, 28, "1 2")),
+        error => panic!("unexpected error {error:?}"),
+    }
+
+    let source = "TAP 0 1 0 4 0 x\nTAP 0 2 0 4 0\n";
+    let tokens = tokenize_with_options(source, &options.lenient()).unwrap();
+    assert_eq!(tokens.len(), 1);
+    assert!(matches!(
+        tokens.errors(),
+        [LexError::TrailingContent { .. }]
+    ));
+}
+
+#[test]
+fn test_multibyte_positions() {
+    let source = "CREATOR 譜面作者　さん\nTAP 0 0 0 4 0\n\u{3000}BEL 0 0 ８ A\n";
+    match tokenize(source).unwrap_err() {
+        LexError::ExpectedToken {
+            line, col, offset, ..
+        } => {
+            assert_eq!((line, col), (3, 10));
+            assert_eq!(&source[offset..offset + "８".len()], "８");
+            assert_eq!(line_col(source, offset), (line, col));
+        }
+        error => panic!("unexpected error {error:?}"),
+    }
+
+    let source = "CREATOR 譜面作者 NOPE\nTAP 0 0 0 4 0 ゴミ\n";
+    let tokens = tokenize(source).unwrap();
+    assert_eq!(tokens.len(), 2);
+    let span = tokens.span(1).unwrap();
+    assert_eq!(line_col(source, span.start), (2, 1));
+    assert_eq!(line_col(source, span.end), (2, 14));
+
+    let options = LexOptions::default().strict_whitespace();
+    match tokenize_with_options(source, &options).unwrap_err() {
+        LexError::TrailingContent { line, col, .. } => assert_eq!((line, col), (2, 15)),
+        error => panic!("unexpected error {error:?}"),
+    }
+
+    assert_eq!(line_col("\u{feff}譜面\r作者", 7), (1, 2));
+    assert_eq!(line_col("\u{feff}譜面\r作者", 100), (2, 3));
+}
+
+#[test]
+fn test_bullet_syntaxes() {
+    let source = "BPL A UPS 0 PLR 1.0 N CIR 0\nBPL B UPS 0 PLR 1.0 STR\nBLT A 0 0 0 NML\nBLT B 0 0 0\nBLT B 0 0 0\n";
+    let tokens = tokenize(source).unwrap().into_iter().collect::<Vec<_>>();
+    match (&tokens[0], &tokens[1]) {
+        (Token::BulletPalette(current), Token::BulletPalette(legacy)) => {
+            assert_eq!(
+                (current.size, current.ty, current.damage_type),
+                (Some(BulletSize::Normal), Some(BulletType::Circle), None)
+            );
+            assert_eq!(
+                (legacy.size, legacy.ty, legacy.damage_type),
+                (None, None, Some(BulletDamageType::Hard))
+            );
+        }
+        tokens => panic!("unexpected tokens {tokens:?}"),
+    }
+    assert_eq!(
+        tokens[2..]
+            .iter()
+            .map(|token| match token {
+                Token::Bullet(bullet) => bullet.damage_type,
+                token => panic!("unexpected token {token:?}"),
+            })
+            .collect::<Vec<_>>(),
+        [Some(BulletDamageType::Normal), None, None]
+    );
+
+    match tokenize("BPL A UPS 0 PLR 1.0 N BOX 0\n").unwrap_err() {
+        LexError::ExpectedToken { token, offset, .. } => {
+            assert_eq!((token.as_str(), offset), ("BOX", 22))
+        }
+        error => panic!("unexpected error {error:?}"),
+    }
+
+    let options = LexOptions::default().strict_whitespace();
+    assert!(tokenize_with_options("BPL A UPS 0 PLR 1.0 STR\n", &options).is_ok());
+    assert!(matches!(
+        tokenize_with_options("BPL A UPS 0 PLR 1.0 N  CIR 0\n", &options).unwrap_err(),
+        LexError::IrregularWhitespace { offset: 21, .. }
+    ));
+}
@@ -0,0 +1,61 @@
+use ogkr::{
+    lex::token::TokenKind,
+    parse::{raw::parse_source_with_options, Totals},
+    Ogkr,
+};
+
+#[test]
+fn test_declared_totals() {
+    let source = include_str!("../charts/1.ogkr");
+    let raw = parse_source_with_options(source, &Default::default()).unwrap();
+    assert_eq!(
+        raw.header.totals,
+        Totals {
+            notes: 7,
+            tap: 3,
+            hold: 1,
+            side: 0,
+            side_hold: 1,
+            flick: 2,
+            bell: 2,
+        }
+    );
+    assert_eq!(
+        raw.header.totals.get(TokenKind::TotalSideHoldNotes),
+        Some(1)
+    );
+    assert_eq!(raw.header.totals.get(TokenKind::Tap), None);
+}
+
+#[test]
+fn test_count_totals() {
+    let ogkr: Ogkr = include_str!("../charts/1.ogkr").parse().unwrap();
+    assert_eq!(ogkr.count_totals(), ogkr.header.totals);
+
+    for source in [
+        include_str!("../charts/2.ogkr"),
+        include_str!("../charts/3.ogkr"),
+    ] {
+        let ogkr: Ogkr = source.parse().unwrap();
+        let totals = ogkr.count_totals();
+        assert_eq!(
+            totals.notes as usize,
+            ogkr.notes.all_taps().count()
+                + ogkr.notes.all_holds().count()
+                + ogkr.notes.all_flicks().count()
+        );
+        assert_eq!(totals.bell as usize, ogkr.notes.all_bells().count());
+    }
+}
+
+#[test]
+fn test_total_mismatches() {
+    let ogkr: Ogkr = include_str!("../charts/1.ogkr").parse().unwrap();
+    let mut totals = ogkr.count_totals();
+    totals.side_hold = 0;
+    totals.notes = 6;
+    assert_eq!(
+        ogkr.header.totals.mismatches(&totals),
+        [TokenKind::TotalNotes, TokenKind::TotalSideHoldNotes]
+    );
+}
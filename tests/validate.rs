@@ -0,0 +1,247 @@
+use ogkr::{
+    parse::analysis::{BeamId, LaneId, LaneType, TimingPoint},
+    validate::{Issue, QuickCheckFailure, SectionId},
+    ErrorCode, Ogkr,
+};
+
+#[test]
+fn test_quick_check() {
+    for source in [
+        include_str!("../charts/1.ogkr"),
+        include_str!("../charts/2.ogkr"),
+        include_str!("../charts/3.ogkr"),
+    ] {
+        let ogkr: Ogkr = source.parse().unwrap();
+        assert!(ogkr.quick_check().passed());
+    }
+
+    let source = "
+        WLS 0 0 0 -24
+        WLE 0 4 0 -24
+        LCS 1 0 0 0
+        LCN 1 1 0 0
+        LCE 1 4 0 0
+    ";
+    let mut ogkr: Ogkr = source.parse().unwrap();
+    assert_eq!(
+        ogkr.quick_check().failures,
+        [
+            QuickCheckFailure::MissingWall(LaneType::WallRight),
+            QuickCheckFailure::NoBpm,
+        ]
+    );
+
+    // Tracks built by hand are not sorted by the parser.
+    ogkr.track
+        .lanes_data
+        .get_mut(&LaneId(1))
+        .unwrap()
+        .points
+        .swap(0, 1);
+    let check = ogkr.quick_check();
+    assert!(!check.passed());
+    assert_eq!(
+        check.failures,
+        [
+            QuickCheckFailure::MissingWall(LaneType::WallRight),
+            QuickCheckFailure::UnorderedPoints(SectionId::Lane(LaneId(1))),
+            QuickCheckFailure::NoBpm,
+        ]
+    );
+}
+
+#[test]
+fn test_unordered_points() {
+    let source = "
+        LCS 1 0 0 0
+        LCN 1 1 0 0
+        LCE 1 4 0 0
+        BMS 2 0 0 0 4
+        BMN 2 2 0 0 4
+        BMN 2 2 0 8 4
+        BME 2 3 0 0 4
+    ";
+    let mut ogkr: Ogkr = source.parse().unwrap();
+    assert_eq!(
+        ogkr.validate().issues,
+        [Issue::UnorderedPoints {
+            section: SectionId::Beam(BeamId(2)),
+            time: TimingPoint::new(2, 0),
+        }]
+    );
+
+    ogkr.track
+        .lanes_data
+        .get_mut(&LaneId(1))
+        .unwrap()
+        .points
+        .swap(0, 1);
+    let report = ogkr.validate();
+    assert!(!report.is_valid());
+    assert_eq!(
+        report.issues[0],
+        Issue::UnorderedPoints {
+            section: SectionId::Lane(LaneId(1)),
+            time: TimingPoint::new(0, 0),
+        }
+    );
+
+    assert_eq!(ogkr.sort_section_points(), [SectionId::Lane(LaneId(1))]);
+    assert_eq!(ogkr.validate().issues.len(), 1);
+    assert_eq!(
+        ogkr.track.lanes_center.get(&TimingPoint::new(0, 0)),
+        Some(&vec![LaneId(1)])
+    );
+    assert_eq!(ogkr.track.lanes_center.len(), 1);
+}
+
+#[test]
+fn test_overlapping_holds() {
+    let source = "
+        LCS 0 0 0 0
+        LCE 0 8 0 0
+        LLS 1 0 0 -8
+        LLE 1 8 0 -8
+        HLD 0 1 0 0 0 2 0 0 0
+        HLD 0 2 0 0 0 3 0 0 0
+        CHD 0 1 960 0 0 1 1440 0 0
+        HLD 1 1 960 -8 0 2 0 -8 0
+        HLD 0 4 0 0 0 6 0 0 0
+        HLD 0 5 0 0 0 5 960 0 0
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let report = ogkr.validate();
+    assert!(!report.is_valid());
+    assert_eq!(
+        report.issues,
+        [
+            Issue::OverlappingHolds {
+                lane_id: LaneId(0),
+                first: TimingPoint::new(1, 0),
+                second: TimingPoint::new(1, 960),
+            },
+            Issue::OverlappingHolds {
+                lane_id: LaneId(0),
+                first: TimingPoint::new(4, 0),
+                second: TimingPoint::new(5, 0),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_report_json() {
+    let source = "
+        LCS 0 0 0 0
+        LCE 0 8 0 0
+        HLD 0 1 0 0 0 2 0 0 0
+        HLD 0 1 960 0 0 1 1440 0 0
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let report = ogkr.validate();
+    assert_eq!(report.issues[0].code(), ErrorCode::OverlappingHolds);
+    assert_eq!(report.issues[0].name(), "overlapping_holds");
+    assert_eq!(
+        report.to_json(),
+        concat!(
+            r#"{"valid":false,"issues":[{"code":"OGKR0302","name":"overlapping_holds","severity":"error","#,
+            r#""message":"hold on lane 0 at measure 1 offset 960 overlaps the hold at measure 1 offset 0","#,
+            r#""span":{"start":{"measure":1,"offset":0},"end":{"measure":1,"offset":960}},"#,
+            r#""object":{"kind":"lane","id":0}}]}"#
+        )
+    );
+
+    let ogkr: Ogkr = include_str!("../charts/1.ogkr").parse().unwrap();
+    assert_eq!(ogkr.validate().to_json(), r#"{"valid":true,"issues":[]}"#);
+}
+
+#[test]
+fn test_soflans() {
+    let source = "
+        TRESOLUTION 1920
+        LCS 0 0 0 0
+        LCE 0 3 0 0
+        SFL 0 0 1920 2.000000
+        SFL 0 960 1920 0.500000
+        SFL 0 960 480 3.000000
+        SFL 2 0 960 4.000000
+        SFL 3 0 3840 0.250000
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+
+    assert_eq!(ogkr.effective_speed_at(TimingPoint::new(0, 0)), 2.0);
+    assert_eq!(ogkr.effective_speed_at(TimingPoint::new(0, 960)), 3.0);
+    assert_eq!(ogkr.effective_speed_at(TimingPoint::new(0, 1440)), 0.5);
+    assert_eq!(ogkr.effective_speed_at(TimingPoint::new(1, 900)), 0.5);
+    assert_eq!(ogkr.effective_speed_at(TimingPoint::new(1, 960)), 1.0);
+    assert_eq!(ogkr.effective_speed_at(TimingPoint::new(2, 959)), 4.0);
+    assert_eq!(ogkr.effective_speed_at(TimingPoint::new(2, 960)), 1.0);
+
+    let report = ogkr.validate();
+    assert!(report.is_valid());
+    assert_eq!(
+        report.issues,
+        [
+            Issue::OverlappingSoflans {
+                first: TimingPoint::new(0, 0),
+                second: TimingPoint::new(0, 960),
+            },
+            Issue::OverlappingSoflans {
+                first: TimingPoint::new(0, 960),
+                second: TimingPoint::new(0, 960),
+            },
+            Issue::SoflanPastEnd {
+                start: TimingPoint::new(3, 0),
+                end: TimingPoint::new(5, 0),
+            },
+        ]
+    );
+    assert!(report
+        .to_json()
+        .contains(r#""code":"OGKR0304","name":"soflan_past_end","severity":"warning""#));
+    assert!(report.to_json().ends_with(r#""object":null}]}"#));
+}
+
+#[test]
+fn test_soflans_at_last_measure() {
+    let source = "
+        TRESOLUTION 1920
+        LCS 0 4294967294 0 0
+        LCE 0 4294967295 0 0
+        SFL 4294967295 0 3840 2.000000
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let resolution = ogkr.track.resolution;
+    assert_eq!(
+        resolution.time_of(u64::MAX),
+        TimingPoint::new(u32::MAX, 1919)
+    );
+    assert_eq!(
+        ogkr.effective_speed_at(TimingPoint::new(u32::MAX, 1919)),
+        2.0
+    );
+    assert_eq!(
+        ogkr.validate().issues,
+        [Issue::SoflanPastEnd {
+            start: TimingPoint::new(u32::MAX, 0),
+            end: TimingPoint::new(u32::MAX, 1919),
+        }]
+    );
+}
+
+#[test]
+fn test_suppressed_issues() {
+    let source = "
+        LCS 0 0 0 0
+        LCE 0 8 0 0
+        HLD 0 1 0 0 0 2 0 0 0
+        HLD 0 1 960 0 0 1 1440 0 0
+    ";
+    let ogkr: Ogkr = source.parse().unwrap();
+    let mut report = ogkr.validate();
+    assert_eq!(report.issues[0].code(), ErrorCode::OverlappingHolds);
+
+    report.suppress(&[ErrorCode::OverlappingHolds]);
+    assert!(report.issues.is_empty());
+    assert!(report.is_valid());
+}
@@ -0,0 +1,109 @@
+use std::ops::Range;
+
+use ogkr::{
+    lex::{
+        command::{Bell, Extension},
+        token::Token,
+        tokenize,
+    },
+    parse::analysis::{Lane, TapNote, TimingPoint},
+    visit::{walk_chart, walk_tokens, ChartVisitor, TokenVisitor},
+    Ogkr,
+};
+
+const SOURCE: &str = "[HEADER]
+VERSION 1 8 0
+[LANE]
+LCS 1 0 0 8
+LCE 1 4 0 8
+WLS 0 0 0 -24
+WLE 0 4 0 -24
+[BELL]
+BEL 0 2 0
+[NOTES]
+TAP 1 1 0 8 0
+X_EDITOR first
+CTP 1 0 480 8 0
+";
+
+#[derive(Default)]
+struct Counter {
+    sections: usize,
+    lane_points: usize,
+    notes: usize,
+    bells: usize,
+    extensions: usize,
+    last_span: Range<usize>,
+}
+
+impl TokenVisitor for Counter {
+    fn visit_token(&mut self, _token: &Token, span: Range<usize>) {
+        self.last_span = span;
+    }
+
+    fn visit_section_name(&mut self) {
+        self.sections += 1;
+    }
+
+    fn visit_lane(&mut self, _token: &Token) {
+        self.lane_points += 1;
+    }
+
+    fn visit_note(&mut self, token: &Token) {
+        assert!(matches!(token, Token::Tap(_) | Token::CriticalTap(_)));
+        self.notes += 1;
+    }
+
+    fn visit_bell(&mut self, _bell: &Bell) {
+        self.bells += 1;
+    }
+
+    fn visit_extension(&mut self, _extension: &Extension) {
+        self.extensions += 1;
+    }
+}
+
+#[test]
+fn test_walk_tokens() {
+    let mut counter = Counter::default();
+    walk_tokens(&tokenize(SOURCE).unwrap(), &mut counter);
+    assert_eq!(
+        (
+            counter.sections,
+            counter.lane_points,
+            counter.notes,
+            counter.bells,
+            counter.extensions
+        ),
+        (4, 4, 2, 1, 1)
+    );
+    assert_eq!(&SOURCE[counter.last_span], "CTP 1 0 480 8 0");
+}
+
+#[derive(Default)]
+struct Timeline {
+    lanes: Vec<u32>,
+    taps: Vec<TimingPoint>,
+}
+
+impl ChartVisitor for Timeline {
+    fn visit_lane(&mut self, lane: &Lane) {
+        self.lanes.push(lane.id.0);
+    }
+
+    fn visit_tap(&mut self, tap: &TapNote) {
+        self.taps.push(tap.position.time);
+    }
+}
+
+#[test]
+fn test_walk_chart() {
+    let ogkr: Ogkr = SOURCE.parse().unwrap();
+    let mut timeline = Timeline::default();
+    walk_chart(&ogkr, &mut timeline);
+    assert_eq!(timeline.lanes, [0, 1]);
+    assert_eq!(
+        timeline.taps,
+        [TimingPoint::new(0, 480), TimingPoint::new(1, 0)]
+    );
+}
@@ -0,0 +1,89 @@
+use ogkr::{
+    lex::{token::TokenKind, tokenize, LexOptions},
+    parse::{
+        analysis::parse_raw_ogkr,
+        raw::{parse_source_with_options, parse_tokens},
+        DuplicateHeaderPolicy, ParseError, ParseOptions, Warning,
+    },
+};
+
+#[test]
+fn test_no_warnings() {
+    let tokens = tokenize(include_str!("../charts/1.ogkr")).unwrap();
+    let ogkr = parse_raw_ogkr(parse_tokens(tokens).unwrap()).unwrap();
+    assert!(ogkr.warnings.is_empty());
+}
+
+#[test]
+fn test_duplicate_ids() {
+    let source = "
+        WLS 0 0 0 -24
+        WLE 0 1 0 -24
+        LCS 0 0 0 0
+        LCE 0 1 0 0
+        BMS 1 0 0 0 4
+        BME 1 1 0 0 4
+        BMS 1 2 0 0 4
+        BME 1 3 0 0 4
+    ";
+    let tokens = tokenize(source).unwrap();
+    let ogkr = parse_raw_ogkr(parse_tokens(tokens).unwrap()).unwrap();
+
+    let warnings: Vec<_> = ogkr.warnings.into_iter().collect();
+    assert_eq!(
+        warnings,
+        vec![Warning::DuplicateLaneId(0), Warning::DuplicateBeamId(1)]
+    );
+}
+
+#[test]
+fn test_conflicting_header() {
+    let source = "
+        VERSION 1 7 0
+        TRESOLUTION 1920
+        VERSION 1 7 0
+        TRESOLUTION 480
+        BPM_DEF 120 120 120 120
+        BPM_DEF 150 150 150 150
+    ";
+    let raw = parse_tokens(tokenize(source).unwrap()).unwrap();
+    assert_eq!(raw.header.tick_resolution.unwrap().resolution, 480);
+
+    let warnings: Vec<_> = raw.warnings.into_iter().collect();
+    assert_eq!(
+        warnings,
+        vec![
+            Warning::ConflictingHeaderCommand(TokenKind::TickResolution),
+            Warning::ConflictingHeaderCommand(TokenKind::BpmDefinition),
+        ]
+    );
+
+    let mut options = ParseOptions::default();
+    options.duplicate_header = DuplicateHeaderPolicy::Error;
+    assert!(matches!(
+        parse_source_with_options(source, &options),
+        Err(ParseError::SemanticError(_))
+    ));
+}
+
+#[test]
+fn test_unknown_command_census() {
+    let source = "
+        LCS 0 0 0 0
+        NEW 1 2
+        LCE 0 4 0 0
+        TAP 0 1 0 0
+        NEW 3 4
+        OTHER
+        TAP 0 1 0 0 0
+    ";
+    let mut options = ParseOptions::default();
+    options.lex = LexOptions::default().lenient();
+    let ogkr = ogkr::parse_with_options(source, &options).unwrap();
+
+    let census = ogkr.warnings.unknown_commands();
+    assert_eq!(census.len(), 2);
+    assert_eq!(census["NEW"], 2);
+    assert_eq!(census["OTHER"], 1);
+    assert_eq!(ogkr.warnings.len(), 4);
+}